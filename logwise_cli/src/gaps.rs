@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Finding history that never arrived.
+//!
+//! Each guest worker numbers its envelopes from 1 and counts, cumulatively,
+//! the envelopes it dropped (`dropped_before`) and the values it truncated
+//! (`truncated_before`) before each one. A jump in the sequence is history
+//! missing from the input; the change in `dropped_before` across the jump
+//! says how much of it the guest knew it lost, and the remainder went
+//! missing after the guest handed it over.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use logwise_runtime_wasm::DecodedEnvelope;
+
+/// History missing before one envelope.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Gap {
+    /// The first envelope seen from a worker is not its first.
+    Before {
+        worker: u64,
+        sequence: u64,
+        dropped: u64,
+    },
+    /// Sequence numbers were skipped between two envelopes.
+    Missing {
+        worker: u64,
+        after: u64,
+        before: u64,
+        dropped: u64,
+    },
+    /// The sequence went backwards, as when a guest restarts.
+    Restart {
+        worker: u64,
+        after: u64,
+        sequence: u64,
+    },
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Before {
+                worker,
+                sequence,
+                dropped,
+            } => write!(
+                formatter,
+                "worker {worker}: {} envelopes before #{sequence} are not in the input ({dropped} dropped by the guest)",
+                sequence - 1
+            ),
+            Self::Missing {
+                worker,
+                after,
+                before,
+                dropped,
+            } => {
+                let missing = before - after - 1;
+                write!(
+                    formatter,
+                    "worker {worker}: {missing} envelopes missing between #{after} and #{before} ({} dropped by the guest, {} lost after it)",
+                    dropped.min(missing),
+                    missing.saturating_sub(dropped)
+                )
+            }
+            Self::Restart {
+                worker,
+                after,
+                sequence,
+            } => write!(
+                formatter,
+                "worker {worker}: sequence went back from #{after} to #{sequence}; counting a new run"
+            ),
+        }
+    }
+}
+
+/// One worker's run of envelopes so far.
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    first: u64,
+    last: u64,
+    envelopes: u64,
+    missing: u64,
+    dropped_before: u64,
+    truncated_before: u64,
+}
+
+/// Tracks every worker's sequence across one input.
+#[derive(Debug, Default)]
+pub struct Gaps {
+    runs: BTreeMap<u64, Run>,
+}
+
+impl Gaps {
+    /// Records an envelope and returns the history missing before it, if any.
+    pub fn observe(&mut self, envelope: &DecodedEnvelope<'_>) -> Option<Gap> {
+        let worker = envelope.worker;
+        let sequence = envelope.sequence;
+        let start = Run {
+            first: sequence,
+            last: sequence,
+            envelopes: 1,
+            missing: sequence.saturating_sub(1),
+            dropped_before: envelope.dropped_before,
+            truncated_before: envelope.truncated_before,
+        };
+        let Some(run) = self.runs.get_mut(&worker) else {
+            self.runs.insert(worker, start);
+            return (sequence > 1).then_some(Gap::Before {
+                worker,
+                sequence,
+                dropped: envelope.dropped_before,
+            });
+        };
+        if sequence <= run.last {
+            let after = run.last;
+            *run = start;
+            return Some(Gap::Restart {
+                worker,
+                after,
+                sequence,
+            });
+        }
+        let gap = (sequence > run.last + 1).then_some(Gap::Missing {
+            worker,
+            after: run.last,
+            before: sequence,
+            dropped: envelope.dropped_before.saturating_sub(run.dropped_before),
+        });
+        run.missing += sequence - run.last - 1;
+        run.last = sequence;
+        run.envelopes += 1;
+        run.dropped_before = envelope.dropped_before;
+        run.truncated_before = envelope.truncated_before;
+        gap
+    }
+
+    /// One line per worker, in worker order.
+    pub fn summaries(&self) -> impl Iterator<Item = String> + '_ {
+        self.runs.iter().map(|(worker, run)| {
+            format!(
+                "worker {worker}: {} envelopes #{}..#{}, {} missing, {} dropped and {} values truncated by the guest before #{}",
+                run.envelopes,
+                run.first,
+                run.last,
+                run.missing,
+                run.dropped_before,
+                run.truncated_before,
+                run.last
+            )
+        })
+    }
+}
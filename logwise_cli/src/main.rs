@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! `logwise`: reads the `logwise_v1` frames a wasm host collected and makes
+//! them legible.
+//!
+//! Input is read incrementally, so `logwise` can sit at the end of a pipe. A
+//! frame cut short by a read boundary waits for more bytes; one that is
+//! damaged is reported on stderr with its byte offset and reading resumes at
+//! the next frame. The exit status is 0 when every input decoded cleanly, 1
+//! when any was damaged or unreadable, and 2 for a usage error.
+//!
+//! Version 2 event records name a call site defined by an earlier site
+//! record. Sites are kept per input, so an event whose site the input never
+//! defined counts as damage.
+//!
+//! A native log file starts with a header naming the process that wrote it.
+//! `logwise` reads past it, and prints it before the file's events.
+
+mod gaps;
+mod options;
+mod render;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::process::ExitCode;
+
+use logwise_runtime_wasm::{
+    DecodeError, DecodedEnvelope, Frame, Frames, LogFileHeader, MAX_ENVELOPE_BYTES, SiteEvent,
+    decode_frame,
+};
+
+use crate::gaps::Gaps;
+use crate::options::{Command, Input, Options, USAGE};
+use crate::render::Lineage;
+
+const READ_BYTES: usize = 64 * 1024;
+
+fn main() -> ExitCode {
+    let options = match Options::parse(std::env::args_os().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprint!("logwise: {error}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut output = BufWriter::new(io::stdout().lock());
+    let mut clean = true;
+    for input in &options.inputs {
+        let result = match input {
+            Input::Stdin => read(io::stdin().lock(), input, &options, &mut output),
+            Input::File(path) => {
+                File::open(path).and_then(|file| read(file, input, &options, &mut output))
+            }
+        };
+        match result {
+            Ok(damaged) => clean &= !damaged,
+            // A closed pipe, as from `| head`, is the reader saying it has
+            // seen enough.
+            Err(error) if error.kind() == ErrorKind::BrokenPipe => return ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("logwise: {input}: {error}");
+                clean = false;
+            }
+        }
+    }
+    match output.flush() {
+        Err(error) if error.kind() != ErrorKind::BrokenPipe => {
+            eprintln!("logwise: {error}");
+            ExitCode::FAILURE
+        }
+        _ if clean => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// Decodes one input to `output`, returning whether any of it was damaged.
+fn read(
+    mut reader: impl Read,
+    input: &Input,
+    options: &Options,
+    output: &mut impl Write,
+) -> io::Result<bool> {
+    let mut gaps = Gaps::default();
+    let mut lineage = Lineage::default();
+    // Site record bytes by worker and site ID.
+    let mut sites = HashMap::new();
+    let mut damaged = false;
+    let mut buffer = Vec::new();
+    // Where `buffer` starts in the input, for reporting offsets.
+    let mut base = 0;
+    let mut ended = false;
+    // Whether the input may still turn out to start with a log file header.
+    let mut header = true;
+    // Whether the bytes being skipped past an oversized frame are already
+    // reported.
+    let mut resyncing = false;
+    while !ended {
+        let filled = buffer.len();
+        buffer.resize(filled + READ_BYTES, 0);
+        let count = loop {
+            match reader.read(&mut buffer[filled..]) {
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                result => break result?,
+            }
+        };
+        buffer.truncate(filled + count);
+        ended = count == 0;
+
+        if header {
+            match LogFileHeader::decode(&buffer) {
+                Err(DecodeError::Incomplete { .. }) if !ended => continue,
+                Ok((file, length)) => {
+                    if options.command == Command::Print {
+                        writeln!(output, "-- log file: {file}")?;
+                    }
+                    buffer.drain(..length);
+                    base += length;
+                }
+                // Not a log file, or one the frames below report as damage.
+                Err(_) => {}
+            }
+            header = false;
+        }
+
+        let mut frames = Frames::new(&buffer);
+        let mut consumed = 0;
+        loop {
+            let at = base + frames.position();
+            let Some(frame) = frames.next() else {
+                break;
+            };
+            if frame.is_ok() {
+                resyncing = false;
+            }
+            match frame {
+                Ok(Frame::Envelope(envelope)) => {
+                    show(&envelope, input, options, &mut gaps, &mut lineage, output)?;
+                }
+                Ok(Frame::Site(site)) => {
+                    sites.insert((site.worker, site.site), site.bytes().to_vec());
+                }
+                Ok(Frame::Event(event)) => match resolve(&sites, &event) {
+                    Ok(envelope) => {
+                        show(&envelope, input, options, &mut gaps, &mut lineage, output)?;
+                    }
+                    Err(error) => {
+                        damaged = true;
+                        eprintln!("logwise: {input}: frame at byte {at}: {error}");
+                    }
+                },
+                // More bytes may complete this frame, unless it claims more
+                // than any encoder writes: then it is corrupt, and nothing
+                // before the end of the buffer starts another frame.
+                Err(error)
+                    if !ended
+                        && error.skipped == 0
+                        && matches!(
+                            error.error,
+                            DecodeError::Incomplete { needed } if needed <= MAX_ENVELOPE_BYTES
+                        ) =>
+                {
+                    consumed = error.offset;
+                    break;
+                }
+                Err(error) if !ended && error.skipped == 0 => {
+                    damaged = true;
+                    eprintln!(
+                        "logwise: {input}: frame at byte {}: {}, more than {MAX_ENVELOPE_BYTES}; skipping to the next frame",
+                        base + error.offset,
+                        error.error
+                    );
+                    resyncing = true;
+                    consumed = buffer.len() - partial_magic(&buffer);
+                    break;
+                }
+                // The rest of an oversized frame.
+                Err(error) if resyncing && error.error == DecodeError::BadMagic => {}
+                Err(error) => {
+                    damaged = true;
+                    let offset = base + error.offset;
+                    match error.error {
+                        DecodeError::Incomplete { .. } => eprintln!(
+                            "logwise: {input}: frame at byte {offset}: {}; the input ends inside it",
+                            error.error
+                        ),
+                        _ => eprintln!(
+                            "logwise: {input}: frame at byte {offset}: {}; skipped {} bytes",
+                            error.error, error.skipped
+                        ),
+                    }
+                }
+            }
+            consumed = frames.position();
+        }
+        buffer.drain(..consumed);
+        base += consumed;
+    }
+    if options.command == Command::Gaps {
+        for summary in gaps.summaries() {
+            writeln!(output, "{summary}")?;
+        }
+    }
+    Ok(damaged)
+}
+
+/// How many trailing bytes of `bytes` could start a frame's magic.
+fn partial_magic(bytes: &[u8]) -> usize {
+    const MAGIC: &[u8] = b"LW1\0";
+    (1..MAGIC.len())
+        .rev()
+        .find(|length| bytes.ends_with(&MAGIC[..*length]))
+        .unwrap_or(0)
+}
+
+/// Joins a version 2 event record to the site record it names.
+fn resolve<'a>(
+    sites: &'a HashMap<(u64, u32), Vec<u8>>,
+    event: &SiteEvent<'a>,
+) -> Result<DecodedEnvelope<'a>, String> {
+    let Some(bytes) = sites.get(&(event.worker, event.site)) else {
+        return Err(format!(
+            "event #{} of worker {} names site {}, which the input has not defined",
+            event.sequence, event.worker, event.site
+        ));
+    };
+    match decode_frame(bytes) {
+        Ok(Frame::Site(site)) => event.resolve(&site).map_err(|error| error.to_string()),
+        _ => unreachable!("only decoded site records are kept"),
+    }
+}
+
+/// Reports any gap before `envelope`, then prints it if it is selected.
+fn show(
+    envelope: &DecodedEnvelope<'_>,
+    input: &Input,
+    options: &Options,
+    gaps: &mut Gaps,
+    lineage: &mut Lineage,
+    output: &mut impl Write,
+) -> io::Result<()> {
+    let gap = gaps.observe(envelope);
+    lineage.observe(envelope);
+    match (options.command, gap) {
+        (Command::Print | Command::Gaps, Some(gap)) => writeln!(output, "-- gap: {gap}")?,
+        (Command::Json, Some(gap)) => eprintln!("logwise: {input}: gap: {gap}"),
+        (_, None) => {}
+    }
+    if options.selection.matches(envelope) {
+        match options.command {
+            Command::Print => writeln!(output, "{}", render::text(envelope, lineage))?,
+            Command::Json => writeln!(output, "{}", render::json(envelope))?,
+            Command::Gaps => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Command-line parsing.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+use logwise::Severity;
+use logwise_runtime_wasm::DecodedEnvelope;
+
+pub const USAGE: &str = "\
+Usage: logwise [print|json|gaps] [OPTIONS] [FILE ...]
+
+Reads concatenated logwise_v1 frames from each FILE, or from standard input
+when there is none or FILE is `-`, skipping past damaged frames.
+
+Commands:
+  print   one line per event, with context lineage and gaps inline (default)
+  json    JSON Lines, schema version 1 plus wire keys; gaps go to stderr
+  gaps    only the gaps, then a summary per worker
+
+Options (repeat one to match any of its values):
+  --event NAME      event name, or a dotted prefix of one
+  --domain NAME     domain, or a dotted prefix of one
+  --severity LEVEL  at least trace, debug, info, warn, error or critical
+  --worker ID       guest worker identity
+  --test NAME       guest test identity
+  -h, --help        print this help
+";
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Command {
+    #[default]
+    Print,
+    Json,
+    Gaps,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => formatter.write_str("<stdin>"),
+            Self::File(path) => write!(formatter, "{}", path.display()),
+        }
+    }
+}
+
+/// Which events are printed. Gaps are tracked over every frame regardless.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    events: Vec<String>,
+    domains: Vec<String>,
+    severity: Option<Severity>,
+    workers: Vec<u64>,
+    tests: Vec<String>,
+}
+
+impl Selection {
+    pub fn matches(&self, envelope: &DecodedEnvelope<'_>) -> bool {
+        (self.events.is_empty()
+            || self
+                .events
+                .iter()
+                .any(|name| dotted_prefix(name, envelope.event_name)))
+            && (self.domains.is_empty()
+                || envelope.domain.is_some_and(|domain| {
+                    self.domains.iter().any(|name| dotted_prefix(name, domain))
+                }))
+            && self
+                .severity
+                .is_none_or(|severity| envelope.severity as u8 >= severity as u8)
+            && (self.workers.is_empty() || self.workers.contains(&envelope.worker))
+            && (self.tests.is_empty()
+                || envelope
+                    .test
+                    .is_some_and(|test| self.tests.iter().any(|name| name == test)))
+    }
+}
+
+/// Whether `name` is `value` or one of its dotted ancestors, as directive
+/// selectors match.
+fn dotted_prefix(name: &str, value: &str) -> bool {
+    value
+        .strip_prefix(name)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub command: Command,
+    pub selection: Selection,
+    pub inputs: Vec<Input>,
+}
+
+impl Options {
+    /// Parses arguments after the program name, or `None` when help was
+    /// asked for.
+    pub fn parse(arguments: impl IntoIterator<Item = OsString>) -> Result<Option<Self>, String> {
+        let mut options = Self::default();
+        let mut arguments = arguments.into_iter().peekable();
+        if let Some(command) = arguments.peek().and_then(|argument| argument.to_str()) {
+            let command = match command {
+                "print" => Some(Command::Print),
+                "json" => Some(Command::Json),
+                "gaps" => Some(Command::Gaps),
+                _ => None,
+            };
+            if let Some(command) = command {
+                options.command = command;
+                arguments.next();
+            }
+        }
+        let mut only_files = false;
+        while let Some(argument) = arguments.next() {
+            let flag = argument.to_str().filter(|_| !only_files);
+            let mut value = |flag: &str| {
+                arguments
+                    .next()
+                    .and_then(|value| value.into_string().ok())
+                    .ok_or_else(|| format!("{flag} needs a value"))
+            };
+            let selection = &mut options.selection;
+            match flag {
+                Some("-h" | "--help") => return Ok(None),
+                Some("--") => only_files = true,
+                Some("-") => options.inputs.push(Input::Stdin),
+                Some("--event") => selection.events.push(value("--event")?),
+                Some("--domain") => selection.domains.push(value("--domain")?),
+                Some("--severity") => {
+                    let level = value("--severity")?;
+                    selection.severity = Some(
+                        severity(&level).ok_or_else(|| format!("unknown severity `{level}`"))?,
+                    );
+                }
+                Some("--worker") => {
+                    let worker = value("--worker")?;
+                    selection.workers.push(
+                        worker
+                            .parse()
+                            .map_err(|_| format!("invalid worker `{worker}`"))?,
+                    );
+                }
+                Some("--test") => selection.tests.push(value("--test")?),
+                Some(flag) if flag.starts_with('-') => {
+                    return Err(format!("unknown option `{flag}`"));
+                }
+                _ => options.inputs.push(Input::File(argument.into())),
+            }
+        }
+        if options.inputs.is_empty() {
+            options.inputs.push(Input::Stdin);
+        }
+        Ok(Some(options))
+    }
+}
+
+fn severity(name: &str) -> Option<Severity> {
+    Some(match name {
+        "trace" => Severity::Trace,
+        "debug" => Severity::Debug,
+        "info" => Severity::Info,
+        "warn" => Severity::Warn,
+        "error" => Severity::Error,
+        "critical" => Severity::Critical,
+        _ => return None,
+    })
+}
@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Text and JSON Lines output for decoded envelopes.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+
+use logwise::{Class, Detail, Kind, Privacy, Severity, SpanTiming};
+use logwise_runtime::{ProjectedSpan, SpanLifecycle, Timestamp};
+use logwise_runtime_wasm::{DecodedEnvelope, WireSpan, WireTimestamp, WireValue};
+
+/// How far [`Lineage::chain`] follows links before giving up.
+const MAX_LINEAGE: usize = 16;
+
+/// The first link seen for each context, per worker, so a line can show the
+/// chain of contexts an event descends from even when the frames that
+/// established it were printed long before.
+#[derive(Debug, Default)]
+pub struct Lineage {
+    links: HashMap<(u64, u64), u64>,
+}
+
+impl Lineage {
+    pub fn observe(&mut self, envelope: &DecodedEnvelope<'_>) {
+        let context = envelope.context.into_parts().0;
+        if let Some(link) = envelope.links().next()
+            && !envelope.context.is_none()
+        {
+            self.links
+                .entry((envelope.worker, context))
+                .or_insert(link.into_parts().0);
+        }
+    }
+
+    /// `context <- link <- link ...`: every direct link of this envelope,
+    /// then the remembered chain behind the first.
+    fn chain(&self, envelope: &DecodedEnvelope<'_>, line: &mut String) {
+        let context = envelope.context.into_parts().0;
+        let _ = write!(line, "context {context}");
+        let mut links = envelope.links().map(|link| link.into_parts().0);
+        let Some(first) = links.next() else {
+            return;
+        };
+        let _ = write!(line, " <- {first}");
+        for link in links {
+            let _ = write!(line, ",{link}");
+        }
+        let mut seen = HashSet::from([context, first]);
+        let mut current = first;
+        for _ in 0..MAX_LINEAGE {
+            match self.links.get(&(envelope.worker, current)) {
+                Some(&next) if seen.insert(next) => {
+                    let _ = write!(line, " <- {next}");
+                    current = next;
+                }
+                _ => return,
+            }
+        }
+        line.push_str(" <- ...");
+    }
+}
+
+fn timestamp(timestamp: WireTimestamp) -> Timestamp {
+    Timestamp {
+        monotonic: timestamp.monotonic,
+        wall: timestamp.wall,
+    }
+}
+
+fn span(span: WireSpan) -> ProjectedSpan {
+    ProjectedSpan {
+        token: span.token,
+        timing: span.timing,
+        warning_threshold: span.warning_threshold,
+        lifecycle: match span.completion {
+            None => SpanLifecycle::Started,
+            Some(completion) => SpanLifecycle::Completed {
+                elapsed: completion.elapsed,
+                threshold_exceeded: completion.threshold_exceeded,
+            },
+        },
+    }
+}
+
+/// One line per event, after the runtime's structured writer: time,
+/// severity, name, message and fields, then identity and lineage.
+pub fn text(envelope: &DecodedEnvelope<'_>, lineage: &Lineage) -> String {
+    let mut line = String::new();
+    match envelope.timestamp {
+        Some(time) => {
+            let _ = write!(line, "{}", timestamp(time));
+        }
+        None => line.push('-'),
+    }
+    let _ = write!(line, " {:?} {}", envelope.severity, envelope.event_name);
+    if let Some(message) = envelope.message {
+        let _ = write!(line, " {message}");
+    }
+    for field in envelope.fields() {
+        let _ = write!(line, " {}=", field.name);
+        match field.value {
+            WireValue::Bool(value) => {
+                let _ = write!(line, "{value}");
+            }
+            WireValue::I64(value) => {
+                let _ = write!(line, "{value}");
+            }
+            WireValue::U64(value) => {
+                let _ = write!(line, "{value}");
+            }
+            WireValue::F64(value) => {
+                let _ = write!(line, "{value:?}");
+            }
+            WireValue::Str(value) | WireValue::Display(value) => {
+                let _ = write!(line, "{value:?}");
+            }
+            WireValue::Debug(value) => line.push_str(value),
+        }
+    }
+    if let Some(wire) = envelope.span {
+        let _ = write!(line, " {}", span(wire));
+    }
+    if envelope.omitted_fields != 0 {
+        let _ = write!(line, " ({} omitted)", envelope.omitted_fields);
+    }
+    if envelope.truncated() {
+        line.push_str(" (truncated)");
+    }
+    let _ = write!(line, " [#{} worker {}", envelope.sequence, envelope.worker);
+    if let Some(test) = envelope.test {
+        let _ = write!(line, " test {test:?}");
+    }
+    if !envelope.context.is_none() {
+        line.push(' ');
+        lineage.chain(envelope, &mut line);
+    }
+    line.push(']');
+    line
+}
+
+/// A JSON Lines object in the runtime's schema, version 1.
+///
+/// The wire has no metrics, so that key is `null`, and spans only in
+/// version 2. It records only that some value was cut short, so
+/// `truncated_fields` is 1 or 0. The
+/// wire's own keys follow: `sequence`, `worker`, `test`, `links`,
+/// `dropped_before` and `truncated_before`.
+pub fn json(envelope: &DecodedEnvelope<'_>) -> String {
+    let mut line = String::from("{\"v\":1,\"time\":");
+    match envelope.timestamp {
+        Some(time) => {
+            let _ = write!(
+                line,
+                "\"{}\",\"monotonic_ns\":{}",
+                timestamp(time),
+                time.monotonic.as_nanos()
+            );
+        }
+        None => line.push_str("null,\"monotonic_ns\":null"),
+    }
+    for (key, value) in [
+        ("event", envelope.event_name),
+        ("package", envelope.package),
+        ("target", envelope.target),
+        ("module", envelope.module),
+    ] {
+        let _ = write!(line, ",\"{key}\":");
+        string(&mut line, value);
+    }
+    line.push_str(",\"domain\":");
+    optional_string(&mut line, envelope.domain);
+    let _ = write!(
+        line,
+        ",\"class\":\"{}\",\"severity\":\"{}\",\"kind\":\"{}\",\"location\":",
+        class_name(envelope.class),
+        severity_name(envelope.severity),
+        kind_name(envelope.kind)
+    );
+    match envelope.location {
+        Some(location) => {
+            line.push_str("{\"file\":");
+            string(&mut line, location.file);
+            let _ = write!(
+                line,
+                ",\"line\":{},\"column\":{}}}",
+                location.line, location.column
+            );
+        }
+        None => line.push_str("null"),
+    }
+    line.push_str(",\"context\":");
+    if envelope.context.is_none() {
+        line.push_str("null");
+    } else {
+        let _ = write!(line, "{}", envelope.context.into_parts().0);
+    }
+
+    line.push_str(",\"fields\":[");
+    for (index, field) in envelope.fields().enumerate() {
+        if index != 0 {
+            line.push(',');
+        }
+        line.push_str("{\"name\":");
+        string(&mut line, field.name);
+        let _ = write!(
+            line,
+            ",\"privacy\":\"{}\",\"detail\":\"{}\",",
+            privacy_name(field.privacy),
+            detail_name(field.detail)
+        );
+        match field.value {
+            WireValue::Bool(value) => {
+                let _ = write!(line, "\"type\":\"bool\",\"value\":{value}");
+            }
+            WireValue::I64(value) => {
+                let _ = write!(line, "\"type\":\"i64\",\"value\":{value}");
+            }
+            WireValue::U64(value) => {
+                let _ = write!(line, "\"type\":\"u64\",\"value\":{value}");
+            }
+            WireValue::F64(value) => {
+                line.push_str("\"type\":\"f64\",\"value\":");
+                float(&mut line, value);
+            }
+            WireValue::Str(value) | WireValue::Debug(value) | WireValue::Display(value) => {
+                line.push_str("\"type\":\"string\",\"value\":");
+                string(&mut line, value);
+            }
+        }
+        line.push('}');
+    }
+    line.push_str("],\"message\":");
+    optional_string(&mut line, envelope.message);
+    let _ = write!(
+        line,
+        ",\"omitted_fields\":{},\"truncated_fields\":{},\"span\":",
+        envelope.omitted_fields,
+        u8::from(envelope.truncated())
+    );
+    match envelope.span {
+        Some(span) => json_span(&mut line, span),
+        None => line.push_str("null"),
+    }
+    line.push_str(",\"metric\":null");
+    let _ = write!(
+        line,
+        ",\"sequence\":{},\"worker\":{},\"test\":",
+        envelope.sequence, envelope.worker
+    );
+    optional_string(&mut line, envelope.test);
+    line.push_str(",\"links\":[");
+    for (index, link) in envelope.links().enumerate() {
+        if index != 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "{}", link.into_parts().0);
+    }
+    let _ = write!(
+        line,
+        "],\"dropped_before\":{},\"truncated_before\":{}}}",
+        envelope.dropped_before, envelope.truncated_before
+    );
+    line
+}
+
+/// The runtime's span object.
+fn json_span(line: &mut String, span: WireSpan) {
+    let timing = match span.timing {
+        SpanTiming::WallTime => "wall_time",
+        SpanTiming::ActiveTime => "active_time",
+        SpanTiming::WakeLatency => "wake_latency",
+    };
+    let lifecycle = match span.completion {
+        None => "started",
+        Some(_) => "completed",
+    };
+    let _ = write!(
+        line,
+        "{{\"token\":{},\"lifecycle\":\"{lifecycle}\",\"timing\":\"{timing}\",\"threshold_ns\":",
+        span.token.into_parts().0
+    );
+    match span.warning_threshold {
+        Some(threshold) => {
+            let _ = write!(line, "{}", threshold.as_nanos());
+        }
+        None => line.push_str("null"),
+    }
+    if let Some(completion) = span.completion {
+        let _ = write!(
+            line,
+            ",\"elapsed_ns\":{},\"threshold_exceeded\":{}",
+            completion.elapsed.as_nanos(),
+            completion.threshold_exceeded
+        );
+    }
+    line.push('}');
+}
+
+fn optional_string(line: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => string(line, value),
+        None => line.push_str("null"),
+    }
+}
+
+fn string(line: &mut String, value: &str) {
+    line.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            '\u{08}' => line.push_str("\\b"),
+            '\u{0c}' => line.push_str("\\f"),
+            control if control < ' ' => {
+                let _ = write!(line, "\\u{:04x}", control as u32);
+            }
+            other => line.push(other),
+        }
+    }
+    line.push('"');
+}
+
+fn float(line: &mut String, value: f64) {
+    if value.is_nan() {
+        line.push_str("\"NaN\"");
+    } else if value == f64::INFINITY {
+        line.push_str("\"inf\"");
+    } else if value == f64::NEG_INFINITY {
+        line.push_str("\"-inf\"");
+    } else {
+        let _ = write!(line, "{value:?}");
+    }
+}
+
+const fn class_name(class: Class) -> &'static str {
+    match class {
+        Class::Operational => "operational",
+        Class::Diagnostic => "diagnostic",
+        Class::Forensic => "forensic",
+        Class::Performance => "performance",
+        Class::Metric => "metric",
+    }
+}
+
+const fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Trace => "trace",
+        Severity::Debug => "debug",
+        Severity::Info => "info",
+        Severity::Warn => "warn",
+        Severity::Error => "error",
+        Severity::Critical => "critical",
+    }
+}
+
+const fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Event => "event",
+        Kind::AdHocText => "ad_hoc_text",
+        Kind::Span => "span",
+        Kind::Counter => "counter",
+        Kind::Measurement => "measurement",
+    }
+}
+
+const fn privacy_name(privacy: Privacy) -> &'static str {
+    match privacy {
+        Privacy::SupportSafe => "support",
+        Privacy::LocalOnly => "local",
+        Privacy::Secret => "secret",
+    }
+}
+
+const fn detail_name(detail: Detail) -> &'static str {
+    match detail {
+        Detail::Core => "core",
+        Detail::Detail => "detail",
+    }
+}
@@ -0,0 +1,356 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The `logwise` binary over a stream with a corrupt frame, missing
+//! sequence numbers, two workers and a context chain.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+
+use logwise::{
+    Class, ContextToken, Detail, Domain, EventRef, FieldMetadata, FieldRef, Kind, Metadata,
+    Privacy, Severity, ValueRef,
+};
+use logwise::{SpanTiming, SpanToken};
+use logwise_runtime_wasm::{
+    Envelope, Identity, LogFileHeader, WireSpan, WireSpanCompletion, WireTimestamp, encode_event,
+    encode_site, encode_timestamped_envelope,
+};
+
+static FIELDS: &[FieldMetadata] = &[
+    FieldMetadata::new("attempt", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("user", Privacy::LocalOnly, Detail::Core),
+];
+static REQUEST: Metadata = Metadata {
+    event_name: "browser.fetch.request",
+    package: "guest",
+    target: "guest",
+    module: "guest::fetch",
+    domain: Some(Domain::new("browser.fetch")),
+    severity: Severity::Info,
+    class: Class::Operational,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+static FAILURE: Metadata = Metadata {
+    event_name: "browser.fetch.failure",
+    severity: Severity::Error,
+    ..REQUEST
+};
+static RENDER: Metadata = Metadata {
+    event_name: "browser.render",
+    domain: Some(Domain::new("browser.render")),
+    fields: &[],
+    ..REQUEST
+};
+
+/// One frame from `worker`, in context `context` linked from `link`.
+fn frame(
+    metadata: &'static Metadata,
+    worker: u64,
+    sequence: u64,
+    dropped_before: u64,
+    context: u64,
+    link: Option<u64>,
+) -> Vec<u8> {
+    with_envelope(
+        metadata,
+        worker,
+        sequence,
+        dropped_before,
+        context,
+        link,
+        |envelope| {
+            let mut output = [0_u8; 512];
+            let mut scratch = [0_u8; 64];
+            encode_timestamped_envelope(envelope, 64, &mut output, &mut scratch)
+                .unwrap()
+                .bytes
+                .to_vec()
+        },
+    )
+}
+
+/// The version 2 record for the same event, naming `site`.
+fn record(
+    metadata: &'static Metadata,
+    site: u32,
+    sequence: u64,
+    span: Option<WireSpan>,
+) -> Vec<u8> {
+    with_envelope(metadata, 1, sequence, 0, 10, None, |envelope| {
+        let mut output = [0_u8; 512];
+        let mut scratch = [0_u8; 64];
+        encode_event(envelope, site, span, 64, &mut output, &mut scratch)
+            .unwrap()
+            .bytes
+            .to_vec()
+    })
+}
+
+fn site(metadata: &Metadata, site: u32) -> Vec<u8> {
+    let mut output = [0_u8; 512];
+    encode_site(1, site, metadata, &mut output)
+        .unwrap()
+        .to_vec()
+}
+
+fn with_envelope(
+    metadata: &'static Metadata,
+    worker: u64,
+    sequence: u64,
+    dropped_before: u64,
+    context: u64,
+    link: Option<u64>,
+    encode: impl FnOnce(Envelope<'_>) -> Vec<u8>,
+) -> Vec<u8> {
+    let fields = [
+        Some(FieldRef::new(&FIELDS[0], ValueRef::U64(sequence))),
+        Some(FieldRef::new(&FIELDS[1], ValueRef::Str("alice \"a\""))),
+    ];
+    let fields = if metadata.fields.is_empty() {
+        &fields[..0]
+    } else {
+        &fields[..]
+    };
+    let links = link
+        .map(|link| ContextToken::from_parts(link, 0))
+        .into_iter()
+        .collect::<Vec<_>>();
+    let envelope = Envelope {
+        event: EventRef::structured(metadata, ContextToken::from_parts(context, 0), fields),
+        timestamp: Some(WireTimestamp {
+            monotonic: Duration::from_millis(sequence),
+            wall: Duration::from_secs(1_700_000_000),
+        }),
+        sequence,
+        dropped_before,
+        truncated_before: 0,
+        omitted_fields: 0,
+        links: &links,
+        identity: Identity {
+            worker,
+            test: Some("fetch_retries"),
+        },
+    };
+    encode(envelope)
+}
+
+fn logwise(arguments: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_logwise"))
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8(bytes.to_vec())
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn decodes_filters_and_reports_gaps() {
+    let mut stream = Vec::new();
+    stream.extend(frame(&REQUEST, 1, 1, 0, 10, None));
+    stream.extend(frame(&RENDER, 1, 2, 0, 11, Some(10)));
+    let mut corrupt = frame(&REQUEST, 1, 3, 0, 12, None);
+    corrupt[4] = 9;
+    let corrupt_at = stream.len();
+    stream.extend(&corrupt);
+    // Sequence 4 was dropped by the guest; 3 was lost after it.
+    stream.extend(frame(&FAILURE, 1, 5, 1, 12, Some(11)));
+    stream.extend(frame(&REQUEST, 2, 7, 0, 10, None));
+
+    let output = logwise(&[], &stream);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        lines(&output.stderr),
+        [format!(
+            "logwise: <stdin>: frame at byte {corrupt_at}: unsupported logwise ABI version 9; skipped {} bytes",
+            corrupt.len()
+        )]
+    );
+    let printed = lines(&output.stdout);
+    assert_eq!(
+        printed,
+        [
+            r#"2023-11-14T22:13:20.000000Z Info browser.fetch.request attempt=1 user="alice \"a\"" [#1 worker 1 test "fetch_retries" context 10]"#,
+            r#"2023-11-14T22:13:20.000000Z Info browser.render [#2 worker 1 test "fetch_retries" context 11 <- 10]"#,
+            "-- gap: worker 1: 2 envelopes missing between #2 and #5 (1 dropped by the guest, 1 lost after it)",
+            r#"2023-11-14T22:13:20.000000Z Error browser.fetch.failure attempt=5 user="alice \"a\"" [#5 worker 1 test "fetch_retries" context 12 <- 11 <- 10]"#,
+            "-- gap: worker 2: 6 envelopes before #7 are not in the input (0 dropped by the guest)",
+            r#"2023-11-14T22:13:20.000000Z Info browser.fetch.request attempt=7 user="alice \"a\"" [#7 worker 2 test "fetch_retries" context 10]"#,
+        ]
+    );
+
+    // Filters narrow the events but not the gap report, and JSON keeps gaps
+    // off stdout.
+    let clean = [
+        frame(&REQUEST, 1, 1, 0, 10, None),
+        frame(&FAILURE, 1, 3, 0, 10, None),
+        frame(&RENDER, 2, 1, 0, 0, None),
+    ]
+    .concat();
+    let output = logwise(
+        &["json", "--domain", "browser.fetch", "--severity", "warn"],
+        &clean,
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        lines(&output.stdout),
+        [concat!(
+            r#"{"v":1,"time":"2023-11-14T22:13:20.000000Z","monotonic_ns":3000000,"#,
+            r#""event":"browser.fetch.failure","package":"guest","target":"guest","module":"guest::fetch","#,
+            r#""domain":"browser.fetch","class":"operational","severity":"error","kind":"event","location":null,"#,
+            r#""context":10,"fields":[{"name":"attempt","privacy":"support","detail":"core","type":"u64","value":3},"#,
+            r#"{"name":"user","privacy":"local","detail":"core","type":"string","value":"alice \"a\""}],"#,
+            r#""message":null,"omitted_fields":0,"truncated_fields":0,"span":null,"metric":null,"#,
+            r#""sequence":3,"worker":1,"test":"fetch_retries","links":[],"dropped_before":0,"truncated_before":0}"#
+        )]
+    );
+    assert_eq!(
+        lines(&output.stderr),
+        [
+            "logwise: <stdin>: gap: worker 1: 1 envelopes missing between #1 and #3 (0 dropped by the guest, 1 lost after it)"
+        ]
+    );
+    let output = logwise(&["--event", "browser.render", "--worker", "2"], &clean);
+    assert_eq!(lines(&output.stdout).len(), 2, "the gap, then the event");
+    assert!(lines(&output.stdout)[1].contains("browser.render [#1 worker 2"));
+    let output = logwise(&["--event", "browser.fetch.req"], &clean);
+    assert!(
+        lines(&output.stdout)
+            .iter()
+            .all(|line| line.starts_with("-- gap:")),
+        "a name matches whole dotted segments"
+    );
+
+    // A frame cut off at the end of input is damage, reported once.
+    let output = logwise(&["gaps"], &stream[..stream.len() - 5]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(lines(&output.stderr)[1].ends_with("; the input ends inside it"));
+    assert_eq!(
+        lines(&output.stdout),
+        [
+            "-- gap: worker 1: 2 envelopes missing between #2 and #5 (1 dropped by the guest, 1 lost after it)",
+            "worker 1: 3 envelopes #1..#5, 2 missing, 1 dropped and 0 values truncated by the guest before #5",
+        ]
+    );
+
+    // Frames split across reads wait for the rest rather than resyncing.
+    let long = (1..=3_000)
+        .flat_map(|sequence| frame(&REQUEST, 3, sequence, 0, 10, None))
+        .collect::<Vec<_>>();
+    assert!(long.len() > 4 * 64 * 1024);
+    let output = logwise(&["gaps"], &long);
+    assert!(output.status.success());
+    assert_eq!(
+        lines(&output.stdout),
+        [
+            "worker 3: 3000 envelopes #1..#3000, 0 missing, 0 dropped and 0 values truncated by the guest before #3000"
+        ]
+    );
+
+    // A frame that claims more than any encoder writes is skipped rather
+    // than waited for, however much follows before the next frame.
+    let mut stream = frame(&REQUEST, 4, 1, 0, 10, None);
+    stream[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    stream.resize(stream.len() + 4 * 64 * 1024, 0);
+    stream.extend(frame(&REQUEST, 4, 2, 0, 10, None));
+    let output = logwise(&[], &stream);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        lines(&output.stderr),
+        [
+            "logwise: <stdin>: frame at byte 0: incomplete frame: need 4294967295 bytes, more than 65536; skipping to the next frame"
+        ]
+    );
+    assert!(
+        lines(&output.stdout)
+            .last()
+            .unwrap()
+            .contains("[#2 worker 4")
+    );
+
+    let output = logwise(&["--severity", "loud"], &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(lines(&output.stderr)[0].contains("unknown severity `loud`"));
+}
+
+#[test]
+fn resolves_version_2_records_against_their_sites() {
+    let span = WireSpan {
+        token: SpanToken::from_parts(5, 0),
+        timing: SpanTiming::WallTime,
+        warning_threshold: None,
+        completion: Some(WireSpanCompletion {
+            elapsed: Duration::from_millis(3),
+            threshold_exceeded: false,
+        }),
+    };
+    let stream = [
+        site(&REQUEST, 1),
+        record(&REQUEST, 1, 1, None),
+        site(&RENDER, 2),
+        record(&RENDER, 2, 2, Some(span)),
+        record(&REQUEST, 1, 3, None),
+        record(&FAILURE, 9, 4, None),
+    ]
+    .concat();
+    let undefined_at = stream.len() - record(&FAILURE, 9, 4, None).len();
+
+    let output = logwise(&[], &stream);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        lines(&output.stderr),
+        [format!(
+            "logwise: <stdin>: frame at byte {undefined_at}: event #4 of worker 1 names site 9, which the input has not defined"
+        )]
+    );
+    assert_eq!(
+        lines(&output.stdout),
+        [
+            r#"2023-11-14T22:13:20.000000Z Info browser.fetch.request attempt=1 user="alice \"a\"" [#1 worker 1 test "fetch_retries" context 10]"#,
+            r#"2023-11-14T22:13:20.000000Z Info browser.render span=completed timing=WallTime elapsed=3ms [#2 worker 1 test "fetch_retries" context 10]"#,
+            r#"2023-11-14T22:13:20.000000Z Info browser.fetch.request attempt=3 user="alice \"a\"" [#3 worker 1 test "fetch_retries" context 10]"#,
+        ]
+    );
+
+    let output = logwise(&["json", "--event", "browser.render"], &stream);
+    assert!(lines(&output.stdout)[0].contains(
+        r#""span":{"token":5,"lifecycle":"completed","timing":"wall_time","threshold_ns":null,"elapsed_ns":3000000,"threshold_exceeded":false}"#
+    ));
+}
+
+#[test]
+fn reads_past_a_log_file_header() {
+    let header = LogFileHeader {
+        process_id: 77,
+        process_name: "checkout".into(),
+        started: Duration::from_secs(1_700_000_000),
+    };
+    let file = [header.encode(), frame(&RENDER, 77, 1, 0, 0, None)].concat();
+
+    let output = logwise(&[], &file);
+    assert!(output.status.success());
+    assert_eq!(
+        lines(&output.stdout),
+        [
+            "-- log file: checkout (pid 77) started 2023-11-14T22:13:20.000000Z",
+            r#"2023-11-14T22:13:20.000000Z Info browser.render [#1 worker 77 test "fetch_retries"]"#,
+        ]
+    );
+    let output = logwise(&["json"], &file);
+    assert_eq!(lines(&output.stdout).len(), 1);
+}
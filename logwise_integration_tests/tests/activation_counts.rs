@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A count-bounded activation evaluates detail for exactly the next N
+//! matching events a sink receives, retires itself, and reports what it
+//! captured, whether the count or a TTL ran out first.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use logwise::{Dispatch, Interest};
+use logwise_runtime::{
+    ActivationId, ActivationResult, DetailLevel, Filter, InMemorySink, OverflowPolicy, RateLimit,
+    Runtime,
+};
+
+fn query(evaluations: &AtomicUsize) {
+    logwise::event!(
+        "integration.counted.query",
+        table = support("orders"),
+        detail sql = local({
+            evaluations.fetch_add(1, Ordering::Relaxed);
+            "select * from orders"
+        }),
+    );
+}
+
+fn activate_next(runtime: &Runtime, events: u64, ttl: Option<Duration>) -> ActivationId {
+    let result = runtime.activate_next(
+        Filter::new().event("integration.counted.query"),
+        Interest::DETAIL_LOCAL,
+        events,
+        ttl,
+    );
+    let ActivationResult::Enabled(id) = result else {
+        panic!("unexpected result {result:?}");
+    };
+    id
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn count_bounded_activations_capture_the_next_events_then_retire() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let evaluations = AtomicUsize::new(0);
+    // The call site has to be in the catalog before it can be activated.
+    query(&evaluations);
+    assert_eq!(evaluations.load(Ordering::Relaxed), 0);
+    let sink = runtime.add_local_sink(
+        Arc::new(InMemorySink::new(64, 64, OverflowPolicy::DropNewest)),
+        Filter::new().event("integration.counted.query"),
+        DetailLevel::Core,
+    );
+
+    // Zero events activates nothing.
+    let generation = runtime.generation();
+    assert_eq!(
+        runtime.activate_next(
+            Filter::new().event("integration.counted.query"),
+            Interest::DETAIL_LOCAL,
+            0,
+            None,
+        ),
+        ActivationResult::NoEvents
+    );
+    assert_eq!(runtime.generation(), generation);
+
+    let counted = activate_next(runtime, 3, None);
+    let [listed] = &runtime.activations()[..] else {
+        panic!("one activation");
+    };
+    assert_eq!(listed.id, counted);
+    assert_eq!((listed.events_left, listed.remaining), (Some(3), None));
+    query(&evaluations);
+    let live = runtime.activation(counted).expect("live activation");
+    assert_eq!((live.captured, live.events_left), (1, Some(2)));
+    for _ in 0..4 {
+        query(&evaluations);
+    }
+    assert_eq!(evaluations.load(Ordering::Relaxed), 3);
+    assert!(runtime.activations().is_empty());
+    // Its ID still finds it, with what it captured, until it is taken.
+    let retired = runtime.activation(counted).expect("retired activation");
+    assert_eq!((retired.captured, retired.events_left), (3, Some(0)));
+    assert_eq!(runtime.take_retired_activations(), [retired]);
+    assert!(runtime.take_retired_activations().is_empty());
+    assert_eq!(runtime.activation(counted), None);
+
+    // A TTL that runs out first retires it with what it captured so far.
+    let expired = activate_next(runtime, 100, Some(Duration::ZERO));
+    query(&evaluations);
+    assert_eq!(evaluations.load(Ordering::Relaxed), 3);
+    let [retired] = &runtime.take_retired_activations()[..] else {
+        panic!("one retired activation");
+    };
+    assert_eq!(retired.id, expired);
+    assert_eq!(retired.captured, 0);
+    assert_eq!(retired.remaining, Some(Duration::ZERO));
+
+    // Without a TTL there is no deadline to extend, but it can be
+    // cancelled, and a cancelled activation is not reported as retired.
+    let cancelled = activate_next(runtime, 10, None);
+    query(&evaluations);
+    assert_eq!(runtime.activations()[0].captured, 1);
+    assert!(!runtime.extend(cancelled, Duration::from_secs(60)));
+    assert!(runtime.cancel(cancelled));
+    assert_eq!(runtime.activation(cancelled), None);
+    query(&evaluations);
+    assert_eq!(evaluations.load(Ordering::Relaxed), 4);
+    assert!(runtime.take_retired_activations().is_empty());
+
+    // Only events a sink receives are captured: not those a rate limit
+    // suppresses, and not those no sink selects.
+    let limited = activate_next(runtime, 2, None);
+    let limit = runtime.add_rate_limit(
+        Filter::new().event("integration.counted.query"),
+        RateLimit::new(1, Duration::from_secs(3_600)),
+    );
+    for _ in 0..3 {
+        query(&evaluations);
+    }
+    assert!(runtime.remove_rate_limit(limit));
+    assert!(runtime.remove_sink(sink));
+    query(&evaluations);
+    let [listed] = &runtime.activations()[..] else {
+        panic!("one activation");
+    };
+    assert_eq!(listed.id, limited);
+    assert_eq!((listed.captured, listed.events_left), (1, Some(1)));
+    assert!(runtime.cancel(limited));
+    assert_eq!(runtime.delivery_stats().retired_activations_dropped, 0);
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! An error in a context activates detail for that context's whole tree,
+//! reports the firing, and is held back for a root already activated, past
+//! `max_active`, and past the rate limit.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use logwise::{
+    Class, ContextToken, Detail, Dispatch, EventRef, FieldMetadata, Interest, Kind, Metadata,
+    Privacy, Severity,
+};
+use logwise_runtime::{
+    ActivationRule, DetailLevel, Filter, InMemorySink, OverflowPolicy, OwnedValue, RateLimit,
+};
+
+static FIELDS: &[FieldMetadata] = &[];
+static STEP: Metadata = Metadata {
+    event_name: "integration.rules.step",
+    package: "logwise_integration_tests",
+    target: "activation_rules",
+    module: "activation_rules",
+    domain: None,
+    severity: Severity::Trace,
+    class: Class::Diagnostic,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+static FAILED: Metadata = Metadata {
+    event_name: "integration.rules.failed",
+    severity: Severity::Error,
+    class: Class::Operational,
+    ..STEP
+};
+static RETRYING: Metadata = Metadata {
+    event_name: "integration.rules.retrying",
+    severity: Severity::Warn,
+    ..FAILED
+};
+static REFUSED: Metadata = Metadata {
+    event_name: "integration.refused",
+    ..FAILED
+};
+
+fn detailed(interest: Interest) -> bool {
+    interest.wants(Privacy::LocalOnly, Detail::Detail)
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn failing_contexts_activate_their_tree_within_bounds() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let root = logwise::context::child(ContextToken::NONE, "rules.root");
+    let task = logwise::context::child(root, "rules.task");
+    let retry = logwise::context::child(root, "rules.retry");
+    let other = logwise::context::child(ContextToken::NONE, "rules.other");
+    let reports = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    runtime.add_local_sink(
+        reports.clone(),
+        Filter::new().event("logwise.activation_rule.fired"),
+        DetailLevel::Core,
+    );
+
+    let rule = runtime.add_activation_rule(
+        ActivationRule::new(
+            Filter::new().name("integration.rules"),
+            Interest::DETAIL_LOCAL,
+            Duration::from_secs(60),
+        )
+        .max_active(1),
+    );
+    // The rule observes the sites it can fire on, but not milder ones.
+    assert!(runtime.interest(&FAILED).is_contextual());
+    assert!(!runtime.interest(&RETRYING).is_contextual());
+    assert!(!detailed(runtime.contextual_interest(&STEP, retry)));
+
+    // A failure in one task details its siblings under the same root.
+    runtime.emit(EventRef::structured(&FAILED, task, &[]));
+    assert!(detailed(runtime.contextual_interest(&STEP, retry)));
+    assert!(runtime.context_is_active(root));
+    assert!(!detailed(runtime.contextual_interest(&STEP, other)));
+    let fired = reports.drain();
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].context, task);
+    let field = |name| {
+        fired[0]
+            .fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value.clone())
+    };
+    assert_eq!(
+        field("event"),
+        Some(OwnedValue::String("integration.rules.failed".into()))
+    );
+    assert_eq!(field("root"), Some(OwnedValue::U64(root.into_parts().0)));
+    assert_eq!(field("ttl_ms"), Some(OwnedValue::U64(60_000)));
+    assert!(field("rule").is_some());
+
+    // The same root again, and another root past `max_active`, are held
+    // back; no context, or too mild an event, fires nothing at all.
+    runtime.emit(EventRef::structured(&FAILED, retry, &[]));
+    runtime.emit(EventRef::structured(&FAILED, other, &[]));
+    runtime.emit(EventRef::structured(&FAILED, ContextToken::NONE, &[]));
+    runtime.emit(EventRef::structured(&RETRYING, other, &[]));
+    assert!(!runtime.context_is_active(other));
+    assert!(reports.drain().is_empty());
+    assert_eq!(runtime.delivery_stats().activation_rules_suppressed, 2);
+
+    // A removed rule fires no more; its activation runs out its TTL.
+    assert!(runtime.remove_activation_rule(rule));
+    assert!(!runtime.remove_activation_rule(rule));
+    runtime.emit(EventRef::structured(&FAILED, other, &[]));
+    assert!(!runtime.context_is_active(other));
+    assert!(runtime.context_is_active(root));
+    assert_eq!(runtime.delivery_stats().activation_rules_suppressed, 2);
+
+    // The rate limit holds back a call site that keeps failing new roots.
+    runtime.add_activation_rule(
+        ActivationRule::new(
+            Filter::new().event("integration.refused"),
+            Interest::DETAIL_LOCAL,
+            Duration::from_secs(60),
+        )
+        .rate_limit(RateLimit::new(1, Duration::from_secs(3_600))),
+    );
+    let first = logwise::context::child(ContextToken::NONE, "rules.first");
+    let second = logwise::context::child(ContextToken::NONE, "rules.second");
+    runtime.emit(EventRef::structured(&REFUSED, first, &[]));
+    runtime.emit(EventRef::structured(&REFUSED, second, &[]));
+    assert!(runtime.context_is_active(first));
+    assert!(!runtime.context_is_active(second));
+    assert_eq!(reports.drain().len(), 1);
+    assert_eq!(runtime.delivery_stats().activation_rules_suppressed, 3);
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The control plane answers the line protocol over a private Unix socket and
+//! loopback TCP: catalog, activation and cancellation, recorder reads in both
+//! views, and delivery stats.
+
+#![cfg(all(unix, not(target_arch = "wasm32")))]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+
+use logwise_runtime::{ControlAddress, ControlPlane, DetailLevel, Filter, FlightRecorder};
+
+const DOMAIN: logwise::Domain = logwise::domain!("integration.control");
+
+fn request(attempt: u64) {
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.control.request",
+        attempt = support(attempt),
+        user = local("alice"),
+    );
+}
+
+/// Sends one request and returns its data lines and final line.
+fn ask(stream: &mut BufReader<impl Read + Write>, line: &str) -> (Vec<String>, String) {
+    writeln!(stream.get_mut(), "{line}").unwrap();
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        assert_ne!(stream.read_line(&mut line).unwrap(), 0, "connection closed");
+        let line = line.trim_end().to_owned();
+        if line.starts_with("ok") || line.starts_with("error") {
+            return (lines, line);
+        }
+        lines.push(line);
+    }
+}
+
+#[test]
+fn control_plane_serves_the_line_protocol() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let recorder = Arc::new(FlightRecorder::new(64, 256));
+    let sink = runtime.add_local_sink(
+        recorder.clone(),
+        Filter::new().domain("integration.control"),
+        DetailLevel::Core,
+    );
+    request(0);
+
+    // A directory other users can enter is refused before anything is bound.
+    let open = std::env::temp_dir().join(format!("logwise-control-open-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&open);
+    std::fs::create_dir(&open).unwrap();
+    std::fs::set_permissions(&open, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let refused = ControlPlane::new(runtime).bind_unix(open.join("control.sock"));
+    assert_eq!(
+        refused.err().map(|error| error.kind()),
+        Some(std::io::ErrorKind::PermissionDenied)
+    );
+    assert!(!open.join("control.sock").exists());
+    std::fs::remove_dir(&open).unwrap();
+
+    let directory = std::env::temp_dir().join(format!("logwise-control-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let path = directory.join("control.sock");
+    let socket = ControlPlane::new(runtime)
+        .recorder(recorder)
+        .bind_unix(&path)
+        .expect("bind control socket");
+    assert_eq!(socket.address(), &ControlAddress::Unix(path.clone()));
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let mode = std::fs::metadata(&directory).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+    let mut stream = BufReader::new(UnixStream::connect(&path).unwrap());
+
+    let (sites, status) = ask(&mut stream, "catalog");
+    assert!(status.starts_with("ok "), "{status}");
+    assert!(sites.contains(
+        &"integration.control.request event info operational logwise_integration_tests control_plane"
+            .to_owned()
+    ));
+
+    let (results, status) = ask(
+        &mut stream,
+        "activate 30s integration.control.request=debug+detail,integration.control.missing",
+    );
+    assert_eq!(status, "ok");
+    assert_eq!(
+        results,
+        [
+            "integration.control.request=debug+detail enabled",
+            "integration.control.missing unknown_selector",
+        ]
+    );
+    assert!(
+        ask(&mut stream, "activate soon integration.control")
+            .1
+            .starts_with("error invalid ttl")
+    );
+    assert_eq!(
+        ask(&mut stream, "activate 30s").1,
+        "error missing directives"
+    );
+    assert!(
+        ask(&mut stream, "activate 30s a=b=c")
+            .1
+            .starts_with("error invalid logwise directive")
+    );
+    assert_eq!(
+        ask(
+            &mut stream,
+            "cancel integration.control.request=debug+detail"
+        ),
+        (Vec::new(), "ok 1".to_owned())
+    );
+    assert_eq!(
+        ask(&mut stream, "cancel integration.control.request").1,
+        "ok 0"
+    );
+
+    // The remote view never carries local-only values; the local view does.
+    let (remote, status) = ask(&mut stream, "read 0 remote");
+    assert_eq!(status, "ok 1 busy=0 dropped=0 overwritten=0");
+    assert_eq!(remote.len(), 1);
+    assert!(remote[0].starts_with(r#"record 1 {"v":1,"#));
+    assert!(remote[0].contains(r#""event":"integration.control.request""#));
+    assert!(!remote[0].contains("alice"));
+    let (local, _) = ask(&mut stream, "read 0 local");
+    assert!(local[0].contains(r#""value":"alice""#));
+
+    // Paging: a limit returns the oldest records and a cursor past them.
+    for attempt in 1..=3 {
+        request(attempt);
+    }
+    let (page, status) = ask(&mut stream, "read 1 local 2");
+    assert_eq!(page.len(), 2);
+    assert_eq!(status, "ok 3 busy=0 dropped=0 overwritten=0");
+    let (page, status) = ask(&mut stream, "read 3 local 2");
+    assert_eq!(page.len(), 1);
+    assert_eq!(status, "ok 4 busy=0 dropped=0 overwritten=0");
+    assert!(ask(&mut stream, "read 4 local").0.is_empty());
+    assert!(
+        ask(&mut stream, "read x local")
+            .1
+            .starts_with("error invalid cursor")
+    );
+    assert!(
+        ask(&mut stream, "read 0 everyone")
+            .1
+            .starts_with("error invalid view")
+    );
+
+    let (stats, status) = ask(&mut stream, "stats");
+    assert_eq!(status, "ok");
+    assert!(stats.contains(&"sink_panics 0".to_owned()));
+    assert!(stats.contains(&"recorder_accepted 4".to_owned()));
+    assert_eq!(
+        ask(&mut stream, "shutdown").1,
+        "error unknown request `shutdown`"
+    );
+
+    drop(stream);
+    drop(socket);
+    assert!(!path.exists());
+    std::fs::remove_dir(&directory).unwrap();
+
+    // Loopback TCP, for platforms without Unix sockets. Without a recorder
+    // there is nothing to read.
+    let error = ControlPlane::new(runtime)
+        .bind_tcp("0.0.0.0:0")
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    let socket = ControlPlane::new(runtime).bind_tcp("127.0.0.1:0").unwrap();
+    let ControlAddress::Tcp(address) = socket.address() else {
+        panic!("bound over TCP");
+    };
+    let mut stream = BufReader::new(TcpStream::connect(address).unwrap());
+    assert_eq!(ask(&mut stream, "stats").1, "ok");
+    assert_eq!(
+        ask(&mut stream, "read 0 local").1,
+        "error no flight recorder is attached"
+    );
+    drop(stream);
+    drop(socket);
+
+    assert!(runtime.remove_sink(sink));
+}
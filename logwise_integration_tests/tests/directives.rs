@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Directive strings parse into filters and interest, round-trip through
+//! `Display`, name the token they reject, configure the runtime from
+//! `LOGWISE` at `init`, and drive TTL activations that can be listed,
+//! extended and cancelled.
+
+use core::time::Duration;
+
+use logwise::{Class, Dispatch, FieldMetadata, Interest, Kind, Metadata, Privacy, Severity};
+use logwise_runtime::{
+    ActivationResult, DIRECTIVES_ENV, DetailLevel, Directive, Filter, parse_directives,
+};
+
+static FIELDS: &[FieldMetadata] = &[];
+static REQUEST: Metadata = Metadata {
+    event_name: "app.http.request",
+    package: "app",
+    target: "app",
+    module: "app::http",
+    domain: Some(logwise::domain!("app.http")),
+    severity: Severity::Trace,
+    class: Class::Diagnostic,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+static SPAWN: Metadata = Metadata {
+    event_name: "some_executor.task.spawn",
+    package: "some_executor",
+    target: "some_executor",
+    module: "some_executor::task",
+    domain: None,
+    severity: Severity::Debug,
+    class: Class::Diagnostic,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn directives_parse_round_trip_and_configure_the_runtime() {
+    let text = "some_executor=debug,class:forensic,app.http.request=trace+detail";
+    let directives = parse_directives(text).unwrap();
+    assert_eq!(
+        directives,
+        vec![
+            Directive {
+                filter: Filter::new()
+                    .name("some_executor")
+                    .minimum_severity(Severity::Debug),
+                ..Directive::default()
+            },
+            Directive {
+                filter: Filter::new().class(Class::Forensic),
+                ..Directive::default()
+            },
+            Directive {
+                filter: Filter::new()
+                    .name("app.http.request")
+                    .minimum_severity(Severity::Trace),
+                detail: DetailLevel::Full,
+                privacy: Privacy::LocalOnly,
+            },
+        ]
+    );
+    let rendered: Vec<_> = directives.iter().map(ToString::to_string).collect();
+    assert_eq!(rendered.join(","), text);
+    assert_eq!(
+        directives[2].interest(),
+        Interest::CORE_SUPPORT
+            .union(Interest::CORE_LOCAL)
+            .union(Interest::DETAIL_SUPPORT)
+            .union(Interest::DETAIL_LOCAL)
+    );
+
+    for canonical in [
+        "",
+        "info",
+        "detail+support",
+        "name:debug",
+        "my_crate::net&domain:app.http&event:app.http.request&class:metric&target:wasm&descendants:7=warn",
+        "context:3/1=critical+secret",
+    ] {
+        let directive: Directive = canonical.parse().unwrap();
+        assert_eq!(directive.to_string(), canonical);
+    }
+    let filter: Filter = "domain:app.http&class:diagnostic=error".parse().unwrap();
+    assert_eq!(
+        filter,
+        Filter::new()
+            .domain("app.http")
+            .class(Class::Diagnostic)
+            .minimum_severity(Severity::Error)
+    );
+    assert_eq!(filter.to_string(), "domain:app.http&class:diagnostic=error");
+
+    let rejected = |text: &str| parse_directives(text).unwrap_err();
+    assert_eq!(rejected("app,class:forensics").token, "class:forensics");
+    assert_eq!(rejected("app=verbose").token, "verbose");
+    assert_eq!(rejected("app=debug+info").token, "info");
+    assert_eq!(rejected("flavor:mild").token, "flavor:mild");
+    assert_eq!(rejected("app&app").token, "app");
+    assert_eq!(rejected("app=").token, "app=");
+    assert_eq!(
+        rejected("context:seven").to_string(),
+        "invalid logwise directive `context:seven`: is not a context ID"
+    );
+    assert_eq!(
+        "app=trace+detail".parse::<Filter>().unwrap_err().token,
+        "detail"
+    );
+
+    // Configuration is applied before the catalog has seen anything, does
+    // not expire, and is resolved into the call site's cached interest.
+    #[cfg(not(target_arch = "wasm32"))]
+    // SAFETY: this file is its own test binary, with this as its only test,
+    // so no other thread is reading the environment.
+    unsafe {
+        std::env::set_var(DIRECTIVES_ENV, text);
+    }
+    let runtime = logwise_runtime::init().expect("install runtime");
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let request = runtime.interest(&REQUEST);
+        assert!(!request.is_contextual());
+        assert!(request.wants(Privacy::LocalOnly, logwise::Detail::Detail));
+        assert!(
+            runtime
+                .contextual_interest(&REQUEST, logwise::ContextToken::NONE)
+                .wants(Privacy::LocalOnly, logwise::Detail::Detail)
+        );
+        assert!(
+            !runtime
+                .contextual_interest(&SPAWN, logwise::ContextToken::NONE)
+                .wants(Privacy::LocalOnly, logwise::Detail::Detail)
+        );
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = DIRECTIVES_ENV;
+
+    runtime.interest(&SPAWN);
+    let detailed = || {
+        runtime
+            .contextual_interest(&SPAWN, logwise::ContextToken::NONE)
+            .wants(Privacy::LocalOnly, logwise::Detail::Detail)
+    };
+    let results = runtime
+        .activate_directives(
+            "some_executor.task=debug+detail,nowhere.at.all",
+            Duration::from_secs(60),
+        )
+        .unwrap();
+    let [
+        ActivationResult::Enabled(spawn),
+        ActivationResult::UnknownSelector,
+    ] = results[..]
+    else {
+        panic!("unexpected results {results:?}");
+    };
+    assert!(detailed());
+
+    // A live activation is listed with the time it has left, and can be
+    // extended or ended early by ID; configuration is not listed.
+    let listed = runtime.activations();
+    let snapshot = listed.iter().find(|listed| listed.id == spawn).unwrap();
+    assert_eq!(
+        snapshot.filter,
+        Filter::new()
+            .name("some_executor.task")
+            .minimum_severity(Severity::Debug)
+    );
+    assert!(
+        snapshot
+            .remaining
+            .is_some_and(|remaining| remaining <= Duration::from_secs(60))
+    );
+    assert_eq!(listed.len(), 1);
+    let generation = runtime.generation();
+    assert!(runtime.extend(spawn, Duration::from_secs(60)));
+    assert_ne!(runtime.generation(), generation);
+    let remaining = |id| {
+        runtime
+            .activations()
+            .into_iter()
+            .find(|listed| listed.id == id)
+            .and_then(|listed| listed.remaining)
+    };
+    assert!(remaining(spawn).unwrap() > Duration::from_secs(60));
+    assert!(runtime.cancel(spawn));
+    assert!(!detailed());
+    assert_eq!(remaining(spawn), None);
+    assert!(!runtime.cancel(spawn));
+    assert!(!runtime.extend(spawn, Duration::from_secs(60)));
+    assert_eq!(
+        runtime
+            .activate_directives("some_executor=bogus", Duration::from_secs(60))
+            .unwrap_err()
+            .token,
+        "bogus"
+    );
+}
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Every dispatch is stamped once, and the same stamp reaches every sink,
+//! the flight recorder and the wasm wire.
+
+use core::time::Duration;
+use std::sync::Arc;
+
+use logwise_runtime::{
+    DetailLevel, Filter, FlightRecorder, InMemorySink, OverflowPolicy, RecorderView, Timestamp,
+};
+use logwise_runtime_wasm::{Identity, Transport, WireTimestamp};
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn dispatches_are_stamped_once_for_every_view() {
+    assert_eq!(
+        Timestamp {
+            monotonic: Duration::ZERO,
+            wall: Duration::from_secs(1_700_000_000) + Duration::from_micros(123_456),
+        }
+        .to_string(),
+        "2023-11-14T22:13:20.123456Z"
+    );
+    assert_eq!(
+        Timestamp {
+            monotonic: Duration::ZERO,
+            wall: Duration::from_secs(1_709_164_800),
+        }
+        .to_string(),
+        "2024-02-29T00:00:00.000000Z"
+    );
+
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let local = Arc::new(InMemorySink::new(8, 64, OverflowPolicy::DropNewest));
+    let remote = Arc::new(InMemorySink::new(8, 64, OverflowPolicy::DropNewest));
+    let recorder = Arc::new(FlightRecorder::with_shards(8, 64, 1));
+    let filter = Filter::new().event("integration.timestamps");
+    let local_id = runtime.add_local_sink(local.clone(), filter.clone(), DetailLevel::Core);
+    let remote_id = runtime.add_remote_sink(remote.clone(), filter.clone(), DetailLevel::Core);
+    let recorder_id = runtime.add_local_sink(recorder.clone(), filter, DetailLevel::Core);
+
+    let before = Timestamp::now();
+    logwise::event!("integration.timestamps.first", step = support(1_u8));
+    logwise::event!("integration.timestamps.second", step = support(2_u8));
+    let after = Timestamp::now();
+
+    let local_events = local.drain();
+    let remote_events = remote.drain();
+    assert_eq!(local_events.len(), 2);
+    assert_eq!(remote_events.len(), 2);
+    for (local_event, remote_event) in local_events.iter().zip(&remote_events) {
+        assert_eq!(
+            local_event.timestamp, remote_event.timestamp,
+            "projections of one dispatch share its stamp"
+        );
+        assert!(before.monotonic <= local_event.timestamp.monotonic);
+        assert!(local_event.timestamp.monotonic <= after.monotonic);
+        assert!(local_event.timestamp.wall >= Duration::from_secs(1_600_000_000));
+    }
+    assert!(local_events[0].timestamp.monotonic <= local_events[1].timestamp.monotonic);
+
+    let read = recorder.tail(2, RecorderView::Remote);
+    assert_eq!(read.records[1].event.timestamp, local_events[1].timestamp);
+    let rendered = read.records[1].to_string();
+    assert!(
+        rendered.contains(&local_events[1].timestamp.to_string()),
+        "{rendered}"
+    );
+
+    logwise::event!("integration.timestamps.wire", step = support(3_u8));
+    let stamped = local.drain().pop().expect("wire event").timestamp;
+    let transport = Transport::new(64).with_timestamps();
+    let mut output = [0_u8; 512];
+    let mut scratch = [0_u8; 64];
+    let metadata = local_events[0].metadata;
+    let encoded = transport
+        .encode(
+            logwise::EventRef::structured(metadata, logwise::ContextToken::NONE, &[]),
+            Some(WireTimestamp {
+                monotonic: stamped.monotonic,
+                wall: stamped.wall,
+            }),
+            &[],
+            Identity::default(),
+            &mut output,
+            &mut scratch,
+        )
+        .unwrap();
+    let flags = u16::from_le_bytes(encoded.bytes[6..8].try_into().unwrap());
+    assert_eq!(flags & 0b10, 0b10, "timestamp trailer flag");
+    let trailer = &encoded.bytes[encoded.bytes.len() - 16..];
+    assert_eq!(
+        u64::from_le_bytes(trailer[8..].try_into().unwrap()),
+        stamped.wall.as_nanos() as u64
+    );
+
+    assert!(runtime.remove_sink(local_id));
+    assert!(runtime.remove_sink(remote_id));
+    assert!(runtime.remove_sink(recorder_id));
+}
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Field filters run after projection: they see only what the sink's view
+//! contains, leave interest alone, and count what they reject.
+
+use std::sync::Arc;
+
+use logwise::{Dispatch, Interest};
+use logwise_runtime::{
+    Comparison, DetailLevel, FieldFilter, Filter, InMemorySink, OverflowPolicy, OwnedValue,
+};
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn field_filters_see_only_the_projected_view() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let remote = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let local = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let filter = Filter::new().domain("integration.field_filters");
+    let remote_id = runtime.add_remote_sink(remote.clone(), filter.clone(), DetailLevel::Core);
+    let local_id = runtime.add_local_sink(local.clone(), filter, DetailLevel::Core);
+
+    let server_errors = FieldFilter::compare("status", Comparison::Ge, 500);
+    let acme = FieldFilter::compare("tenant", Comparison::Eq, "acme");
+    assert!(runtime.set_field_filter(remote_id, Some(server_errors.clone().or(acme.clone()))));
+    // `owner` is local-only, so only the local view can match on it.
+    let owned = FieldFilter::compare("owner", Comparison::Eq, "ops");
+    assert!(runtime.set_field_filter(local_id, Some(owned.clone())));
+
+    const DOMAIN: logwise::Domain = logwise::domain!("integration.field_filters");
+    let rejected_before = runtime.delivery_stats().field_filter_rejections;
+    for (status, tenant, owner) in [
+        (200_u64, "acme", "ops"),
+        (503, "globex", "dev"),
+        (404, "globex", "ops"),
+        (500, "initech", "dev"),
+    ] {
+        logwise::event!(
+            domain: DOMAIN,
+            name: "integration.field_filters.response",
+            status = support(status),
+            tenant = support(tenant),
+            owner = local(owner),
+        );
+    }
+    let statuses = |sink: &InMemorySink| -> Vec<OwnedValue> {
+        sink.drain()
+            .into_iter()
+            .map(|event| event.fields[0].value.clone())
+            .collect()
+    };
+    assert_eq!(
+        statuses(&remote),
+        [
+            OwnedValue::U64(200),
+            OwnedValue::U64(503),
+            OwnedValue::U64(500)
+        ]
+    );
+    assert_eq!(
+        statuses(&local),
+        [OwnedValue::U64(200), OwnedValue::U64(404)]
+    );
+    assert_eq!(
+        runtime.delivery_stats().field_filter_rejections - rejected_before,
+        3
+    );
+
+    // The same local-only predicate on a remote sink never matches, and its
+    // negation always does: the field is simply not in the view.
+    assert!(runtime.set_field_filter(remote_id, Some(owned.clone())));
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.field_filters.owned",
+        owner = local("ops"),
+    );
+    assert!(remote.drain().is_empty());
+    assert!(runtime.set_field_filter(remote_id, Some(!owned)));
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.field_filters.owned",
+        owner = local("ops"),
+    );
+    assert_eq!(remote.drain().len(), 1);
+    assert_eq!(local.drain().len(), 2);
+
+    // Comparisons: numbers across widths and floats, strings against
+    // `Display` values, and mismatched types never match.
+    assert!(
+        runtime.set_field_filter(
+            remote_id,
+            Some(
+                FieldFilter::compare("latency", Comparison::Lt, 1.5)
+                    .and(FieldFilter::compare("retries", Comparison::Ne, 0))
+                    .and(FieldFilter::compare("route", Comparison::Gt, "/a"))
+                    .and(!FieldFilter::compare("retries", Comparison::Eq, "2")),
+            ),
+        )
+    );
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.field_filters.timing",
+        latency = support(1_i32),
+        retries = support(2_i8),
+        route = support(logwise::ValueRef::display(&"/b")),
+    );
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.field_filters.timing",
+        latency = support(1.5_f64),
+        retries = support(2_i8),
+        route = support(logwise::ValueRef::display(&"/b")),
+    );
+    assert_eq!(remote.drain().len(), 1);
+
+    // Interest is decided before projection, so a field filter never lowers
+    // it, and clearing one restores plain delivery.
+    static CALLSITE: logwise::Metadata = logwise::Metadata {
+        event_name: "integration.field_filters.callsite",
+        package: "logwise_integration_tests",
+        target: "field_filters",
+        module: "field_filters",
+        domain: Some(DOMAIN),
+        severity: logwise::Severity::Info,
+        class: logwise::Class::Operational,
+        kind: logwise::Kind::Event,
+        location: None,
+        fields: &[],
+    };
+    assert_ne!(runtime.interest(&CALLSITE), Interest::NONE);
+    assert!(runtime.set_field_filter(remote_id, None));
+    logwise::event!(domain: DOMAIN, name: "integration.field_filters.cleared");
+    assert_eq!(remote.drain().len(), 1);
+
+    assert!(runtime.remove_sink(remote_id));
+    assert!(runtime.remove_sink(local_id));
+    assert!(!runtime.set_field_filter(remote_id, Some(server_errors.and(acme))));
+}
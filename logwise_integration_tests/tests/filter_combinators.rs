@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Filters compose with and/or/not over static selectors, kind, package and
+//! source file, are decided once per call site in `interest`, and keep
+//! their directive round trip.
+
+use std::sync::Arc;
+
+use logwise::{
+    Class, ContextToken, Dispatch, FieldMetadata, Interest, Kind, Location, Metadata, Severity,
+};
+use logwise_runtime::{DetailLevel, Filter, InMemorySink, OverflowPolicy};
+
+static FIELDS: &[FieldMetadata] = &[];
+
+const fn site(
+    event_name: &'static str,
+    package: &'static str,
+    severity: Severity,
+    class: Class,
+    kind: Kind,
+    file: &'static str,
+) -> Metadata {
+    Metadata {
+        event_name,
+        package,
+        target: package,
+        module: package,
+        domain: None,
+        severity,
+        class,
+        kind,
+        location: Some(Location::new(file, 1, 1)),
+        fields: FIELDS,
+    }
+}
+
+static SPAWNED: Metadata = site(
+    "combinators.executor.spawned",
+    "combinators_executor",
+    Severity::Debug,
+    Class::Diagnostic,
+    Kind::Event,
+    "executor/src/task.rs",
+);
+static POLLED: Metadata = site(
+    "combinators.executor.polled",
+    "combinators_executor",
+    Severity::Trace,
+    Class::Diagnostic,
+    Kind::Event,
+    "executor/src/task.rs",
+);
+static AUDIT: Metadata = site(
+    "combinators.app.audit",
+    "combinators_app",
+    Severity::Info,
+    Class::Forensic,
+    Kind::Event,
+    "app/src/audit.rs",
+);
+static SLOW: Metadata = site(
+    "combinators.app.slow",
+    "combinators_app",
+    Severity::Warn,
+    Class::Performance,
+    Kind::Event,
+    "app/src/net/http.rs",
+);
+static QUIET: Metadata = site(
+    "combinators.app.quiet",
+    "combinators_app",
+    Severity::Info,
+    Class::Operational,
+    Kind::Event,
+    "app/src/main.rs",
+);
+static HITS: Metadata = site(
+    "combinators.app.hits",
+    "combinators_app",
+    Severity::Info,
+    Class::Metric,
+    Kind::Counter,
+    "app/src/net/http.rs",
+);
+static ALL: [&Metadata; 6] = [&SPAWNED, &POLLED, &AUDIT, &SLOW, &QUIET, &HITS];
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn filters_compose_and_are_decided_per_call_site() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let selected = |filter: Filter| {
+        let sink = Arc::new(InMemorySink::new(8, 64, OverflowPolicy::DropNewest));
+        let id = runtime.add_remote_sink(sink, filter, DetailLevel::Core);
+        let names: Vec<_> = ALL
+            .iter()
+            .filter(|metadata| runtime.interest(metadata) != Interest::NONE)
+            .map(|metadata| metadata.event_name.rsplit('.').next().unwrap())
+            .collect();
+        assert!(runtime.remove_sink(id));
+        names
+    };
+
+    let executor = Filter::new()
+        .package("combinators_executor")
+        .except(Filter::new().event("combinators.executor.polled"));
+    assert_eq!(selected(executor.clone()), ["spawned"]);
+    let urgent = Filter::new()
+        .class(Class::Forensic)
+        .or(Filter::new().minimum_severity(Severity::Warn));
+    assert_eq!(selected(urgent.clone()), ["audit", "slow"]);
+    assert_eq!(selected(Filter::new().kind(Kind::Counter)), ["hits"]);
+    assert_eq!(
+        selected(Filter::new().file("app/src/net")),
+        ["slow", "hits"]
+    );
+    assert_eq!(
+        selected(Filter::new().file("src/task.rs")),
+        ["spawned", "polled"]
+    );
+    assert_eq!(
+        selected(
+            Filter::new()
+                .package("combinators_app")
+                .and(!urgent.clone())
+        ),
+        ["quiet", "hits"]
+    );
+    assert_eq!(
+        selected(Filter::any_of([executor.clone(), urgent.clone()])),
+        ["spawned", "audit", "slow"]
+    );
+    assert!(selected(Filter::any_of([])).is_empty());
+    assert_eq!(selected(Filter::none_of([])).len(), ALL.len());
+
+    // A context selector anywhere in the expression leaves the static part
+    // decided per site and only the rest to each event.
+    let root = logwise::context::child(ContextToken::NONE, "combinators.root");
+    let sink = Arc::new(InMemorySink::new(8, 64, OverflowPolicy::DropNewest));
+    let id = runtime.add_remote_sink(
+        sink,
+        Filter::new()
+            .kind(Kind::Counter)
+            .or(Filter::new().class(Class::Forensic).context(root, true)),
+        DetailLevel::Core,
+    );
+    assert_eq!(runtime.interest(&HITS), Interest::CORE_SUPPORT);
+    assert_eq!(runtime.interest(&AUDIT), Interest::CONTEXTUAL);
+    assert_eq!(runtime.interest(&QUIET), Interest::NONE);
+    let child = logwise::context::child(root, "combinators.child");
+    assert_eq!(
+        runtime.contextual_interest(&AUDIT, child),
+        Interest::CORE_SUPPORT
+    );
+    assert_eq!(
+        runtime.contextual_interest(&AUDIT, ContextToken::NONE),
+        Interest::NONE
+    );
+    assert!(runtime.remove_sink(id));
+
+    for (filter, text) in [
+        (
+            executor,
+            "package:combinators_executor&!event:combinators.executor.polled",
+        ),
+        (urgent.clone(), "class:forensic|severity:warn"),
+        (
+            Filter::new()
+                .name("app")
+                .and(Filter::new().name("other"))
+                .and(urgent.clone())
+                .minimum_severity(Severity::Info),
+            "app&(class:forensic|severity:warn)&(other)=info",
+        ),
+        (
+            Filter::new()
+                .kind(Kind::Measurement)
+                .file("src/net")
+                .except(!urgent.clone()),
+            "file:src/net&kind:measurement&!(!(class:forensic|severity:warn))",
+        ),
+        (Filter::any_of([]), "!*"),
+        (
+            Filter::any_of([urgent, Filter::new()]),
+            "(class:forensic|severity:warn)|*",
+        ),
+    ] {
+        assert_eq!(filter.to_string(), text);
+        assert_eq!(text.parse::<Filter>().unwrap(), filter, "{text}");
+    }
+    assert_eq!("(a|b".parse::<Filter>().unwrap_err().token, "(a|b");
+    assert_eq!("a)".parse::<Filter>().unwrap_err().token, ")");
+    assert_eq!("a&|b".parse::<Filter>().unwrap_err().token, "|b");
+    assert_eq!(
+        "kind:gauge".parse::<Filter>().unwrap_err().token,
+        "kind:gauge"
+    );
+}
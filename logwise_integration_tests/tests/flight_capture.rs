@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Errors and threshold breaches freeze the records around them as
+//! incidents that later traffic cannot overwrite, kept by ID or handed to a
+//! sink, and an error loop is held back by the rate limit and the keep
+//! bound.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use logwise::{
+    Class, ContextToken, Detail, FieldMetadata, Kind, Metadata, Privacy, Severity, SpanTiming,
+    SpanToken, ValueRef,
+};
+use logwise_runtime::{
+    EventSink, Filter, FlightCapture, FlightRecord, FlightRecorder, InMemorySink, OverflowPolicy,
+    ProjectedEvent, ProjectedField, ProjectedSpan, RateLimit, RecorderView, SpanLifecycle,
+    Timestamp,
+};
+
+static FIELDS: &[FieldMetadata] = &[
+    FieldMetadata::new("step", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("user", Privacy::LocalOnly, Detail::Core),
+];
+static STEP: Metadata = Metadata {
+    event_name: "integration.capture.step",
+    package: "logwise_integration_tests",
+    target: "flight_capture",
+    module: "flight_capture",
+    domain: None,
+    severity: Severity::Debug,
+    class: Class::Diagnostic,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+static FAILED: Metadata = Metadata {
+    event_name: "integration.capture.failed",
+    severity: Severity::Error,
+    class: Class::Operational,
+    ..STEP
+};
+static REFUSED: Metadata = Metadata {
+    event_name: "integration.capture.refused",
+    ..FAILED
+};
+static SLOW: Metadata = Metadata {
+    event_name: "integration.capture.slow",
+    severity: Severity::Warn,
+    class: Class::Performance,
+    kind: Kind::Span,
+    ..STEP
+};
+
+fn emit(recorder: &FlightRecorder, metadata: &'static Metadata, context: ContextToken, step: u64) {
+    let field = |name, privacy, value| ProjectedField {
+        name,
+        privacy,
+        detail: Detail::Core,
+        value,
+        pseudonymized: false,
+    };
+    let span = (metadata.kind == Kind::Span).then_some(ProjectedSpan {
+        token: SpanToken::from_parts(step, 0),
+        timing: SpanTiming::ActiveTime,
+        warning_threshold: Some(Duration::from_millis(5)),
+        lifecycle: SpanLifecycle::Completed {
+            elapsed: Duration::from_millis(9),
+            threshold_exceeded: true,
+        },
+    });
+    recorder.emit(ProjectedEvent {
+        metadata,
+        context,
+        timestamp: Timestamp::now(),
+        fields: vec![
+            field("step", Privacy::SupportSafe, ValueRef::U64(step)),
+            field("user", Privacy::LocalOnly, ValueRef::Str("alice")),
+        ],
+        message: None,
+        omitted_fields: 0,
+        span,
+        metric: None,
+    });
+}
+
+fn steps(records: &[FlightRecord]) -> Vec<u64> {
+    records.iter().map(|record| record.sequence).collect()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn triggers_freeze_windows_as_rate_limited_incidents() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let once_an_hour = RateLimit::new(1, Duration::from_secs(3_600));
+    let recorder = FlightRecorder::with_shards(8, 64, 1).with_capture(
+        FlightCapture::new(runtime)
+            .window(3, 2)
+            .rate_limit(once_an_hour)
+            .keep(2),
+    );
+    let none = ContextToken::NONE;
+    for step in 1..=5 {
+        emit(&recorder, &STEP, none, step);
+    }
+    emit(&recorder, &FAILED, none, 6);
+    emit(&recorder, &STEP, none, 7);
+    assert!(recorder.incidents().is_empty(), "waiting for the window");
+    emit(&recorder, &STEP, none, 8);
+    let [first] = recorder.incidents()[..] else {
+        panic!("one incident");
+    };
+    for step in 9..=30 {
+        emit(&recorder, &STEP, none, step);
+    }
+
+    // Later traffic overwrote the ring, not the incident.
+    let incident = recorder.incident(first, RecorderView::Local).unwrap();
+    assert_eq!(incident.trigger, 6);
+    assert_eq!(steps(&incident.records), [3, 4, 5, 6, 7, 8]);
+    assert_eq!(incident.missed, 0);
+    assert_eq!(incident.records[0].event.fields.len(), 2);
+
+    // The same call site again is rate limited; a breach is its own trigger.
+    emit(&recorder, &FAILED, none, 31);
+    emit(&recorder, &SLOW, none, 32);
+    emit(&recorder, &STEP, none, 33);
+    emit(&recorder, &STEP, none, 34);
+    let stats = recorder.capture_stats();
+    assert_eq!((stats.triggered, stats.suppressed), (2, 1));
+    let second = recorder.incidents()[1];
+    assert_eq!(
+        steps(
+            &recorder
+                .incident(second, RecorderView::Local)
+                .unwrap()
+                .records
+        ),
+        [29, 30, 31, 32, 33, 34]
+    );
+
+    // Past `keep`, the oldest goes.
+    emit(&recorder, &REFUSED, none, 35);
+    emit(&recorder, &STEP, none, 36);
+    emit(&recorder, &STEP, none, 37);
+    assert_eq!(recorder.capture_stats().evicted, 1);
+    let ids = recorder.incidents();
+    assert_eq!(ids.len(), 2);
+    assert!(recorder.incident(first, RecorderView::Local).is_none());
+
+    // Taking an incident removes it; a remote view holds no local value.
+    let taken = recorder
+        .take_incident(ids[1], RecorderView::Remote)
+        .unwrap();
+    assert_eq!(taken.trigger, 35);
+    assert!(
+        taken
+            .records
+            .iter()
+            .all(|record| record.event.fields.len() == 1 && record.event.omitted_fields == 1)
+    );
+    assert_eq!(recorder.incidents(), [ids[0]]);
+
+    // A context trigger follows the runtime's lineage, and a delivered
+    // incident goes to the sink in its view instead of being kept.
+    let root = logwise::context::child(ContextToken::NONE, "capture.root");
+    let child = logwise::context::child(root, "capture.child");
+    let sink = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let recorder = FlightRecorder::with_shards(8, 64, 1).with_capture(
+        FlightCapture::new(runtime)
+            .trigger(Filter::new().context(root, true))
+            .threshold_breaches(false)
+            .window(1, 0)
+            .deliver_to(sink.clone(), RecorderView::Remote),
+    );
+    emit(&recorder, &SLOW, none, 1);
+    emit(&recorder, &STEP, none, 2);
+    emit(&recorder, &STEP, child, 3);
+    assert!(recorder.incidents().is_empty());
+    assert_eq!(recorder.capture_stats().delivered, 1);
+    let delivered = sink.drain();
+    assert_eq!(delivered.len(), 2);
+    assert_eq!(delivered[1].context, child);
+    assert!(delivered.iter().all(|event| event.fields.len() == 1));
+}
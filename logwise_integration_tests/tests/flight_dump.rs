@@ -0,0 +1,263 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Flight recorder dumps load back into the records, cursors and loss totals
+//! they were written from, in the view they were written in, and the panic
+//! hook writes one before the previous hook runs.
+
+use std::time::Duration;
+
+use logwise::{
+    Class, ContextToken, Detail, FieldMetadata, Kind, Location, Metadata, Privacy, Severity,
+    SpanTiming, SpanToken, ValueRef,
+};
+use logwise_runtime::{
+    EventSink, FlightCursor, FlightDump, FlightRecorder, Histogram, HistogramBucket, MetricValue,
+    OwnedValue, ProjectedEvent, ProjectedField, ProjectedSpan, RecorderView, RetentionBand,
+    SpanLifecycle, Timestamp,
+};
+
+static FIELDS: &[FieldMetadata] = &[
+    FieldMetadata::new("path", Privacy::LocalOnly, Detail::Core),
+    FieldMetadata::new("bytes", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("ratio", Privacy::SupportSafe, Detail::Detail),
+];
+static UPLOAD: Metadata = Metadata {
+    event_name: "integration.dump.upload",
+    package: "logwise_integration_tests",
+    target: "flight_dump",
+    module: "flight_dump",
+    domain: Some(logwise::Domain::new("integration.dump")),
+    severity: Severity::Warn,
+    class: Class::Operational,
+    kind: Kind::Span,
+    location: Some(Location::new("tests/flight_dump.rs", 12, 5)),
+    fields: FIELDS,
+};
+static LATENCY: Metadata = Metadata {
+    event_name: "integration.dump.latency",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Metric,
+    kind: Kind::Measurement,
+    location: None,
+    fields: &[],
+    ..UPLOAD
+};
+
+fn upload(recorder: &FlightRecorder, path: &str, bytes: u64) {
+    let field = |name, privacy, detail, value| ProjectedField {
+        name,
+        privacy,
+        detail,
+        value,
+        pseudonymized: false,
+    };
+    recorder.emit(ProjectedEvent {
+        metadata: &UPLOAD,
+        context: ContextToken::from_parts(7, 3),
+        timestamp: Timestamp {
+            monotonic: Duration::from_nanos(1_234_567),
+            wall: Duration::from_nanos(1_700_000_000_123_456_789),
+        },
+        fields: vec![
+            field(
+                "path",
+                Privacy::LocalOnly,
+                Detail::Core,
+                ValueRef::Str(path),
+            ),
+            field(
+                "bytes",
+                Privacy::SupportSafe,
+                Detail::Core,
+                ValueRef::U64(bytes),
+            ),
+            field(
+                "ratio",
+                Privacy::SupportSafe,
+                Detail::Detail,
+                ValueRef::F64(f64::NAN),
+            ),
+        ],
+        message: Some(format_args!("uploading {path}")),
+        omitted_fields: 1,
+        span: Some(ProjectedSpan {
+            token: SpanToken::from_parts(11, 2),
+            timing: SpanTiming::ActiveTime,
+            warning_threshold: Some(Duration::from_millis(5)),
+            lifecycle: SpanLifecycle::Completed {
+                elapsed: Duration::from_millis(8),
+                threshold_exceeded: true,
+            },
+        }),
+        metric: None,
+    });
+}
+
+fn latency(recorder: &FlightRecorder) {
+    let histogram = MetricValue::Histogram(Histogram {
+        count: 2,
+        sum: 3.5,
+        min: 1.0,
+        max: 2.5,
+        buckets: vec![
+            HistogramBucket {
+                upper_bound: 1.0,
+                count: 1,
+            },
+            HistogramBucket {
+                upper_bound: f64::INFINITY,
+                count: 1,
+            },
+        ],
+    });
+    recorder.emit(ProjectedEvent {
+        metadata: &LATENCY,
+        context: ContextToken::NONE,
+        timestamp: Timestamp::now(),
+        fields: Vec::new(),
+        message: None,
+        omitted_fields: 0,
+        span: None,
+        metric: Some(&histogram),
+    });
+}
+
+fn load(recorder: &FlightRecorder, view: RecorderView) -> FlightDump {
+    let mut bytes = Vec::new();
+    let read = recorder.dump(&mut bytes, view).unwrap();
+    let dump = FlightDump::load(&bytes[..]).unwrap();
+    assert_eq!(dump.read.records.len(), read.records.len());
+    dump
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn dumps_load_back_into_the_records_they_were_written_from() {
+    let recorder = FlightRecorder::with_shards(3, 64, 1);
+    upload(&recorder, "/srv/overwritten", 1);
+    latency(&recorder);
+    upload(&recorder, "/srv/\"quoted\"\n\u{1f600}", u64::MAX);
+    upload(&recorder, "/srv/c", 3);
+
+    let dump = load(&recorder, RecorderView::Local);
+    let live = recorder.read_since(FlightCursor(0), RecorderView::Local);
+    assert_eq!(dump.view, RecorderView::Local);
+    assert_eq!(dump.accepted, 4);
+    assert_eq!(dump.read.next_cursor, FlightCursor(4));
+    assert_eq!(dump.read.overwritten_total, 1);
+    assert_eq!(dump.read.overwritten_by_band, live.overwritten_by_band);
+    assert_eq!(dump.read.rejected_by_band, live.rejected_by_band);
+    assert_eq!(dump.read.omitted_fields, live.omitted_fields);
+    assert_eq!(dump.catalog.len(), 2);
+    for (loaded, live) in dump.read.records.iter().zip(&live.records) {
+        assert_eq!(loaded.sequence, live.sequence);
+        assert_eq!(loaded.to_string(), live.to_string());
+        let (loaded, live) = (&loaded.event, &live.event);
+        assert_eq!(loaded.metadata.event_name, live.metadata.event_name);
+        assert_eq!(loaded.metadata.location, live.metadata.location);
+        assert_eq!(loaded.context, live.context);
+        assert_eq!(loaded.timestamp, live.timestamp);
+        assert_eq!(loaded.message, live.message);
+        assert_eq!(loaded.span, live.span);
+        assert_eq!(loaded.metric, live.metric);
+        assert_eq!(loaded.omitted_fields, live.omitted_fields);
+        assert_eq!(loaded.fields.len(), live.fields.len());
+    }
+    let path = &dump.read.records[1].event.fields;
+    assert_eq!(
+        path[0].value,
+        OwnedValue::from("/srv/\"quoted\"\n\u{1f600}")
+    );
+    assert_eq!(path[1].value, OwnedValue::U64(u64::MAX));
+    assert!(matches!(path[2].value, OwnedValue::F64(ratio) if ratio.is_nan()));
+    let metric = &dump.read.records[0].event.metric;
+    assert!(
+        matches!(metric, Some(MetricValue::Histogram(histogram)) if histogram.buckets[1].upper_bound.is_infinite())
+    );
+
+    // The same sites load to the same metadata.
+    let again = load(&recorder, RecorderView::Local);
+    assert!(std::ptr::eq(dump.catalog[0], again.catalog[0]));
+
+    // A remote dump never holds a local value or a message.
+    let remote = load(&recorder, RecorderView::Remote);
+    assert_eq!(remote.view, RecorderView::Remote);
+    let upload = &remote.read.records[1].event;
+    assert_eq!(
+        upload
+            .fields
+            .iter()
+            .map(|field| field.name)
+            .collect::<Vec<_>>(),
+        ["bytes", "ratio"]
+    );
+    assert_eq!(upload.message, None);
+    assert_eq!(upload.omitted_fields, 2);
+
+    // A cut-short or foreign file is an error, not an empty dump.
+    let mut bytes = Vec::new();
+    recorder.dump(&mut bytes, RecorderView::Local).unwrap();
+    let error = FlightDump::load(&bytes[..bytes.len() / 2]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(FlightDump::load(&b"{\"v\":1}\n"[..]).is_err());
+
+    // A dump from before retention bands loads with no band counts.
+    let mut text = String::from_utf8(bytes).unwrap();
+    for key in [",\"overwritten_by_band\"", ",\"rejected_by_band\""] {
+        let start = text.find(key).unwrap();
+        let end = start + text[start..].find('}').unwrap() + 1;
+        text.replace_range(start..end, "");
+    }
+    let older = FlightDump::load(text.as_bytes()).unwrap();
+    assert_eq!(older.read.overwritten_total, 1);
+    assert_eq!(older.read.overwritten_by_band, [0; RetentionBand::COUNT]);
+    assert_eq!(older.read.rejected_by_band, [0; RetentionBand::COUNT]);
+    assert_eq!(older.read.records.len(), dump.read.records.len());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn the_panic_hook_dumps_the_recorder_before_the_previous_hook() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use logwise_runtime::{DetailLevel, Filter, PanicDump, install_panic_hook_with_dump};
+
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let recorder = Arc::new(FlightRecorder::with_shards(16, 256, 1));
+    let id = runtime.add_local_sink(
+        recorder.clone(),
+        Filter::new().event("foreign.text"),
+        DetailLevel::Core,
+    );
+    let path = std::env::temp_dir().join(format!("logwise-panic-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    static DUMPED_FIRST: AtomicBool = AtomicBool::new(false);
+    let original_hook = std::panic::take_hook();
+    let hook_path = path.clone();
+    std::panic::set_hook(Box::new(move |_| {
+        DUMPED_FIRST.store(hook_path.exists(), Ordering::Relaxed);
+    }));
+    let registration = install_panic_hook_with_dump(PanicDump::new(recorder, &path));
+    let _ = std::panic::catch_unwind(|| panic!("dumped panic marker"));
+    registration.restore();
+    std::panic::set_hook(original_hook);
+    assert!(runtime.remove_sink(id));
+
+    assert!(DUMPED_FIRST.load(Ordering::Relaxed));
+    let dump = FlightDump::load(std::fs::File::open(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let record = &dump
+        .read
+        .records
+        .last()
+        .expect("the panic was recorded")
+        .event;
+    assert_eq!(record.metadata.event_name, "foreign.text");
+    assert!(record.fields.iter().any(|field| matches!(
+        &field.value,
+        OwnedValue::String(text) if text.contains("dumped panic marker")
+    )));
+}
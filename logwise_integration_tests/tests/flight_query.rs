@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Flight recorder queries select by filter, context subtree, severity and
+//! class bounds and field predicates, page with a limit, and never let a
+//! remote predicate test a local-only value.
+
+use logwise::{
+    Class, ContextToken, Detail, FieldMetadata, Kind, Metadata, Privacy, Severity, ValueRef,
+};
+use logwise_runtime::{
+    Comparison, EventSink, FieldFilter, Filter, FlightCursor, FlightQuery, FlightRecorder,
+    ProjectedEvent, ProjectedField, RecorderView, Timestamp,
+};
+
+static FIELDS: &[FieldMetadata] = &[
+    FieldMetadata::new("status", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("user", Privacy::LocalOnly, Detail::Core),
+];
+static REQUEST: Metadata = Metadata {
+    event_name: "integration.query.request",
+    package: "logwise_integration_tests",
+    target: "flight_query",
+    module: "flight_query",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Operational,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+static FAILURE: Metadata = Metadata {
+    event_name: "integration.query.failure",
+    severity: Severity::Error,
+    ..REQUEST
+};
+static SLOW: Metadata = Metadata {
+    event_name: "integration.query.slow",
+    severity: Severity::Warn,
+    class: Class::Performance,
+    ..REQUEST
+};
+
+fn emit(
+    recorder: &FlightRecorder,
+    metadata: &'static Metadata,
+    context: ContextToken,
+    status: u64,
+    user: &str,
+) {
+    let field = |name, privacy, value| ProjectedField {
+        name,
+        privacy,
+        detail: Detail::Core,
+        value,
+        pseudonymized: false,
+    };
+    recorder.emit(ProjectedEvent {
+        metadata,
+        context,
+        timestamp: Timestamp::now(),
+        fields: vec![
+            field("status", Privacy::SupportSafe, ValueRef::U64(status)),
+            field("user", Privacy::LocalOnly, ValueRef::Str(user)),
+        ],
+        message: None,
+        omitted_fields: 0,
+        span: None,
+        metric: None,
+    });
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn queries_select_subtrees_bounds_and_fields_with_loss_accounting() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let root = logwise::context::child(ContextToken::NONE, "query.root");
+    let child = logwise::context::child(root, "query.child");
+    let grandchild = logwise::context::child(child, "query.grandchild");
+    let other = logwise::context::child(ContextToken::NONE, "query.other");
+
+    let recorder = FlightRecorder::with_shards(8, 64, 1);
+    emit(&recorder, &REQUEST, other, 200, "overwritten");
+    emit(&recorder, &REQUEST, root, 200, "alice");
+    emit(&recorder, &FAILURE, child, 500, "bob");
+    emit(&recorder, &SLOW, grandchild, 200, "carol");
+    emit(&recorder, &FAILURE, other, 503, "dave");
+    emit(&recorder, &REQUEST, grandchild, 404, "erin");
+    emit(&recorder, &FAILURE, grandchild, 502, "frank");
+    emit(&recorder, &REQUEST, other, 200, "grace");
+    emit(&recorder, &SLOW, child, 200, "heidi");
+    let sequences = |query: FlightQuery, view| {
+        recorder
+            .query_since(runtime, FlightCursor(0), &query, view)
+            .records
+            .iter()
+            .map(|record| record.sequence)
+            .collect::<Vec<_>>()
+    };
+
+    // A context with its descendants follows the runtime's lineage; without
+    // them only the context itself matches.
+    let subtree = FlightQuery::new().filter(Filter::new().context(root, true));
+    assert_eq!(
+        sequences(subtree.clone(), RecorderView::Local),
+        [2, 3, 4, 6, 7, 9]
+    );
+    assert_eq!(
+        sequences(
+            FlightQuery::new().filter(Filter::new().context(child, false)),
+            RecorderView::Local
+        ),
+        [3, 9]
+    );
+
+    // Severity and class bounds narrow the filter.
+    assert_eq!(
+        sequences(
+            subtree.clone().maximum_severity(Severity::Warn),
+            RecorderView::Local
+        ),
+        [2, 4, 6, 9]
+    );
+    assert_eq!(
+        sequences(
+            FlightQuery::new()
+                .filter(Filter::new().minimum_severity(Severity::Warn))
+                .maximum_severity(Severity::Warn),
+            RecorderView::Local
+        ),
+        [4, 9]
+    );
+    assert_eq!(
+        sequences(
+            FlightQuery::new().classes([Class::Performance, Class::Forensic]),
+            RecorderView::Local
+        ),
+        [4, 9]
+    );
+
+    // Field predicates see the record as the view projects it.
+    let server_errors =
+        subtree
+            .clone()
+            .fields(FieldFilter::compare("status", Comparison::Ge, 500u64));
+    assert_eq!(sequences(server_errors, RecorderView::Remote), [3, 7]);
+    let bob = FlightQuery::new().fields(FieldFilter::compare("user", Comparison::Eq, "bob"));
+    assert_eq!(sequences(bob.clone(), RecorderView::Local), [3]);
+    assert!(sequences(bob, RecorderView::Remote).is_empty());
+
+    // A limit pages through the oldest matches; the cursor resumes after
+    // the last one returned, and loss totals are the recorder's.
+    let paged = subtree.clone().limit(4);
+    let first = recorder.query_since(runtime, FlightCursor(0), &paged, RecorderView::Remote);
+    assert_eq!(first.records.len(), 4);
+    assert_eq!(first.next_cursor, FlightCursor(6));
+    assert_eq!(first.overwritten_total, 1);
+    assert_eq!(first.omitted_fields, 4);
+    assert!(first.records.iter().all(|record| {
+        record.event.fields.len() == 1 && record.event.fields[0].name == "status"
+    }));
+    let second = recorder.query_since(runtime, first.next_cursor, &paged, RecorderView::Remote);
+    assert_eq!(
+        second
+            .records
+            .iter()
+            .map(|record| record.sequence)
+            .collect::<Vec<_>>(),
+        [7, 9]
+    );
+    assert_eq!(second.next_cursor, FlightCursor(9));
+
+    // The tail keeps the newest matches.
+    let tail = recorder.query_tail(runtime, &subtree.limit(2), RecorderView::Local);
+    assert_eq!(
+        tail.records
+            .iter()
+            .map(|record| record.sequence)
+            .collect::<Vec<_>>(),
+        [7, 9]
+    );
+    assert_eq!(tail.overwritten_total, 1);
+}
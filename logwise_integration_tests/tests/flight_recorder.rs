@@ -8,8 +8,8 @@ use logwise::{
     Class, ContextToken, Detail, FieldMetadata, Kind, Metadata, Privacy, Severity, ValueRef,
 };
 use logwise_runtime::{
-    DetailLevel, EventSink, Filter, FlightCursor, FlightRecorder, ProjectedEvent, ProjectedField,
-    RecorderView,
+    DetailLevel, EventSink, Filter, FlightCursor, FlightRecorder, FlightRetention, ProjectedEvent,
+    ProjectedField, RecorderView, RetentionBand, Timestamp,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -52,18 +52,38 @@ static DIRECT_METADATA: Metadata = Metadata {
     fields: DIRECT_FIELDS,
 };
 
+static CHATTER_METADATA: Metadata = Metadata {
+    event_name: "integration.flight.chatter",
+    class: Class::Diagnostic,
+    ..DIRECT_METADATA
+};
+static FAILURE_METADATA: Metadata = Metadata {
+    event_name: "integration.flight.failure",
+    severity: Severity::Error,
+    class: Class::Operational,
+    ..DIRECT_METADATA
+};
+
 fn record_value(recorder: &FlightRecorder, value: u64) {
+    record_site(recorder, &DIRECT_METADATA, value);
+}
+
+fn record_site(recorder: &FlightRecorder, metadata: &'static Metadata, value: u64) {
     recorder.emit(ProjectedEvent {
-        metadata: &DIRECT_METADATA,
+        metadata,
         context: ContextToken::NONE,
+        timestamp: Timestamp::now(),
         fields: vec![ProjectedField {
             name: "value",
             privacy: Privacy::SupportSafe,
             detail: Detail::Core,
             value: ValueRef::U64(value),
+            pseudonymized: false,
         }],
         message: None,
         omitted_fields: 0,
+        span: None,
+        metric: None,
     });
 }
 
@@ -252,3 +272,75 @@ fn concurrent_writers_and_readers_preserve_order_and_accounting() {
     assert_eq!(final_read.dropped_total, stats.dropped);
     assert_eq!(final_read.overwritten_total, stats.overwritten);
 }
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn reserved_bands_keep_errors_through_a_debug_storm() {
+    let retention = FlightRetention::new()
+        .reserve(RetentionBand::Error, 2)
+        .reserve(RetentionBand::Forensic, 1);
+    let recorder = FlightRecorder::with_retention(6, 64, 1, retention);
+    record_site(&recorder, &FAILURE_METADATA, 1);
+    record_value(&recorder, 2);
+    for value in 3..=40 {
+        record_site(&recorder, &CHATTER_METADATA, value);
+    }
+    record_site(&recorder, &FAILURE_METADATA, 41);
+    record_site(&recorder, &CHATTER_METADATA, 42);
+
+    let read = recorder.read_since(FlightCursor(0), RecorderView::Local);
+    let sequences: Vec<_> = read.records.iter().map(|record| record.sequence).collect();
+    assert_eq!(sequences, [1, 2, 39, 40, 41, 42]);
+    assert_eq!(read.next_cursor, FlightCursor(42));
+    let stats = recorder.stats();
+    assert_eq!(stats.overwritten, 36);
+    assert_eq!(
+        stats.overwritten_by_band[RetentionBand::Chatter as usize],
+        36
+    );
+    assert_eq!(read.overwritten_by_band, stats.overwritten_by_band);
+
+    // Past its reservation a band shares the rest oldest-first, so the
+    // oldest error is the next to go.
+    record_site(&recorder, &FAILURE_METADATA, 43);
+    record_site(&recorder, &FAILURE_METADATA, 44);
+    let read = recorder.read_since(FlightCursor(0), RecorderView::Local);
+    let sequences: Vec<_> = read.records.iter().map(|record| record.sequence).collect();
+    assert_eq!(sequences, [2, 40, 41, 42, 43, 44]);
+    assert_eq!(read.overwritten_by_band[RetentionBand::Error as usize], 1);
+
+    // With every record within its reservation, a new record of another band
+    // is turned away. That is retention, not lock contention.
+    let recorder = FlightRecorder::with_retention(
+        2,
+        64,
+        1,
+        FlightRetention::new().reserve(RetentionBand::Error, 2),
+    );
+    record_site(&recorder, &FAILURE_METADATA, 1);
+    record_site(&recorder, &FAILURE_METADATA, 2);
+    record_site(&recorder, &CHATTER_METADATA, 3);
+    let stats = recorder.stats();
+    assert_eq!(
+        (stats.accepted, stats.dropped, stats.overwritten),
+        (2, 0, 0)
+    );
+    assert_eq!(stats.rejected_by_band[RetentionBand::Chatter as usize], 1);
+    let read = recorder.read_since(FlightCursor(0), RecorderView::Local);
+    assert_eq!(read.rejected_by_band, stats.rejected_by_band);
+
+    // Reservations are granted from the forensic band down.
+    let recorder = FlightRecorder::with_retention(
+        4,
+        64,
+        2,
+        FlightRetention::new()
+            .reserve(RetentionBand::Chatter, 4)
+            .reserve(RetentionBand::Error, 3)
+            .reserve(RetentionBand::Forensic, 3),
+    );
+    let granted = recorder.retention();
+    assert_eq!(granted.reserved(RetentionBand::Forensic), 3);
+    assert_eq!(granted.reserved(RetentionBand::Error), 1);
+    assert_eq!(granted.reserved(RetentionBand::Chatter), 0);
+}
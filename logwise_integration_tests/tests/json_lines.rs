@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The JSON Lines writer emits the documented version-1 schema with correct
+//! escaping, directly and behind `AsyncSink`.
+
+use core::time::Duration;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use logwise::{
+    Class, ContextToken, Detail, FieldMetadata, Kind, Location, Metadata, Privacy, Severity,
+    SpanTiming, SpanToken,
+};
+use logwise_runtime::{
+    AsyncSink, DetailLevel, Filter, JSON_LINES_SCHEMA_VERSION, JsonLinesWriter, OverflowPolicy,
+    OwnedEventWriter, OwnedField, OwnedProjectedEvent, OwnedValue, ProjectedSpan, SpanLifecycle,
+    Timestamp,
+};
+
+struct SharedBytes(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBytes {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+static FIELDS: &[FieldMetadata] = &[];
+static METADATA: Metadata = Metadata {
+    event_name: "integration.json.request",
+    package: "logwise_integration_tests",
+    target: "json_lines",
+    module: "json_lines",
+    domain: Some(logwise::domain!("app.http")),
+    severity: Severity::Warn,
+    class: Class::Forensic,
+    kind: Kind::Span,
+    location: Some(Location::new("json_lines.rs", 12, 5)),
+    fields: FIELDS,
+};
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test(worker))]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn events_encode_as_versioned_json_lines() {
+    let field = |name, privacy, detail, value| OwnedField {
+        name,
+        privacy,
+        detail,
+        value,
+        pseudonymized: false,
+    };
+    let event = OwnedProjectedEvent {
+        metadata: &METADATA,
+        context: ContextToken::from_parts(7, 0),
+        timestamp: Timestamp {
+            monotonic: Duration::from_nanos(1_500),
+            wall: Duration::from_secs(1_700_000_000),
+        },
+        fields: vec![
+            field(
+                "path",
+                Privacy::SupportSafe,
+                Detail::Core,
+                OwnedValue::String("/a\"b\\c\n\u{1}é".into()),
+            ),
+            field(
+                "ok",
+                Privacy::LocalOnly,
+                Detail::Detail,
+                OwnedValue::Bool(true),
+            ),
+            field(
+                "delta",
+                Privacy::LocalOnly,
+                Detail::Core,
+                OwnedValue::I64(-3),
+            ),
+            field(
+                "big",
+                Privacy::SupportSafe,
+                Detail::Core,
+                OwnedValue::U64(u64::MAX),
+            ),
+            field(
+                "ratio",
+                Privacy::SupportSafe,
+                Detail::Core,
+                OwnedValue::F64(0.5),
+            ),
+            field(
+                "bad",
+                Privacy::SupportSafe,
+                Detail::Core,
+                OwnedValue::F64(f64::NAN),
+            ),
+        ],
+        message: Some("tab\there".into()),
+        omitted_fields: 1,
+        truncated_fields: 2,
+        span: Some(ProjectedSpan {
+            token: SpanToken::from_parts(9, 0),
+            timing: SpanTiming::WallTime,
+            warning_threshold: Some(Duration::from_millis(1)),
+            lifecycle: SpanLifecycle::Completed {
+                elapsed: Duration::from_millis(2),
+                threshold_exceeded: true,
+            },
+        }),
+        metric: None,
+    };
+
+    let mut writer = JsonLinesWriter::new(Vec::new());
+    writer.write_event(&event).unwrap();
+    let line = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(JSON_LINES_SCHEMA_VERSION, 1);
+    assert_eq!(
+        line,
+        concat!(
+            r#"{"v":1,"time":"2023-11-14T22:13:20.000000Z","monotonic_ns":1500,"#,
+            r#""event":"integration.json.request","package":"logwise_integration_tests","#,
+            r#""target":"json_lines","module":"json_lines","domain":"app.http","#,
+            r#""class":"forensic","severity":"warn","kind":"span","#,
+            r#""location":{"file":"json_lines.rs","line":12,"column":5},"context":7,"#,
+            r#""fields":[{"name":"path","privacy":"support","detail":"core","type":"string","value":"/a\"b\\c\n\u0001é"},"#,
+            r#"{"name":"ok","privacy":"local","detail":"detail","type":"bool","value":true},"#,
+            r#"{"name":"delta","privacy":"local","detail":"core","type":"i64","value":-3},"#,
+            r#"{"name":"big","privacy":"support","detail":"core","type":"u64","value":18446744073709551615},"#,
+            r#"{"name":"ratio","privacy":"support","detail":"core","type":"f64","value":0.5},"#,
+            r#"{"name":"bad","privacy":"support","detail":"core","type":"f64","value":"NaN"}],"#,
+            r#""message":"tab\there","omitted_fields":1,"truncated_fields":2,"#,
+            r#""span":{"token":9,"lifecycle":"completed","timing":"wall_time","threshold_ns":1000000,"#,
+            r#""elapsed_ns":2000000,"threshold_exceeded":true},"metric":null}"#,
+            "\n"
+        )
+    );
+
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let bytes = Arc::new(Mutex::new(Vec::new()));
+    let sink = AsyncSink::new(
+        JsonLinesWriter::new(SharedBytes(bytes.clone())),
+        16,
+        64,
+        OverflowPolicy::DropNewest,
+    );
+    let id = runtime.add_remote_sink(
+        Arc::new(sink.clone()),
+        Filter::new().event("integration.json.async"),
+        DetailLevel::Core,
+    );
+    logwise::event!("integration.json.async", route = support("/x"));
+    logwise::event!("integration.json.async.private", user = local("alice"));
+    sink.flush_blocking().expect("flush");
+    assert!(runtime.remove_sink(id));
+
+    let written = String::from_utf8(bytes.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = written.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(r#"{"v":1,"time":""#));
+    assert!(lines[0].contains(r#""fields":[{"name":"route","privacy":"support","detail":"core","type":"string","value":"/x"}]"#));
+    assert!(lines[1].contains(r#""fields":[],"message":null,"omitted_fields":1"#));
+    assert!(!written.contains("alice"));
+}
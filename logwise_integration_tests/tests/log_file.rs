@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Native log files hold `logwise_v1` envelopes after a process header: the
+//! reader gets back what the writer was given, skips damage, stops cleanly
+//! at a torn final frame and seeks by sequence number.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use core::time::Duration;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Write};
+
+use logwise::{Class, ContextToken, Detail, FieldMetadata, Kind, Metadata, Privacy, Severity};
+use logwise_runtime::{OwnedEventWriter, OwnedField, OwnedProjectedEvent, OwnedValue, Timestamp};
+use logwise_runtime_wasm::{LogFileHeader, LogFileReader, LogFileWriter, WireValue};
+
+static FIELDS: &[FieldMetadata] = &[
+    FieldMetadata::new("attempt", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("user", Privacy::LocalOnly, Detail::Core),
+];
+static METADATA: Metadata = Metadata {
+    event_name: "integration.log_file.request",
+    package: "logwise_integration_tests",
+    target: "log_file",
+    module: "log_file",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Operational,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+
+fn record(attempt: u64) -> OwnedProjectedEvent {
+    OwnedProjectedEvent {
+        metadata: &METADATA,
+        context: ContextToken::from_parts(attempt, 0),
+        timestamp: Timestamp {
+            monotonic: Duration::from_millis(attempt),
+            wall: Duration::from_secs(1_700_000_000),
+        },
+        fields: vec![
+            OwnedField {
+                name: "attempt",
+                privacy: Privacy::SupportSafe,
+                detail: Detail::Core,
+                value: OwnedValue::U64(attempt),
+                pseudonymized: false,
+            },
+            OwnedField {
+                name: "user",
+                privacy: Privacy::LocalOnly,
+                detail: Detail::Core,
+                value: OwnedValue::from("pseudonym"),
+                pseudonymized: true,
+            },
+        ],
+        message: Some(format!("attempt {attempt}")),
+        omitted_fields: 0,
+        truncated_fields: 0,
+        span: None,
+        metric: None,
+    }
+}
+
+/// Accepts writes until told to fail.
+struct Flaky {
+    bytes: Vec<u8>,
+    failing: bool,
+}
+
+impl Write for Flaky {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if self.failing {
+            return Err(io::Error::other("disk full"));
+        }
+        self.bytes.write(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Envelope starts, then the file bytes, for attempts 1..=`count` with
+/// sequence 3 lost to a failed write.
+fn written(header: &LogFileHeader, count: u64) -> (Vec<usize>, Vec<u8>) {
+    let sink = Flaky {
+        bytes: Vec::new(),
+        failing: false,
+    };
+    let mut writer = LogFileWriter::new(sink, header, 64).unwrap();
+    let mut starts = Vec::new();
+    for attempt in 1..=count {
+        writer.get_mut().failing = attempt == 3;
+        let start = writer.get_ref().bytes.len();
+        match writer.write_event(&record(attempt)) {
+            Ok(()) => starts.push(start),
+            Err(error) => assert_eq!(attempt, 3, "{error}"),
+        }
+    }
+    assert_eq!(writer.transport().dropped(), 1);
+    (starts, writer.into_inner().bytes)
+}
+
+fn sequences(reader: &mut LogFileReader<Cursor<&[u8]>>) -> Vec<u64> {
+    let mut sequences = Vec::new();
+    while let Some(envelope) = reader.next_record().unwrap() {
+        sequences.push(envelope.sequence);
+    }
+    sequences
+}
+
+#[test]
+fn log_files_round_trip_skip_damage_and_seek() {
+    let header = LogFileHeader {
+        process_id: 4242,
+        process_name: "checkout".into(),
+        started: Duration::from_secs(1_700_000_000),
+    };
+    assert_eq!(
+        header.to_string(),
+        "checkout (pid 4242) started 2023-11-14T22:13:20.000000Z"
+    );
+    let (starts, bytes) = written(&header, 6);
+
+    let mut reader = LogFileReader::new(Cursor::new(&bytes[..])).unwrap();
+    assert_eq!(reader.header(), &header);
+    let first = reader.next_record().unwrap().unwrap();
+    assert_eq!(first.event_name, "integration.log_file.request");
+    assert_eq!((first.sequence, first.worker, first.test), (1, 4242, None));
+    assert_eq!(first.context, ContextToken::from_parts(1, 0));
+    assert_eq!(first.message, Some("attempt 1"));
+    assert_eq!(first.field("attempt").unwrap().value, WireValue::U64(1));
+    assert!(
+        first.field("user").is_none(),
+        "a pseudonymized value is omitted"
+    );
+    assert_eq!(first.omitted_fields, 1);
+    assert_eq!(first.timestamp.unwrap().monotonic, Duration::from_millis(1));
+    assert_eq!(sequences(&mut reader), [2, 4, 5, 6]);
+    assert_eq!((reader.damaged(), reader.torn_tail()), (0, None));
+    let mut reader = LogFileReader::new(Cursor::new(&bytes[..])).unwrap();
+    reader.seek_sequence(4).unwrap();
+    assert_eq!(
+        reader.next_record().unwrap().unwrap().dropped_before,
+        1,
+        "the failed write is counted in the next envelope"
+    );
+
+    // A crash mid-write leaves the last frame short: it is the torn tail,
+    // not damage.
+    let torn = &bytes[..bytes.len() - 5];
+    let mut reader = LogFileReader::new(Cursor::new(torn)).unwrap();
+    assert_eq!(sequences(&mut reader), [1, 2, 4, 5]);
+    assert_eq!(reader.damaged(), 0);
+    assert_eq!(reader.torn_tail(), Some(starts[4] as u64));
+
+    // A damaged frame costs that frame.
+    let mut damaged = bytes.clone();
+    damaged[starts[1] + 4] = 9;
+    let mut reader = LogFileReader::new(Cursor::new(&damaged[..])).unwrap();
+    assert_eq!(sequences(&mut reader), [1, 4, 5, 6]);
+    assert_eq!((reader.damaged(), reader.torn_tail()), (1, None));
+
+    // Seeking lands on the first envelope at or after the sequence number.
+    let mut reader = LogFileReader::new(Cursor::new(&bytes[..])).unwrap();
+    assert!(reader.seek_sequence(5).unwrap());
+    assert_eq!(sequences(&mut reader), [5, 6]);
+    assert!(reader.seek_sequence(3).unwrap());
+    assert_eq!(reader.next_record().unwrap().unwrap().sequence, 4);
+    assert!(reader.seek_sequence(1).unwrap());
+    assert_eq!(reader.next_record().unwrap().unwrap().sequence, 1);
+    assert!(!reader.seek_sequence(7).unwrap());
+    assert!(reader.next_record().unwrap().is_none());
+
+    // Across a file many reads long, and with damage in the way.
+    let (starts, mut bytes) = written(&header, 4_000);
+    assert!(bytes.len() > 4 * 64 * 1024);
+    bytes[starts[2_000] + 4] = 9;
+    let mut reader = LogFileReader::new(Cursor::new(&bytes[..])).unwrap();
+    for target in [2, 1_000, 2_001, 3_999, 4_000] {
+        assert!(reader.seek_sequence(target).unwrap());
+        assert_eq!(reader.next_record().unwrap().unwrap().sequence, target);
+    }
+    assert!(reader.seek_sequence(2_002).unwrap());
+    assert_eq!(reader.next_record().unwrap().unwrap().sequence, 2_003);
+    let mut reader = LogFileReader::new(Cursor::new(&bytes[..])).unwrap();
+    assert_eq!(sequences(&mut reader).len(), 3_998);
+    assert_eq!(reader.damaged(), 1);
+
+    // Not a log file.
+    assert!(LogFileReader::new(Cursor::new(&b"LW1\0 not a header"[..])).is_err());
+
+    // On disk, for this process.
+    let path = std::env::temp_dir().join(format!("logwise-log-file-{}.lwf", std::process::id()));
+    let mut writer = LogFileWriter::create(&path, 64).unwrap();
+    writer.write_event(&record(1)).unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+    let mut reader = LogFileReader::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.header().process_id, std::process::id());
+    let envelope = reader.next_record().unwrap().unwrap();
+    assert_eq!(envelope.worker, u64::from(std::process::id()));
+    assert!(reader.next_record().unwrap().is_none());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Counters and measurements fold into aggregates keyed by support-safe
+//! labels, and reach sinks only when flushed.
+
+use core::time::Duration;
+use std::sync::Arc;
+
+use logwise::{Class, Privacy};
+use logwise_runtime::{DetailLevel, Filter, InMemorySink, MetricValue, OverflowPolicy, OwnedValue};
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn metric_sites_are_aggregated_and_flushed() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let local = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let remote = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let filter = Filter::new().class(Class::Metric);
+    let local_id = runtime.add_local_sink(local.clone(), filter.clone(), DetailLevel::Core);
+    let remote_id = runtime.add_remote_sink(remote.clone(), filter, DetailLevel::Core);
+    runtime.enable_metrics(2, None);
+
+    for route in ["/a", "/a", "/b", "/c"] {
+        logwise::counter!(
+            "integration.metrics.requests",
+            route = support(route),
+            user = local("alice"),
+        );
+    }
+    logwise::counter!("integration.metrics.bytes", value = support(512_u64));
+    logwise::counter!("integration.metrics.bytes", value = support(-1_i64));
+    for latency in [0.25, 3.0, 3.5] {
+        logwise::measurement!("integration.metrics.latency", value = support(latency));
+    }
+    logwise::measurement!("integration.metrics.latency", value = local(9.0));
+    assert!(
+        local.drain().is_empty(),
+        "observations are folded, not fanned out"
+    );
+
+    let snapshot = runtime.metrics();
+    assert_eq!(snapshot.rejected, 2, "negative increment and hidden value");
+    let names: Vec<_> = snapshot
+        .families
+        .iter()
+        .map(|family| family.metadata.event_name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "integration.metrics.bytes",
+            "integration.metrics.latency",
+            "integration.metrics.requests"
+        ]
+    );
+
+    let bytes = &snapshot.families[0];
+    assert_eq!(bytes.series.len(), 1);
+    assert!(bytes.series[0].labels.is_empty());
+    assert_eq!(bytes.series[0].value, MetricValue::Counter { total: 512.0 });
+
+    let MetricValue::Histogram(latency) = &snapshot.families[1].series[0].value else {
+        panic!("measurements aggregate into histograms");
+    };
+    assert_eq!(latency.count, 3);
+    assert_eq!(latency.sum, 6.75);
+    assert_eq!(latency.min, 0.25);
+    assert_eq!(latency.max, 3.5);
+    assert_eq!(
+        latency
+            .buckets
+            .iter()
+            .map(|bucket| bucket.count)
+            .sum::<u64>(),
+        3
+    );
+
+    let requests = &snapshot.families[2];
+    assert_eq!(requests.overflowed, 1, "`/c` would exceed the cap of two");
+    assert_eq!(
+        requests
+            .series
+            .iter()
+            .map(|series| (series.labels.clone(), series.value.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                vec![("route", "/a".to_owned())],
+                MetricValue::Counter { total: 2.0 }
+            ),
+            (
+                vec![("route", "/b".to_owned())],
+                MetricValue::Counter { total: 1.0 }
+            ),
+        ],
+        "local fields never become labels"
+    );
+
+    runtime.flush_metrics();
+    let flushed = local.drain();
+    assert_eq!(flushed.len(), 4);
+    assert!(
+        flushed
+            .iter()
+            .all(|record| record.metric.is_some() && record.omitted_fields == 0),
+        "a series withholds nothing it was given"
+    );
+    let route_a = flushed
+        .iter()
+        .find(|record| {
+            record.fields.len() == 1 && record.fields[0].value == OwnedValue::String("/a".into())
+        })
+        .expect("flushed /a series");
+    assert_eq!(route_a.metadata.event_name, "integration.metrics.requests");
+    assert_eq!(route_a.fields[0].privacy, Privacy::SupportSafe);
+    assert_eq!(route_a.metric, Some(MetricValue::Counter { total: 2.0 }));
+    assert_eq!(
+        remote.drain().len(),
+        4,
+        "labels are support-safe by construction"
+    );
+
+    runtime.enable_metrics(2, Some(Duration::ZERO));
+    logwise::counter!("integration.metrics.bytes", value = support(8_u64));
+    let periodic = local.drain();
+    assert_eq!(periodic.len(), 4, "the next observation flushes when due");
+    assert!(
+        periodic
+            .iter()
+            .any(|record| record.metric == Some(MetricValue::Counter { total: 520.0 }))
+    );
+
+    assert!(runtime.remove_sink(local_id));
+    assert!(runtime.remove_sink(remote_id));
+}
@@ -0,0 +1,224 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Aggregated metrics render as OpenMetrics text, and the opt-in listener
+//! serves that text to a scraper on loopback.
+
+use logwise::{Class, Detail, FieldMetadata, Kind, Metadata, Privacy, Severity};
+use logwise_runtime::{
+    Histogram, HistogramBucket, MetricFamily, MetricSeries, MetricValue, MetricsSnapshot,
+    render_openmetrics,
+};
+
+static ROUTE: FieldMetadata = FieldMetadata::new("route", Privacy::SupportSafe, Detail::Core);
+static REQUESTS: Metadata = Metadata {
+    event_name: "app.http.requests_total",
+    package: "app",
+    target: "app",
+    module: "app::http",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Metric,
+    kind: Kind::Counter,
+    location: None,
+    fields: &[ROUTE],
+};
+static LATENCY: Metadata = Metadata {
+    event_name: "app.http.latency",
+    package: "app",
+    target: "app",
+    module: "app::http",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Metric,
+    kind: Kind::Measurement,
+    location: None,
+    fields: &[],
+};
+static LE: FieldMetadata = FieldMetadata::new("le", Privacy::SupportSafe, Detail::Core);
+// Both sanitize to `app_http_latency_count`, which the histogram above claims.
+static LATENCY_COUNT: Metadata = Metadata {
+    event_name: "app.http.latency.count",
+    kind: Kind::Counter,
+    fields: &[LE],
+    ..LATENCY
+};
+static SIZES: Metadata = Metadata {
+    event_name: "app.sizes",
+    fields: &[LE],
+    ..LATENCY
+};
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn snapshot_renders_as_openmetrics_text() {
+    let snapshot = MetricsSnapshot {
+        families: vec![
+            MetricFamily {
+                metadata: &LATENCY,
+                label_fields: Vec::new(),
+                series: vec![MetricSeries {
+                    labels: Vec::new(),
+                    value: MetricValue::Histogram(Histogram {
+                        count: 3,
+                        sum: 6.75,
+                        min: 0.25,
+                        max: 3.5,
+                        buckets: vec![
+                            HistogramBucket {
+                                upper_bound: 0.25,
+                                count: 1,
+                            },
+                            HistogramBucket {
+                                upper_bound: 3.5,
+                                count: 2,
+                            },
+                        ],
+                    }),
+                }],
+                overflowed: 0,
+            },
+            MetricFamily {
+                metadata: &REQUESTS,
+                label_fields: vec![&ROUTE],
+                series: vec![MetricSeries {
+                    labels: vec![("route", "/a \"quoted\"\\".to_owned())],
+                    value: MetricValue::Counter { total: 2.0 },
+                }],
+                overflowed: 4,
+            },
+        ],
+        rejected: 1,
+    };
+    assert_eq!(
+        render_openmetrics(&snapshot),
+        "# HELP app_http_latency logwise measurement app.http.latency from app::http\n\
+         # TYPE app_http_latency histogram\n\
+         app_http_latency_bucket{le=\"0.25\"} 1\n\
+         app_http_latency_bucket{le=\"3.5\"} 3\n\
+         app_http_latency_bucket{le=\"+Inf\"} 3\n\
+         app_http_latency_count 3\n\
+         app_http_latency_sum 6.75\n\
+         # HELP app_http_requests logwise counter app.http.requests_total from app::http\n\
+         # TYPE app_http_requests counter\n\
+         app_http_requests_total{route=\"/a \\\"quoted\\\"\\\\\"} 2.0\n\
+         # HELP logwise_metric_overflow Observations dropped by the per-metric cardinality cap.\n\
+         # TYPE logwise_metric_overflow counter\n\
+         logwise_metric_overflow_total{metric=\"app.http.latency\"} 0\n\
+         logwise_metric_overflow_total{metric=\"app.http.requests_total\"} 4\n\
+         # HELP logwise_metric_rejected Observations with no usable quantity.\n\
+         # TYPE logwise_metric_rejected counter\n\
+         logwise_metric_rejected_total 1\n\
+         # EOF\n"
+    );
+
+    // A family whose names are taken is counted instead of exposed, and a
+    // field named `le` does not repeat the bucket label.
+    let sizes = || MetricSeries {
+        labels: vec![("le", "small".to_owned())],
+        value: MetricValue::Histogram(Histogram {
+            count: 1,
+            sum: 2.0,
+            min: 2.0,
+            max: 2.0,
+            buckets: vec![HistogramBucket {
+                upper_bound: 2.0,
+                count: 1,
+            }],
+        }),
+    };
+    let snapshot = MetricsSnapshot {
+        families: vec![
+            MetricFamily {
+                metadata: &SIZES,
+                label_fields: vec![&LE],
+                series: vec![sizes()],
+                overflowed: 0,
+            },
+            MetricFamily {
+                metadata: &LATENCY,
+                label_fields: Vec::new(),
+                series: Vec::new(),
+                overflowed: 0,
+            },
+            MetricFamily {
+                metadata: &LATENCY_COUNT,
+                label_fields: vec![&LE],
+                series: vec![
+                    MetricSeries {
+                        labels: vec![("le", "a".to_owned())],
+                        value: MetricValue::Counter { total: 1.0 },
+                    },
+                    MetricSeries {
+                        labels: vec![("le", "b".to_owned())],
+                        value: MetricValue::Counter { total: 1.0 },
+                    },
+                ],
+                overflowed: 3,
+            },
+        ],
+        rejected: 0,
+    };
+    assert_eq!(
+        render_openmetrics(&snapshot),
+        "# HELP app_sizes logwise measurement app.sizes from app::http\n\
+         # TYPE app_sizes histogram\n\
+         app_sizes_bucket{le_=\"small\",le=\"2.0\"} 1\n\
+         app_sizes_bucket{le_=\"small\",le=\"+Inf\"} 1\n\
+         app_sizes_count{le_=\"small\"} 1\n\
+         app_sizes_sum{le_=\"small\"} 2.0\n\
+         # HELP app_http_latency logwise measurement app.http.latency from app::http\n\
+         # TYPE app_http_latency histogram\n\
+         # HELP logwise_metric_collision Series not exposed because their metric name is already taken.\n\
+         # TYPE logwise_metric_collision counter\n\
+         logwise_metric_collision_total{metric=\"app.http.latency.count\"} 2\n\
+         # HELP logwise_metric_rejected Observations with no usable quantity.\n\
+         # TYPE logwise_metric_rejected counter\n\
+         logwise_metric_rejected_total 0\n\
+         # EOF\n"
+    );
+
+    let runtime = logwise_runtime::init().expect("install runtime");
+    runtime.enable_metrics(8, None);
+    logwise::counter!(
+        "integration.openmetrics.hits",
+        route = support("/x"),
+        user = local("alice"),
+        token = secret("hunter2"),
+    );
+    let rendered = render_openmetrics(&runtime.metrics());
+    assert!(rendered.contains("integration_openmetrics_hits_total{route=\"/x\"} 1.0\n"));
+    assert!(!rendered.contains("alice") && !rendered.contains("hunter2"));
+
+    #[cfg(not(target_arch = "wasm32"))]
+    scrape_over_loopback(runtime);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn scrape_over_loopback(runtime: &'static logwise_runtime::Runtime) {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    use logwise_runtime::{MetricsListener, OPENMETRICS_CONTENT_TYPE};
+
+    assert_eq!(
+        MetricsListener::bind(runtime, "0.0.0.0:0")
+            .unwrap_err()
+            .kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    let listener = MetricsListener::bind(runtime, "127.0.0.1:0").expect("bind loopback");
+    let get = |path: &str| {
+        let mut stream = TcpStream::connect(listener.local_addr()).expect("connect");
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let response = get("/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.contains(&format!("Content-Type: {OPENMETRICS_CONTENT_TYPE}\r\n")));
+    assert!(response.contains("integration_openmetrics_hits_total{route=\"/x\"} 1.0\n"));
+    assert!(response.ends_with("# EOF\n"));
+    assert!(get("/other").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    drop(listener);
+}
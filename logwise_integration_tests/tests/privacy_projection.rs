@@ -160,14 +160,14 @@ fn simultaneous_views_are_projected_before_sinks() {
     );
     assert_eq!(remote.seen.lock().unwrap().len(), 1);
 
-    assert_eq!(
+    assert!(matches!(
         runtime.activate(
             Filter::new().event("integration.projection.event"),
             Interest::DETAIL_LOCAL,
             core::time::Duration::from_secs(60),
         ),
-        ActivationResult::Enabled
-    );
+        ActivationResult::Enabled(_)
+    ));
     assert_eq!(
         runtime.activate(
             Filter::new()
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A remote sink can opt in to keyed tokens for named local-only fields: the
+//! same value gives the same token, the value itself never arrives, and
+//! secret fields never qualify.
+
+use std::sync::Arc;
+
+use logwise::{Dispatch, Interest, Privacy, ValueRef};
+use logwise_runtime::{
+    DetailLevel, Filter, InMemorySink, JsonLinesWriter, OverflowPolicy, OwnedEventWriter,
+    OwnedProjectedEvent, OwnedValue, Pseudonymization,
+};
+
+const DOMAIN: logwise::Domain = logwise::domain!("integration.pseudonyms");
+
+static CALLSITE: logwise::Metadata = logwise::Metadata {
+    event_name: "integration.pseudonyms.callsite",
+    package: "logwise_integration_tests",
+    target: "pseudonymization",
+    module: "pseudonymization",
+    domain: Some(DOMAIN),
+    severity: logwise::Severity::Info,
+    class: logwise::Class::Operational,
+    kind: logwise::Kind::Event,
+    location: None,
+    fields: &[],
+};
+
+fn failure(status: u64, user: &str, card: &str, note: &str) {
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.pseudonyms.failure",
+        status = support(status),
+        user = local(user),
+        card = secret(card),
+        note = local(note),
+    );
+}
+
+fn token(event: &OwnedProjectedEvent) -> u64 {
+    let field = &event.fields[1];
+    assert_eq!((field.name, field.privacy), ("user", Privacy::LocalOnly));
+    assert!(field.pseudonymized);
+    let OwnedValue::U64(token) = field.value else {
+        panic!("a token is a u64");
+    };
+    token
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn remote_sinks_see_tokens_for_named_local_fields() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let remote = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let filter = Filter::new().domain("integration.pseudonyms");
+    let remote_id = runtime.add_remote_sink(remote.clone(), filter.clone(), DetailLevel::Core);
+    let local_only = |runtime: &logwise_runtime::Runtime| {
+        runtime.interest(&CALLSITE).bits() & Interest::CORE_LOCAL.bits() != 0
+    };
+    assert!(!local_only(runtime));
+
+    failure(500, "alice", "4111", "retrying");
+    let plain = remote.drain();
+    assert_eq!(plain[0].fields.len(), 1);
+    assert_eq!(plain[0].omitted_fields, 3);
+
+    // Naming `card` is not enough to expose a secret, even as a token.
+    let policy = Pseudonymization::new().field("user").field("card");
+    assert!(runtime.set_pseudonymization(remote_id, Some(policy.clone())));
+    assert!(local_only(runtime));
+    failure(500, "alice", "4111", "retrying");
+    failure(503, "alice", "5500", "gave up");
+    failure(500, "bob", "4111", "retrying");
+    let events = remote.drain();
+    assert_eq!(events.len(), 3);
+    for event in &events {
+        assert_eq!(event.fields.len(), 2);
+        assert_eq!(event.omitted_fields, 2);
+        assert!(!event.fields[0].pseudonymized);
+    }
+    assert_eq!(token(&events[0]), token(&events[1]));
+    assert_ne!(token(&events[0]), token(&events[2]));
+
+    // The same text hashes the same however it was captured.
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.pseudonyms.failure",
+        status = support(500_u64),
+        user = local(ValueRef::display(&"alice")),
+    );
+    assert_eq!(token(&remote.drain()[0]), token(&events[0]));
+
+    let mut writer = JsonLinesWriter::new(Vec::new());
+    writer.write_event(&events[0]).unwrap();
+    let line = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(line.contains(&format!(
+        r#"{{"name":"user","privacy":"local","detail":"core","type":"u64","value":{},"pseudonymized":true}}"#,
+        token(&events[0])
+    )));
+
+    // Local sinks already see the value, so they take no policy.
+    let local = Arc::new(InMemorySink::new(16, 64, OverflowPolicy::DropNewest));
+    let local_id = runtime.add_local_sink(local.clone(), filter, DetailLevel::Core);
+    assert!(!runtime.set_pseudonymization(local_id, Some(policy)));
+    failure(500, "alice", "4111", "retrying");
+    let user = &local.drain()[0].fields[1];
+    assert_eq!(user.value, OwnedValue::String("alice".into()));
+    assert!(!user.pseudonymized);
+    assert!(remote.drain()[0].fields[1].pseudonymized);
+    assert!(runtime.remove_sink(local_id));
+
+    assert!(runtime.set_pseudonymization(remote_id, None));
+    assert!(!local_only(runtime));
+    failure(500, "alice", "4111", "retrying");
+    assert_eq!(remote.drain()[0].fields.len(), 1);
+    assert!(runtime.remove_sink(remote_id));
+    assert!(!runtime.set_pseudonymization(remote_id, None));
+}
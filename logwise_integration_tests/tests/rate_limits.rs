@@ -0,0 +1,108 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Rate limits suppress a noisy call site before any sink sees it, summarize
+//! what they held back to the sinks that would have received it, and count
+//! suppressions per call site.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use core::time::Duration;
+use std::sync::Arc;
+
+use logwise::ContextToken;
+use logwise_runtime::{
+    DetailLevel, Filter, InMemorySink, OverflowPolicy, OwnedValue, RateLimit, SUPPRESSION_SUMMARY,
+};
+
+const DOMAIN: logwise::Domain = logwise::domain!("integration.rate");
+
+fn retry(attempt: u64) {
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.rate.retry",
+        attempt = support(attempt),
+    );
+}
+
+#[test]
+fn rate_limits_suppress_summarize_and_count() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let remote = Arc::new(InMemorySink::new(256, 128, OverflowPolicy::DropNewest));
+    let local = Arc::new(InMemorySink::new(256, 128, OverflowPolicy::DropNewest));
+    let filter = Filter::new().domain("integration.rate");
+    let remote_id = runtime.add_remote_sink(remote.clone(), filter.clone(), DetailLevel::Core);
+    let local_id = runtime.add_local_sink(local.clone(), filter, DetailLevel::Core);
+
+    let limit = runtime.add_rate_limit(
+        Filter::new().event("integration.rate.retry"),
+        RateLimit::new(3, Duration::from_millis(100)),
+    );
+    for attempt in 0..50 {
+        retry(attempt);
+    }
+    assert_eq!(remote.drain().len(), 3);
+    assert_eq!(local.drain().len(), 3);
+    let sites = runtime.delivery_stats().rate_limited;
+    assert_eq!(sites.len(), 1);
+    assert_eq!(sites[0].metadata.event_name, "integration.rate.retry");
+    assert_eq!(sites[0].suppressed, 47);
+
+    // Once the bucket refills, the summary comes first, to the same sinks
+    // as the event, even though it has no domain of its own.
+    std::thread::sleep(Duration::from_millis(60));
+    retry(50);
+    let local_events = local.drain();
+    assert_eq!(local_events.len(), 2);
+    let summary = &local_events[0];
+    assert!(core::ptr::eq(summary.metadata, &SUPPRESSION_SUMMARY));
+    let message = summary.message.as_deref().unwrap();
+    assert!(
+        message.starts_with("suppressed 47 occurrences of `integration.rate.retry` in "),
+        "{message}"
+    );
+    assert_eq!(
+        summary.fields[0].value,
+        OwnedValue::String("integration.rate.retry".into())
+    );
+    assert_eq!(summary.fields[1].value, OwnedValue::U64(47));
+    let (OwnedValue::String(first), OwnedValue::String(last)) =
+        (&summary.fields[2].value, &summary.fields[3].value)
+    else {
+        panic!("timestamps are text");
+    };
+    assert!(first.ends_with('Z') && first <= last);
+    assert_eq!(local_events[1].fields[0].value, OwnedValue::U64(50));
+    // A remote view gets the fields but never the text.
+    let remote_events = remote.drain();
+    assert_eq!(remote_events.len(), 2);
+    assert!(remote_events[0].message.is_none());
+    assert_eq!(remote_events[0].fields.len(), 4);
+
+    // Per context, each task has its own allowance.
+    assert!(runtime.remove_rate_limit(limit));
+    let limit = runtime.add_rate_limit(
+        Filter::new().domain("integration.rate"),
+        RateLimit::new(2, Duration::from_secs(60)).per_context(),
+    );
+    let tasks: [ContextToken; 2] =
+        core::array::from_fn(|_| logwise::context::child(ContextToken::NONE, "integration.rate"));
+    for task in tasks {
+        let _entered = logwise::context::enter(task);
+        for attempt in 0..5 {
+            retry(attempt);
+        }
+    }
+    assert_eq!(local.drain().len(), 4);
+    assert_eq!(runtime.delivery_stats().rate_limited[0].suppressed, 47 + 6);
+
+    // Removing the limit lifts it.
+    assert!(runtime.remove_rate_limit(limit));
+    assert!(!runtime.remove_rate_limit(limit));
+    for attempt in 0..5 {
+        retry(attempt);
+    }
+    assert_eq!(local.drain().len(), 5);
+
+    assert!(runtime.remove_sink(remote_id));
+    assert!(runtime.remove_sink(local_id));
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The rolling file writer rotates by size and interval into deterministic
+//! names, follows external rotation, and turns rotation failures into
+//! counted write errors.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use core::time::Duration;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use logwise::{Class, ContextToken, FieldMetadata, Kind, Metadata, Severity};
+use logwise_runtime::{
+    AsyncSink, DetailLevel, FileFormat, Filter, OverflowPolicy, OwnedEventWriter,
+    OwnedProjectedEvent, RollingFileWriter, RotationPolicy, Timestamp,
+};
+
+static FIELDS: &[FieldMetadata] = &[];
+static METADATA: Metadata = Metadata {
+    event_name: "integration.rolling.record",
+    package: "logwise_integration_tests",
+    target: "rolling_file",
+    module: "rolling_file",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Operational,
+    kind: Kind::Event,
+    location: None,
+    fields: FIELDS,
+};
+
+fn record(message: &str, wall_secs: u64) -> OwnedProjectedEvent {
+    OwnedProjectedEvent {
+        metadata: &METADATA,
+        context: ContextToken::NONE,
+        timestamp: Timestamp {
+            monotonic: Duration::ZERO,
+            wall: Duration::from_secs(wall_secs),
+        },
+        fields: Vec::new(),
+        message: Some(message.into()),
+        omitted_fields: 0,
+        truncated_fields: 0,
+        span: None,
+        metric: None,
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+#[test]
+fn files_rotate_follow_external_moves_and_count_failures() {
+    let directory = std::env::temp_dir().join(format!("logwise-rolling-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    // Size: each line is far larger than 20 bytes, so every record after the
+    // first rotates, and only two old files survive.
+    let path = directory.join("size.log");
+    let mut writer = RollingFileWriter::open(
+        &path,
+        RotationPolicy::new().max_bytes(20).retain(2),
+        FileFormat::Structured,
+    )
+    .unwrap();
+    for message in ["one", "two", "three", "four"] {
+        writer.write_event(&record(message, 0)).unwrap();
+    }
+    assert_eq!(writer.rotated_path(2), directory.join("size.log.2"));
+    assert!(read(&path).ends_with(" Info integration.rolling.record four\n"));
+    assert!(read(&writer.rotated_path(1)).ends_with(" three\n"));
+    assert!(read(&writer.rotated_path(2)).ends_with(" two\n"));
+    assert!(!writer.rotated_path(3).exists());
+
+    // Interval: hourly boundaries since the epoch, not an hour since opening.
+    let path = directory.join("hourly.jsonl");
+    let mut writer = RollingFileWriter::open(
+        &path,
+        RotationPolicy::new()
+            .interval(Duration::from_secs(3600))
+            .retain(3),
+        FileFormat::JsonLines,
+    )
+    .unwrap();
+    for (message, wall) in [("a", 36_000), ("b", 37_799), ("c", 39_600), ("d", 39_601)] {
+        writer.write_event(&record(message, wall)).unwrap();
+    }
+    assert_eq!(read(&writer.rotated_path(1)).lines().count(), 2);
+    assert_eq!(read(&path).lines().count(), 2);
+    assert!(read(&path).starts_with(r#"{"v":1,"time":"1970-01-01T11:00:00.000000Z""#));
+    assert!(!writer.rotated_path(2).exists());
+
+    // External rotation: a rename-and-create rotator moves the file away.
+    let moved = directory.join("hourly.jsonl.moved");
+    fs::rename(&path, &moved).unwrap();
+    writer.write_event(&record("e", 39_602)).unwrap();
+    assert_eq!(read(&moved).lines().count(), 2);
+    assert_eq!(read(&path).lines().count(), 1);
+    writer.reopen_handle().request();
+    writer.write_event(&record("f", 39_603)).unwrap();
+    assert_eq!(read(&path).lines().count(), 2);
+
+    // Failure: the name rotation needs is taken by a directory. The writer
+    // keeps appending to `path` and the sink counts each failed rotation.
+    let path = directory.join("blocked.log");
+    let writer = RollingFileWriter::open(
+        &path,
+        RotationPolicy::new().max_bytes(1).retain(1),
+        FileFormat::Structured,
+    )
+    .unwrap();
+    fs::create_dir_all(writer.rotated_path(1).join("occupied")).unwrap();
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let sink = AsyncSink::new(writer, 16, 64, OverflowPolicy::DropNewest);
+    let id = runtime.add_local_sink(
+        Arc::new(sink.clone()),
+        Filter::new().event("integration.rolling.blocked"),
+        DetailLevel::Core,
+    );
+    for _ in 0..3 {
+        logwise::event!("integration.rolling.blocked");
+    }
+    assert!(sink.flush_blocking().is_err());
+    assert!(runtime.remove_sink(id));
+    assert_eq!(sink.stats().accepted, 3);
+    assert_eq!(sink.stats().write_errors, 2);
+    assert_eq!(read(&path).lines().count(), 3);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
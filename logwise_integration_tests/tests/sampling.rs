@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Sink sampling: a fixed rate, whole task trees kept or dropped together by
+//! the key in each context token, and tail sampling that only forwards a
+//! tree once it fails — with every discard counted.
+
+use std::sync::Arc;
+
+use logwise::ContextToken;
+use logwise_runtime::{DetailLevel, Filter, InMemorySink, OverflowPolicy, Runtime, Sampling};
+
+const DOMAIN: logwise::Domain = logwise::domain!("integration.sampling");
+
+fn sink(runtime: &Runtime, sampling: Sampling) -> (Arc<InMemorySink>, logwise_runtime::SinkId) {
+    let sink = Arc::new(InMemorySink::new(4096, 64, OverflowPolicy::DropNewest));
+    let id = runtime.add_remote_sink(
+        sink.clone(),
+        Filter::new().domain("integration.sampling"),
+        DetailLevel::Core,
+    );
+    assert!(runtime.set_sampling(id, Some(sampling)));
+    (sink, id)
+}
+
+fn step(tree: u64) {
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.sampling.step",
+        tree = support(tree),
+    );
+}
+
+fn failure(tree: u64) {
+    logwise::event!(
+        domain: DOMAIN,
+        class: operational,
+        severity: error,
+        name: "integration.sampling.failure",
+        tree = support(tree),
+    );
+}
+
+fn trees(sink: &InMemorySink) -> Vec<String> {
+    sink.drain()
+        .into_iter()
+        .map(|event| {
+            let name = event.metadata.event_name.rsplit('.').next().unwrap();
+            format!("{name} {:?}", event.fields[0].value.as_value_ref())
+        })
+        .collect()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn sampling_thins_sinks_and_counts_what_it_discards() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let sampled_out = || runtime.delivery_stats().sampled_out;
+    let before = sampled_out();
+
+    // Fixed rate.
+    let (none, none_id) = sink(runtime, Sampling::Rate(0.0));
+    let (all, all_id) = sink(runtime, Sampling::Rate(1.0));
+    let (half, half_id) = sink(runtime, Sampling::Rate(0.5));
+    for tree in 0..1000 {
+        step(tree);
+    }
+    assert!(none.drain().is_empty());
+    assert_eq!(all.drain().len(), 1000);
+    let kept = half.drain().len();
+    assert!((350..650).contains(&kept), "{kept}");
+    assert_eq!(sampled_out() - before, 2000 - kept as u64);
+    for id in [none_id, all_id, half_id] {
+        assert!(runtime.remove_sink(id));
+    }
+
+    // Per context: a tree is kept or dropped whole, and a lower rate keeps a
+    // subset of what a higher one does.
+    let (fifth, fifth_id) = sink(runtime, Sampling::PerContext(0.2));
+    let (most, most_id) = sink(runtime, Sampling::PerContext(0.6));
+    for tree in 0..200 {
+        let root = logwise::context::child(ContextToken::NONE, "integration.sampling.root");
+        let task = logwise::context::child(root, "integration.sampling.task");
+        assert_eq!(task.into_parts().1, root.into_parts().1);
+        assert_ne!(root.into_parts().1, 0);
+        for context in [root, task] {
+            let _entered = logwise::context::enter(context);
+            step(tree);
+        }
+    }
+    let pairs = |sink: &InMemorySink| {
+        let trees = trees(sink);
+        assert!(
+            trees
+                .chunks(2)
+                .all(|pair| pair.len() == 2 && pair[0] == pair[1])
+        );
+        trees.into_iter().step_by(2).collect::<Vec<_>>()
+    };
+    let (fifth_trees, most_trees) = (pairs(&fifth), pairs(&most));
+    assert!(
+        (20..70).contains(&fifth_trees.len()),
+        "{}",
+        fifth_trees.len()
+    );
+    assert!(
+        (90..150).contains(&most_trees.len()),
+        "{}",
+        most_trees.len()
+    );
+    assert!(fifth_trees.iter().all(|tree| most_trees.contains(tree)));
+    assert!(runtime.remove_sink(fifth_id));
+    assert!(runtime.remove_sink(most_id));
+
+    // Keep on error: at most two events per tree and two trees are held.
+    let before = sampled_out();
+    let (tail, tail_id) = sink(
+        runtime,
+        Sampling::KeepOnError {
+            events_per_context: 2,
+            contexts: 2,
+        },
+    );
+    let root = |_| logwise::context::child(ContextToken::NONE, "integration.sampling.tail");
+    let [failing, quiet, third, fourth]: [ContextToken; 4] = core::array::from_fn(root);
+    let task = logwise::context::child(failing, "integration.sampling.retry");
+    {
+        let _entered = logwise::context::enter(failing);
+        step(1);
+        step(2);
+        step(3);
+    }
+    {
+        let _entered = logwise::context::enter(quiet);
+        step(10);
+    }
+    assert!(tail.drain().is_empty());
+    {
+        let _entered = logwise::context::enter(task);
+        failure(4);
+        step(5);
+    }
+    assert_eq!(
+        trees(&tail),
+        ["step 2", "step 3", "failure 4", "step 5"].map(String::from)
+    );
+    // Outside any context only failures get through.
+    step(20);
+    failure(21);
+    assert_eq!(trees(&tail), ["failure 21"].map(String::from));
+    // Newer trees push out the quiet one, then the failed one's memory, so
+    // when the quiet tree fails after all, its step 10 is already gone.
+    for (context, tree) in [(third, 30), (fourth, 40)] {
+        let _entered = logwise::context::enter(context);
+        step(tree);
+    }
+    {
+        let _entered = logwise::context::enter(quiet);
+        failure(11);
+    }
+    assert_eq!(trees(&tail), ["failure 11"].map(String::from));
+    // Step 1 was displaced, steps 10 and 30 evicted, step 20 discarded, and
+    // the held step 40 goes with the policy.
+    assert!(runtime.set_sampling(tail_id, None));
+    assert_eq!(sampled_out() - before, 5);
+    {
+        let _entered = logwise::context::enter(fourth);
+        step(41);
+    }
+    assert_eq!(trees(&tail), ["step 41"].map(String::from));
+    assert!(runtime.remove_sink(tail_id));
+    assert!(!runtime.set_sampling(tail_id, Some(Sampling::Rate(1.0))));
+}
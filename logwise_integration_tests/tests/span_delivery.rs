@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Span start and completion reach sinks through the same privacy projection
+//! as events, so a perf warning is visible without polling
+//! `take_completed_spans`.
+
+use core::time::Duration;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use logwise::{Privacy, SpanTiming};
+use logwise_runtime::{
+    DetailLevel, Filter, InMemorySink, OverflowPolicy, OwnedEventWriter, OwnedValue, SpanLifecycle,
+    StructuredWriter,
+};
+
+struct SharedBytes(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBytes {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn span_lifecycle_is_projected_to_sinks() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+
+    let local = Arc::new(InMemorySink::new(8, 64, OverflowPolicy::DropNewest));
+    let remote = Arc::new(InMemorySink::new(8, 64, OverflowPolicy::DropNewest));
+    let local_id = runtime.add_local_sink(
+        local.clone(),
+        Filter::new().event("integration.spans.delivery"),
+        DetailLevel::Core,
+    );
+    let remote_id = runtime.add_remote_sink(
+        remote.clone(),
+        Filter::new().event("integration.spans.delivery"),
+        DetailLevel::Core,
+    );
+
+    let root = logwise::context::child(logwise::ContextToken::NONE, "integration.spans");
+    {
+        let _entered = logwise::context::enter(root);
+        let warning = logwise::perfwarn!(
+            threshold: Duration::ZERO,
+            name: "integration.spans.delivery.slow",
+            route = support("/health"),
+            user = local("alice"),
+            token = secret("hunter2"),
+        );
+        let started = local.drain();
+        assert_eq!(started.len(), 1);
+        let span = started[0].span.expect("span start");
+        assert_eq!(span.lifecycle, SpanLifecycle::Started);
+        assert_eq!(span.timing, SpanTiming::WallTime);
+        assert_eq!(span.warning_threshold, Some(Duration::ZERO));
+        assert_eq!(started[0].fields.len(), 2);
+        drop(warning);
+    }
+
+    let completed = local.drain();
+    assert_eq!(completed.len(), 1);
+    let span = completed[0].span.expect("span completion");
+    assert!(matches!(
+        span.lifecycle,
+        SpanLifecycle::Completed {
+            threshold_exceeded: true,
+            ..
+        }
+    ));
+    assert_eq!(completed[0].context, root);
+    assert_eq!(
+        completed[0]
+            .fields
+            .iter()
+            .map(|field| (field.name, field.value.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("route", OwnedValue::String("/health".into())),
+            ("user", OwnedValue::String("alice".into())),
+        ]
+    );
+    assert_eq!(
+        completed[0].omitted_fields, 1,
+        "the secret is never retained"
+    );
+
+    let remote_seen = remote.drain();
+    assert_eq!(remote_seen.len(), 2);
+    assert!(remote_seen.iter().all(|event| {
+        event.fields.len() == 1
+            && event.fields[0].name == "route"
+            && event
+                .fields
+                .iter()
+                .all(|field| field.privacy == Privacy::SupportSafe)
+    }));
+
+    let rendered = Arc::new(Mutex::new(Vec::new()));
+    let mut writer = StructuredWriter::new(SharedBytes(rendered.clone()));
+    writer.write_event(&completed[0]).unwrap();
+    let rendered = String::from_utf8(rendered.lock().unwrap().clone()).unwrap();
+    assert!(rendered.contains("span=completed timing=WallTime"));
+    assert!(rendered.contains("threshold_exceeded"));
+
+    let retained = runtime.take_completed_spans();
+    assert!(
+        retained
+            .iter()
+            .any(|span| span.event_name == "integration.spans.delivery.slow"),
+        "delivery to sinks does not replace the completed-span queue"
+    );
+
+    assert!(runtime.remove_sink(local_id));
+    assert!(runtime.remove_sink(remote_id));
+}
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! On the version 2 wire a [`TransportSink`] sends each call site once and
+//! gives spans their own records, until the host asks for version 1.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use logwise::SpanTiming;
+use logwise_runtime::{DetailLevel, Filter};
+use logwise_runtime_wasm::{
+    ABI_VERSION, DICTIONARY_ABI_VERSION, DecodedEnvelope, Frame, Frames, HostStatus, TransportSink,
+    WireValue, decode_envelope, decode_frame,
+};
+
+const DICTIONARY: logwise::Domain = logwise::domain!("integration.transport_dictionary");
+
+fn fetch(attempt: u64) {
+    logwise::event!(
+        domain: DICTIONARY,
+        name: "integration.transport_dictionary.fetch",
+        attempt = support(attempt),
+    );
+}
+
+/// Site records seen so far, by worker and site ID.
+type Sites = HashMap<(u64, u32), Vec<u8>>;
+
+/// The kinds of frame in one delivery, remembering its sites and resolving
+/// its events.
+fn frames<'a>(delivery: &'a [u8], sites: &'a mut Sites) -> Vec<&'static str> {
+    Frames::new(delivery)
+        .map(|frame| match frame.unwrap() {
+            Frame::Envelope(_) => "envelope",
+            Frame::Site(site) => {
+                sites.insert((site.worker, site.site), site.bytes().to_vec());
+                "site"
+            }
+            Frame::Event(_) => "event",
+        })
+        .collect()
+}
+
+fn resolve<'a>(delivery: &'a [u8], sites: &'a Sites) -> DecodedEnvelope<'a> {
+    let Some(Ok(Frame::Event(event))) = Frames::new(delivery).last() else {
+        panic!("a delivery ends in an event record");
+    };
+    let Ok(Frame::Site(site)) = decode_frame(&sites[&(event.worker, event.site)]) else {
+        panic!("a kept site record");
+    };
+    event.resolve(&site).unwrap()
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn dictionary_sink_sends_each_site_once_and_falls_back_to_version_1() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let deliveries = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+    let captured = deliveries.clone();
+    let version_1_only = Arc::new(Mutex::new(false));
+    let refusing = version_1_only.clone();
+    let sink = Arc::new(
+        TransportSink::with_delivery(runtime, 64, move |bytes| {
+            captured.lock().unwrap().push(bytes.to_vec());
+            let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+            if *refusing.lock().unwrap() && version != ABI_VERSION {
+                HostStatus::VersionMismatch
+            } else {
+                HostStatus::Accepted
+            }
+        })
+        .prefer_dictionary(),
+    );
+    assert_eq!(sink.transport().version(), DICTIONARY_ABI_VERSION);
+    let events = runtime.add_remote_sink(
+        sink.clone(),
+        Filter::new().domain("integration.transport_dictionary"),
+        DetailLevel::Core,
+    );
+    let spans = runtime.add_remote_sink(
+        sink.clone(),
+        Filter::new().event("integration.transport_dictionary.span"),
+        DetailLevel::Core,
+    );
+
+    fetch(1);
+    fetch(2);
+    drop(logwise::span!("integration.transport_dictionary.span"));
+    let taken = std::mem::take(&mut *deliveries.lock().unwrap());
+    let mut sites = Sites::new();
+    let kinds = taken
+        .iter()
+        .map(|delivery| frames(delivery, &mut sites))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            vec!["site", "event"],
+            vec!["event"],
+            vec!["site", "event"],
+            vec!["event"],
+        ],
+        "each call site is defined once, before its first use"
+    );
+    let first = resolve(&taken[0], &sites);
+    let second = resolve(&taken[1], &sites);
+    assert_eq!(
+        (first.event_name, second.sequence),
+        ("integration.transport_dictionary.fetch", 2)
+    );
+    assert_eq!(second.field("attempt").unwrap().value, WireValue::U64(2));
+    assert!(second.timestamp.is_some());
+    assert!(taken[1].len() < taken[0].len() - first.event_name.len());
+    let started = resolve(&taken[2], &sites).span.unwrap();
+    let completed = resolve(&taken[3], &sites).span.unwrap();
+    assert_eq!(started.token, completed.token);
+    assert_eq!(started.timing, SpanTiming::WallTime);
+    assert_eq!(started.completion, None);
+    assert!(completed.completion.is_some());
+
+    // A host that only reads version 1 gets the same event again, under the
+    // same sequence number, and every event after it.
+    *version_1_only.lock().unwrap() = true;
+    fetch(3);
+    fetch(4);
+    let taken = std::mem::take(&mut *deliveries.lock().unwrap());
+    assert_eq!(taken.len(), 3);
+    assert_eq!(u16::from_le_bytes([taken[0][4], taken[0][5]]), 2);
+    let retried = decode_envelope(&taken[1]).unwrap();
+    assert_eq!(
+        (retried.sequence, retried.dropped_before),
+        (5, 0),
+        "the refused version 2 envelope is not a drop"
+    );
+    assert_eq!(retried.field("attempt").unwrap().value, WireValue::U64(3));
+    assert_eq!(decode_envelope(&taken[2]).unwrap().sequence, 6);
+    assert_eq!(sink.transport().version(), ABI_VERSION);
+    assert_eq!(sink.transport().dropped(), 0);
+    assert!(runtime.remove_sink(events));
+    assert!(runtime.remove_sink(spans));
+}
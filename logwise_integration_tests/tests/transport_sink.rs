@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The wire registered as a runtime sink gets the runtime's projection: a
+//! remote registration never encodes local-only values or messages, omitted
+//! fields are counted, and links, identity and timestamps are filled in.
+
+use std::sync::{Arc, Mutex};
+
+use logwise_runtime::{DetailLevel, Filter};
+use logwise_runtime_wasm::{
+    HostStatus, TransportSink, WireValue, decode_envelope, set_current_test, set_current_worker,
+};
+
+const DOMAIN: logwise::Domain = logwise::domain!("integration.transport_sink");
+
+fn upload(bytes: u64, path: &str) {
+    logwise::event!(
+        domain: DOMAIN,
+        name: "integration.transport_sink.upload",
+        bytes = support(bytes),
+        path = local(path),
+        token = secret("hunter2"),
+        detail trace = support(7_u64),
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn transport_sink_encodes_the_projected_view() {
+    let runtime = logwise_runtime::init().expect("install runtime");
+    let frames = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+    let captured = frames.clone();
+    let accept = Arc::new(Mutex::new(true));
+    let accepting = accept.clone();
+    let sink = Arc::new(TransportSink::with_delivery(runtime, 64, move |bytes| {
+        captured.lock().unwrap().push(bytes.to_vec());
+        if *accepting.lock().unwrap() {
+            HostStatus::Accepted
+        } else {
+            HostStatus::Dropped
+        }
+    }));
+    let filter = Filter::new().domain("integration.transport_sink");
+    let id = runtime.add_remote_sink(sink.clone(), filter.clone(), DetailLevel::Core);
+
+    let root = logwise::context::child(logwise::ContextToken::NONE, "transport.root");
+    let related = logwise::context::child(logwise::ContextToken::NONE, "transport.related");
+    logwise::context::link(root, related);
+    set_current_worker(4);
+    set_current_test(Some("transport_sink"));
+    {
+        let _root = logwise::context::enter(root);
+        upload(512, "/home/alice/report.pdf");
+    }
+
+    let remote = frames.lock().unwrap().pop().expect("one envelope");
+    let envelope = decode_envelope(&remote).unwrap();
+    assert_eq!(envelope.event_name, "integration.transport_sink.upload");
+    assert_eq!(envelope.sequence, 1);
+    assert_eq!(
+        (envelope.worker, envelope.test),
+        (4, Some("transport_sink"))
+    );
+    assert_eq!(envelope.context, root);
+    assert_eq!(envelope.links().collect::<Vec<_>>(), [related]);
+    assert_eq!(envelope.timestamp, None, "version 1 has no timestamp");
+    assert_eq!(
+        envelope
+            .fields()
+            .map(|field| field.name)
+            .collect::<Vec<_>>(),
+        ["bytes"]
+    );
+    assert_eq!(envelope.field("bytes").unwrap().value, WireValue::U64(512));
+    assert_eq!(envelope.omitted_fields, 3);
+    assert!(
+        !remote.windows(5).any(|window| window == b"alice"),
+        "a remote registration never encodes a local-only value"
+    );
+    assert!(runtime.remove_sink(id));
+
+    // A local registration at full detail carries local values, but still
+    // never a secret.
+    set_current_test(None);
+    let id = runtime.add_local_sink(sink.clone(), filter, DetailLevel::Full);
+    upload(1, "/tmp/a");
+    let local = frames.lock().unwrap().pop().expect("one envelope");
+    let envelope = decode_envelope(&local).unwrap();
+    assert_eq!(envelope.sequence, 2);
+    assert_eq!(envelope.test, None);
+    assert_eq!(envelope.context, logwise::ContextToken::NONE);
+    assert_eq!(
+        envelope
+            .fields()
+            .map(|field| field.name)
+            .collect::<Vec<_>>(),
+        ["bytes", "path", "trace"]
+    );
+    assert_eq!(
+        envelope.field("path").unwrap().value.as_str(),
+        Some("/tmp/a")
+    );
+    assert_eq!(envelope.omitted_fields, 1);
+
+    // A host that refuses an envelope shows up in the next one's counters.
+    *accept.lock().unwrap() = false;
+    upload(2, "/tmp/b");
+    *accept.lock().unwrap() = true;
+    upload(3, "/tmp/c");
+    assert_eq!(sink.transport().dropped(), 1);
+    let envelope = frames.lock().unwrap().pop().unwrap();
+    assert_eq!(decode_envelope(&envelope).unwrap().dropped_before, 1);
+    assert!(runtime.remove_sink(id));
+    set_current_worker(0);
+}
@@ -4,7 +4,9 @@ use logwise::{
     Class, ContextToken, Detail, EventRef, FieldMetadata, FieldRef, Kind, Metadata, Privacy,
     Severity, ValueRef,
 };
-use logwise_runtime_wasm::{ABI_VERSION, HostStatus, Identity, Transport};
+use logwise_runtime_wasm::{
+    ABI_VERSION, DICTIONARY_ABI_VERSION, Frame, Frames, HostStatus, Identity, Transport,
+};
 
 static FIELD: FieldMetadata = FieldMetadata::new("task_id", Privacy::SupportSafe, Detail::Core);
 static FIELDS: &[FieldMetadata] = &[FIELD];
@@ -33,6 +35,7 @@ fn versioned_wire_encoder_runs_without_host_glue() {
     let encoded = transport
         .encode(
             event,
+            None,
             &[],
             Identity {
                 worker: 3,
@@ -71,13 +74,27 @@ fn versioned_wire_encoder_runs_without_host_glue() {
     );
 
     let status = transport
-        .emit(event, &[], Identity::default(), &mut output, &mut scratch)
+        .emit(
+            event,
+            None,
+            &[],
+            Identity::default(),
+            &mut output,
+            &mut scratch,
+        )
         .unwrap();
     assert_eq!(status, HostStatus::Unavailable);
     assert_eq!(transport.dropped(), 1);
 
     let encoded_after_drop = transport
-        .encode(event, &[], Identity::default(), &mut output, &mut scratch)
+        .encode(
+            event,
+            None,
+            &[],
+            Identity::default(),
+            &mut output,
+            &mut scratch,
+        )
         .unwrap();
     assert_eq!(
         u64::from_le_bytes(encoded_after_drop.bytes[20..28].try_into().unwrap()),
@@ -85,3 +102,55 @@ fn versioned_wire_encoder_runs_without_host_glue() {
         "the next envelope distinguishes transport loss from no events"
     );
 }
+
+#[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn dictionary_transport_defines_each_site_once_per_worker() {
+    let field = Some(FieldRef::new(&FIELD, ValueRef::U64(487)));
+    let fields = [field];
+    let event = EventRef::structured(&METADATA, ContextToken::from_parts(9, 1), &fields);
+    let transport = Transport::with_dictionary(64);
+    let mut output = [0_u8; 512];
+    let mut scratch = [0_u8; 64];
+    let mut encode = |worker| {
+        let encoded = transport
+            .encode(
+                event,
+                None,
+                &[],
+                Identity { worker, test: None },
+                &mut output,
+                &mut scratch,
+            )
+            .unwrap();
+        Frames::new(encoded.bytes)
+            .map(|frame| match frame.unwrap() {
+                Frame::Envelope(_) => "envelope",
+                Frame::Site(_) => "site",
+                Frame::Event(_) => "event",
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(encode(3), ["site", "event"]);
+    assert_eq!(encode(3), ["event"]);
+    assert_eq!(encode(4), ["site", "event"], "site IDs are per worker");
+    transport.resend_sites();
+    assert_eq!(encode(3), ["site", "event"]);
+    assert_eq!(transport.version(), DICTIONARY_ABI_VERSION);
+
+    // Without the host import, nothing was delivered and nothing is
+    // negotiated: the transport keeps its version.
+    let status = transport
+        .emit(
+            event,
+            None,
+            &[],
+            Identity::default(),
+            &mut output,
+            &mut scratch,
+        )
+        .unwrap();
+    assert_eq!(status, HostStatus::Unavailable);
+    assert_eq!(transport.version(), DICTIONARY_ABI_VERSION);
+    assert_eq!(transport.dropped(), 1);
+}
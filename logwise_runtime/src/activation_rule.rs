@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Rules that turn on detail for a failing task without a human in the loop.
+//!
+//! [`Runtime::activate_context`](crate::Runtime::activate_context) raises
+//! interest for a context subtree, but only once someone has noticed a
+//! failure and asked. An [`ActivationRule`] added with
+//! [`Runtime::add_activation_rule`](crate::Runtime::add_activation_rule)
+//! asks on their behalf. When an event its trigger [`Filter`] selects fires
+//! at error severity or worse in a context, the rule activates its interest
+//! for that context's root and every descendant for its TTL. A retry of the
+//! failing task then records in full.
+//!
+//! Each firing is reported as a `logwise.activation_rule.fired` event in the
+//! failing context, with the support-safe fields `rule`, `event`, `root`,
+//! and `ttl_ms`, to whichever sinks select it.
+//!
+//! An error storm must not keep everything activated, so a rule is bounded
+//! three ways. A root it has already activated is not activated again until
+//! that activation expires. No more than `max_active` of its activations
+//! are live at once. Firings also pass a [`RateLimit`] per call site. A
+//! firing any of these holds back is counted in
+//! [`RuntimeDeliveryStats::activation_rules_suppressed`](crate::RuntimeDeliveryStats::activation_rules_suppressed).
+//! An event outside any context has no subtree to activate and fires
+//! nothing.
+//!
+//! A rule observes the call sites it can fire on even when no sink wants
+//! them, which costs those sites a context check but evaluates none of
+//! their fields.
+
+use logwise::{Class, Detail, FieldMetadata, Interest, Kind, Metadata, Privacy, Severity};
+
+use crate::Filter;
+use crate::rate_limit::RateLimit;
+use crate::sys::Duration;
+
+static FIRED_FIELDS: [FieldMetadata; 4] = [
+    FieldMetadata::new("rule", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("event", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("root", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("ttl_ms", Privacy::SupportSafe, Detail::Core),
+];
+
+/// The call site of every rule firing report.
+pub static ACTIVATION_RULE_FIRED: Metadata = Metadata {
+    event_name: "logwise.activation_rule.fired",
+    package: "logwise_runtime",
+    target: "logwise_runtime",
+    module: "logwise_runtime::activation_rule",
+    domain: None,
+    severity: Severity::Info,
+    class: Class::Operational,
+    kind: Kind::Event,
+    location: None,
+    fields: &FIRED_FIELDS,
+};
+
+/// When an event `trigger` selects fails a context, activate `interest` for
+/// the context's root and its descendants for `ttl`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ActivationRule {
+    pub(crate) trigger: Filter,
+    pub(crate) minimum_severity: Severity,
+    pub(crate) interest: Interest,
+    pub(crate) ttl: Duration,
+    pub(crate) limit: RateLimit,
+    pub(crate) max_active: usize,
+}
+
+impl ActivationRule {
+    /// Fires on error severity or worse, at most 4 times a minute from any
+    /// one call site, with at most 4 activations live at once.
+    pub fn new(trigger: Filter, interest: Interest, ttl: Duration) -> Self {
+        Self {
+            trigger,
+            minimum_severity: Severity::Error,
+            interest: interest.without_contextual(),
+            ttl,
+            limit: RateLimit::new(4, Duration::from_secs(60)),
+            max_active: 4,
+        }
+    }
+
+    /// Lowers or raises the severity an event needs to fire the rule.
+    pub const fn minimum_severity(mut self, severity: Severity) -> Self {
+        self.minimum_severity = severity;
+        self
+    }
+
+    /// Replaces the per-call-site limit on firings.
+    pub const fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// How many of this rule's activations may be live at once.
+    pub const fn max_active(mut self, activations: usize) -> Self {
+        self.max_active = activations;
+        self
+    }
+
+    /// Whether an event from `metadata` is severe enough to fire the rule.
+    pub(crate) fn severe_enough(&self, metadata: &Metadata) -> bool {
+        metadata.severity as u8 >= self.minimum_severity as u8
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ActivationRuleId(pub(crate) u64);
@@ -0,0 +1,587 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A local control plane, for asking a running process what it can log and
+//! turning that up, without a debugger or a redeploy.
+//!
+//! [`Runtime::activate`](crate::Runtime::activate),
+//! [`Runtime::catalog`](crate::Runtime::catalog) and
+//! [`FlightRecorder::read_since`] only answer callers inside the process.
+//! With the `control-socket` feature, a [`ControlPlane`] serves them over a
+//! Unix domain socket, or on platforms without one, a loopback TCP port.
+//! It is native only, runs on one thread, and serves one connection at a
+//! time.
+//!
+//! # Access control
+//!
+//! There is no authentication: whoever can connect can activate. On Unix the
+//! socket file is created with mode `0600`, so only the process's user and
+//! root can connect. Since the socket is reachable from the moment it is
+//! bound, [`ControlPlane::bind_unix`] also requires a directory of the
+//! process's user with mode `0700`, creating one if needed, and refuses to
+//! bind anywhere more open. A TCP
+//! listener binds loopback only, but any local user can reach it; prefer the
+//! socket wherever there is one.
+//!
+//! # Protocol
+//!
+//! A client sends one request per line and may send several per connection.
+//! Every response is zero or more lines of data followed by one line that
+//! starts with `ok` or `error`:
+//!
+//! | Request | Data lines | Final line |
+//! |---|---|---|
+//! | `catalog` | `<event> <kind> <severity> <class> <package> <module>` per observed call site | `ok <count>` |
+//! | `activate <ttl> <directives>` | `<directive> <result>` per directive | `ok` |
+//! | `cancel <directives>` | none | `ok <cancelled>` |
+//! | `read <cursor> local\|remote [<limit>]` | `record <sequence> <json>` per flight record | `ok <next cursor> busy=<shards> dropped=<n> overwritten=<n>` |
+//! | `stats` | `<counter> <value>`, and `rate_limited <event> <suppressed>` per site | `ok` |
+//!
+//! `<ttl>` is a whole number of `ms`, `s`, `m` or `h`, such as `30s`.
+//! `<directives>` uses the [`directive`](crate::directive) syntax, and
+//! `<result>` is `enabled`, `unavailable_target`, `not_compiled` or
+//! `unknown_selector`. `cancel` ends TTL activations whose filter equals one
+//! of the directives'. A flight record's `<json>` is its event in the
+//! [`json_lines`](crate::json_lines) schema, projected for the requested
+//! [`RecorderView`]; start reading at cursor `0` and pass each `ok` line's
+//! cursor to the next `read`. A `<limit>` returns only the oldest records and,
+//! as [`FlightRecorder::query_since`] does, a cursor that resumes after them;
+//! while `busy` is nonzero the cursor stays put, so records in a busy shard
+//! are read later instead of skipped. `read` needs a recorder attached with
+//! [`ControlPlane::recorder`].
+//!
+//! ```text
+//! > activate 30s app.http=debug+detail
+//! < app.http=debug+detail enabled
+//! < ok
+//! > read 0 remote
+//! < record 1 {"v":1,"time":"2026-10-16T09:30:00.000000Z",...}
+//! < ok 1 busy=0 dropped=0 overwritten=0
+//! ```
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::directive::{Directive, parse_directives};
+use crate::flight_recorder::{
+    FlightCursor, FlightQuery, FlightRecorder, RecorderView, RetentionBand,
+};
+use crate::json_lines::encode_event;
+use crate::names::{class_name, kind_name, severity_name};
+use crate::{ActivationResult, Runtime};
+
+/// How long a connection may sit idle before it is closed, so a client that
+/// walked away does not keep the next one waiting.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Request lines longer than this are rejected.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// What a control connection can reach.
+#[derive(Clone, Debug)]
+pub struct ControlPlane {
+    runtime: &'static Runtime,
+    recorder: Option<Arc<FlightRecorder>>,
+}
+
+impl ControlPlane {
+    pub fn new(runtime: &'static Runtime) -> Self {
+        Self {
+            runtime,
+            recorder: None,
+        }
+    }
+
+    /// Lets `read` requests page through `recorder`.
+    pub fn recorder(mut self, recorder: Arc<FlightRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Binds a Unix domain socket at `path`, with mode `0600`, and starts
+    /// serving. Fails if anything already exists at `path`.
+    ///
+    /// The socket's directory is created with mode `0700` if it is missing.
+    /// If it exists, it must belong to this process's user and be closed to
+    /// everyone else, or binding fails with
+    /// [`PermissionDenied`](io::ErrorKind::PermissionDenied): the socket is
+    /// reachable as soon as it is bound, before its own mode is set.
+    #[cfg(unix)]
+    pub fn bind_unix(self, path: impl AsRef<std::path::Path>) -> io::Result<ControlSocket> {
+        use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+        use std::os::unix::net::UnixListener;
+
+        let path = path.as_ref().to_path_buf();
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => std::path::Path::new("."),
+        };
+        match std::fs::DirBuilder::new().mode(0o700).create(directory) {
+            Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+            _ => {}
+        }
+        let directory_metadata = std::fs::metadata(directory)?;
+        if !directory_metadata.is_dir() || directory_metadata.mode() & 0o077 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is open to other users; the control socket needs a mode 0700 directory",
+                    directory.display()
+                ),
+            ));
+        }
+        let listener = UnixListener::bind(&path)?;
+        // The new socket belongs to this process's user, who must also own
+        // the directory, or its owner could connect.
+        let secured = std::fs::symlink_metadata(&path).and_then(|socket| {
+            if socket.uid() != directory_metadata.uid() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} belongs to another user", directory.display()),
+                ));
+            }
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        });
+        if let Err(error) = secured {
+            let _ = std::fs::remove_file(&path);
+            return Err(error);
+        }
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker_stopped = stopped.clone();
+        let worker = std::thread::Builder::new()
+            .name("logwise-control".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if worker_stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if let Ok(stream) = stream
+                        && stream.set_read_timeout(Some(IDLE_TIMEOUT)).is_ok()
+                        && stream.set_write_timeout(Some(IDLE_TIMEOUT)).is_ok()
+                    {
+                        let _ = self.serve(&stream, &stream);
+                    }
+                }
+            });
+        let worker = match worker {
+            Ok(worker) => worker,
+            Err(error) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(error);
+            }
+        };
+        Ok(ControlSocket {
+            address: ControlAddress::Unix(path),
+            stopped,
+            worker: Some(worker),
+        })
+    }
+
+    /// Binds a loopback TCP address and starts serving. Fails with
+    /// [`io::ErrorKind::InvalidInput`] for a non-loopback address.
+    pub fn bind_tcp(self, address: impl ToSocketAddrs) -> io::Result<ControlSocket> {
+        let address = address
+            .to_socket_addrs()?
+            .find(|address| address.ip().is_loopback())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the control plane binds loopback addresses only",
+                )
+            })?;
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker_stopped = stopped.clone();
+        let worker = std::thread::Builder::new()
+            .name("logwise-control".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if worker_stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if let Ok(stream) = stream
+                        && stream.set_read_timeout(Some(IDLE_TIMEOUT)).is_ok()
+                        && stream.set_write_timeout(Some(IDLE_TIMEOUT)).is_ok()
+                    {
+                        let _ = self.serve(&stream, &stream);
+                    }
+                }
+            })?;
+        Ok(ControlSocket {
+            address: ControlAddress::Tcp(local_addr),
+            stopped,
+            worker: Some(worker),
+        })
+    }
+
+    /// Answers one request line, returning the response lines, each ending
+    /// in a newline. This is what a connection does per line, for callers
+    /// that already have their own transport.
+    pub fn respond(&self, request: &str) -> String {
+        let mut response = String::new();
+        let (command, arguments) = split_word(request);
+        let result = match command {
+            "catalog" => self.catalog(&mut response),
+            "activate" => {
+                let (ttl, directives) = split_word(arguments);
+                self.activate(&mut response, ttl, directives)
+            }
+            "cancel" => self.cancel(arguments),
+            "read" => self.read(&mut response, arguments.split_whitespace().collect()),
+            "stats" => self.stats(&mut response),
+            "" => Err("empty request".into()),
+            command => Err(format!("unknown request `{command}`")),
+        };
+        match result {
+            Ok(status) if status.is_empty() => response.push_str("ok\n"),
+            Ok(status) => {
+                let _ = writeln!(response, "ok {status}");
+            }
+            Err(reason) => {
+                let _ = writeln!(response, "error {}", reason.replace('\n', " "));
+            }
+        }
+        response
+    }
+
+    fn serve(&self, reader: impl Read, mut writer: impl Write) -> io::Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = (&mut reader)
+                .take(MAX_REQUEST_BYTES as u64 + 1)
+                .read_until(b'\n', &mut line)?;
+            if read == 0 {
+                return Ok(());
+            }
+            if line.len() > MAX_REQUEST_BYTES {
+                writer.write_all(b"error request too long\n")?;
+                return writer.flush();
+            }
+            let response = match std::str::from_utf8(&line) {
+                Ok(request) => self.respond(request),
+                Err(_) => "error request is not UTF-8\n".into(),
+            };
+            writer.write_all(response.as_bytes())?;
+            writer.flush()?;
+        }
+    }
+
+    fn catalog(&self, response: &mut String) -> Result<String, String> {
+        let catalog = self.runtime.catalog();
+        for metadata in &catalog {
+            let _ = writeln!(
+                response,
+                "{} {} {} {} {} {}",
+                metadata.event_name,
+                kind_name(metadata.kind),
+                severity_name(metadata.severity),
+                class_name(metadata.class),
+                metadata.package,
+                metadata.module
+            );
+        }
+        Ok(catalog.len().to_string())
+    }
+
+    fn activate(
+        &self,
+        response: &mut String,
+        ttl: &str,
+        directives: &str,
+    ) -> Result<String, String> {
+        let ttl = parse_ttl(ttl)?;
+        let directives = directive_list(directives)?;
+        for directive in directives {
+            let interest = directive.interest();
+            let result = self
+                .runtime
+                .activate(directive.filter.clone(), interest, ttl);
+            let _ = writeln!(response, "{directive} {}", result_name(result));
+        }
+        Ok(String::new())
+    }
+
+    fn cancel(&self, directives: &str) -> Result<String, String> {
+        let directives = directive_list(directives)?;
+        let cancelled: usize = directives
+            .iter()
+            .map(|directive| self.runtime.cancel_activations(&directive.filter))
+            .sum();
+        Ok(cancelled.to_string())
+    }
+
+    fn read(&self, response: &mut String, arguments: Vec<&str>) -> Result<String, String> {
+        let recorder = self
+            .recorder
+            .as_ref()
+            .ok_or("no flight recorder is attached")?;
+        let (cursor, view, limit) = match arguments[..] {
+            [cursor, view] => (cursor, view, None),
+            [cursor, view, limit] => (cursor, view, Some(limit)),
+            _ => return Err("expected `read <cursor> local|remote [<limit>]`".into()),
+        };
+        let cursor = cursor
+            .parse()
+            .map_err(|_| format!("invalid cursor `{cursor}`"))?;
+        let view = match view {
+            "local" => RecorderView::Local,
+            "remote" => RecorderView::Remote,
+            _ => return Err(format!("invalid view `{view}`")),
+        };
+        let limit = limit
+            .map(|limit| {
+                limit
+                    .parse()
+                    .map_err(|_| format!("invalid limit `{limit}`"))
+            })
+            .transpose()?;
+
+        let query = limit.map_or_else(FlightQuery::new, |limit| FlightQuery::new().limit(limit));
+        let read = recorder.query_since(self.runtime, FlightCursor(cursor), &query, view);
+        for record in &read.records {
+            let _ = write!(response, "record {} ", record.sequence);
+            encode_event(response, &record.event);
+            response.push('\n');
+        }
+        Ok(format!(
+            "{} busy={} dropped={} overwritten={}",
+            read.next_cursor.0, read.busy_shards, read.dropped_total, read.overwritten_total
+        ))
+    }
+
+    fn stats(&self, response: &mut String) -> Result<String, String> {
+        let stats = self.runtime.delivery_stats();
+        for (name, value) in [
+            ("sink_panics", stats.sink_panics),
+            ("reentrant_events_dropped", stats.reentrant_events_dropped),
+            ("completed_spans_dropped", stats.completed_spans_dropped),
+            (
+                "retired_activations_dropped",
+                stats.retired_activations_dropped,
+            ),
+            ("field_filter_rejections", stats.field_filter_rejections),
+            ("sampled_out", stats.sampled_out),
+            (
+                "activation_rules_suppressed",
+                stats.activation_rules_suppressed,
+            ),
+        ] {
+            let _ = writeln!(response, "{name} {value}");
+        }
+        for site in &stats.rate_limited {
+            let _ = writeln!(
+                response,
+                "rate_limited {} {}",
+                site.metadata.event_name, site.suppressed
+            );
+        }
+        if let Some(recorder) = &self.recorder {
+            let recorder = recorder.stats();
+            for (name, value) in [
+                ("recorder_accepted", recorder.accepted),
+                ("recorder_dropped", recorder.dropped),
+                ("recorder_overwritten", recorder.overwritten),
+                ("recorder_truncated_fields", recorder.truncated_fields),
+            ] {
+                let _ = writeln!(response, "{name} {value}");
+            }
+            for band in RetentionBand::ALL {
+                let _ = writeln!(
+                    response,
+                    "recorder_overwritten_band {} {}",
+                    band.name(),
+                    recorder.overwritten_by_band[band as usize]
+                );
+                let _ = writeln!(
+                    response,
+                    "recorder_rejected_band {} {}",
+                    band.name(),
+                    recorder.rejected_by_band[band as usize]
+                );
+            }
+        }
+        Ok(String::new())
+    }
+}
+
+/// Where a [`ControlSocket`] listens.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ControlAddress {
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+    Tcp(SocketAddr),
+}
+
+/// A serving control plane. Dropping it stops and joins its thread and, for
+/// a Unix socket, removes the socket file.
+#[derive(Debug)]
+pub struct ControlSocket {
+    address: ControlAddress,
+    stopped: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ControlSocket {
+    pub fn address(&self) -> &ControlAddress {
+        &self.address
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+        // `accept` has no timeout; a connection of our own wakes it.
+        match &self.address {
+            #[cfg(unix)]
+            ControlAddress::Unix(path) => {
+                let _ = std::os::unix::net::UnixStream::connect(path);
+            }
+            ControlAddress::Tcp(address) => {
+                let _ = TcpStream::connect(address);
+            }
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        #[cfg(unix)]
+        if let ControlAddress::Unix(path) = &self.address {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn directive_list(text: &str) -> Result<Vec<Directive>, String> {
+    match parse_directives(text) {
+        Ok(directives) if directives.is_empty() => Err("missing directives".into()),
+        Ok(directives) => Ok(directives),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Splits off the first whitespace-separated word, trimming both parts.
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    text.split_once(char::is_whitespace)
+        .map_or((text, ""), |(word, rest)| (word, rest.trim_start()))
+}
+
+fn parse_ttl(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid ttl `{text}`; expected a number of ms, s, m or h");
+    let split = text
+        .find(|character: char| !character.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number.saturating_mul(60))),
+        "h" => Ok(Duration::from_secs(number.saturating_mul(3_600))),
+        _ => Err(invalid()),
+    }
+}
+
+const fn result_name(result: ActivationResult) -> &'static str {
+    match result {
+        ActivationResult::Enabled(_) => "enabled",
+        ActivationResult::UnavailableTarget => "unavailable_target",
+        ActivationResult::NotCompiled => "not_compiled",
+        ActivationResult::UnknownSelector => "unknown_selector",
+        ActivationResult::NoEvents => "no_events",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use logwise::{Class, ContextToken, Kind, Metadata, Severity};
+
+    use super::*;
+    use crate::projection::{EventSink, ProjectedEvent, Timestamp};
+
+    static METADATA: Metadata = Metadata {
+        event_name: "logwise_runtime.test.control",
+        package: "logwise_runtime",
+        target: "logwise_runtime",
+        module: "control::tests",
+        domain: None,
+        severity: Severity::Info,
+        class: Class::Operational,
+        kind: Kind::Event,
+        location: None,
+        fields: &[],
+    };
+
+    fn record(recorder: &FlightRecorder) {
+        recorder.emit(ProjectedEvent {
+            metadata: &METADATA,
+            context: ContextToken::NONE,
+            timestamp: Timestamp::now(),
+            fields: Vec::new(),
+            message: None,
+            omitted_fields: 0,
+            span: None,
+            metric: None,
+        });
+    }
+
+    #[test]
+    fn limited_read_keeps_its_cursor_while_a_shard_is_busy() {
+        let recorder = Arc::new(FlightRecorder::with_shards(64, 64, 2));
+        let plane =
+            ControlPlane::new(Box::leak(Box::new(Runtime::new()))).recorder(Arc::clone(&recorder));
+        record(&recorder);
+        let busy = recorder.current_shard();
+        // Threads land on shards by ID; keep going until one lands elsewhere.
+        while std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    for _ in 0..3 {
+                        record(&recorder);
+                    }
+                    recorder.current_shard() == busy
+                })
+                .join()
+                .unwrap()
+        }) {}
+        let total = recorder.stats().accepted;
+
+        let held = recorder.hold_shard(busy);
+        let response = plane.respond("read 0 local 1");
+        assert_eq!(response.lines().count(), 2);
+        assert!(response.ends_with("ok 0 busy=1 dropped=0 overwritten=0\n"));
+        drop(held);
+
+        let mut cursor = 0;
+        let mut sequences = Vec::new();
+        loop {
+            let response = plane.respond(&format!("read {cursor} local 1"));
+            let (status, records) = response
+                .trim_end()
+                .rsplit_once('\n')
+                .map_or((response.trim_end(), ""), |(records, status)| {
+                    (status, records)
+                });
+            let next = status.split_whitespace().nth(1).unwrap().parse().unwrap();
+            if next == cursor {
+                break;
+            }
+            for line in records.lines() {
+                sequences.push(
+                    line.split_whitespace()
+                        .nth(1)
+                        .unwrap()
+                        .parse::<u64>()
+                        .unwrap(),
+                );
+            }
+            cursor = next;
+        }
+        assert_eq!(sequences, (1..=total).collect::<Vec<_>>());
+    }
+}
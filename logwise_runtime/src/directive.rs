@@ -0,0 +1,530 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A compact text form for filters and interest, for environment variables
+//! and operators.
+//!
+//! ```text
+//! LOGWISE="some_executor=debug,class:forensic,app.http.request=trace+detail"
+//! ```
+//!
+//! A directive list is comma-separated. Each directive is a selector
+//! expression, optionally followed by `=` and a level, and becomes one
+//! [`Directive`]: a [`Filter`] plus the [`Interest`] to raise for what it
+//! selects.
+//!
+//! | Selector | Filter |
+//! |---|---|
+//! | `name` or `name:name` | [`Filter::name`]: event name, domain, package, target, or module |
+//! | `domain:name` | [`Filter::domain`] |
+//! | `event:name` | [`Filter::event`] |
+//! | `package:name` | [`Filter::package`] |
+//! | `file:path` | [`Filter::file`] |
+//! | `class:forensic` | [`Filter::class`] |
+//! | `kind:counter` | [`Filter::kind`] |
+//! | `severity:warn` | [`Filter::minimum_severity`], inside a group |
+//! | `target:native` or `target:wasm` | [`Filter::target`] |
+//! | `context:7` | [`Filter::context`], that context only |
+//! | `descendants:7` | [`Filter::context`], with its descendants |
+//! | `*` | everything |
+//!
+//! Selectors combine with `&` ([`Filter::and`]), `|` ([`Filter::or`]), and a
+//! leading `!` (`!` on a [`Filter`]), grouped with parentheses. `&` binds tighter
+//! than `|`, so `some_executor&!event:some_executor.task.polled` and
+//! `class:forensic|severity:warn` need none.
+//!
+//! A level is a severity, modifiers, or both, joined by `+`: the severity
+//! (`trace` through `critical`) becomes [`Filter::minimum_severity`] of the
+//! whole expression, `detail` asks for detail fields as well as core ones,
+//! and `support` or `secret` moves the privacy ceiling from the default of
+//! local-only. A directive with no selectors may leave out the `=`, as in
+//! `debug` or `info+detail`.
+//!
+//! Names may not contain `,`, `=`, `&`, `|`, `+`, parentheses, or whitespace.
+//! A bare name that would read as a level keyword, a `key:`, `*`, or an
+//! exclusion is written with `name:`. `Display` on a [`Filter`] or [`Directive`]
+//! writes the canonical form — selectors in the order of the table, then
+//! any-of and all-of groups and exclusions, then severity, `detail`, and
+//! privacy — which parses back to an equal value.
+//!
+//! [`init`](crate::init) applies [`DIRECTIVES_ENV`] with
+//! [`Runtime::apply_directives`](crate::Runtime::apply_directives), and
+//! [`Runtime::activate_directives`](crate::Runtime::activate_directives)
+//! takes the same syntax for a TTL activation.
+
+use std::fmt;
+use std::str::FromStr;
+
+use logwise::{Class, ContextToken, Interest, Kind, Privacy, Severity};
+
+use crate::facade_runtime::{Filter, Target};
+use crate::names::{class_name, kind_name, severity_name};
+use crate::projection::DetailLevel;
+
+/// The environment variable [`init`](crate::init) reads directives from.
+pub const DIRECTIVES_ENV: &str = "LOGWISE";
+
+/// One parsed directive: what it selects and how much to record there.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Directive {
+    pub filter: Filter,
+    pub detail: DetailLevel,
+    /// The most sensitive privacy tier to evaluate.
+    pub privacy: Privacy,
+}
+
+impl Default for Directive {
+    /// Everything, at core detail, up to local-only fields.
+    fn default() -> Self {
+        Self {
+            filter: Filter::new(),
+            detail: DetailLevel::Core,
+            privacy: Privacy::LocalOnly,
+        }
+    }
+}
+
+impl Directive {
+    /// The field groups this directive asks call sites to evaluate.
+    pub fn interest(&self) -> Interest {
+        let (core, detail) = match self.privacy {
+            Privacy::SupportSafe => (Interest::CORE_SUPPORT, Interest::DETAIL_SUPPORT),
+            Privacy::LocalOnly => (
+                Interest::CORE_SUPPORT.union(Interest::CORE_LOCAL),
+                Interest::DETAIL_SUPPORT.union(Interest::DETAIL_LOCAL),
+            ),
+            Privacy::Secret => (
+                Interest::CORE_SUPPORT
+                    .union(Interest::CORE_LOCAL)
+                    .union(Interest::CORE_SECRET),
+                Interest::DETAIL_SUPPORT
+                    .union(Interest::DETAIL_LOCAL)
+                    .union(Interest::DETAIL_SECRET),
+            ),
+        };
+        match self.detail {
+            DetailLevel::Core => core,
+            DetailLevel::Full => core.union(detail),
+        }
+    }
+}
+
+/// A directive that does not parse, and the token responsible.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DirectiveError {
+    pub token: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for DirectiveError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "invalid logwise directive `{}`: {}",
+            self.token, self.reason
+        )
+    }
+}
+
+impl std::error::Error for DirectiveError {}
+
+/// Parses a comma-separated directive list. Empty entries are skipped.
+pub fn parse_directives(text: &str) -> Result<Vec<Directive>, DirectiveError> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+impl FromStr for Directive {
+    type Err = DirectiveError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text.contains(',') {
+            return Err(error(text, "is a list; use `parse_directives`"));
+        }
+        let (selectors, level) = match text.split_once('=') {
+            Some((selectors, level)) => (selectors, Some(level)),
+            None if text.split('+').next().is_some_and(is_level_word) => ("", Some(text)),
+            None => (text, None),
+        };
+
+        let mut directive = Self::default();
+        if !selectors.is_empty() {
+            let mut parser = Parser { rest: selectors };
+            directive.filter = parser.expression()?;
+            if !parser.rest.is_empty() {
+                return Err(error(parser.rest, "has an unmatched `)`"));
+            }
+        }
+        if let Some(level) = level {
+            if level.is_empty() {
+                return Err(error(text, "expects a level after `=`"));
+            }
+            let mut privacy = None;
+            for word in level.split('+') {
+                let repeated = match word {
+                    "detail" => {
+                        std::mem::replace(&mut directive.detail, DetailLevel::Full)
+                            == DetailLevel::Full
+                    }
+                    "support" | "local" | "secret" => privacy
+                        .replace(match word {
+                            "support" => Privacy::SupportSafe,
+                            "local" => Privacy::LocalOnly,
+                            _ => Privacy::Secret,
+                        })
+                        .is_some(),
+                    _ => match severity(word) {
+                        Some(severity) => directive
+                            .filter
+                            .minimum_severity
+                            .replace(severity)
+                            .is_some(),
+                        None => return Err(error(word, "is not a severity or modifier")),
+                    },
+                };
+                if repeated {
+                    return Err(error(word, "repeats part of the level"));
+                }
+            }
+            directive.privacy = privacy.unwrap_or(Privacy::LocalOnly);
+        }
+        Ok(directive)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = DirectiveError;
+
+    /// Parses one directive without interest modifiers.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let directive: Directive = text.parse()?;
+        if directive.detail != DetailLevel::Core || directive.privacy != Privacy::LocalOnly {
+            let modifier = text
+                .split(['=', '+'])
+                .skip(1)
+                .find(|word| !is_severity_word(word))
+                .unwrap_or(text);
+            return Err(error(
+                modifier,
+                "sets interest, which a filter does not carry",
+            ));
+        }
+        Ok(directive.filter)
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let expression = render(&self.filter, true);
+        let mut level = Vec::new();
+        if let Some(severity) = self.filter.minimum_severity {
+            level.push(severity_name(severity));
+        }
+        if self.detail == DetailLevel::Full {
+            level.push("detail");
+        }
+        match self.privacy {
+            Privacy::SupportSafe => level.push("support"),
+            Privacy::LocalOnly => {}
+            Privacy::Secret => level.push("secret"),
+        }
+
+        formatter.write_str(&expression)?;
+        if !level.is_empty() {
+            if !expression.is_empty() {
+                formatter.write_str("=")?;
+            }
+            formatter.write_str(&level.join("+"))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Directive {
+            filter: self.clone(),
+            ..Directive::default()
+        }
+        .fmt(formatter)
+    }
+}
+
+/// Writes a filter's selector expression. At the top of a directive the
+/// minimum severity belongs to the level and a lone any-of group needs no
+/// parentheses; anywhere else the severity is a `severity:` selector, and
+/// a filter with nothing to say is `*`.
+fn render(filter: &Filter, top: bool) -> String {
+    let mut parts = Vec::new();
+    if let Some(name) = &filter.name {
+        parts.push(
+            if is_level_word(name)
+                || split_key(name).is_some()
+                || name.starts_with('!')
+                || name == "*"
+            {
+                format!("name:{name}")
+            } else {
+                name.clone()
+            },
+        );
+    }
+    for (key, value) in [
+        ("domain", &filter.domain),
+        ("event", &filter.event_name),
+        ("package", &filter.package),
+        ("file", &filter.file),
+    ] {
+        if let Some(value) = value {
+            parts.push(format!("{key}:{value}"));
+        }
+    }
+    if let Some(class) = filter.class {
+        parts.push(format!("class:{}", class_name(class)));
+    }
+    if let Some(kind) = filter.kind {
+        parts.push(format!("kind:{}", kind_name(kind)));
+    }
+    if let Some(severity) = filter.minimum_severity
+        && !top
+    {
+        parts.push(format!("severity:{}", severity_name(severity)));
+    }
+    if let Some(target) = filter.target {
+        parts.push(format!("target:{}", target_name(target)));
+    }
+    if let Some(context) = filter.context {
+        let key = if filter.descendants {
+            "descendants"
+        } else {
+            "context"
+        };
+        let (id, flags) = context.into_parts();
+        parts.push(if flags == 0 {
+            format!("{key}:{id}")
+        } else {
+            format!("{key}:{id}/{flags}")
+        });
+    }
+    let selectors = parts.len();
+    if !filter.any.is_empty() {
+        let members: Vec<_> = filter
+            .any
+            .iter()
+            .map(|member| render(member, false))
+            .collect();
+        parts.push(format!("({})", members.join("|")));
+    }
+    for member in &filter.all {
+        parts.push(format!("({})", render(member, false)));
+    }
+    for excluded in &filter.none {
+        let rendered = render(excluded, false);
+        let bare = rendered == "*"
+            || excluded.is_any_only()
+            || !rendered.contains(['&', '|', '(', ')', '!']);
+        parts.push(if bare {
+            format!("!{rendered}")
+        } else {
+            format!("!({rendered})")
+        });
+    }
+
+    match parts.len() {
+        0 if top => String::new(),
+        0 => "*".to_owned(),
+        // A lone group's parentheses are only needed inside another group.
+        1 if top && selectors == 0 && !filter.any.is_empty() => {
+            let group = parts.pop().unwrap();
+            group[1..group.len() - 1].to_owned()
+        }
+        _ => parts.join("&"),
+    }
+}
+
+/// Recursive descent over `expression := term ("|" term)*`,
+/// `term := factor ("&" factor)*` and
+/// `factor := "!" factor | "(" expression ")" | "*" | selector`.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, token: char) -> bool {
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expression(&mut self) -> Result<Filter, DirectiveError> {
+        let mut terms = vec![self.term()?];
+        while self.eat('|') {
+            terms.push(self.term()?);
+        }
+        Ok(Filter::any_of(terms))
+    }
+
+    fn term(&mut self) -> Result<Filter, DirectiveError> {
+        let mut filter = Filter::new();
+        loop {
+            self.factor(&mut filter)?;
+            if !self.eat('&') {
+                return Ok(filter);
+            }
+        }
+    }
+
+    /// Parses one factor and conjoins it with `filter`.
+    fn factor(&mut self, filter: &mut Filter) -> Result<(), DirectiveError> {
+        let start = self.rest;
+        if self.eat('!') {
+            let mut excluded = Filter::new();
+            self.factor(&mut excluded)?;
+            filter.none.push(excluded);
+        } else if self.eat('(') {
+            let group = self.expression()?;
+            if !self.eat(')') {
+                return Err(error(start, "has an unclosed `(`"));
+            }
+            *filter = std::mem::take(filter).and(group);
+        } else if !self.eat('*') {
+            let end = self
+                .rest
+                .find(['&', '|', '(', ')'])
+                .unwrap_or(self.rest.len());
+            let (selector, rest) = self.rest.split_at(end);
+            if selector.is_empty() {
+                return Err(error(start, "is missing a selector"));
+            }
+            self.rest = rest;
+            parse_selector(filter, selector)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_selector(filter: &mut Filter, selector: &str) -> Result<(), DirectiveError> {
+    let (key, value) = match split_key(selector) {
+        Some((key, value)) => (Some(key), value),
+        None => (None, selector),
+    };
+    if value.is_empty() {
+        return Err(error(selector, "names nothing"));
+    }
+    if value.contains(char::is_whitespace) || value.contains(['=', '+', ',']) {
+        return Err(error(selector, "contains a character names may not use"));
+    }
+    let repeated = match key {
+        None | Some("name") => filter.name.replace(value.to_owned()).is_some(),
+        Some("domain") => filter.domain.replace(value.to_owned()).is_some(),
+        Some("event") => filter.event_name.replace(value.to_owned()).is_some(),
+        Some("package") => filter.package.replace(value.to_owned()).is_some(),
+        Some("file") => filter.file.replace(value.to_owned()).is_some(),
+        Some("class") => {
+            let class = class(value).ok_or_else(|| error(selector, "is not a class"))?;
+            filter.class.replace(class).is_some()
+        }
+        Some("kind") => {
+            let kind = kind(value).ok_or_else(|| error(selector, "is not a kind"))?;
+            filter.kind.replace(kind).is_some()
+        }
+        Some("severity") => {
+            let severity = severity(value).ok_or_else(|| error(selector, "is not a severity"))?;
+            filter.minimum_severity.replace(severity).is_some()
+        }
+        Some("target") => {
+            let target = match value {
+                "native" => Target::Native,
+                "wasm" => Target::Wasm,
+                _ => return Err(error(selector, "is not `native` or `wasm`")),
+            };
+            filter.target.replace(target).is_some()
+        }
+        Some(key @ ("context" | "descendants")) => {
+            let (id, flags) = value.split_once('/').unwrap_or((value, "0"));
+            let (Ok(id), Ok(flags)) = (id.parse(), flags.parse()) else {
+                return Err(error(selector, "is not a context ID"));
+            };
+            filter.descendants = key == "descendants";
+            filter
+                .context
+                .replace(ContextToken::from_parts(id, flags))
+                .is_some()
+        }
+        Some(_) => return Err(error(selector, "has an unknown selector key")),
+    };
+    if repeated {
+        return Err(error(selector, "repeats a selector"));
+    }
+    Ok(())
+}
+
+/// Splits `key:value`, leaving a module path's `::` alone.
+fn split_key(selector: &str) -> Option<(&str, &str)> {
+    selector
+        .split_once(':')
+        .filter(|(_, value)| !value.starts_with(':'))
+}
+
+fn error(token: &str, reason: &'static str) -> DirectiveError {
+    DirectiveError {
+        token: token.to_owned(),
+        reason,
+    }
+}
+
+fn is_level_word(word: &str) -> bool {
+    is_severity_word(word) || matches!(word, "detail" | "support" | "local" | "secret")
+}
+
+fn is_severity_word(word: &str) -> bool {
+    severity(word).is_some()
+}
+
+fn severity(word: &str) -> Option<Severity> {
+    Some(match word {
+        "trace" => Severity::Trace,
+        "debug" => Severity::Debug,
+        "info" => Severity::Info,
+        "warn" => Severity::Warn,
+        "error" => Severity::Error,
+        "critical" => Severity::Critical,
+        _ => return None,
+    })
+}
+
+fn kind(word: &str) -> Option<Kind> {
+    Some(match word {
+        "event" => Kind::Event,
+        "ad_hoc_text" => Kind::AdHocText,
+        "span" => Kind::Span,
+        "counter" => Kind::Counter,
+        "measurement" => Kind::Measurement,
+        _ => return None,
+    })
+}
+
+fn class(word: &str) -> Option<Class> {
+    Some(match word {
+        "operational" => Class::Operational,
+        "diagnostic" => Class::Diagnostic,
+        "forensic" => Class::Forensic,
+        "performance" => Class::Performance,
+        "metric" => Class::Metric,
+        _ => return None,
+    })
+}
+
+const fn target_name(target: Target) -> &'static str {
+    match target {
+        Target::Native => "native",
+        Target::Wasm => "wasm",
+    }
+}
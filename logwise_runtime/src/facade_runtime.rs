@@ -1,19 +1,45 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 //! Runtime-owned context storage and monotonic span timing for the facade.
+//!
+//! Spans are delivered to sinks twice: once when the guard is created, with
+//! the fields it was opened with, and once when it drops, with the elapsed
+//! time and those same fields. The runtime keeps an owned copy of the opening
+//! fields between the two only when a sink actually received the start, and
+//! never keeps a secret one.
+//!
+//! With metrics enabled, counter and measurement observations bypass sink
+//! fan-out entirely and fold into the [`metrics`](crate::metrics) aggregator;
+//! sinks see the aggregate when it is flushed.
 
 use std::cell::Cell;
 use std::collections::{HashMap, VecDeque};
+use std::io::Write;
 use std::panic::{AssertUnwindSafe, catch_unwind};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock};
 
 use logwise::{
-    Class, ContextToken, Detail, Dispatch, EventRef, InstallError, Interest, Metadata, Privacy,
-    Severity, SpanRef, SpanTiming, SpanToken, install_dispatcher,
+    Class, ContextToken, Detail, Dispatch, EventRef, FieldMetadata, FieldRef, InstallError,
+    Interest, Kind, Metadata, Privacy, Severity, SpanRef, SpanTiming, SpanToken, ValueRef,
+    install_dispatcher,
 };
 
-use crate::projection::{Capability, DetailLevel, EventSink, ProjectedEvent, ProjectedField};
+use crate::activation_rule::{ACTIVATION_RULE_FIRED, ActivationRule, ActivationRuleId};
+use crate::directive::{DIRECTIVES_ENV, Directive, DirectiveError, parse_directives};
+use crate::field_filter::FieldFilter;
+use crate::flight_recorder::FlightRecord;
+use crate::metrics::{Aggregator, MetricValue, MetricsSnapshot};
+use crate::projection::{
+    Capability, DetailLevel, EventSink, ProjectedEvent, ProjectedField, ProjectedSpan,
+    SpanLifecycle, Timestamp,
+};
+use crate::pseudonym::{PseudonymKey, Pseudonymization};
+use crate::rate_limit::{
+    Admission, Limiter, RateLimit, RateLimitId, RateLimitedSite, SUPPRESSION_SUMMARY, Suppressed,
+};
+use crate::sampling::{self, Decision, Sampler, Sampling, replay};
+use crate::sinks::{OwnedValue, own_value};
 use crate::spinlock::Spinlock;
 use crate::sys::{Duration, Instant};
 
@@ -46,6 +72,11 @@ pub struct CompletedSpan {
 /// [`RuntimeDeliveryStats::completed_spans_dropped`].
 pub const COMPLETED_SPAN_RETENTION: usize = 1024;
 
+/// How many retired count-bounded activations the runtime keeps for
+/// [`Runtime::take_retired_activations`]. Once full, the oldest is discarded
+/// and counted in [`RuntimeDeliveryStats::retired_activations_dropped`].
+pub const RETIRED_ACTIVATION_RETENTION: usize = 64;
+
 /// Platform constraint for an activation request.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Target {
@@ -54,15 +85,35 @@ pub enum Target {
 }
 
 /// Runtime selector over static metadata and causal context.
+///
+/// The builder methods that take a selector each narrow one slot, and a filter
+/// matches when every slot it sets matches. [`Filter::and`], [`Filter::or`],
+/// `!`, [`Filter::any_of`] and [`Filter::none_of`] compose whole filters.
+/// However it is composed, everything but the context selector is decided
+/// once per call site, so a filter never costs more than a cached interest
+/// lookup for the sites it cannot match.
+///
+/// A filter can also be written as a [directive](crate::directive) and parsed
+/// with `str::parse`; its `Display` writes the same directive back.
 #[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct Filter {
-    domain: Option<String>,
-    event_name: Option<String>,
-    class: Option<Class>,
-    minimum_severity: Option<Severity>,
-    context: Option<ContextToken>,
-    descendants: bool,
-    target: Option<Target>,
+    pub(crate) name: Option<String>,
+    pub(crate) domain: Option<String>,
+    pub(crate) event_name: Option<String>,
+    pub(crate) package: Option<String>,
+    pub(crate) file: Option<String>,
+    pub(crate) class: Option<Class>,
+    pub(crate) kind: Option<Kind>,
+    pub(crate) minimum_severity: Option<Severity>,
+    pub(crate) context: Option<ContextToken>,
+    pub(crate) descendants: bool,
+    pub(crate) target: Option<Target>,
+    /// Filters that must also match, where their slots clash with these.
+    pub(crate) all: Vec<Filter>,
+    /// At least one must match; never a single filter.
+    pub(crate) any: Vec<Filter>,
+    /// None may match.
+    pub(crate) none: Vec<Filter>,
 }
 
 impl Filter {
@@ -70,6 +121,14 @@ impl Filter {
         Self::default()
     }
 
+    /// Selects by event name, domain, package, target, or module — whichever
+    /// `name` is a hierarchical prefix of. This is what a bare name in a
+    /// directive means.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
     pub fn domain(mut self, domain: impl Into<String>) -> Self {
         self.domain = Some(domain.into());
         self
@@ -80,6 +139,24 @@ impl Filter {
         self
     }
 
+    /// Selects by package name, hierarchically.
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+
+    /// Selects call sites in a source file: the path as `file!()` recorded
+    /// it, a directory it is under, or a trailing run of its components.
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub const fn kind(mut self, kind: Kind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
     pub const fn class(mut self, class: Class) -> Self {
         self.class = Some(class);
         self
@@ -100,43 +177,254 @@ impl Filter {
         self.target = Some(target);
         self
     }
+
+    /// Matches what both filters match. Selectors of `other` that this
+    /// filter leaves unset are folded into it; the rest are kept as a nested
+    /// filter.
+    pub fn and(mut self, other: Filter) -> Self {
+        let clashes = (self.name.is_some() && other.name.is_some())
+            || (self.domain.is_some() && other.domain.is_some())
+            || (self.event_name.is_some() && other.event_name.is_some())
+            || (self.package.is_some() && other.package.is_some())
+            || (self.file.is_some() && other.file.is_some())
+            || (self.class.is_some() && other.class.is_some())
+            || (self.kind.is_some() && other.kind.is_some())
+            || (self.minimum_severity.is_some() && other.minimum_severity.is_some())
+            || (self.context.is_some() && other.context.is_some())
+            || (self.target.is_some() && other.target.is_some())
+            || (!self.any.is_empty() && !other.any.is_empty())
+            || !other.all.is_empty();
+        if clashes {
+            self.all.push(other);
+            return self;
+        }
+        self.name = self.name.or(other.name);
+        self.domain = self.domain.or(other.domain);
+        self.event_name = self.event_name.or(other.event_name);
+        self.package = self.package.or(other.package);
+        self.file = self.file.or(other.file);
+        self.class = self.class.or(other.class);
+        self.kind = self.kind.or(other.kind);
+        self.minimum_severity = self.minimum_severity.or(other.minimum_severity);
+        if other.context.is_some() {
+            self.context = other.context;
+            self.descendants = other.descendants;
+        }
+        self.target = self.target.or(other.target);
+        if self.any.is_empty() {
+            self.any = other.any;
+        }
+        self.none.extend(other.none);
+        self
+    }
+
+    /// Matches what either filter matches.
+    pub fn or(self, other: Filter) -> Self {
+        if self.is_any_only() {
+            let mut this = self;
+            this.any.push(other);
+            this
+        } else {
+            Self::any_of([self, other])
+        }
+    }
+
+    /// Matches what this filter matches, except what `excluded` matches.
+    pub fn except(mut self, excluded: Filter) -> Self {
+        self.none.push(excluded);
+        self
+    }
+
+    /// Matches what at least one of `filters` matches. With none, matches
+    /// nothing.
+    pub fn any_of(filters: impl IntoIterator<Item = Filter>) -> Self {
+        let mut filters: Vec<_> = filters.into_iter().collect();
+        match filters.len() {
+            0 => !Self::new(),
+            1 => filters.pop().unwrap(),
+            _ => Self {
+                any: filters,
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Matches what none of `filters` matches.
+    pub fn none_of(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Self {
+            none: filters.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Whether this is nothing but an any-of group.
+    pub(crate) fn is_any_only(&self) -> bool {
+        !self.any.is_empty()
+            && Self {
+                any: Vec::new(),
+                ..self.clone()
+            } == Self::new()
+    }
+}
+
+impl std::ops::Not for Filter {
+    type Output = Self;
+
+    /// Matches what this filter does not.
+    fn not(self) -> Self {
+        Self::none_of([self])
+    }
+}
+
+/// Whether a filter matches a call site, decided from static metadata alone.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum StaticMatch {
+    Never,
+    /// Depends on the event's context.
+    Contextual,
+    Always,
+}
+
+impl StaticMatch {
+    fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Never, _) | (_, Self::Never) => Self::Never,
+            (Self::Contextual, _) | (_, Self::Contextual) => Self::Contextual,
+            _ => Self::Always,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Always, _) | (_, Self::Always) => Self::Always,
+            (Self::Contextual, _) | (_, Self::Contextual) => Self::Contextual,
+            _ => Self::Never,
+        }
+    }
+
+    fn not(self) -> Self {
+        match self {
+            Self::Never => Self::Always,
+            Self::Contextual => Self::Contextual,
+            Self::Always => Self::Never,
+        }
+    }
 }
 
 /// Result of asking the runtime to activate an observed selector.
+///
+/// The result carries no counts. How many events an activation has captured
+/// is [`Runtime::activation`]'s answer for the [`ActivationId`] in
+/// `Enabled`, while it is live and after it retires, until
+/// [`Runtime::take_retired_activations`] hands it over.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum ActivationResult {
-    Enabled,
+    /// Live until its TTL runs out, or until
+    /// [`Runtime::cancel`] ends it early.
+    Enabled(ActivationId),
     UnavailableTarget,
     NotCompiled,
     UnknownSelector,
+    /// [`Runtime::activate_next`] was asked for no events, so there was
+    /// nothing to activate.
+    NoEvents,
+}
+
+/// Names one activation for [`Runtime::extend`] and [`Runtime::cancel`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ActivationId(u64);
+
+/// One live activation, as [`Runtime::activations`] lists it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ActivationSnapshot {
+    pub id: ActivationId,
+    pub filter: Filter,
+    pub interest: Interest,
+    /// `None` for an activation bounded only by a count. Zero once retired
+    /// by its TTL.
+    pub remaining: Option<Duration>,
+    /// Matching events it has captured so far: those a sink received while
+    /// it was live.
+    pub captured: u64,
+    /// Matching events left before a count-bounded activation retires.
+    pub events_left: Option<u64>,
+    /// The [`ActivationRule`] that fired it, if one did.
+    pub rule: Option<ActivationRuleId>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct SinkId(u64);
 
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
 pub struct RuntimeDeliveryStats {
     pub sink_panics: u64,
     pub reentrant_events_dropped: u64,
     /// Completed spans discarded because nothing drained
     /// [`Runtime::take_completed_spans`] before the retention window filled.
     pub completed_spans_dropped: u64,
+    /// Retired count-bounded activations discarded because nothing drained
+    /// [`Runtime::take_retired_activations`] before the retention window
+    /// filled.
+    pub retired_activations_dropped: u64,
+    /// Projected events a sink's [`FieldFilter`] kept from it.
+    pub field_filter_rejections: u64,
+    /// Events a sink's [`Sampling`] policy discarded, including held events
+    /// it evicted or dropped with the policy or the sink.
+    pub sampled_out: u64,
+    /// Events each call site had suppressed by a [`RateLimit`], most first.
+    pub rate_limited: Vec<RateLimitedSite>,
+    /// [`ActivationRule`] firings held back by a rule's bounds.
+    pub activation_rules_suppressed: u64,
 }
 
 #[derive(Debug)]
 struct ActiveSpan {
-    event_name: &'static str,
+    metadata: &'static Metadata,
     context: ContextToken,
     timing: SpanTiming,
     started: Instant,
     warning_threshold: Option<Duration>,
+    /// The non-secret fields the span was opened with, kept for the
+    /// completion event. Empty when no sink received the start.
+    fields: Vec<(&'static FieldMetadata, OwnedValue)>,
 }
 
 #[derive(Debug)]
 struct Activation {
+    id: ActivationId,
     filter: Filter,
     interest: Interest,
-    expires: Instant,
+    /// `None` for an activation bounded only by a count.
+    expires: Option<Instant>,
+    /// The rule that fired it, if one did.
+    rule: Option<ActivationRuleId>,
+    /// Matching events left before it retires, if it is count-bounded.
+    events_left: Option<u64>,
+    captured: u64,
+}
+
+impl Activation {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires.is_none_or(|expires| expires > now) && self.events_left != Some(0)
+    }
+
+    fn snapshot(&self, now: Instant) -> ActivationSnapshot {
+        ActivationSnapshot {
+            id: self.id,
+            filter: self.filter.clone(),
+            interest: self.interest,
+            remaining: self.expires.map(|expires| {
+                if expires > now {
+                    expires - now
+                } else {
+                    Duration::ZERO
+                }
+            }),
+            captured: self.captured,
+            events_left: self.events_left,
+            rule: self.rule,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -146,6 +434,9 @@ struct SinkRegistration {
     capability: Capability,
     detail: DetailLevel,
     filter: Filter,
+    field_filter: Option<Arc<FieldFilter>>,
+    sampler: Option<Arc<Sampler>>,
+    pseudonymization: Option<Arc<Pseudonymization>>,
 }
 
 #[derive(Default)]
@@ -154,8 +445,14 @@ struct State {
     active_spans: HashMap<u64, ActiveSpan>,
     completed_spans: VecDeque<CompletedSpan>,
     activations: Vec<Activation>,
+    /// Directives applied as configuration, folded into static interest the
+    /// way sink filters are.
+    configuration: Vec<(Filter, Interest)>,
+    retired_activations: VecDeque<ActivationSnapshot>,
     sinks: Vec<SinkRegistration>,
     catalog: Vec<&'static Metadata>,
+    rate_limits: Vec<(RateLimitId, Filter, RateLimit)>,
+    activation_rules: Vec<(ActivationRuleId, ActivationRule)>,
 }
 
 /// The mutable runtime installed behind logwise's stable facade dispatcher.
@@ -165,10 +462,22 @@ pub struct Runtime {
     next_context: AtomicU64,
     next_span: AtomicU64,
     next_sink: AtomicU64,
+    next_rate_limit: AtomicU64,
+    next_activation: AtomicU64,
+    next_activation_rule: AtomicU64,
     sink_panics: AtomicU64,
     reentrant_events_dropped: AtomicU64,
     completed_spans_dropped: AtomicU64,
+    retired_activations_dropped: AtomicU64,
+    field_filter_rejections: AtomicU64,
+    sampled_out: AtomicU64,
+    activation_rules_suppressed: AtomicU64,
     state: Spinlock<State>,
+    metrics: Aggregator,
+    limiter: Limiter,
+    /// Firing rate limits, keyed by activation rule ID.
+    rule_limiter: Limiter,
+    pseudonym_key: PseudonymKey,
 }
 
 impl std::fmt::Debug for Runtime {
@@ -204,10 +513,21 @@ impl Runtime {
             next_context: AtomicU64::new(1),
             next_span: AtomicU64::new(1),
             next_sink: AtomicU64::new(1),
+            next_rate_limit: AtomicU64::new(1),
+            next_activation: AtomicU64::new(1),
+            next_activation_rule: AtomicU64::new(1),
             sink_panics: AtomicU64::new(0),
             reentrant_events_dropped: AtomicU64::new(0),
             completed_spans_dropped: AtomicU64::new(0),
+            retired_activations_dropped: AtomicU64::new(0),
+            field_filter_rejections: AtomicU64::new(0),
+            sampled_out: AtomicU64::new(0),
+            activation_rules_suppressed: AtomicU64::new(0),
             state: Spinlock::new(State::default()),
+            metrics: Aggregator::new(),
+            limiter: Limiter::new(),
+            rule_limiter: Limiter::new(),
+            pseudonym_key: PseudonymKey::new(),
         }
     }
 
@@ -225,6 +545,40 @@ impl Runtime {
 
     /// Activates a selector over the observed call-site catalog.
     pub fn activate(&self, filter: Filter, interest: Interest, ttl: Duration) -> ActivationResult {
+        self.activate_bounded(filter, interest, Some(ttl), None)
+    }
+
+    /// Activates a selector for the next `events` events it matches, and
+    /// with a `ttl`, for no longer than that either. Whichever runs out
+    /// first retires the activation. Zero events is
+    /// [`ActivationResult::NoEvents`].
+    ///
+    /// An event is captured, and counted, when a sink receives it. One that
+    /// a rate limit suppresses, that no sink selects, or that every sink's
+    /// field filter or sampling holds back leaves the count alone. How many
+    /// events the activation captured is its
+    /// [`ActivationSnapshot::captured`], which [`Runtime::activation`]
+    /// reports for the returned ID while it is live and once it retires.
+    pub fn activate_next(
+        &self,
+        filter: Filter,
+        interest: Interest,
+        events: u64,
+        ttl: Option<Duration>,
+    ) -> ActivationResult {
+        if events == 0 {
+            return ActivationResult::NoEvents;
+        }
+        self.activate_bounded(filter, interest, ttl, Some(events))
+    }
+
+    fn activate_bounded(
+        &self,
+        filter: Filter,
+        interest: Interest,
+        ttl: Option<Duration>,
+        events: Option<u64>,
+    ) -> ActivationResult {
         if filter
             .target
             .is_some_and(|target| target != current_target())
@@ -232,21 +586,248 @@ impl Runtime {
             return ActivationResult::UnavailableTarget;
         }
 
-        let availability = self.selector_availability(&filter);
-        if availability != ActivationResult::Enabled && filter.context.is_none() {
-            return availability;
+        if let Some(unavailable) = self.selector_availability(&filter)
+            && filter.context.is_none()
+        {
+            return unavailable;
         }
 
-        let expires = Instant::now() + ttl;
+        let id = self.next_activation_id();
+        let expires = ttl.map(|ttl| Instant::now() + ttl);
         self.state.with_mut(|state| {
             state.activations.push(Activation {
+                id,
                 filter,
                 interest: interest.without_contextual(),
                 expires,
+                rule: None,
+                events_left: events,
+                captured: 0,
             });
         });
         self.advance_generation();
-        ActivationResult::Enabled
+        ActivationResult::Enabled(id)
+    }
+
+    /// Parses a directive list and activates each directive for `ttl`,
+    /// returning one result per directive in order.
+    ///
+    /// Nothing is activated unless the whole list parses.
+    pub fn activate_directives(
+        &self,
+        directives: &str,
+        ttl: Duration,
+    ) -> Result<Vec<ActivationResult>, DirectiveError> {
+        let directives = parse_directives(directives)?;
+        Ok(directives
+            .into_iter()
+            .map(|directive| {
+                let interest = directive.interest();
+                self.activate(directive.filter, interest, ttl)
+            })
+            .collect())
+    }
+
+    /// Ends every TTL activation whose filter equals `filter` before its
+    /// deadline, returning how many there were. Directives applied as
+    /// configuration are not activations and are not affected.
+    pub fn cancel_activations(&self, filter: &Filter) -> usize {
+        let cancelled = self.state.with_mut(|state| {
+            let before = state.activations.len();
+            state
+                .activations
+                .retain(|activation| activation.filter != *filter);
+            before - state.activations.len()
+        });
+        if cancelled != 0 {
+            self.advance_generation();
+        }
+        cancelled
+    }
+
+    /// Lists the live activations, oldest first, including those an
+    /// [`ActivationRule`] fired.
+    pub fn activations(&self) -> Vec<ActivationSnapshot> {
+        let now = Instant::now();
+        self.state.with(|state| {
+            state
+                .activations
+                .iter()
+                .filter(|activation| activation.is_live(now))
+                .map(|activation| activation.snapshot(now))
+                .collect()
+        })
+    }
+
+    /// The activation `id` names, with what it has
+    /// [`captured`](ActivationSnapshot::captured): live, or retired and not
+    /// yet taken by [`take_retired_activations`](Runtime::take_retired_activations).
+    /// `None` once it was cancelled, taken, or dropped from the retired ones.
+    pub fn activation(&self, id: ActivationId) -> Option<ActivationSnapshot> {
+        self.prune_expired_activations();
+        let now = Instant::now();
+        self.state.with(|state| {
+            state
+                .activations
+                .iter()
+                .find(|activation| activation.id == id && activation.is_live(now))
+                .map(|activation| activation.snapshot(now))
+                .or_else(|| {
+                    state
+                        .retired_activations
+                        .iter()
+                        .find(|retired| retired.id == id)
+                        .cloned()
+                })
+        })
+    }
+
+    /// Drains the count-bounded activations that have retired, each with
+    /// how many events it captured: the result of an
+    /// [`activate_next`](Runtime::activate_next).
+    ///
+    /// At most [`RETIRED_ACTIVATION_RETENTION`] are kept between calls.
+    /// Cancelled activations are not included: whoever cancelled one could
+    /// read [`Runtime::activations`] first.
+    pub fn take_retired_activations(&self) -> Vec<ActivationSnapshot> {
+        self.prune_expired_activations();
+        self.state
+            .with_mut(|state| Vec::from(std::mem::take(&mut state.retired_activations)))
+    }
+
+    /// Adds `ttl` to the time a live TTL activation has left. Returns
+    /// whether there was one to extend; an activation bounded only by a
+    /// count has no deadline to move.
+    pub fn extend(&self, id: ActivationId, ttl: Duration) -> bool {
+        let now = Instant::now();
+        let extended = self.state.with_mut(|state| {
+            let Some(expires) = state
+                .activations
+                .iter_mut()
+                .find(|activation| activation.id == id && activation.is_live(now))
+                .and_then(|activation| activation.expires.as_mut())
+            else {
+                return false;
+            };
+            *expires += ttl;
+            true
+        });
+        if extended {
+            self.advance_generation();
+        }
+        extended
+    }
+
+    /// Ends a TTL or count-bounded activation early. Returns whether it was
+    /// live.
+    pub fn cancel(&self, id: ActivationId) -> bool {
+        let now = Instant::now();
+        let cancelled = self.state.with_mut(|state| {
+            let Some(index) = state
+                .activations
+                .iter()
+                .position(|activation| activation.id == id && activation.is_live(now))
+            else {
+                return false;
+            };
+            state.activations.remove(index);
+            true
+        });
+        if cancelled {
+            self.advance_generation();
+        }
+        cancelled
+    }
+
+    /// Applies directives as configuration: a directive with no selector
+    /// raises the baseline interest, and every other directive raises the
+    /// interest of the call sites its filter selects, for as long as the
+    /// runtime lives. Like a sink filter, it is resolved when a call site's
+    /// interest is cached, so only a context selector leaves the site
+    /// contextual.
+    ///
+    /// Unlike [`Runtime::activate`], this does not consult the catalog, since
+    /// configuration is normally applied before any call site has run.
+    pub fn apply_directives(&self, directives: &[Directive]) {
+        let mut baseline = Interest::from_bits(self.baseline_interest.load(Ordering::Acquire));
+        self.state.with_mut(|state| {
+            for directive in directives {
+                if directive.filter == Filter::new() {
+                    baseline = baseline.union(directive.interest());
+                } else {
+                    state
+                        .configuration
+                        .push((directive.filter.clone(), directive.interest()));
+                }
+            }
+        });
+        self.set_interest(baseline);
+    }
+
+    /// Applies [`DIRECTIVES_ENV`] if it is set. A value that does not parse
+    /// is reported on stderr and otherwise ignored: `init` has no better
+    /// place to put it, and a typo must not take logging down with it.
+    fn apply_environment(&self) {
+        let Ok(directives) = std::env::var(DIRECTIVES_ENV) else {
+            return;
+        };
+        match parse_directives(&directives) {
+            Ok(directives) => self.apply_directives(&directives),
+            Err(error) => {
+                let _ = writeln!(
+                    std::io::stderr().lock(),
+                    "logwise: ignoring {DIRECTIVES_ENV}: {error}"
+                );
+            }
+        }
+    }
+
+    /// Starts aggregating counter and measurement call sites instead of
+    /// delivering each observation.
+    ///
+    /// Each metric keeps at most `cardinality_limit` label sets. With a
+    /// `flush_interval`, the aggregate is delivered to matching sinks from the
+    /// first observation after each interval elapses; without one, only
+    /// [`Runtime::flush_metrics`] delivers it. Calling this again changes the
+    /// limits and keeps what has been aggregated.
+    pub fn enable_metrics(&self, cardinality_limit: usize, flush_interval: Option<Duration>) {
+        self.metrics.enable(cardinality_limit, flush_interval);
+        self.advance_generation();
+    }
+
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Delivers every aggregated series to matching sinks as a metric record.
+    pub fn flush_metrics(&self) {
+        let snapshot = self.metrics.snapshot();
+        for family in &snapshot.families {
+            for series in &family.series {
+                let values: Vec<_> = series
+                    .labels
+                    .iter()
+                    .map(|(_, value)| ValueRef::Str(value))
+                    .collect();
+                let fields: Vec<_> = series
+                    .labels
+                    .iter()
+                    .zip(&values)
+                    .filter_map(|((name, _), value)| {
+                        family
+                            .label_fields
+                            .iter()
+                            .find(|field| field.name == *name)
+                            .map(|field| Some(FieldRef::new(field, *value)))
+                    })
+                    .collect();
+                self.deliver(
+                    EventRef::structured(family.metadata, ContextToken::NONE, &fields),
+                    None,
+                    Some(&series.value),
+                );
+            }
+        }
     }
 
     pub fn add_remote_sink(
@@ -278,6 +859,73 @@ impl Runtime {
         self.add_sink(sink, Capability::TrustedEphemeral, filter, detail)
     }
 
+    /// Attaches a post-projection [`FieldFilter`] to a sink, or with `None`
+    /// removes it. Returns whether the sink is registered.
+    ///
+    /// The filter only ever sees the fields this sink's view contains, and
+    /// does not change interest; see [`field_filter`](crate::field_filter).
+    pub fn set_field_filter(&self, id: SinkId, filter: Option<FieldFilter>) -> bool {
+        let filter = filter.map(Arc::new);
+        self.state.with_mut(|state| {
+            state
+                .sinks
+                .iter_mut()
+                .find(|registration| registration.id == id)
+                .map(|registration| registration.field_filter = filter)
+                .is_some()
+        })
+    }
+
+    /// Attaches a [`Sampling`] policy to a sink, or with `None` removes it.
+    /// Returns whether the sink is registered.
+    ///
+    /// Sampling runs after the sink's filters and does not change interest;
+    /// see [`sampling`]. Events a replaced policy was
+    /// holding are discarded and counted as sampled out.
+    pub fn set_sampling(&self, id: SinkId, sampling: Option<Sampling>) -> bool {
+        let sampler = sampling.map(|sampling| Arc::new(Sampler::new(sampling)));
+        let replaced = self.state.with_mut(|state| {
+            state
+                .sinks
+                .iter_mut()
+                .find(|registration| registration.id == id)
+                .map(|registration| std::mem::replace(&mut registration.sampler, sampler))
+        });
+        let found = replaced.is_some();
+        if let Some(Some(previous)) = replaced {
+            self.discard_held(&previous);
+        }
+        found
+    }
+
+    /// Attaches a [`Pseudonymization`] policy to a remote sink, or with
+    /// `None` removes it. Returns whether a remote sink with this ID is
+    /// registered; local sinks already see local-only values.
+    ///
+    /// See [`pseudonym`](crate::pseudonym) for which fields qualify.
+    pub fn set_pseudonymization(
+        &self,
+        id: SinkId,
+        pseudonymization: Option<Pseudonymization>,
+    ) -> bool {
+        let pseudonymization = pseudonymization.map(Arc::new);
+        let found = self.state.with_mut(|state| {
+            state
+                .sinks
+                .iter_mut()
+                .find(|registration| {
+                    registration.id == id && registration.capability == Capability::Remote
+                })
+                .map(|registration| registration.pseudonymization = pseudonymization)
+                .is_some()
+        });
+        if found {
+            // Pseudonymized fields have to be evaluated to be hashed.
+            self.advance_generation();
+        }
+        found
+    }
+
     pub fn remove_sink(&self, id: SinkId) -> bool {
         let removed = self.state.with_mut(|state| {
             state
@@ -290,12 +938,69 @@ impl Runtime {
         if did_remove {
             self.advance_generation();
         }
+        if let Some(sampler) = removed
+            .as_ref()
+            .and_then(|registration| registration.sampler.as_ref())
+        {
+            self.discard_held(sampler);
+        }
         // Sink destructors are user code and may log. Drop only after the
         // configuration lock has been released.
         drop(removed);
         did_remove
     }
 
+    /// Limits the call sites `filter` selects to `limit`; see
+    /// [`rate_limit`](crate::rate_limit). Limits are tried in the order they
+    /// were added, and the first that matches an event applies.
+    pub fn add_rate_limit(&self, filter: Filter, limit: RateLimit) -> RateLimitId {
+        let raw = self.next_rate_limit.fetch_add(1, Ordering::Relaxed);
+        assert_ne!(raw, u64::MAX, "logwise rate limit IDs exhausted");
+        let id = RateLimitId(raw);
+        self.state
+            .with_mut(|state| state.rate_limits.push((id, filter, limit)));
+        id
+    }
+
+    pub fn remove_rate_limit(&self, id: RateLimitId) -> bool {
+        let removed = self.state.with_mut(|state| {
+            let before = state.rate_limits.len();
+            state.rate_limits.retain(|(limit, _, _)| *limit != id);
+            state.rate_limits.len() != before
+        });
+        if removed {
+            self.limiter.forget(id);
+        }
+        removed
+    }
+
+    /// Adds a rule that activates detail for failing contexts; see
+    /// [`activation_rule`](crate::activation_rule).
+    pub fn add_activation_rule(&self, rule: ActivationRule) -> ActivationRuleId {
+        let raw = self.next_activation_rule.fetch_add(1, Ordering::Relaxed);
+        assert_ne!(raw, u64::MAX, "logwise activation rule IDs exhausted");
+        let id = ActivationRuleId(raw);
+        self.state
+            .with_mut(|state| state.activation_rules.push((id, rule)));
+        // Call sites the rule can fire on must now reach `emit`.
+        self.advance_generation();
+        id
+    }
+
+    /// Removes a rule. Activations it already made run out their TTL.
+    pub fn remove_activation_rule(&self, id: ActivationRuleId) -> bool {
+        let removed = self.state.with_mut(|state| {
+            let before = state.activation_rules.len();
+            state.activation_rules.retain(|(rule, _)| *rule != id);
+            state.activation_rules.len() != before
+        });
+        if removed {
+            self.rule_limiter.forget(RateLimitId(id.0));
+            self.advance_generation();
+        }
+        removed
+    }
+
     pub fn catalog(&self) -> Vec<&'static Metadata> {
         self.state.with(|state| state.catalog.clone())
     }
@@ -305,6 +1010,11 @@ impl Runtime {
             sink_panics: self.sink_panics.load(Ordering::Relaxed),
             reentrant_events_dropped: self.reentrant_events_dropped.load(Ordering::Relaxed),
             completed_spans_dropped: self.completed_spans_dropped.load(Ordering::Relaxed),
+            retired_activations_dropped: self.retired_activations_dropped.load(Ordering::Relaxed),
+            field_filter_rejections: self.field_filter_rejections.load(Ordering::Relaxed),
+            sampled_out: self.sampled_out.load(Ordering::Relaxed),
+            rate_limited: self.limiter.sites(),
+            activation_rules_suppressed: self.activation_rules_suppressed.load(Ordering::Relaxed),
         }
     }
 
@@ -317,6 +1027,33 @@ impl Runtime {
         self.state.with(|state| state.contexts.get(&id).cloned())
     }
 
+    /// Whether `filter` selects an event from `metadata` in `context`,
+    /// deciding its context selectors by this runtime's lineage.
+    pub(crate) fn selects(
+        &self,
+        filter: &Filter,
+        metadata: &'static Metadata,
+        context: ContextToken,
+    ) -> bool {
+        match filter.static_match(metadata) {
+            StaticMatch::Never => false,
+            StaticMatch::Always => true,
+            StaticMatch::Contextual => self
+                .state
+                .with(|state| filter.matches(metadata, &state.contexts, context)),
+        }
+    }
+
+    /// Keeps the records `filter` selects, deciding its context selectors
+    /// by this runtime's lineage.
+    pub(crate) fn retain_selected(&self, filter: &Filter, records: &mut Vec<FlightRecord>) {
+        self.state.with(|state| {
+            records.retain(|record| {
+                filter.matches(record.event.metadata, &state.contexts, record.event.context)
+            })
+        });
+    }
+
     /// Drains the retained completions.
     ///
     /// At most [`COMPLETED_SPAN_RETENTION`] are kept between calls; see
@@ -327,6 +1064,270 @@ impl Runtime {
             .with_mut(|state| Vec::from(std::mem::take(&mut state.completed_spans)))
     }
 
+    /// Projects one event for every matching sink and hands each its view
+    /// that its field filter accepts. Returns whether any sink's [`Filter`]
+    /// selected the event.
+    fn deliver(
+        &self,
+        event: EventRef<'_>,
+        span: Option<ProjectedSpan>,
+        metric: Option<&MetricValue>,
+    ) -> bool {
+        self.deliver_as(event, event.metadata, span, metric)
+    }
+
+    /// [`deliver`](Self::deliver), to the sinks that would receive an event
+    /// from `selector` rather than from the event's own call site.
+    fn deliver_as(
+        &self,
+        event: EventRef<'_>,
+        selector: &'static Metadata,
+        span: Option<ProjectedSpan>,
+        metric: Option<&MetricValue>,
+    ) -> bool {
+        let sinks = self
+            .state
+            .with(|state| Sinks::select(state, selector, event.context));
+        self.deliver_to(event, &sinks, span, metric).is_some()
+    }
+
+    /// [`deliver`](Self::deliver), to sinks already selected. Returns how
+    /// many sinks received the event then, or `None` if none selected it.
+    fn deliver_to(
+        &self,
+        event: EventRef<'_>,
+        sinks: &Sinks,
+        span: Option<ProjectedSpan>,
+        metric: Option<&MetricValue>,
+    ) -> Option<usize> {
+        if IN_DISPATCH.replace(true) {
+            self.reentrant_events_dropped
+                .fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        struct ResetDispatch;
+        impl Drop for ResetDispatch {
+            fn drop(&mut self) {
+                IN_DISPATCH.set(false);
+            }
+        }
+        let _reset = ResetDispatch;
+
+        let Sinks { sinks, root, key } = sinks;
+        let (root, key) = (*root, *key);
+        if sinks.is_empty() {
+            return None;
+        }
+        // One stamp per dispatch, so every view agrees on when it happened.
+        let timestamp = Timestamp::now();
+        let mut received = 0;
+        for sink in sinks {
+            // A field filter may format `Debug` or `Display` values, which is
+            // user code, so it runs under the same unwind guard as the sink.
+            let emitted = catch_unwind(AssertUnwindSafe(|| {
+                let pseudonyms = sink
+                    .pseudonymization
+                    .as_deref()
+                    .map(|policy| (policy, &self.pseudonym_key));
+                let projected = project_event(
+                    event,
+                    sink.capability,
+                    sink.detail,
+                    pseudonyms,
+                    timestamp,
+                    span,
+                    metric,
+                );
+                if let Some(field_filter) = &sink.field_filter
+                    && !field_filter.matches(&projected.fields)
+                {
+                    return None;
+                }
+                let Some(sampler) = &sink.sampler else {
+                    sink.sink.emit(projected);
+                    return Some(true);
+                };
+                Some(
+                    match sampler.decide(projected, root, key, &self.sampled_out) {
+                        Decision::Deliver(projected) => {
+                            sink.sink.emit(projected);
+                            true
+                        }
+                        Decision::Release(held, projected) => {
+                            for event in &held {
+                                replay(&*sink.sink, event);
+                            }
+                            if let Some(projected) = projected {
+                                sink.sink.emit(projected);
+                            }
+                            true
+                        }
+                        Decision::Withhold => false,
+                    },
+                )
+            }));
+            match emitted {
+                Ok(Some(emitted)) => received += usize::from(emitted),
+                Ok(None) => {
+                    self.field_filter_rejections.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(_) => {
+                    self.sink_panics.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        Some(received)
+    }
+
+    /// Passes an event through `limit`, the first rate limit that selects
+    /// it, if one does.
+    fn rate_limit(
+        &self,
+        event: &EventRef<'_>,
+        limit: Option<(RateLimitId, RateLimit)>,
+    ) -> Admission {
+        let Some((id, limit)) = limit else {
+            return Admission::Admit(None);
+        };
+        let context = if limit.is_per_context() {
+            event.context.into_parts().0
+        } else {
+            0
+        };
+        self.limiter.admit(id, limit, event.metadata, context)
+    }
+
+    /// Tells the sinks that would have received `metadata`'s events how many
+    /// a rate limit held back.
+    fn deliver_suppression_summary(
+        &self,
+        metadata: &'static Metadata,
+        context: ContextToken,
+        sinks: &Sinks,
+        suppressed: Suppressed,
+    ) {
+        let fields = [
+            Some(FieldRef::new(
+                &SUPPRESSION_SUMMARY.fields[0],
+                ValueRef::Str(metadata.event_name),
+            )),
+            Some(FieldRef::new(
+                &SUPPRESSION_SUMMARY.fields[1],
+                ValueRef::U64(suppressed.count),
+            )),
+            Some(FieldRef::new(
+                &SUPPRESSION_SUMMARY.fields[2],
+                ValueRef::Display(&suppressed.first),
+            )),
+            Some(FieldRef::new(
+                &SUPPRESSION_SUMMARY.fields[3],
+                ValueRef::Display(&suppressed.last),
+            )),
+        ];
+        self.deliver_to(
+            EventRef {
+                metadata: &SUPPRESSION_SUMMARY,
+                context,
+                fields: &fields,
+                message: Some(format_args!(
+                    "suppressed {} occurrences of `{}` in {:?}",
+                    suppressed.count,
+                    metadata.event_name,
+                    suppressed.window()
+                )),
+            },
+            sinks,
+            None,
+            None,
+        );
+    }
+
+    /// Fires `rules`, the activation rules `event` triggers, on `root`, then
+    /// reports each firing in the event's context.
+    fn fire_activation_rules(
+        &self,
+        event: &EventRef<'_>,
+        rules: Vec<(ActivationRuleId, ActivationRule)>,
+        root: ContextToken,
+    ) {
+        for (id, rule) in rules {
+            let filter = Filter::new().context(root, true);
+            let now = Instant::now();
+            let context = if rule.limit.is_per_context() {
+                event.context.into_parts().0
+            } else {
+                0
+            };
+            let activated = !self
+                .state
+                .with(|state| rule_blocked(state, id, &rule, &filter, now))
+                && matches!(
+                    self.rule_limiter
+                        .admit(RateLimitId(id.0), rule.limit, event.metadata, context),
+                    Admission::Admit(_)
+                )
+                && self.state.with_mut(|state| {
+                    if rule_blocked(state, id, &rule, &filter, now) {
+                        return false;
+                    }
+                    state.activations.push(Activation {
+                        id: self.next_activation_id(),
+                        filter,
+                        interest: rule.interest,
+                        expires: Some(now + rule.ttl),
+                        rule: Some(id),
+                        events_left: None,
+                        captured: 0,
+                    });
+                    true
+                });
+            if !activated {
+                self.activation_rules_suppressed
+                    .fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            self.advance_generation();
+            let fields = [
+                Some(FieldRef::new(
+                    &ACTIVATION_RULE_FIRED.fields[0],
+                    ValueRef::U64(id.0),
+                )),
+                Some(FieldRef::new(
+                    &ACTIVATION_RULE_FIRED.fields[1],
+                    ValueRef::Str(event.metadata.event_name),
+                )),
+                Some(FieldRef::new(
+                    &ACTIVATION_RULE_FIRED.fields[2],
+                    ValueRef::U64(root.into_parts().0),
+                )),
+                Some(FieldRef::new(
+                    &ACTIVATION_RULE_FIRED.fields[3],
+                    ValueRef::U64(rule.ttl.as_millis() as u64),
+                )),
+            ];
+            self.deliver(
+                EventRef {
+                    metadata: &ACTIVATION_RULE_FIRED,
+                    context: event.context,
+                    fields: &fields,
+                    message: Some(format_args!(
+                        "activated context {} for {:?} after `{}`",
+                        root.into_parts().0,
+                        rule.ttl,
+                        event.metadata.event_name
+                    )),
+                },
+                None,
+                None,
+            );
+        }
+    }
+
+    fn discard_held(&self, sampler: &Sampler) {
+        self.sampled_out
+            .fetch_add(sampler.discard_held() as u64, Ordering::Relaxed);
+    }
+
     fn advance_generation(&self) {
         let previous = self.generation.fetch_add(1, Ordering::AcqRel);
         assert_ne!(previous, usize::MAX - 1, "logwise generation exhausted");
@@ -349,20 +1350,25 @@ impl Runtime {
                 capability,
                 detail,
                 filter,
+                field_filter: None,
+                sampler: None,
+                pseudonymization: None,
             });
         });
         self.advance_generation();
         id
     }
 
-    fn selector_availability(&self, filter: &Filter) -> ActivationResult {
+    /// Why `filter` cannot be activated, or `None` if it selects an observed
+    /// call site.
+    fn selector_availability(&self, filter: &Filter) -> Option<ActivationResult> {
         self.state.with(|state| {
             if state
                 .catalog
                 .iter()
                 .any(|metadata| filter.matches_static(metadata))
             {
-                return ActivationResult::Enabled;
+                return None;
             }
             if filter.event_name.is_some()
                 && state.catalog.iter().any(|metadata| {
@@ -372,52 +1378,101 @@ impl Runtime {
                         .is_none_or(|domain| domain_matches(metadata, domain))
                 })
             {
-                ActivationResult::NotCompiled
+                Some(ActivationResult::NotCompiled)
             } else {
-                ActivationResult::UnknownSelector
+                Some(ActivationResult::UnknownSelector)
             }
         })
     }
 
+    fn next_activation_id(&self) -> ActivationId {
+        let id = self.next_activation.fetch_add(1, Ordering::Relaxed);
+        assert_ne!(id, u64::MAX, "logwise activation IDs exhausted");
+        ActivationId(id)
+    }
+
     fn activation_interest(
         &self,
         metadata: Option<&'static Metadata>,
         context: ContextToken,
     ) -> Interest {
         let now = Instant::now();
-        let (interest, removed_expired) = self.state.with_mut(|state| {
-            let before = state.activations.len();
-            state
-                .activations
-                .retain(|activation| activation.expires > now);
+        let (interest, retired) = self.state.with_mut(|state| {
+            let retired = self.retire_activations(state, now);
             let mut interest = Interest::NONE;
             for activation in &state.activations {
-                if metadata.is_none_or(|metadata| activation.filter.matches_static(metadata))
-                    && activation.filter.matches_context(&state.contexts, context)
-                {
+                let matches = match metadata {
+                    Some(metadata) => activation
+                        .filter
+                        .matches(metadata, &state.contexts, context),
+                    // Asked about a context, not an event: only the
+                    // activation's own context selector can answer.
+                    None => activation.filter.matches_context(&state.contexts, context),
+                };
+                if matches {
                     interest = interest.union(activation.interest);
                 }
             }
-            (interest, before != state.activations.len())
+            (interest, retired)
         });
-        if removed_expired {
+        if retired {
             self.advance_generation();
         }
         interest
     }
 
+    /// Counts an event some sink received against the live activations
+    /// among `ids`, retiring those it leaves with no events.
+    fn capture(&self, ids: &[ActivationId]) {
+        let now = Instant::now();
+        let retired = self.state.with_mut(|state| {
+            let mut exhausted = false;
+            for activation in &mut state.activations {
+                if ids.contains(&activation.id) && activation.is_live(now) {
+                    activation.captured += 1;
+                    if let Some(left) = &mut activation.events_left {
+                        *left -= 1;
+                        exhausted |= *left == 0;
+                    }
+                }
+            }
+            exhausted && self.retire_activations(state, now)
+        });
+        if retired {
+            self.advance_generation();
+        }
+    }
+
+    /// Removes the activations that have run out of time or events, keeping
+    /// what count-bounded ones captured. Returns whether any were removed.
+    fn retire_activations(&self, state: &mut State, now: Instant) -> bool {
+        let before = state.activations.len();
+        let retired = &mut state.retired_activations;
+        state.activations.retain(|activation| {
+            if activation.is_live(now) {
+                return true;
+            }
+            if activation.events_left.is_some() {
+                if retired.len() == RETIRED_ACTIVATION_RETENTION {
+                    retired.pop_front();
+                    self.retired_activations_dropped
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                retired.push_back(activation.snapshot(now));
+            }
+            false
+        });
+        before != state.activations.len()
+    }
+
     fn prune_expired_activations(&self) {
         if self.state.with(|state| state.activations.is_empty()) {
             return;
         }
         let now = Instant::now();
-        let removed = self.state.with_mut(|state| {
-            let before = state.activations.len();
-            state
-                .activations
-                .retain(|activation| activation.expires > now);
-            before != state.activations.len()
-        });
+        let removed = self
+            .state
+            .with_mut(|state| self.retire_activations(state, now));
         if removed {
             self.advance_generation();
         }
@@ -430,16 +1485,111 @@ impl Default for Runtime {
     }
 }
 
+/// The root context ID of `context`'s tree and its sampling key, or zeros
+/// outside any context. The root is the oldest ancestor this runtime knows.
+fn sampling_key(contexts: &HashMap<u64, ContextSnapshot>, context: ContextToken) -> (u64, u64) {
+    if context.is_none() {
+        return (0, 0);
+    }
+    let mut root = context.into_parts().0;
+    while let Some(parent) = contexts.get(&root).and_then(|snapshot| snapshot.parent) {
+        root = parent.into_parts().0;
+    }
+    match context.into_parts().1 {
+        0 => (root, sampling::root_key(root)),
+        key => (root, key),
+    }
+}
+
+/// The sinks that would receive an event, with its sampling root and key
+/// when one of them samples.
+struct Sinks {
+    sinks: Vec<SinkRegistration>,
+    root: u64,
+    key: u64,
+}
+
+impl Sinks {
+    fn select(state: &State, selector: &'static Metadata, context: ContextToken) -> Self {
+        let sinks: Vec<_> = state
+            .sinks
+            .iter()
+            .filter(|sink| {
+                !(selector.kind == logwise::Kind::AdHocText
+                    && sink.capability == Capability::Remote)
+                    && sink.filter.matches(selector, &state.contexts, context)
+            })
+            .cloned()
+            .collect();
+        let (root, key) = if sinks.iter().any(|sink| sink.sampler.is_some()) {
+            sampling_key(&state.contexts, context)
+        } else {
+            (0, 0)
+        };
+        Self { sinks, root, key }
+    }
+}
+
+/// The activation rules `event` triggers, and the root they would activate.
+fn triggered_rules(
+    state: &State,
+    event: &EventRef<'_>,
+) -> (Vec<(ActivationRuleId, ActivationRule)>, ContextToken) {
+    if event.context.is_none() || state.activation_rules.is_empty() {
+        return (Vec::new(), ContextToken::NONE);
+    }
+    let rules: Vec<_> = state
+        .activation_rules
+        .iter()
+        .filter(|(_, rule)| {
+            rule.severe_enough(event.metadata)
+                && rule
+                    .trigger
+                    .matches(event.metadata, &state.contexts, event.context)
+        })
+        .cloned()
+        .collect();
+    let root = sampling_key(&state.contexts, event.context).0;
+    let root = state
+        .contexts
+        .get(&root)
+        .map_or(ContextToken::from_parts(root, 0), |snapshot| snapshot.token);
+    (rules, root)
+}
+
+/// Whether `rule` may not activate `filter` now: it already has, or it has
+/// as many activations live as it may.
+fn rule_blocked(
+    state: &State,
+    id: ActivationRuleId,
+    rule: &ActivationRule,
+    filter: &Filter,
+    now: Instant,
+) -> bool {
+    let mut live = 0;
+    for activation in &state.activations {
+        if activation.rule == Some(id) && activation.is_live(now) {
+            if activation.filter == *filter {
+                return true;
+            }
+            live += 1;
+        }
+    }
+    live >= rule.max_active
+}
+
+/// Whether `candidate` is `ancestor` or below it. Tokens are compared by ID:
+/// the flags word carries a sampling key, not identity.
 fn is_descendant(
     contexts: &HashMap<u64, ContextSnapshot>,
     mut candidate: ContextToken,
     ancestor: ContextToken,
 ) -> bool {
     while !candidate.is_none() {
-        if candidate == ancestor {
+        let id = candidate.into_parts().0;
+        if id == ancestor.into_parts().0 {
             return true;
         }
-        let id = candidate.into_parts().0;
         let Some(snapshot) = contexts.get(&id) else {
             return false;
         };
@@ -452,8 +1602,71 @@ fn is_descendant(
 }
 
 impl Filter {
+    /// Whether this filter can match events from `metadata`, and whether
+    /// that depends on their context.
+    fn static_match(&self, metadata: &'static Metadata) -> StaticMatch {
+        if !self.matches_selectors(metadata) {
+            return StaticMatch::Never;
+        }
+        let mut result = if self.context.is_some() {
+            StaticMatch::Contextual
+        } else {
+            StaticMatch::Always
+        };
+        for filter in &self.all {
+            result = result.and(filter.static_match(metadata));
+        }
+        if !self.any.is_empty() {
+            result = result.and(self.any.iter().fold(StaticMatch::Never, |any, filter| {
+                any.or(filter.static_match(metadata))
+            }));
+        }
+        for filter in &self.none {
+            result = result.and(filter.static_match(metadata).not());
+        }
+        result
+    }
+
     fn matches_static(&self, metadata: &'static Metadata) -> bool {
+        self.static_match(metadata) != StaticMatch::Never
+    }
+
+    /// Whether an event from `metadata` in `context` matches.
+    fn matches(
+        &self,
+        metadata: &'static Metadata,
+        contexts: &HashMap<u64, ContextSnapshot>,
+        context: ContextToken,
+    ) -> bool {
+        match self.static_match(metadata) {
+            StaticMatch::Never => false,
+            StaticMatch::Always => true,
+            StaticMatch::Contextual => {
+                self.matches_selectors(metadata)
+                    && self.matches_context(contexts, context)
+                    && self
+                        .all
+                        .iter()
+                        .all(|filter| filter.matches(metadata, contexts, context))
+                    && (self.any.is_empty()
+                        || self
+                            .any
+                            .iter()
+                            .any(|filter| filter.matches(metadata, contexts, context)))
+                    && !self
+                        .none
+                        .iter()
+                        .any(|filter| filter.matches(metadata, contexts, context))
+            }
+        }
+    }
+
+    /// The static selectors of this filter alone, without nested filters.
+    fn matches_selectors(&self, metadata: &'static Metadata) -> bool {
         self.target.is_none_or(|target| target == current_target())
+            && self.name.as_deref().is_none_or(|name| {
+                hierarchy_matches(metadata.event_name, name) || domain_matches(metadata, name)
+            })
             && self
                 .domain
                 .as_deref()
@@ -462,7 +1675,17 @@ impl Filter {
                 .event_name
                 .as_deref()
                 .is_none_or(|event| hierarchy_matches(metadata.event_name, event))
+            && self
+                .package
+                .as_deref()
+                .is_none_or(|package| hierarchy_matches(metadata.package, package))
+            && self.file.as_deref().is_none_or(|file| {
+                metadata
+                    .location
+                    .is_some_and(|location| file_matches(location.file, file))
+            })
             && self.class.is_none_or(|class| metadata.class == class)
+            && self.kind.is_none_or(|kind| metadata.kind == kind)
             && self
                 .minimum_severity
                 .is_none_or(|severity| metadata.severity as u8 >= severity as u8)
@@ -479,7 +1702,7 @@ impl Filter {
         if self.descendants {
             is_descendant(contexts, context, selected)
         } else {
-            context == selected
+            context.into_parts().0 == selected.into_parts().0
         }
     }
 }
@@ -491,6 +1714,17 @@ fn hierarchy_matches(value: &str, selector: &str) -> bool {
             .is_some_and(|suffix| suffix.starts_with('.') || suffix.starts_with("::"))
 }
 
+fn file_matches(file: &str, selector: &str) -> bool {
+    let selector = selector.trim_end_matches('/');
+    file == selector
+        || file
+            .strip_prefix(selector)
+            .is_some_and(|rest| rest.starts_with('/'))
+        || file
+            .strip_suffix(selector)
+            .is_some_and(|rest| rest.ends_with('/'))
+}
+
 fn domain_matches(metadata: &Metadata, selector: &str) -> bool {
     metadata
         .domain
@@ -508,7 +1742,13 @@ const fn current_target() -> Target {
     }
 }
 
-fn sink_interest(capability: Capability, detail: DetailLevel) -> Interest {
+fn sink_interest(sink: &SinkRegistration) -> Interest {
+    // A pseudonymizing remote sink needs local-only values to hash.
+    let capability = if sink.pseudonymization.is_some() {
+        Capability::LocalRetained
+    } else {
+        sink.capability
+    };
     let core = match capability {
         Capability::Remote => Interest::CORE_SUPPORT,
         Capability::LocalRetained => Interest::CORE_SUPPORT.union(Interest::CORE_LOCAL),
@@ -516,7 +1756,7 @@ fn sink_interest(capability: Capability, detail: DetailLevel) -> Interest {
             .union(Interest::CORE_LOCAL)
             .union(Interest::CORE_SECRET),
     };
-    if detail == DetailLevel::Core {
+    if sink.detail == DetailLevel::Core {
         return core;
     }
     core.union(match capability {
@@ -536,35 +1776,59 @@ fn privacy_allowed(capability: Capability, privacy: Privacy) -> bool {
     }
 }
 
-fn project_event(
-    event: EventRef<'_>,
+fn project_event<'a>(
+    event: EventRef<'a>,
     capability: Capability,
     detail: DetailLevel,
-) -> ProjectedEvent<'_> {
+    pseudonyms: Option<(&Pseudonymization, &PseudonymKey)>,
+    timestamp: Timestamp,
+    span: Option<ProjectedSpan>,
+    metric: Option<&'a MetricValue>,
+) -> ProjectedEvent<'a> {
     let fields: Vec<_> = event
         .fields
         .iter()
         .flatten()
-        .filter(|field| {
-            privacy_allowed(capability, field.metadata.privacy)
-                && (detail == DetailLevel::Full || field.metadata.detail == Detail::Core)
-        })
-        .map(|field| ProjectedField {
-            name: field.metadata.name,
-            privacy: field.metadata.privacy,
-            detail: field.metadata.detail,
-            value: field.value,
+        .filter(|field| detail == DetailLevel::Full || field.metadata.detail == Detail::Core)
+        .filter_map(|field| {
+            let FieldMetadata {
+                name,
+                privacy,
+                detail,
+            } = *field.metadata;
+            let (value, pseudonymized) = if privacy_allowed(capability, privacy) {
+                (field.value, false)
+            } else {
+                let (_, key) = pseudonyms.filter(|(policy, _)| policy.covers(name, privacy))?;
+                (ValueRef::U64(key.token(name, field.value)), true)
+            };
+            Some(ProjectedField {
+                name,
+                privacy,
+                detail,
+                value,
+                pseudonymized,
+            })
         })
         .collect();
-    let omitted_fields = event.metadata.fields.len().saturating_sub(fields.len());
+    // A metric record carries its series labels, not the call site's
+    // fields, so only labels this view lost count as omitted.
+    let offered = match metric {
+        Some(_) => event.fields.iter().flatten().count(),
+        None => event.metadata.fields.len(),
+    };
+    let omitted_fields = offered.saturating_sub(fields.len());
     ProjectedEvent {
         metadata: event.metadata,
         context: event.context,
+        timestamp,
         fields,
         message: (capability != Capability::Remote)
             .then_some(event.message)
             .flatten(),
         omitted_fields,
+        span,
+        metric,
     }
 }
 
@@ -576,6 +1840,7 @@ impl Dispatch for Runtime {
     fn interest(&self, metadata: &'static Metadata) -> Interest {
         self.prune_expired_activations();
         let mut interest = Interest::from_bits(self.baseline_interest.load(Ordering::Acquire));
+        let aggregated = self.metrics.aggregates(metadata);
         self.state.with_mut(|state| {
             if !state
                 .catalog
@@ -584,13 +1849,31 @@ impl Dispatch for Runtime {
             {
                 state.catalog.push(metadata);
             }
+            if aggregated {
+                // Labels and quantities only ever come from core support-safe
+                // fields, so that is all an aggregated site needs to evaluate.
+                interest = Interest::CORE_SUPPORT;
+                return;
+            }
             for sink in &state.sinks {
-                if sink.filter.matches_static(metadata) {
-                    interest = interest.union(if sink.filter.context.is_some() {
-                        Interest::CONTEXTUAL
-                    } else {
-                        sink_interest(sink.capability, sink.detail)
-                    });
+                interest = interest.union(match sink.filter.static_match(metadata) {
+                    StaticMatch::Never => Interest::NONE,
+                    StaticMatch::Contextual => Interest::CONTEXTUAL,
+                    StaticMatch::Always => sink_interest(sink),
+                });
+            }
+            for (filter, configured) in &state.configuration {
+                interest = interest.union(match filter.static_match(metadata) {
+                    StaticMatch::Never => Interest::NONE,
+                    StaticMatch::Contextual => Interest::CONTEXTUAL,
+                    StaticMatch::Always => *configured,
+                });
+            }
+            for (_, rule) in &state.activation_rules {
+                if rule.severe_enough(metadata) && rule.trigger.matches_static(metadata) {
+                    // Field groups are left to sinks: a rule only needs the
+                    // event to reach `emit`.
+                    interest = interest.union(Interest::CONTEXTUAL);
                 }
             }
             for activation in &state.activations {
@@ -610,52 +1893,74 @@ impl Dispatch for Runtime {
             .union(self.activation_interest(Some(metadata), context));
         self.state.with(|state| {
             for sink in &state.sinks {
-                if sink.filter.matches_static(metadata)
-                    && sink.filter.matches_context(&state.contexts, context)
-                {
-                    interest = interest.union(sink_interest(sink.capability, sink.detail));
+                if sink.filter.matches(metadata, &state.contexts, context) {
+                    interest = interest.union(sink_interest(sink));
                 }
             }
+            for (filter, configured) in &state.configuration {
+                if filter.matches(metadata, &state.contexts, context) {
+                    interest = interest.union(*configured);
+                }
+            }
+            if !context.is_none()
+                && state.activation_rules.iter().any(|(_, rule)| {
+                    rule.severe_enough(metadata)
+                        && rule.trigger.matches(metadata, &state.contexts, context)
+                })
+            {
+                interest = interest.union(Interest::CONTEXTUAL);
+            }
         });
         interest
     }
 
     fn emit(&self, event: EventRef<'_>) {
-        if IN_DISPATCH.replace(true) {
-            self.reentrant_events_dropped
-                .fetch_add(1, Ordering::Relaxed);
-            return;
-        }
-        struct ResetDispatch;
-        impl Drop for ResetDispatch {
-            fn drop(&mut self) {
-                IN_DISPATCH.set(false);
+        if self.metrics.aggregates(event.metadata) {
+            // A flush from inside a sink callback would only be dropped as
+            // reentrant; leave it for the next observation.
+            if self.metrics.record(event) && !IN_DISPATCH.get() {
+                self.flush_metrics();
             }
+            return;
         }
-        let _reset = ResetDispatch;
-
-        let sinks: Vec<_> = self.state.with(|state| {
-            state
-                .sinks
-                .iter()
-                .filter(|sink| {
-                    !(event.metadata.kind == logwise::Kind::AdHocText
-                        && sink.capability == Capability::Remote)
-                        && sink.filter.matches_static(event.metadata)
-                        && sink.filter.matches_context(&state.contexts, event.context)
-                })
-                .cloned()
-                .collect()
+        // One pass over the state settles the rate limit, the sinks and the
+        // activation rules, so an event takes the lock once on the way in.
+        let (limit, sinks, (rules, root), activations) = self.state.with(|state| {
+            (
+                state
+                    .rate_limits
+                    .iter()
+                    .find(|(_, filter, _)| {
+                        filter.matches(event.metadata, &state.contexts, event.context)
+                    })
+                    .map(|(id, _, limit)| (*id, *limit)),
+                Sinks::select(state, event.metadata, event.context),
+                triggered_rules(state, &event),
+                state
+                    .activations
+                    .iter()
+                    .filter(|activation| {
+                        activation
+                            .filter
+                            .matches(event.metadata, &state.contexts, event.context)
+                    })
+                    .map(|activation| activation.id)
+                    .collect::<Vec<_>>(),
+            )
         });
-        for sink in sinks {
-            if catch_unwind(AssertUnwindSafe(|| {
-                sink.sink
-                    .emit(project_event(event, sink.capability, sink.detail));
-            }))
-            .is_err()
-            {
-                self.sink_panics.fetch_add(1, Ordering::Relaxed);
+        match self.rate_limit(&event, limit) {
+            Admission::Suppress => return,
+            Admission::Admit(Some(suppressed)) => {
+                self.deliver_suppression_summary(event.metadata, event.context, &sinks, suppressed);
             }
+            Admission::Admit(None) => {}
+        }
+        let received = self.deliver_to(event, &sinks, None, None);
+        if !activations.is_empty() && received.is_some_and(|received| received != 0) {
+            self.capture(&activations);
+        }
+        if !rules.is_empty() {
+            self.fire_activation_rules(&event, rules, root);
         }
     }
 
@@ -666,8 +1971,14 @@ impl Dispatch for Runtime {
     fn create_context(&self, parent: ContextToken, name: &'static str) -> ContextToken {
         let id = self.next_context.fetch_add(1, Ordering::Relaxed);
         assert_ne!(id, u64::MAX, "logwise context IDs exhausted");
-        let token = ContextToken::from_parts(id, 0);
         self.state.with_mut(|state| {
+            // Every context in a tree carries its root's sampling key.
+            let key = if parent.is_none() {
+                sampling::root_key(id)
+            } else {
+                sampling_key(&state.contexts, parent).1
+            };
+            let token = ContextToken::from_parts(id, key);
             state.contexts.insert(
                 id,
                 ContextSnapshot {
@@ -677,8 +1988,8 @@ impl Dispatch for Runtime {
                     links: Vec::new(),
                 },
             );
-        });
-        token
+            token
+        })
     }
 
     fn link_context(&self, context: ContextToken, related: ContextToken) {
@@ -704,12 +2015,38 @@ impl Dispatch for Runtime {
         let id = self.next_span.fetch_add(1, Ordering::Relaxed);
         assert_ne!(id, u64::MAX, "logwise span IDs exhausted");
         let token = SpanToken::from_parts(id, 0);
+        let started = Instant::now();
+        let delivered = self.deliver(
+            span.event,
+            Some(ProjectedSpan {
+                token,
+                timing: span.timing,
+                warning_threshold: span.warning_threshold,
+                lifecycle: SpanLifecycle::Started,
+            }),
+            None,
+        );
+        // Copied only for a span some sink is watching, so a span that only
+        // feeds `take_completed_spans` costs no formatting. Secret values are
+        // never retained, which leaves them counted as omitted on completion.
+        let fields = if delivered {
+            span.event
+                .fields
+                .iter()
+                .flatten()
+                .filter(|field| field.metadata.privacy != Privacy::Secret)
+                .map(|field| (field.metadata, own_value(field.value, usize::MAX, &mut 0)))
+                .collect()
+        } else {
+            Vec::new()
+        };
         let active = ActiveSpan {
-            event_name: span.event.metadata.event_name,
+            metadata: span.event.metadata,
             context: span.event.context,
             timing: span.timing,
-            started: Instant::now(),
+            started,
             warning_threshold: span.warning_threshold,
+            fields,
         };
         self.state.with_mut(|state| {
             state.active_spans.insert(id, active);
@@ -738,7 +2075,7 @@ impl Dispatch for Runtime {
             }
             state.completed_spans.push_back(CompletedSpan {
                 token: span,
-                event_name: active.event_name,
+                event_name: active.metadata.event_name,
                 context: captured_context,
                 timing: active.timing,
                 elapsed,
@@ -746,14 +2083,39 @@ impl Dispatch for Runtime {
                 threshold_exceeded,
             });
         });
+
+        let fields: Vec<_> = active
+            .fields
+            .iter()
+            .map(|(metadata, value)| Some(FieldRef::new(metadata, value.as_value_ref())))
+            .collect();
+        self.deliver(
+            EventRef::structured(active.metadata, captured_context, &fields),
+            Some(ProjectedSpan {
+                token: span,
+                timing: active.timing,
+                warning_threshold: active.warning_threshold,
+                lifecycle: SpanLifecycle::Completed {
+                    elapsed,
+                    threshold_exceeded,
+                },
+            }),
+            None,
+        );
     }
 }
 
 static RUNTIME: OnceLock<Runtime> = OnceLock::new();
 
 /// Installs the standard runtime dispatcher once and returns its mutable core.
+///
+/// The first call applies [`DIRECTIVES_ENV`] from the environment, if set.
 pub fn init() -> Result<&'static Runtime, InstallError> {
-    let runtime = RUNTIME.get_or_init(Runtime::new);
+    let runtime = RUNTIME.get_or_init(|| {
+        let runtime = Runtime::new();
+        runtime.apply_environment();
+        runtime
+    });
     install_dispatcher(runtime)?;
     Ok(runtime)
 }
@@ -869,14 +2231,14 @@ mod tests {
 
         let isolated = Runtime::new();
         assert_eq!(isolated.interest(&METADATA), Interest::NONE);
-        assert_eq!(
+        assert!(matches!(
             isolated.activate(
                 Filter::new().event(METADATA.event_name),
                 Interest::DETAIL_LOCAL,
                 Duration::ZERO,
             ),
-            ActivationResult::Enabled
-        );
+            ActivationResult::Enabled(_)
+        ));
         assert_eq!(isolated.interest(&METADATA), Interest::NONE);
     }
 }
@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Sink filters on what an event says, not just where it came from.
+//!
+//! A [`Filter`](crate::Filter) is decided from static metadata and context,
+//! mostly once per call site. A [`FieldFilter`] is decided per event, after
+//! projection: [`Runtime::set_field_filter`](crate::Runtime::set_field_filter)
+//! attaches one to a sink, and the runtime evaluates it against the
+//! [`ProjectedEvent`](crate::ProjectedEvent) it built for that sink, just
+//! before handing it over.
+//!
+//! Evaluating after projection is what keeps it honest about privacy. The
+//! predicate sees exactly the fields the sink would have been given, so a
+//! remote sink's filter cannot test a local-only value, and a core-detail
+//! sink's filter cannot test a detail field. A field the view does not
+//! contain, for whatever reason, fails every comparison.
+//!
+//! Because nothing about it is known at interest time, a field filter never
+//! lowers interest: call sites still evaluate the fields the sink asked for,
+//! and the events it rejects are counted in
+//! [`RuntimeDeliveryStats::field_filter_rejections`](crate::RuntimeDeliveryStats::field_filter_rejections).
+//! Each end of a span is an event of its own and is filtered on its own.
+
+use core::cmp::Ordering;
+
+use logwise::ValueRef;
+
+use crate::projection::ProjectedField;
+use crate::sinks::OwnedValue;
+
+/// How a field's value must relate to a [`FieldFilter`]'s literal.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    const fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Ne => ordering.is_ne(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// A predicate over one projected event's fields.
+///
+/// Integers and floats compare numerically with each other, booleans with
+/// booleans, and strings lexicographically with string fields, including
+/// values captured through `Debug` or `Display`, which are formatted for the
+/// comparison. Any other pairing, like a string literal against a number,
+/// fails, so `status != "500"` does not match a numeric `status`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldFilter {
+    /// Some field named `field` compares to `value` as `comparison` says.
+    Compare {
+        field: String,
+        comparison: Comparison,
+        value: OwnedValue,
+    },
+    /// Every filter matches. Empty matches everything.
+    All(Vec<FieldFilter>),
+    /// At least one filter matches. Empty matches nothing.
+    Any(Vec<FieldFilter>),
+    /// The filter does not match, which includes its field being absent.
+    Not(Box<FieldFilter>),
+}
+
+impl FieldFilter {
+    pub fn compare(
+        field: impl Into<String>,
+        comparison: Comparison,
+        value: impl Into<OwnedValue>,
+    ) -> Self {
+        Self::Compare {
+            field: field.into(),
+            comparison,
+            value: value.into(),
+        }
+    }
+
+    /// Matches events where both filters match.
+    pub fn and(self, other: FieldFilter) -> Self {
+        match self {
+            Self::All(mut filters) => {
+                filters.push(other);
+                Self::All(filters)
+            }
+            this => Self::All(vec![this, other]),
+        }
+    }
+
+    /// Matches events where either filter matches.
+    pub fn or(self, other: FieldFilter) -> Self {
+        match self {
+            Self::Any(mut filters) => {
+                filters.push(other);
+                Self::Any(filters)
+            }
+            this => Self::Any(vec![this, other]),
+        }
+    }
+
+    pub fn matches(&self, fields: &[ProjectedField<'_>]) -> bool {
+        match self {
+            Self::Compare {
+                field,
+                comparison,
+                value,
+            } => fields.iter().any(|candidate| {
+                candidate.name == field
+                    && compare(candidate.value, value)
+                        .is_some_and(|ordering| comparison.holds(ordering))
+            }),
+            Self::All(filters) => filters.iter().all(|filter| filter.matches(fields)),
+            Self::Any(filters) => filters.iter().any(|filter| filter.matches(fields)),
+            Self::Not(filter) => !filter.matches(fields),
+        }
+    }
+}
+
+impl std::ops::Not for FieldFilter {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Self::Not(filter) => *filter,
+            this => Self::Not(Box::new(this)),
+        }
+    }
+}
+
+/// Orders a field's value against a literal, or `None` when they are not
+/// comparable.
+fn compare(value: ValueRef<'_>, literal: &OwnedValue) -> Option<Ordering> {
+    match (value, literal) {
+        (ValueRef::Bool(value), OwnedValue::Bool(literal)) => Some(value.cmp(literal)),
+        (ValueRef::Str(value), OwnedValue::String(literal)) => Some(value.cmp(literal.as_str())),
+        (ValueRef::Debug(value), OwnedValue::String(literal)) => {
+            Some(format!("{value:?}").cmp(literal))
+        }
+        (ValueRef::Display(value), OwnedValue::String(literal)) => {
+            Some(value.to_string().cmp(literal))
+        }
+        (value, literal) => {
+            let value = match value {
+                ValueRef::I64(value) => Number::Integer(value.into()),
+                ValueRef::U64(value) => Number::Integer(value.into()),
+                ValueRef::F64(value) => Number::Float(value),
+                _ => return None,
+            };
+            let literal = match literal {
+                OwnedValue::I64(literal) => Number::Integer((*literal).into()),
+                OwnedValue::U64(literal) => Number::Integer((*literal).into()),
+                OwnedValue::F64(literal) => Number::Float(*literal),
+                _ => return None,
+            };
+            match (value, literal) {
+                (Number::Integer(value), Number::Integer(literal)) => Some(value.cmp(&literal)),
+                (value, literal) => value.as_f64().partial_cmp(&literal.as_f64()),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Integer(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+}
@@ -0,0 +1,377 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Incidents: the records around a failure, frozen where later traffic
+//! cannot overwrite them.
+//!
+//! A [`FlightRecorder`] built [`with_capture`](FlightRecorder::with_capture)
+//! watches what it accepts for a trigger: by default an event of error
+//! severity or worse, or a span that finished past its warning threshold, as
+//! `perfwarn` spans do. When one arrives, the recorder copies out the
+//! `before` records that preceded it, waits for `after` more, copies those
+//! too, and either hands the whole window to a designated sink or keeps it as
+//! an incident that [`FlightRecorder::incident`] retrieves by
+//! [`IncidentId`].
+//!
+//! Windows are read from the recorder's shards the way
+//! [`read_since`](FlightRecorder::read_since) reads them, without waiting. A
+//! window record that was already overwritten, or was in a busy shard when
+//! the window was copied, is counted in [`FlightIncident::missed`] rather
+//! than waited for.
+//!
+//! An error in a loop would otherwise pin a window per iteration. Triggers
+//! pass a [`RateLimit`] per call site, and only the newest `keep` incidents
+//! are kept; [`CaptureStats`] counts what both held back.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use logwise::{ContextToken, Metadata, Severity};
+
+use crate::flight_recorder::{
+    FlightCursor, FlightRecord, FlightRecorder, RecorderView, project_record,
+};
+use crate::projection::{ProjectedSpan, SpanLifecycle};
+use crate::rate_limit::{Admission, Limiter, RateLimit, RateLimitId};
+use crate::sampling::replay;
+use crate::sinks::OwnedProjectedEvent;
+use crate::sys::Duration;
+use crate::{EventSink, Filter, Runtime};
+
+/// Identifies an incident for as long as its recorder keeps it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct IncidentId(pub u64);
+
+/// The records around one trigger, oldest first.
+#[derive(Clone, Debug)]
+pub struct FlightIncident {
+    pub id: IncidentId,
+    /// The sequence number of the record that triggered the capture.
+    pub trigger: u64,
+    pub records: Vec<FlightRecord>,
+    /// Window records that were overwritten, or in a busy shard, before
+    /// they could be copied.
+    pub missed: usize,
+}
+
+/// Cumulative capture accounting.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CaptureStats {
+    /// Triggers that started an incident.
+    pub triggered: u64,
+    /// Triggers the rate limit held back.
+    pub suppressed: u64,
+    /// Incidents handed to the designated sink.
+    pub delivered: u64,
+    /// Kept incidents discarded to make room for newer ones.
+    pub evicted: u64,
+}
+
+/// What makes a [`FlightRecorder`] capture an incident, how much it
+/// captures, and where the incident goes.
+pub struct FlightCapture {
+    runtime: &'static Runtime,
+    trigger: Filter,
+    threshold_breaches: bool,
+    before: usize,
+    after: usize,
+    limit: RateLimit,
+    keep: usize,
+    delivery: Option<(Arc<dyn EventSink>, RecorderView)>,
+}
+
+impl std::fmt::Debug for FlightCapture {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("FlightCapture")
+            .field("trigger", &self.trigger)
+            .field("threshold_breaches", &self.threshold_breaches)
+            .field("before", &self.before)
+            .field("after", &self.after)
+            .field("limit", &self.limit)
+            .field("keep", &self.keep)
+            .field("delivery", &self.delivery.as_ref().map(|(_, view)| view))
+            .finish_non_exhaustive()
+    }
+}
+
+impl FlightCapture {
+    /// Captures 32 records before and 8 after each error-severity event or
+    /// threshold breach, at most 4 a minute from any one call site, and
+    /// keeps the newest 8 incidents. `runtime`'s lineage decides the context
+    /// selectors of the trigger filter.
+    pub fn new(runtime: &'static Runtime) -> Self {
+        Self {
+            runtime,
+            trigger: Filter::new().minimum_severity(Severity::Error),
+            threshold_breaches: true,
+            before: 32,
+            after: 8,
+            limit: RateLimit::new(4, Duration::from_secs(60)),
+            keep: 8,
+            delivery: None,
+        }
+    }
+
+    /// Replaces the default error-severity trigger. `!Filter::new()` leaves
+    /// only threshold breaches.
+    pub fn trigger(mut self, filter: Filter) -> Self {
+        self.trigger = filter;
+        self
+    }
+
+    /// Whether a span finishing past its warning threshold triggers a
+    /// capture whatever the filter says.
+    pub const fn threshold_breaches(mut self, enabled: bool) -> Self {
+        self.threshold_breaches = enabled;
+        self
+    }
+
+    /// Records to capture before and after the trigger, which is captured
+    /// too.
+    pub const fn window(mut self, before: usize, after: usize) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    pub const fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// How many incidents to keep. The oldest is discarded for a new one.
+    pub const fn keep(mut self, incidents: usize) -> Self {
+        self.keep = incidents;
+        self
+    }
+
+    /// Hands each finished incident to `sink`, one record at a time and
+    /// projected for `view`, instead of keeping it.
+    pub fn deliver_to(mut self, sink: Arc<dyn EventSink>, view: RecorderView) -> Self {
+        self.delivery = Some((sink, view));
+        self
+    }
+}
+
+/// An incident waiting for the records after its trigger.
+struct Pending {
+    incident: FlightIncident,
+    last: u64,
+}
+
+/// A recorder's capture state.
+pub(crate) struct Capture {
+    config: FlightCapture,
+    limiter: Limiter,
+    next_id: AtomicU64,
+    waiting: AtomicUsize,
+    pending: Mutex<Vec<Pending>>,
+    incidents: Mutex<VecDeque<FlightIncident>>,
+    triggered: AtomicU64,
+    suppressed: AtomicU64,
+    delivered: AtomicU64,
+    evicted: AtomicU64,
+}
+
+impl Capture {
+    /// Whether accepting `event` should start an incident.
+    pub(crate) fn triggers(&self, event: &OwnedProjectedEvent) -> bool {
+        let breached = matches!(
+            event.span,
+            Some(ProjectedSpan {
+                lifecycle: SpanLifecycle::Completed {
+                    threshold_exceeded: true,
+                    ..
+                },
+                ..
+            })
+        );
+        (self.config.threshold_breaches && breached)
+            || self
+                .config
+                .runtime
+                .selects(&self.config.trigger, event.metadata, event.context)
+    }
+
+    /// Called once the recorder has accepted record `sequence`, with the
+    /// trigger's call site and context if it was one.
+    pub(crate) fn accepted(
+        &self,
+        recorder: &FlightRecorder,
+        sequence: u64,
+        trigger: Option<(&'static Metadata, ContextToken)>,
+    ) {
+        if let Some((metadata, context)) = trigger {
+            self.start(recorder, sequence, metadata, context);
+        }
+        if self.waiting.load(Ordering::Acquire) != 0 {
+            self.complete(recorder, sequence);
+        }
+    }
+
+    fn start(
+        &self,
+        recorder: &FlightRecorder,
+        sequence: u64,
+        metadata: &'static Metadata,
+        context: ContextToken,
+    ) {
+        let limit = self.config.limit;
+        let context = if limit.is_per_context() {
+            context.into_parts().0
+        } else {
+            0
+        };
+        if let Admission::Suppress = self.limiter.admit(RateLimitId(0), limit, metadata, context) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.triggered.fetch_add(1, Ordering::Relaxed);
+        // The trigger has no sequence number 0 before it.
+        let first = sequence.saturating_sub(self.config.before as u64).max(1);
+        let (records, missed) = window(recorder, first - 1, sequence);
+        let incident = FlightIncident {
+            id: IncidentId(self.next_id.fetch_add(1, Ordering::Relaxed) + 1),
+            trigger: sequence,
+            records,
+            missed,
+        };
+        if self.config.after == 0 {
+            self.finish(incident);
+            return;
+        }
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Pending {
+                incident,
+                last: sequence + self.config.after as u64,
+            });
+        self.waiting.fetch_add(1, Ordering::Release);
+    }
+
+    /// Finishes the incidents whose window ends at or before `sequence`. A
+    /// contended check is left to the next record.
+    fn complete(&self, recorder: &FlightRecorder, sequence: u64) {
+        let Ok(mut pending) = self.pending.try_lock() else {
+            return;
+        };
+        let done: Vec<_> = pending
+            .extract_if(.., |pending| pending.last <= sequence)
+            .collect();
+        drop(pending);
+        self.waiting.fetch_sub(done.len(), Ordering::Release);
+        for Pending { mut incident, last } in done {
+            let (records, missed) = window(recorder, incident.trigger, last);
+            incident.records.extend(records);
+            incident.missed += missed;
+            self.finish(incident);
+        }
+    }
+
+    fn finish(&self, incident: FlightIncident) {
+        if let Some((sink, view)) = &self.config.delivery {
+            for record in incident.records {
+                replay(&**sink, &project_record(record, *view).event);
+            }
+            self.delivered.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let mut incidents = self
+            .incidents
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if incidents.len() >= self.config.keep {
+            self.evicted.fetch_add(1, Ordering::Relaxed);
+            if self.config.keep == 0 {
+                return;
+            }
+            incidents.pop_front();
+        }
+        incidents.push_back(incident);
+    }
+}
+
+/// The retained records after `after` up to and including `last`, and how
+/// many of them could not be copied.
+fn window(recorder: &FlightRecorder, after: u64, last: u64) -> (Vec<FlightRecord>, usize) {
+    let read = recorder.read(
+        FlightCursor(after),
+        |record| record.sequence <= last,
+        |records| records,
+    );
+    let missed = (last - after) as usize - read.records.len();
+    (read.records, missed)
+}
+
+impl FlightRecorder {
+    /// Captures incidents as `capture` describes.
+    pub fn with_capture(mut self, capture: FlightCapture) -> Self {
+        self.capture = Some(Capture {
+            config: capture,
+            limiter: Limiter::new(),
+            next_id: AtomicU64::new(0),
+            waiting: AtomicUsize::new(0),
+            pending: Mutex::new(Vec::new()),
+            incidents: Mutex::new(VecDeque::new()),
+            triggered: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+        });
+        self
+    }
+
+    /// The kept incidents, oldest first.
+    pub fn incidents(&self) -> Vec<IncidentId> {
+        self.kept(|incidents| incidents.iter().map(|incident| incident.id).collect())
+            .unwrap_or_default()
+    }
+
+    /// A kept incident, projected for `view`.
+    pub fn incident(&self, id: IncidentId, view: RecorderView) -> Option<FlightIncident> {
+        self.kept(|incidents| {
+            let incident = incidents.iter().find(|incident| incident.id == id)?;
+            Some(project_incident(incident.clone(), view))
+        })?
+    }
+
+    /// Removes a kept incident and returns it projected for `view`.
+    pub fn take_incident(&self, id: IncidentId, view: RecorderView) -> Option<FlightIncident> {
+        self.kept(|incidents| {
+            let index = incidents.iter().position(|incident| incident.id == id)?;
+            Some(project_incident(incidents.remove(index)?, view))
+        })?
+    }
+
+    pub fn capture_stats(&self) -> CaptureStats {
+        let Some(capture) = &self.capture else {
+            return CaptureStats::default();
+        };
+        CaptureStats {
+            triggered: capture.triggered.load(Ordering::Relaxed),
+            suppressed: capture.suppressed.load(Ordering::Relaxed),
+            delivered: capture.delivered.load(Ordering::Relaxed),
+            evicted: capture.evicted.load(Ordering::Relaxed),
+        }
+    }
+
+    fn kept<R>(&self, read: impl FnOnce(&mut VecDeque<FlightIncident>) -> R) -> Option<R> {
+        let capture = self.capture.as_ref()?;
+        let mut incidents = capture
+            .incidents
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Some(read(&mut incidents))
+    }
+}
+
+fn project_incident(mut incident: FlightIncident, view: RecorderView) -> FlightIncident {
+    incident.records = incident
+        .records
+        .into_iter()
+        .map(|record| project_record(record, view))
+        .collect();
+    incident
+}
@@ -0,0 +1,906 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Flight recorder snapshots that outlive the process.
+//!
+//! [`FlightRecorder::dump`] writes everything the recorder retains, projected
+//! for one [`RecorderView`], as JSON Lines:
+//!
+//! | Line | Keys |
+//! |---|---|
+//! | first | `logwise_flight_dump` ([`FLIGHT_DUMP_VERSION`]), `view`, `requested_cursor`, `next_cursor`, `accepted`, `dropped`, `overwritten`, `overwritten_by_band` and `rejected_by_band` (counts keyed by [`RetentionBand::name`]; absent from older dumps, and loaded as zeros), `truncated_fields`, `omitted_fields`, `busy_shards`, `sites`, `records` |
+//! | one per call site | `site`, then `event`, `package`, `target`, `module`, `domain`, `class`, `severity`, `kind` and `location` as in the [`json_lines`](crate::json_lines) schema, and `fields`: `{"name","privacy","detail"}` per declared field |
+//! | one per record | `record` (its sequence number), `site`, `context_token` and `span_token` (each `[id,flags]` or `null`), `wall_ns`, and `event`: the record in the `json_lines` schema |
+//!
+//! The site lines are the subset of the catalog the records use, so a dump
+//! reads without the binary that wrote it. Counts in the first line are the
+//! recorder's totals at the time of the dump, as a [`FlightRead`] reports
+//! them, and `sites` and `records` say how many lines follow.
+//!
+//! [`FlightDump::load`] reads a dump back into [`FlightRecord`]s. A record
+//! refers to its call site by `&'static Metadata`, so each distinct site a
+//! dump describes is allocated once for the life of the process and shared
+//! by every later load that describes it again.
+//!
+//! [`install_panic_hook_with_dump`](crate::install_panic_hook_with_dump)
+//! writes a dump to a configured path from the panic hook, after the panic
+//! message has been recorded and before the previous hook runs. The
+//! recorder is read with `try_lock`, so a panic while a shard is held costs
+//! that shard's records, counted in `busy_shards`, rather than a deadlock.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use logwise::{
+    Class, ContextToken, Detail, Domain, FieldMetadata, Kind, Location, Metadata, Privacy,
+    Severity, SpanTiming, SpanToken,
+};
+
+use crate::flight_recorder::{
+    FlightCursor, FlightRead, FlightRecord, FlightRecorder, RecorderView, RetentionBand,
+};
+use crate::json_lines::{encode_event, string};
+use crate::metrics::{Histogram, HistogramBucket, MetricValue};
+use crate::names::{class_name, detail_name, kind_name, privacy_name, severity_name};
+use crate::projection::{ProjectedSpan, SpanLifecycle, Timestamp};
+use crate::sinks::{OwnedField, OwnedProjectedEvent, OwnedValue};
+use crate::sys::Duration;
+
+/// The `logwise_flight_dump` written on the first line of every dump.
+pub const FLIGHT_DUMP_VERSION: u32 = 1;
+
+/// Every call site a load has allocated, so loading the same sites again
+/// allocates nothing.
+static LOADED_SITES: Mutex<Vec<&'static Metadata>> = Mutex::new(Vec::new());
+
+impl FlightRecorder {
+    /// Writes a snapshot of everything retained, projected for `view`, and
+    /// returns the read it was made from.
+    pub fn dump(&self, mut writer: impl Write, view: RecorderView) -> io::Result<FlightRead> {
+        let read = self.read_since(FlightCursor::default(), view);
+        let mut sites: Vec<&'static Metadata> = Vec::new();
+        let indices: Vec<usize> = read
+            .records
+            .iter()
+            .map(|record| {
+                let metadata = record.event.metadata;
+                sites
+                    .iter()
+                    .position(|site| std::ptr::eq(*site, metadata))
+                    .unwrap_or_else(|| {
+                        sites.push(metadata);
+                        sites.len() - 1
+                    })
+            })
+            .collect();
+
+        let mut line = String::new();
+        let _ = writeln!(
+            line,
+            "{{\"logwise_flight_dump\":{FLIGHT_DUMP_VERSION},\"view\":\"{}\",\"requested_cursor\":{},\"next_cursor\":{},\"accepted\":{},\"dropped\":{},\"overwritten\":{},\"overwritten_by_band\":{{{}}},\"rejected_by_band\":{{{}}},\"truncated_fields\":{},\"omitted_fields\":{},\"busy_shards\":{},\"sites\":{},\"records\":{}}}",
+            view_name(view),
+            read.requested_cursor.0,
+            read.next_cursor.0,
+            self.stats().accepted,
+            read.dropped_total,
+            read.overwritten_total,
+            RetentionBand::ALL
+                .map(|band| format!(
+                    "\"{}\":{}",
+                    band.name(),
+                    read.overwritten_by_band[band as usize]
+                ))
+                .join(","),
+            RetentionBand::ALL
+                .map(|band| format!(
+                    "\"{}\":{}",
+                    band.name(),
+                    read.rejected_by_band[band as usize]
+                ))
+                .join(","),
+            read.truncated_fields_total,
+            read.omitted_fields,
+            read.busy_shards,
+            sites.len(),
+            read.records.len(),
+        );
+        writer.write_all(line.as_bytes())?;
+        for (index, site) in sites.iter().enumerate() {
+            line.clear();
+            encode_site(&mut line, index, site);
+            writer.write_all(line.as_bytes())?;
+        }
+        for (record, site) in read.records.iter().zip(indices) {
+            line.clear();
+            let _ = write!(
+                line,
+                "{{\"record\":{},\"site\":{site},\"context_token\":",
+                record.sequence
+            );
+            token(&mut line, record.event.context.into_parts());
+            line.push_str(",\"span_token\":");
+            match record.event.span {
+                Some(span) => token(&mut line, span.token.into_parts()),
+                None => line.push_str("null"),
+            }
+            let _ = write!(
+                line,
+                ",\"wall_ns\":{},\"event\":",
+                record.event.timestamp.wall.as_nanos()
+            );
+            encode_event(&mut line, &record.event);
+            line.push_str("}\n");
+            writer.write_all(line.as_bytes())?;
+        }
+        writer.flush()?;
+        Ok(read)
+    }
+}
+
+fn encode_site(line: &mut String, index: usize, site: &Metadata) {
+    let _ = write!(line, "{{\"site\":{index}");
+    for (key, value) in [
+        ("event", site.event_name),
+        ("package", site.package),
+        ("target", site.target),
+        ("module", site.module),
+    ] {
+        let _ = write!(line, ",\"{key}\":");
+        string(line, value);
+    }
+    line.push_str(",\"domain\":");
+    match site.domain {
+        Some(domain) => string(line, domain.name),
+        None => line.push_str("null"),
+    }
+    let _ = write!(
+        line,
+        ",\"class\":\"{}\",\"severity\":\"{}\",\"kind\":\"{}\",\"location\":",
+        class_name(site.class),
+        severity_name(site.severity),
+        kind_name(site.kind)
+    );
+    match site.location {
+        Some(location) => {
+            line.push_str("{\"file\":");
+            string(line, location.file);
+            let _ = write!(
+                line,
+                ",\"line\":{},\"column\":{}}}",
+                location.line, location.column
+            );
+        }
+        None => line.push_str("null"),
+    }
+    line.push_str(",\"fields\":[");
+    for (index, field) in site.fields.iter().enumerate() {
+        if index != 0 {
+            line.push(',');
+        }
+        line.push_str("{\"name\":");
+        string(line, field.name);
+        let _ = write!(
+            line,
+            ",\"privacy\":\"{}\",\"detail\":\"{}\"}}",
+            privacy_name(field.privacy),
+            detail_name(field.detail)
+        );
+    }
+    line.push_str("]}\n");
+}
+
+fn token(line: &mut String, (id, flags): (u64, u64)) {
+    let _ = write!(line, "[{id},{flags}]");
+}
+
+const fn view_name(view: RecorderView) -> &'static str {
+    match view {
+        RecorderView::Local => "local",
+        RecorderView::Remote => "remote",
+    }
+}
+
+/// A dump read back by [`FlightDump::load`].
+#[derive(Clone, Debug)]
+pub struct FlightDump {
+    /// The projection the records were dumped in.
+    pub view: RecorderView,
+    /// Records the recorder had accepted when it was dumped.
+    pub accepted: u64,
+    /// The records, cursors and loss totals, as the dumping read saw them.
+    pub read: FlightRead,
+    /// The call sites the records refer to.
+    pub catalog: Vec<&'static Metadata>,
+}
+
+impl FlightDump {
+    /// Reads a dump written by [`FlightRecorder::dump`]. Anything else, a
+    /// later [`FLIGHT_DUMP_VERSION`], or a dump cut short is
+    /// [`io::ErrorKind::InvalidData`].
+    pub fn load(reader: impl Read) -> io::Result<Self> {
+        let mut lines = BufReader::new(reader).lines().enumerate();
+        let mut next = |what: &str| -> io::Result<(usize, Json)> {
+            let Some((number, line)) = lines.next() else {
+                return Err(invalid(format!("the dump ends before its {what}")));
+            };
+            let line = line?;
+            let value = Json::parse(&line).map_err(|error| at(number, error))?;
+            Ok((number, value))
+        };
+
+        let (number, header) = next("header")?;
+        let load_header = || -> Result<_, String> {
+            let version = header.u64("logwise_flight_dump")?;
+            if version != u64::from(FLIGHT_DUMP_VERSION) {
+                return Err(format!("unsupported flight dump version {version}"));
+            }
+            let view = match header.str("view")? {
+                "local" => RecorderView::Local,
+                "remote" => RecorderView::Remote,
+                other => return Err(format!("unknown view `{other}`")),
+            };
+            Ok((
+                view,
+                header.u64("sites")?,
+                header.u64("records")?,
+                header.u64("accepted")?,
+                FlightRead {
+                    requested_cursor: FlightCursor(header.u64("requested_cursor")?),
+                    next_cursor: FlightCursor(header.u64("next_cursor")?),
+                    records: Vec::new(),
+                    dropped_total: header.u64("dropped")?,
+                    overwritten_total: header.u64("overwritten")?,
+                    overwritten_by_band: band_counts(&header, "overwritten_by_band")?,
+                    rejected_by_band: band_counts(&header, "rejected_by_band")?,
+                    truncated_fields_total: header.u64("truncated_fields")?,
+                    omitted_fields: header.usize("omitted_fields")?,
+                    busy_shards: header.usize("busy_shards")?,
+                },
+            ))
+        };
+        let (view, sites, records, accepted, mut read) =
+            load_header().map_err(|error| at(number, error))?;
+
+        let mut catalog = Vec::new();
+        for index in 0..sites {
+            let (number, line) = next("call sites")?;
+            let site = load_site(&line, index).map_err(|error| at(number, error))?;
+            catalog.push(site);
+        }
+        for _ in 0..records {
+            let (number, line) = next("records")?;
+            let record = load_record(&line, &catalog).map_err(|error| at(number, error))?;
+            read.records.push(record);
+        }
+        Ok(Self {
+            view,
+            accepted,
+            read,
+            catalog,
+        })
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn at(index: usize, message: String) -> io::Error {
+    invalid(format!("flight dump line {}: {message}", index + 1))
+}
+
+fn load_site(line: &Json, index: u64) -> Result<&'static Metadata, String> {
+    if line.u64("site")? != index {
+        return Err(format!("expected call site {index}"));
+    }
+    let domain = match line.get("domain")? {
+        Json::Null => None,
+        domain => Some(domain.as_str()?),
+    };
+    let location = match line.get("location")? {
+        Json::Null => None,
+        location => Some((
+            location.str("file")?,
+            u32_of(location.u64("line")?)?,
+            u32_of(location.u64("column")?)?,
+        )),
+    };
+    let fields = line
+        .get("fields")?
+        .as_array()?
+        .iter()
+        .map(|field| {
+            Ok((
+                field.str("name")?,
+                privacy(field.str("privacy")?)?,
+                detail(field.str("detail")?)?,
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let (event_name, package, target, module) = (
+        line.str("event")?,
+        line.str("package")?,
+        line.str("target")?,
+        line.str("module")?,
+    );
+    let (severity, class, kind) = (
+        severity(line.str("severity")?)?,
+        class(line.str("class")?)?,
+        kind(line.str("kind")?)?,
+    );
+
+    let mut loaded = LOADED_SITES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let same = |site: &&&'static Metadata| {
+        site.event_name == event_name
+            && site.package == package
+            && site.target == target
+            && site.module == module
+            && site.domain.map(|domain| domain.name) == domain
+            && site.severity == severity
+            && site.class == class
+            && site.kind == kind
+            && site
+                .location
+                .map(|location| (location.file, location.line, location.column))
+                == location
+            && site.fields.len() == fields.len()
+            && site.fields.iter().zip(&fields).all(|(field, loaded)| {
+                (field.name, field.privacy, field.detail) == (loaded.0, loaded.1, loaded.2)
+            })
+    };
+    if let Some(&site) = loaded.iter().find(same) {
+        return Ok(site);
+    }
+    let fields: Vec<FieldMetadata> = fields
+        .into_iter()
+        .map(|(name, privacy, detail)| FieldMetadata::new(leak(name), privacy, detail))
+        .collect();
+    let site: &'static Metadata = Box::leak(Box::new(Metadata {
+        event_name: leak(event_name),
+        package: leak(package),
+        target: leak(target),
+        module: leak(module),
+        domain: domain.map(|domain| Domain::new(leak(domain))),
+        severity,
+        class,
+        kind,
+        location: location.map(|(file, line, column)| Location::new(leak(file), line, column)),
+        fields: Box::leak(fields.into_boxed_slice()),
+    }));
+    loaded.push(site);
+    Ok(site)
+}
+
+fn leak(text: &str) -> &'static str {
+    Box::leak(text.to_owned().into_boxed_str())
+}
+
+fn load_record(line: &Json, catalog: &[&'static Metadata]) -> Result<FlightRecord, String> {
+    let sequence = line.u64("record")?;
+    let site = line.usize("site")?;
+    let metadata = *catalog.get(site).ok_or_else(|| {
+        format!("record {sequence} names call site {site}, which is not in the dump")
+    })?;
+    let (id, flags) = token_parts(line.get("context_token")?)?;
+    let context = ContextToken::from_parts(id, flags);
+    let event = line.get("event")?;
+    let timestamp = Timestamp {
+        monotonic: Duration::from_nanos(event.u64("monotonic_ns")?),
+        wall: Duration::from_nanos(line.u64("wall_ns")?),
+    };
+
+    let fields = event
+        .get("fields")?
+        .as_array()?
+        .iter()
+        .map(|field| {
+            let name = field.str("name")?;
+            let declared = metadata
+                .fields
+                .iter()
+                .find(|declared| declared.name == name)
+                .ok_or_else(|| format!("field `{name}` is not declared by its call site"))?;
+            let value = field.get("value")?;
+            let value = match field.str("type")? {
+                "bool" => OwnedValue::Bool(value.as_bool()?),
+                "i64" => OwnedValue::I64(value.as_i64()?),
+                "u64" => OwnedValue::U64(value.as_u64()?),
+                "f64" => OwnedValue::F64(value.as_f64()?),
+                "string" => OwnedValue::String(value.as_str()?.to_owned()),
+                other => return Err(format!("unknown field type `{other}`")),
+            };
+            let pseudonymized = match field.get("pseudonymized") {
+                Ok(value) => value.as_bool()?,
+                Err(_) => false,
+            };
+            Ok(OwnedField {
+                name: declared.name,
+                privacy: privacy(field.str("privacy")?)?,
+                detail: detail(field.str("detail")?)?,
+                value,
+                pseudonymized,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let message = match event.get("message")? {
+        Json::Null => None,
+        message => Some(message.as_str()?.to_owned()),
+    };
+    let span = match event.get("span")? {
+        Json::Null => None,
+        span => Some(load_span(span, line.get("span_token")?)?),
+    };
+    let metric = match event.get("metric")? {
+        Json::Null => None,
+        metric => Some(load_metric(metric)?),
+    };
+    Ok(FlightRecord {
+        sequence,
+        event: OwnedProjectedEvent {
+            metadata,
+            context,
+            timestamp,
+            fields,
+            message,
+            omitted_fields: event.usize("omitted_fields")?,
+            truncated_fields: event.usize("truncated_fields")?,
+            span,
+            metric,
+        },
+    })
+}
+
+fn load_span(span: &Json, token: &Json) -> Result<ProjectedSpan, String> {
+    let (id, flags) = token_parts(token)?;
+    let timing = match span.str("timing")? {
+        "wall_time" => SpanTiming::WallTime,
+        "active_time" => SpanTiming::ActiveTime,
+        "wake_latency" => SpanTiming::WakeLatency,
+        other => return Err(format!("unknown span timing `{other}`")),
+    };
+    let warning_threshold = match span.get("threshold_ns")? {
+        Json::Null => None,
+        threshold => Some(Duration::from_nanos(threshold.as_u64()?)),
+    };
+    let lifecycle = match span.str("lifecycle")? {
+        "started" => SpanLifecycle::Started,
+        "completed" => SpanLifecycle::Completed {
+            elapsed: Duration::from_nanos(span.u64("elapsed_ns")?),
+            threshold_exceeded: span.get("threshold_exceeded")?.as_bool()?,
+        },
+        other => return Err(format!("unknown span lifecycle `{other}`")),
+    };
+    Ok(ProjectedSpan {
+        token: SpanToken::from_parts(id, flags),
+        timing,
+        warning_threshold,
+        lifecycle,
+    })
+}
+
+fn load_metric(metric: &Json) -> Result<MetricValue, String> {
+    match metric.str("type")? {
+        "counter" => Ok(MetricValue::Counter {
+            total: metric.get("total")?.as_f64()?,
+        }),
+        "histogram" => Ok(MetricValue::Histogram(Histogram {
+            count: metric.u64("count")?,
+            sum: metric.get("sum")?.as_f64()?,
+            min: metric.get("min")?.as_f64()?,
+            max: metric.get("max")?.as_f64()?,
+            buckets: metric
+                .get("buckets")?
+                .as_array()?
+                .iter()
+                .map(|bucket| {
+                    Ok(HistogramBucket {
+                        upper_bound: bucket.get("le")?.as_f64()?,
+                        count: bucket.u64("count")?,
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+        })),
+        other => Err(format!("unknown metric type `{other}`")),
+    }
+}
+
+fn token_parts(token: &Json) -> Result<(u64, u64), String> {
+    match token {
+        Json::Null => Ok((0, 0)),
+        token => match token.as_array()? {
+            [id, flags] => Ok((id.as_u64()?, flags.as_u64()?)),
+            _ => Err("a token is [id,flags]".to_owned()),
+        },
+    }
+}
+
+/// Per-band counts under `key`, or zeros for a dump from before that key.
+fn band_counts(header: &Json, key: &str) -> Result<[u64; RetentionBand::COUNT], String> {
+    let mut counts = [0; RetentionBand::COUNT];
+    if let Ok(bands) = header.get(key) {
+        for band in RetentionBand::ALL {
+            counts[band as usize] = bands.u64(band.name())?;
+        }
+    }
+    Ok(counts)
+}
+
+fn u32_of(value: u64) -> Result<u32, String> {
+    u32::try_from(value).map_err(|_| format!("{value} is out of range"))
+}
+
+fn severity(name: &str) -> Result<Severity, String> {
+    [
+        Severity::Trace,
+        Severity::Debug,
+        Severity::Info,
+        Severity::Warn,
+        Severity::Error,
+        Severity::Critical,
+    ]
+    .into_iter()
+    .find(|severity| severity_name(*severity) == name)
+    .ok_or_else(|| format!("unknown severity `{name}`"))
+}
+
+fn class(name: &str) -> Result<Class, String> {
+    [
+        Class::Operational,
+        Class::Diagnostic,
+        Class::Forensic,
+        Class::Performance,
+        Class::Metric,
+    ]
+    .into_iter()
+    .find(|class| class_name(*class) == name)
+    .ok_or_else(|| format!("unknown class `{name}`"))
+}
+
+fn kind(name: &str) -> Result<Kind, String> {
+    [
+        Kind::Event,
+        Kind::AdHocText,
+        Kind::Span,
+        Kind::Counter,
+        Kind::Measurement,
+    ]
+    .into_iter()
+    .find(|kind| kind_name(*kind) == name)
+    .ok_or_else(|| format!("unknown kind `{name}`"))
+}
+
+fn privacy(name: &str) -> Result<Privacy, String> {
+    [Privacy::SupportSafe, Privacy::LocalOnly, Privacy::Secret]
+        .into_iter()
+        .find(|privacy| privacy_name(*privacy) == name)
+        .ok_or_else(|| format!("unknown privacy `{name}`"))
+}
+
+fn detail(name: &str) -> Result<Detail, String> {
+    [Detail::Core, Detail::Detail]
+        .into_iter()
+        .find(|detail| detail_name(*detail) == name)
+        .ok_or_else(|| format!("unknown detail `{name}`"))
+}
+
+/// Where and in which view
+/// [`install_panic_hook_with_dump`](crate::install_panic_hook_with_dump)
+/// writes.
+#[derive(Clone, Debug)]
+pub struct PanicDump {
+    recorder: Arc<FlightRecorder>,
+    path: PathBuf,
+    view: RecorderView,
+}
+
+impl PanicDump {
+    /// Dumps `recorder` to `path`, replacing it, in the local view.
+    pub fn new(recorder: Arc<FlightRecorder>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            recorder,
+            path: path.into(),
+            view: RecorderView::Local,
+        }
+    }
+
+    #[must_use]
+    pub const fn view(mut self, view: RecorderView) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Writes the dump now, as the panic hook would.
+    pub fn write(&self) -> io::Result<FlightRead> {
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        let read = self.recorder.dump(&mut file, self.view)?;
+        file.into_inner().map_err(io::IntoInnerError::into_error)?;
+        Ok(read)
+    }
+
+    pub(crate) fn write_from_panic(&self) {
+        if let Err(error) = self.write() {
+            eprintln!(
+                "logwise: writing the flight recorder to {} failed: {error}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// A parsed JSON value. Numbers keep their text, so a `u64` beyond 2^53
+/// survives.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// How deep [`Json::parse`] follows nested arrays and objects.
+const MAX_DEPTH: usize = 16;
+
+impl Json {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value(0)?;
+        parser.whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(format!("unexpected text at column {}", parser.position + 1));
+        }
+        Ok(value)
+    }
+
+    fn get(&self, key: &str) -> Result<&Self, String> {
+        match self {
+            Self::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("missing key `{key}`")),
+            _ => Err(format!("expected an object with `{key}`")),
+        }
+    }
+
+    fn str(&self, key: &str) -> Result<&str, String> {
+        self.get(key)?.as_str()
+    }
+
+    fn u64(&self, key: &str) -> Result<u64, String> {
+        self.get(key)?.as_u64()
+    }
+
+    fn usize(&self, key: &str) -> Result<usize, String> {
+        let value = self.u64(key)?;
+        usize::try_from(value).map_err(|_| format!("{value} is out of range"))
+    }
+
+    fn as_str(&self) -> Result<&str, String> {
+        match self {
+            Self::String(value) => Ok(value),
+            _ => Err("expected a string".to_owned()),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool, String> {
+        match self {
+            Self::Bool(value) => Ok(*value),
+            _ => Err("expected a boolean".to_owned()),
+        }
+    }
+
+    fn as_array(&self) -> Result<&[Self], String> {
+        match self {
+            Self::Array(values) => Ok(values),
+            _ => Err("expected an array".to_owned()),
+        }
+    }
+
+    fn as_u64(&self) -> Result<u64, String> {
+        match self {
+            Self::Number(text) => text
+                .parse()
+                .map_err(|_| format!("{text} is not an unsigned integer")),
+            _ => Err("expected a number".to_owned()),
+        }
+    }
+
+    fn as_i64(&self) -> Result<i64, String> {
+        match self {
+            Self::Number(text) => text
+                .parse()
+                .map_err(|_| format!("{text} is not an integer")),
+            _ => Err("expected a number".to_owned()),
+        }
+    }
+
+    /// A number, or the strings the `json_lines` schema uses for non-finite
+    /// values.
+    fn as_f64(&self) -> Result<f64, String> {
+        match self {
+            Self::Number(text) => text.parse().map_err(|_| format!("{text} is not a number")),
+            Self::String(text) => match text.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => Err(format!("{text:?} is not a number")),
+            },
+            _ => Err("expected a number".to_owned()),
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("expected {expected} at column {}", self.position + 1)
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.whitespace();
+        let found = self.bytes.get(self.position) == Some(&byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("nesting deeper than {MAX_DEPTH}"));
+        }
+        self.whitespace();
+        let rest = &self.bytes[self.position..];
+        for (literal, value) in [
+            (&b"null"[..], Json::Null),
+            (b"true", Json::Bool(true)),
+            (b"false", Json::Bool(false)),
+        ] {
+            if rest.starts_with(literal) {
+                self.position += literal.len();
+                return Ok(value);
+            }
+        }
+        match rest.first() {
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.position += 1;
+                let mut values = Vec::new();
+                if self.eat(b']') {
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value(depth + 1)?);
+                    if self.eat(b']') {
+                        return Ok(Json::Array(values));
+                    }
+                    if !self.eat(b',') {
+                        return Err(self.error("`,` or `]`"));
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.position += 1;
+                let mut entries = Vec::new();
+                if self.eat(b'}') {
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    if !self.eat(b':') {
+                        return Err(self.error("`:`"));
+                    }
+                    entries.push((key, self.value(depth + 1)?));
+                    if self.eat(b'}') {
+                        return Ok(Json::Object(entries));
+                    }
+                    if !self.eat(b',') {
+                        return Err(self.error("`,` or `}`"));
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.position;
+                while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+                    self.bytes.get(self.position)
+                {
+                    self.position += 1;
+                }
+                let text = std::str::from_utf8(&self.bytes[start..self.position])
+                    .map_err(|_| self.error("a number"))?;
+                Ok(Json::Number(text.to_owned()))
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.bytes.get(self.position) != Some(&b'"') {
+            return Err(self.error("a string"));
+        }
+        self.position += 1;
+        let mut value = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(self.error("`\"`"));
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.bytes.get(self.position) else {
+                        return Err(self.error("an escape"));
+                    };
+                    self.position += 1;
+                    let character = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'b' => '\u{08}',
+                        b'f' => '\u{0c}',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("an escape")),
+                    };
+                    let mut buffer = [0; 4];
+                    value.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                }
+                byte => value.push(byte),
+            }
+        }
+        String::from_utf8(value).map_err(|_| self.error("UTF-8"))
+    }
+
+    /// The character after `\u`, joining a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.bytes[self.position..].starts_with(b"\\u") {
+                return Err(self.error("a low surrogate"));
+            }
+            self.position += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("a low surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("a character"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("four hex digits"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+}
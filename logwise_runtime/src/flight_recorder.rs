@@ -8,9 +8,11 @@ use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-use logwise::Privacy;
+use logwise::{Class, Metadata, Privacy, Severity};
 
-use crate::{EventSink, OwnedProjectedEvent, ProjectedEvent};
+use crate::flight_capture::Capture;
+use crate::projection::ProjectedField;
+use crate::{EventSink, FieldFilter, Filter, OwnedProjectedEvent, ProjectedEvent, Runtime};
 
 /// The privacy projection applied while querying retained history.
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
@@ -38,8 +40,12 @@ impl fmt::Display for FlightRecord {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             formatter,
-            "{} {:?} {}",
-            self.sequence, self.event.metadata.severity, self.event.metadata.event_name
+            "{} {} +{:?} {:?} {}",
+            self.sequence,
+            self.event.timestamp,
+            self.event.timestamp.monotonic,
+            self.event.metadata.severity,
+            self.event.metadata.event_name
         )?;
         if let Some(message) = &self.event.message {
             write!(formatter, " {message}")?;
@@ -47,6 +53,12 @@ impl fmt::Display for FlightRecord {
         for field in &self.event.fields {
             write!(formatter, " {}={:?}", field.name, field.value)?;
         }
+        if let Some(span) = self.event.span {
+            write!(formatter, " {span}")?;
+        }
+        if let Some(metric) = &self.event.metric {
+            write!(formatter, " {metric}")?;
+        }
         if self.event.omitted_fields != 0 {
             write!(formatter, " omitted={}", self.event.omitted_fields)?;
         }
@@ -57,13 +69,100 @@ impl fmt::Display for FlightRecord {
     }
 }
 
+/// The share of a recorder's capacity a record competes for.
+///
+/// Forensic records have a band of their own whatever their severity;
+/// every other record is banded by severity.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RetentionBand {
+    /// Trace and debug records.
+    Chatter,
+    Info,
+    Warn,
+    /// Error and critical records.
+    Error,
+    Forensic,
+}
+
+impl RetentionBand {
+    pub const COUNT: usize = 5;
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::Chatter,
+        Self::Info,
+        Self::Warn,
+        Self::Error,
+        Self::Forensic,
+    ];
+
+    pub const fn of(metadata: &Metadata) -> Self {
+        if matches!(metadata.class, Class::Forensic) {
+            return Self::Forensic;
+        }
+        match metadata.severity {
+            Severity::Trace | Severity::Debug => Self::Chatter,
+            Severity::Info => Self::Info,
+            Severity::Warn => Self::Warn,
+            Severity::Error | Severity::Critical => Self::Error,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Chatter => "chatter",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+            Self::Forensic => "forensic",
+        }
+    }
+}
+
+/// Capacity reserved for each [`RetentionBand`].
+///
+/// A band's records are never overwritten to make room for another band's
+/// while the band holds no more than its reservation. Capacity nobody
+/// reserved is shared oldest-first, so with no reservations a recorder is a
+/// plain FIFO. A full band that has used up its reservation and all the
+/// shared capacity overwrites its own oldest record; one with no reservation
+/// when every slot is reserved is dropped.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FlightRetention {
+    reserved: [usize; RetentionBand::COUNT],
+}
+
+impl FlightRetention {
+    /// Reserves nothing.
+    pub const fn new() -> Self {
+        Self {
+            reserved: [0; RetentionBand::COUNT],
+        }
+    }
+
+    pub const fn reserve(mut self, band: RetentionBand, capacity: usize) -> Self {
+        self.reserved[band as usize] = capacity;
+        self
+    }
+
+    pub const fn reserved(&self, band: RetentionBand) -> usize {
+        self.reserved[band as usize]
+    }
+}
+
 /// Cumulative recorder accounting. Drops and overwrites are kept separate so
 /// a query can distinguish lock contention from bounded-retention loss.
+///
+/// `dropped` counts only records that found their shard locked.
+/// `overwritten_by_band` splits `overwritten` by the band of the record that
+/// was lost, and `rejected_by_band` counts new records turned away because
+/// every record in their shard was within its band's reservation, by the
+/// band of the new record. Both are indexed by [`RetentionBand`] as `usize`.
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub struct FlightRecorderStats {
     pub accepted: u64,
     pub dropped: u64,
     pub overwritten: u64,
+    pub overwritten_by_band: [u64; RetentionBand::COUNT],
+    pub rejected_by_band: [u64; RetentionBand::COUNT],
     pub truncated_fields: u64,
 }
 
@@ -72,6 +171,8 @@ struct Stats {
     accepted: AtomicU64,
     dropped: AtomicU64,
     overwritten: AtomicU64,
+    overwritten_by_band: [AtomicU64; RetentionBand::COUNT],
+    rejected_by_band: [AtomicU64; RetentionBand::COUNT],
     truncated_fields: AtomicU64,
 }
 
@@ -81,6 +182,14 @@ impl Stats {
             accepted: self.accepted.load(Ordering::Relaxed),
             dropped: self.dropped.load(Ordering::Relaxed),
             overwritten: self.overwritten.load(Ordering::Relaxed),
+            overwritten_by_band: self
+                .overwritten_by_band
+                .each_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
+            rejected_by_band: self
+                .rejected_by_band
+                .each_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
             truncated_fields: self.truncated_fields.load(Ordering::Relaxed),
         }
     }
@@ -88,7 +197,33 @@ impl Stats {
 
 struct Shard {
     capacity: usize,
-    records: VecDeque<FlightRecord>,
+    reserved: [usize; RetentionBand::COUNT],
+    /// Each band's records, oldest first.
+    bands: [VecDeque<FlightRecord>; RetentionBand::COUNT],
+}
+
+impl Shard {
+    fn len(&self) -> usize {
+        self.bands.iter().map(VecDeque::len).sum()
+    }
+
+    fn records(&self) -> impl Iterator<Item = &FlightRecord> {
+        self.bands.iter().flatten()
+    }
+
+    /// The band whose oldest record makes room for one in `band`: the oldest
+    /// record of any band over its reservation, counting `band` as one
+    /// record fuller. A band under its reservation never gives one up.
+    fn victim(&self, band: RetentionBand) -> Option<usize> {
+        (0..RetentionBand::COUNT)
+            .filter(|&index| {
+                let held = self.bands[index].len() + usize::from(index == band as usize);
+                held > self.reserved[index]
+            })
+            .filter_map(|index| Some((self.bands[index].front()?.sequence, index)))
+            .min()
+            .map(|(_, index)| index)
+    }
 }
 
 /// The result of a nonblocking recorder read.
@@ -105,6 +240,8 @@ pub struct FlightRead {
     pub records: Vec<FlightRecord>,
     pub dropped_total: u64,
     pub overwritten_total: u64,
+    pub overwritten_by_band: [u64; RetentionBand::COUNT],
+    pub rejected_by_band: [u64; RetentionBand::COUNT],
     pub truncated_fields_total: u64,
     pub omitted_fields: usize,
     pub busy_shards: usize,
@@ -116,14 +253,102 @@ impl FlightRead {
     }
 }
 
+/// A selection over retained history, for
+/// [`FlightRecorder::query_since`] and [`FlightRecorder::query_tail`].
+///
+/// The [`Filter`] is decided as a sink's would be, with context selectors
+/// judged by the runtime's lineage, so `context(token, true)` selects a
+/// whole subtree. Severity and class bounds narrow it further. Field
+/// predicates are evaluated last, against the record as the query's
+/// [`RecorderView`] projects it: a remote query cannot test a local-only
+/// value, because its predicate never sees one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlightQuery {
+    filter: Filter,
+    maximum_severity: Option<Severity>,
+    classes: Vec<Class>,
+    fields: Option<FieldFilter>,
+    limit: Option<usize>,
+}
+
+impl FlightQuery {
+    /// Selects every record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Selects records no more severe than `severity`. The lower bound is
+    /// the filter's [`minimum_severity`](Filter::minimum_severity).
+    pub const fn maximum_severity(mut self, severity: Severity) -> Self {
+        self.maximum_severity = Some(severity);
+        self
+    }
+
+    /// Selects records of any of `classes`. With none, the class is not
+    /// constrained.
+    pub fn classes(mut self, classes: impl IntoIterator<Item = Class>) -> Self {
+        self.classes = classes.into_iter().collect();
+        self
+    }
+
+    pub fn fields(mut self, fields: FieldFilter) -> Self {
+        self.fields = Some(fields);
+        self
+    }
+
+    /// Returns at most `limit` records: the oldest matches from
+    /// `query_since`, the newest from `query_tail`.
+    pub const fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn within_bounds(&self, record: &FlightRecord) -> bool {
+        let metadata = record.event.metadata;
+        self.maximum_severity
+            .is_none_or(|severity| metadata.severity as u8 <= severity as u8)
+            && (self.classes.is_empty() || self.classes.contains(&metadata.class))
+    }
+
+    fn matches_fields(&self, record: &FlightRecord) -> bool {
+        let Some(predicate) = &self.fields else {
+            return true;
+        };
+        let fields: Vec<_> = record
+            .event
+            .fields
+            .iter()
+            .map(|field| ProjectedField {
+                name: field.name,
+                privacy: field.privacy,
+                detail: field.detail,
+                value: field.value.as_value_ref(),
+                pseudonymized: field.pseudonymized,
+            })
+            .collect();
+        predicate.matches(&fields)
+    }
+}
+
 /// A fixed-slot, sharded structured event recorder.
 ///
 /// The recorder is intended to be registered as a local sink with
 /// `DetailLevel::Core`. Writes and reads use `try_lock`: a stalled writer or
 /// query can make one shard temporarily unavailable, but cannot block the
 /// application or prevent other shards from being retrieved.
+///
+/// A [`FlightRetention`] keeps capacity back for the bands that explain a
+/// failure, so a storm of debug records cannot overwrite the error before
+/// it. Sequence numbers stay global and monotonic across bands.
 pub struct FlightRecorder {
     max_string_bytes: usize,
+    retention: FlightRetention,
+    pub(crate) capture: Option<Capture>,
     next_sequence: AtomicU64,
     shards: Vec<Mutex<Shard>>,
     stats: Stats,
@@ -160,30 +385,73 @@ impl FlightRecorder {
     /// Creates a recorder with an explicit shard count, primarily for hosts
     /// and deterministic tests. Total retention never exceeds `capacity`.
     pub fn with_shards(capacity: usize, max_string_bytes: usize, shards: usize) -> Self {
+        Self::with_retention(capacity, max_string_bytes, shards, FlightRetention::new())
+    }
+
+    /// Creates a recorder that reserves capacity for retention bands.
+    ///
+    /// Reservations are granted from the forensic band down to chatter until
+    /// `capacity` runs out, and are split across shards the way capacity is;
+    /// [`retention`](Self::retention) reports what was granted.
+    pub fn with_retention(
+        capacity: usize,
+        max_string_bytes: usize,
+        shards: usize,
+        retention: FlightRetention,
+    ) -> Self {
         let shard_count = if capacity == 0 {
             1
         } else {
             shards.max(1).min(capacity)
         };
+        let mut granted = FlightRetention::new();
+        let mut unreserved = capacity;
+        for band in RetentionBand::ALL.into_iter().rev() {
+            let reserved = retention.reserved(band).min(unreserved);
+            granted = granted.reserve(band, reserved);
+            unreserved -= reserved;
+        }
+        let mut shard_reserved = vec![[0; RetentionBand::COUNT]; shard_count];
+        // Each band's remainder starts on the shard after the previous
+        // band's, so no shard reserves more than its capacity.
+        let mut offset = 0;
+        for band in RetentionBand::ALL {
+            let reserved = granted.reserved(band);
+            for (index, shard) in shard_reserved.iter_mut().enumerate() {
+                let extra = (index + shard_count - offset) % shard_count < reserved % shard_count;
+                shard[band as usize] = reserved / shard_count + usize::from(extra);
+            }
+            offset = (offset + reserved) % shard_count;
+        }
         let base = capacity / shard_count;
         let remainder = capacity % shard_count;
-        let shards = (0..shard_count)
-            .map(|index| {
+        let shards = shard_reserved
+            .into_iter()
+            .enumerate()
+            .map(|(index, reserved)| {
                 let capacity = base + usize::from(index < remainder);
                 Mutex::new(Shard {
                     capacity,
-                    records: VecDeque::with_capacity(capacity),
+                    reserved,
+                    bands: Default::default(),
                 })
             })
             .collect();
         Self {
             max_string_bytes,
+            retention: granted,
+            capture: None,
             next_sequence: AtomicU64::new(0),
             shards,
             stats: Stats::default(),
         }
     }
 
+    /// The reservations this recorder granted.
+    pub const fn retention(&self) -> FlightRetention {
+        self.retention
+    }
+
     pub fn stats(&self) -> FlightRecorderStats {
         self.stats.snapshot()
     }
@@ -192,6 +460,107 @@ impl FlightRecorder {
     /// Platform transports can poll this method to mirror history even if a
     /// scheduler thread is stalled.
     pub fn read_since(&self, cursor: FlightCursor, view: RecorderView) -> FlightRead {
+        self.read(
+            cursor,
+            |_| true,
+            |records| {
+                records
+                    .into_iter()
+                    .map(|record| project_record(record, view))
+                    .collect()
+            },
+        )
+    }
+
+    /// Returns at most the newest `limit` retained records.
+    pub fn tail(&self, limit: usize, view: RecorderView) -> FlightRead {
+        let mut read = self.read_since(FlightCursor::default(), view);
+        if read.records.len() > limit {
+            read.records.drain(..read.records.len() - limit);
+        }
+        read
+    }
+
+    /// Reads the records newer than `cursor` that `query` selects, with
+    /// context selectors decided by `runtime`'s lineage.
+    ///
+    /// With a limit, the oldest matches are returned and `next_cursor`
+    /// resumes after the last of them, so paging through history with the
+    /// same query visits every match once. Loss totals are the recorder's,
+    /// as in [`read_since`](Self::read_since).
+    pub fn query_since(
+        &self,
+        runtime: &Runtime,
+        cursor: FlightCursor,
+        query: &FlightQuery,
+        view: RecorderView,
+    ) -> FlightRead {
+        let mut read = self.select(runtime, cursor, query, view);
+        if let Some(limit) = query.limit
+            && read.records.len() > limit
+        {
+            read.records.truncate(limit);
+            if read.is_complete() {
+                read.next_cursor = FlightCursor(
+                    read.records
+                        .last()
+                        .map_or(cursor.0, |record| record.sequence),
+                );
+            }
+        }
+        read.omitted_fields = omitted_fields(&read.records);
+        read
+    }
+
+    /// Returns the newest retained records that `query` selects, at most
+    /// its limit of them.
+    pub fn query_tail(
+        &self,
+        runtime: &Runtime,
+        query: &FlightQuery,
+        view: RecorderView,
+    ) -> FlightRead {
+        let mut read = self.select(runtime, FlightCursor::default(), query, view);
+        if let Some(limit) = query.limit
+            && read.records.len() > limit
+        {
+            read.records.drain(..read.records.len() - limit);
+        }
+        read.omitted_fields = omitted_fields(&read.records);
+        read
+    }
+
+    /// Every record newer than `cursor` that `query` selects, in sequence
+    /// order. Records are projected before their fields are tested.
+    fn select(
+        &self,
+        runtime: &Runtime,
+        cursor: FlightCursor,
+        query: &FlightQuery,
+        view: RecorderView,
+    ) -> FlightRead {
+        self.read(
+            cursor,
+            |record| query.within_bounds(record),
+            |mut records| {
+                runtime.retain_selected(&query.filter, &mut records);
+                records
+                    .into_iter()
+                    .map(|record| project_record(record, view))
+                    .filter(|record| query.matches_fields(record))
+                    .collect()
+            },
+        )
+    }
+
+    /// Copies out the retained records newer than `cursor` that `keep`
+    /// accepts and hands them to `finish` in sequence order.
+    pub(crate) fn read(
+        &self,
+        cursor: FlightCursor,
+        keep: impl Fn(&FlightRecord) -> bool,
+        finish: impl FnOnce(Vec<FlightRecord>) -> Vec<FlightRecord>,
+    ) -> FlightRead {
         let watermark = self.next_sequence.load(Ordering::Acquire);
         let mut records = Vec::new();
         let mut busy_shards = 0;
@@ -202,18 +571,16 @@ impl FlightRecorder {
             };
             records.extend(
                 shard
-                    .records
-                    .iter()
-                    .filter(|record| record.sequence > cursor.0 && record.sequence <= watermark)
-                    .map(|record| project_record(record, view)),
+                    .records()
+                    .filter(|record| {
+                        record.sequence > cursor.0 && record.sequence <= watermark && keep(record)
+                    })
+                    .cloned(),
             );
         }
         records.sort_unstable_by_key(|record| record.sequence);
+        let records = finish(records);
         let stats = self.stats();
-        let omitted_fields = records
-            .iter()
-            .map(|record| record.event.omitted_fields)
-            .sum();
         FlightRead {
             requested_cursor: cursor,
             next_cursor: if busy_shards == 0 {
@@ -221,24 +588,17 @@ impl FlightRecorder {
             } else {
                 cursor
             },
+            omitted_fields: omitted_fields(&records),
             records,
             dropped_total: stats.dropped,
             overwritten_total: stats.overwritten,
+            overwritten_by_band: stats.overwritten_by_band,
+            rejected_by_band: stats.rejected_by_band,
             truncated_fields_total: stats.truncated_fields,
-            omitted_fields,
             busy_shards,
         }
     }
 
-    /// Returns at most the newest `limit` retained records.
-    pub fn tail(&self, limit: usize, view: RecorderView) -> FlightRead {
-        let mut read = self.read_since(FlightCursor::default(), view);
-        if read.records.len() > limit {
-            read.records.drain(..read.records.len() - limit);
-        }
-        read
-    }
-
     fn shard_index(&self) -> usize {
         let mut hasher = DefaultHasher::new();
         std::thread::current().id().hash(&mut hasher);
@@ -257,33 +617,47 @@ impl EventSink for FlightRecorder {
         event.omitted_fields += before - event.fields.len();
 
         let owned = OwnedProjectedEvent::copy_from(event, self.max_string_bytes);
+        // Decided before the shard is locked: a contextual trigger asks the
+        // runtime, which takes its own lock.
+        let trigger = self
+            .capture
+            .as_ref()
+            .is_some_and(|capture| capture.triggers(&owned))
+            .then_some((owned.metadata, owned.context));
         let index = self.shard_index();
         let Ok(mut shard) = self.shards[index].try_lock() else {
             self.stats.dropped.fetch_add(1, Ordering::Relaxed);
             return;
         };
-        if shard.capacity == 0 {
-            self.stats.dropped.fetch_add(1, Ordering::Relaxed);
-            return;
-        }
-        if shard.records.len() == shard.capacity {
-            shard.records.pop_front();
+        let band = RetentionBand::of(owned.metadata);
+        if shard.len() == shard.capacity {
+            // A recorder without capacity has no victim either.
+            let Some(victim) = shard.victim(band) else {
+                self.stats.rejected_by_band[band as usize].fetch_add(1, Ordering::Relaxed);
+                return;
+            };
+            shard.bands[victim].pop_front();
             self.stats.overwritten.fetch_add(1, Ordering::Relaxed);
+            self.stats.overwritten_by_band[victim].fetch_add(1, Ordering::Relaxed);
         }
         let sequence = self.next_sequence.fetch_add(1, Ordering::AcqRel) + 1;
         self.stats
             .truncated_fields
             .fetch_add(owned.truncated_fields as u64, Ordering::Relaxed);
-        shard.records.push_back(FlightRecord {
+        shard.bands[band as usize].push_back(FlightRecord {
             sequence,
             event: owned,
         });
         self.stats.accepted.fetch_add(1, Ordering::Relaxed);
+        drop(shard);
+        if let Some(capture) = &self.capture {
+            capture.accepted(self, sequence, trigger);
+        }
     }
 }
 
-fn project_record(record: &FlightRecord, view: RecorderView) -> FlightRecord {
-    let mut event = record.event.clone();
+pub(crate) fn project_record(mut record: FlightRecord, view: RecorderView) -> FlightRecord {
+    let event = &mut record.event;
     // Secret is excluded again in case a future construction path bypasses
     // the sink's ingestion guard.
     let before = event.fields.len();
@@ -295,8 +669,26 @@ fn project_record(record: &FlightRecord, view: RecorderView) -> FlightRecord {
     if view == RecorderView::Remote {
         event.message = None;
     }
-    FlightRecord {
-        sequence: record.sequence,
-        event,
+    record
+}
+
+fn omitted_fields(records: &[FlightRecord]) -> usize {
+    records
+        .iter()
+        .map(|record| record.event.omitted_fields)
+        .sum()
+}
+
+#[cfg(test)]
+impl FlightRecorder {
+    /// The shard this thread's events go to.
+    pub(crate) fn current_shard(&self) -> usize {
+        self.shard_index()
+    }
+
+    /// Holds a shard's lock, as an event being recorded on another thread
+    /// would.
+    pub(crate) fn hold_shard(&self, index: usize) -> impl Sized + '_ {
+        self.shards[index].lock().unwrap()
     }
 }
@@ -10,6 +10,8 @@
 use std::panic;
 use std::sync::Arc;
 
+use crate::flight_dump::PanicDump;
+
 /// The producer of an opaque foreign text record.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum ForeignOrigin {
@@ -87,12 +89,26 @@ impl PanicHookRegistration {
 
 /// Installs a process-wide panic ingress before the previously registered hook.
 pub fn install_panic_hook() -> PanicHookRegistration {
+    install(None)
+}
+
+/// [`install_panic_hook`], then writes `dump` once the panic has been
+/// recorded and before the previous hook runs, so the recorder's history
+/// survives an abort or a `panic = "abort"` profile.
+pub fn install_panic_hook_with_dump(dump: PanicDump) -> PanicHookRegistration {
+    install(Some(dump))
+}
+
+fn install(dump: Option<PanicDump>) -> PanicHookRegistration {
     let previous: Arc<dyn Fn(&panic::PanicHookInfo<'_>) + Send + Sync + 'static> =
         panic::take_hook().into();
     let chained = previous.clone();
     panic::set_hook(Box::new(move |info| {
         let rendered = info.to_string();
         foreign_text(ForeignOrigin::PanicHook, &rendered);
+        if let Some(dump) = &dump {
+            dump.write_from_panic();
+        }
         chained(info);
     }));
     PanicHookRegistration {
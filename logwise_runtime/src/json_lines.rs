@@ -0,0 +1,311 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A JSON Lines [`OwnedEventWriter`] with a versioned schema.
+//!
+//! [`StructuredWriter`](crate::StructuredWriter) is for people; this is for
+//! log shippers. Each event is one JSON object on one line, written with a
+//! single `write_all`, and strings are escaped per RFC 8259 rather than
+//! `Debug`-quoted. Put it behind an [`AsyncSink`](crate::AsyncSink) like any
+//! other writer.
+//!
+//! # Schema, version 1
+//!
+//! Keys always appear, in this order, and `null` marks an absent value:
+//!
+//! | Key | Value |
+//! |---|---|
+//! | `v` | [`JSON_LINES_SCHEMA_VERSION`], currently `1` |
+//! | `time` | RFC 3339 UTC wall-clock dispatch time |
+//! | `monotonic_ns` | nanoseconds since the process clock origin; orders events within one process |
+//! | `event`, `package`, `target`, `module` | call-site metadata strings |
+//! | `domain` | string or `null` |
+//! | `class` | `operational`, `diagnostic`, `forensic`, `performance` or `metric` |
+//! | `severity` | `trace`, `debug`, `info`, `warn`, `error` or `critical` |
+//! | `kind` | `event`, `ad_hoc_text`, `span`, `counter` or `measurement` |
+//! | `location` | `{"file","line","column"}` or `null` |
+//! | `context` | context ID, or `null` outside any context |
+//! | `fields` | array of field objects, in call-site order |
+//! | `message` | string or `null` |
+//! | `omitted_fields`, `truncated_fields` | counts of fields this view did not get, and of values cut short |
+//! | `span` | span object or `null` |
+//! | `metric` | metric object or `null` |
+//!
+//! A field object is `{"name","privacy","detail","type","value"}`. `privacy`
+//! is `support`, `local` or `secret` and `detail` is `core` or `detail`,
+//! matching the call-site keywords. `type` is `bool`, `i64`, `u64`, `f64` or
+//! `string`. `value` is the matching JSON value. A non-finite `f64` is the
+//! string `"NaN"`, `"inf"` or `"-inf"`, and an integer beyond 2^53 keeps every
+//! digit even though some JSON readers will round it. A field that carries a
+//! [`pseudonym`](crate::pseudonym) token instead of its value also has
+//! `"pseudonymized":true`.
+//!
+//! A span object is `{"token","lifecycle","timing"}`, where `lifecycle` is
+//! `started` or `completed` and `timing` is `wall_time`, `active_time` or
+//! `wake_latency`. It also has `threshold_ns` (or `null`), and on completion
+//! `elapsed_ns` and `threshold_exceeded`. A metric object is either
+//! `{"type":"counter","total"}` or
+//! `{"type":"histogram","count","sum","min","max","buckets"}` with buckets
+//! `{"le","count"}`, non-cumulative, in ascending order.
+//!
+//! A reader must ignore keys it does not know. Adding a key does not change
+//! the version; removing, renaming or retyping one does.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use logwise::SpanTiming;
+
+use crate::metrics::MetricValue;
+use crate::names::{class_name, detail_name, kind_name, privacy_name, severity_name};
+use crate::projection::{ProjectedSpan, SpanLifecycle};
+use crate::sinks::{OwnedEventWriter, OwnedProjectedEvent, OwnedValue};
+
+/// The `v` written on every line.
+pub const JSON_LINES_SCHEMA_VERSION: u32 = 1;
+
+pub struct JsonLinesWriter<W> {
+    writer: W,
+    line: String,
+}
+
+impl<W> std::fmt::Debug for JsonLinesWriter<W> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `W` is an arbitrary writer and is not required to be `Debug`.
+        formatter
+            .debug_struct("JsonLinesWriter")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W> JsonLinesWriter<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            line: String::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> OwnedEventWriter for JsonLinesWriter<W> {
+    fn write_event(&mut self, event: &OwnedProjectedEvent) -> io::Result<()> {
+        self.line.clear();
+        encode_event(&mut self.line, event);
+        self.line.push('\n');
+        self.writer.write_all(self.line.as_bytes())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Appends one event's JSON object, without the trailing newline.
+pub(crate) fn encode_event(line: &mut String, event: &OwnedProjectedEvent) {
+    let metadata = event.metadata;
+    let _ = write!(
+        line,
+        "{{\"v\":{JSON_LINES_SCHEMA_VERSION},\"time\":\"{}\",\"monotonic_ns\":{}",
+        event.timestamp,
+        event.timestamp.monotonic.as_nanos()
+    );
+    for (key, value) in [
+        ("event", metadata.event_name),
+        ("package", metadata.package),
+        ("target", metadata.target),
+        ("module", metadata.module),
+    ] {
+        let _ = write!(line, ",\"{key}\":");
+        string(line, value);
+    }
+    line.push_str(",\"domain\":");
+    match metadata.domain {
+        Some(domain) => string(line, domain.name),
+        None => line.push_str("null"),
+    }
+    let _ = write!(
+        line,
+        ",\"class\":\"{}\",\"severity\":\"{}\",\"kind\":\"{}\",\"location\":",
+        class_name(metadata.class),
+        severity_name(metadata.severity),
+        kind_name(metadata.kind)
+    );
+    match metadata.location {
+        Some(location) => {
+            line.push_str("{\"file\":");
+            string(line, location.file);
+            let _ = write!(
+                line,
+                ",\"line\":{},\"column\":{}}}",
+                location.line, location.column
+            );
+        }
+        None => line.push_str("null"),
+    }
+    line.push_str(",\"context\":");
+    if event.context.is_none() {
+        line.push_str("null");
+    } else {
+        let _ = write!(line, "{}", event.context.into_parts().0);
+    }
+
+    line.push_str(",\"fields\":[");
+    for (index, field) in event.fields.iter().enumerate() {
+        if index != 0 {
+            line.push(',');
+        }
+        line.push_str("{\"name\":");
+        string(line, field.name);
+        let _ = write!(
+            line,
+            ",\"privacy\":\"{}\",\"detail\":\"{}\",",
+            privacy_name(field.privacy),
+            detail_name(field.detail)
+        );
+        match &field.value {
+            OwnedValue::Bool(value) => {
+                let _ = write!(line, "\"type\":\"bool\",\"value\":{value}");
+            }
+            OwnedValue::I64(value) => {
+                let _ = write!(line, "\"type\":\"i64\",\"value\":{value}");
+            }
+            OwnedValue::U64(value) => {
+                let _ = write!(line, "\"type\":\"u64\",\"value\":{value}");
+            }
+            OwnedValue::F64(value) => {
+                line.push_str("\"type\":\"f64\",\"value\":");
+                float(line, *value);
+            }
+            OwnedValue::String(value) => {
+                line.push_str("\"type\":\"string\",\"value\":");
+                string(line, value);
+            }
+        }
+        if field.pseudonymized {
+            line.push_str(",\"pseudonymized\":true");
+        }
+        line.push('}');
+    }
+    line.push_str("],\"message\":");
+    match &event.message {
+        Some(message) => string(line, message),
+        None => line.push_str("null"),
+    }
+    let _ = write!(
+        line,
+        ",\"omitted_fields\":{},\"truncated_fields\":{},\"span\":",
+        event.omitted_fields, event.truncated_fields
+    );
+    match event.span {
+        Some(span) => encode_span(line, span),
+        None => line.push_str("null"),
+    }
+    line.push_str(",\"metric\":");
+    match &event.metric {
+        Some(metric) => encode_metric(line, metric),
+        None => line.push_str("null"),
+    }
+    line.push('}');
+}
+
+fn encode_span(line: &mut String, span: ProjectedSpan) {
+    let timing = match span.timing {
+        SpanTiming::WallTime => "wall_time",
+        SpanTiming::ActiveTime => "active_time",
+        SpanTiming::WakeLatency => "wake_latency",
+    };
+    let lifecycle = match span.lifecycle {
+        SpanLifecycle::Started => "started",
+        SpanLifecycle::Completed { .. } => "completed",
+    };
+    let _ = write!(
+        line,
+        "{{\"token\":{},\"lifecycle\":\"{lifecycle}\",\"timing\":\"{timing}\",\"threshold_ns\":",
+        span.token.into_parts().0
+    );
+    match span.warning_threshold {
+        Some(threshold) => {
+            let _ = write!(line, "{}", threshold.as_nanos());
+        }
+        None => line.push_str("null"),
+    }
+    if let SpanLifecycle::Completed {
+        elapsed,
+        threshold_exceeded,
+    } = span.lifecycle
+    {
+        let _ = write!(
+            line,
+            ",\"elapsed_ns\":{},\"threshold_exceeded\":{threshold_exceeded}",
+            elapsed.as_nanos()
+        );
+    }
+    line.push('}');
+}
+
+fn encode_metric(line: &mut String, metric: &MetricValue) {
+    match metric {
+        MetricValue::Counter { total } => {
+            line.push_str("{\"type\":\"counter\",\"total\":");
+            float(line, *total);
+        }
+        MetricValue::Histogram(histogram) => {
+            let _ = write!(
+                line,
+                "{{\"type\":\"histogram\",\"count\":{},\"sum\":",
+                histogram.count
+            );
+            float(line, histogram.sum);
+            line.push_str(",\"min\":");
+            float(line, histogram.min);
+            line.push_str(",\"max\":");
+            float(line, histogram.max);
+            line.push_str(",\"buckets\":[");
+            for (index, bucket) in histogram.buckets.iter().enumerate() {
+                if index != 0 {
+                    line.push(',');
+                }
+                line.push_str("{\"le\":");
+                float(line, bucket.upper_bound);
+                let _ = write!(line, ",\"count\":{}}}", bucket.count);
+            }
+            line.push(']');
+        }
+    }
+    line.push('}');
+}
+
+pub(crate) fn string(line: &mut String, value: &str) {
+    line.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            '\u{08}' => line.push_str("\\b"),
+            '\u{0c}' => line.push_str("\\f"),
+            control if control < ' ' => {
+                let _ = write!(line, "\\u{:04x}", control as u32);
+            }
+            other => line.push(other),
+        }
+    }
+    line.push('"');
+}
+
+fn float(line: &mut String, value: f64) {
+    if value.is_nan() {
+        line.push_str("\"NaN\"");
+    } else if value == f64::INFINITY {
+        line.push_str("\"inf\"");
+    } else if value == f64::NEG_INFINITY {
+        line.push_str("\"-inf\"");
+    } else {
+        let _ = write!(line, "{value:?}");
+    }
+}
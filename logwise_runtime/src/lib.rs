@@ -406,21 +406,36 @@ logwise includes WebAssembly support with browser-specific features:
 
 */
 
+pub mod activation_rule;
 pub mod context;
+#[cfg(all(feature = "control-socket", not(target_arch = "wasm32")))]
+pub mod control;
+pub mod directive;
 mod dispatch;
 pub mod facade_runtime;
+pub mod field_filter;
+pub mod flight_capture;
+pub mod flight_dump;
 pub mod flight_recorder;
 pub mod foreign;
 pub mod global_logger;
 mod heartbeat;
 mod inmemory_logger;
 pub mod interval;
+pub mod json_lines;
 mod level;
 mod log_record;
 mod logger;
 mod macros;
+pub mod metrics;
+mod names;
+pub mod openmetrics;
 pub mod privacy;
 pub mod projection;
+pub mod pseudonym;
+pub mod rate_limit;
+pub mod rolling_file;
+pub mod sampling;
 pub mod sinks;
 mod spinlock;
 mod stderror_logger;
@@ -428,25 +443,51 @@ mod sys;
 declare_logging_domain!();
 
 // Re-export core types and functions for public API
+pub use activation_rule::{ACTIVATION_RULE_FIRED, ActivationRule, ActivationRuleId};
+#[cfg(all(feature = "control-socket", not(target_arch = "wasm32")))]
+pub use control::{ControlAddress, ControlPlane, ControlSocket};
+pub use directive::{DIRECTIVES_ENV, Directive, DirectiveError, parse_directives};
 pub use facade_runtime::{
-    ActivationResult, COMPLETED_SPAN_RETENTION, CompletedSpan, ContextSnapshot, Filter, Runtime,
-    RuntimeDeliveryStats, SinkId, Target, init,
+    ActivationId, ActivationResult, ActivationSnapshot, COMPLETED_SPAN_RETENTION, CompletedSpan,
+    ContextSnapshot, Filter, RETIRED_ACTIVATION_RETENTION, Runtime, RuntimeDeliveryStats, SinkId,
+    Target, init,
 };
+pub use field_filter::{Comparison, FieldFilter};
+pub use flight_capture::{CaptureStats, FlightCapture, FlightIncident, IncidentId};
+pub use flight_dump::{FLIGHT_DUMP_VERSION, FlightDump, PanicDump};
 pub use flight_recorder::{
-    FlightCursor, FlightRead, FlightRecord, FlightRecorder, FlightRecorderStats, RecorderView,
+    FlightCursor, FlightQuery, FlightRead, FlightRecord, FlightRecorder, FlightRecorderStats,
+    FlightRetention, RecorderView, RetentionBand,
 };
 #[cfg(feature = "foreign-nightly-rust-print")]
 pub use foreign::capture_nightly_rust_print;
-pub use foreign::{ForeignOrigin, PanicHookRegistration, foreign_text, install_panic_hook};
+pub use foreign::{
+    ForeignOrigin, PanicHookRegistration, foreign_text, install_panic_hook,
+    install_panic_hook_with_dump,
+};
 #[cfg(unix)]
 pub use foreign::{NativeFd, NativeFdCapture};
 pub use global_logger::{add_global_logger, global_loggers, set_global_loggers};
 pub use heartbeat::{HeartbeatGuard, heartbeat};
 pub use inmemory_logger::InMemoryLogger;
+pub use json_lines::{JSON_LINES_SCHEMA_VERSION, JsonLinesWriter};
 pub use level::Level;
 pub use log_record::LogRecord;
 pub use logger::{LogPrivacy, Logger};
-pub use projection::{DetailLevel, EventSink, ProjectedEvent, ProjectedField};
+pub use metrics::{
+    HISTOGRAM_SUB_BUCKETS, Histogram, HistogramBucket, METRIC_VALUE_FIELD, MetricFamily,
+    MetricSeries, MetricValue, MetricsSnapshot,
+};
+#[cfg(all(feature = "metrics-http", not(target_arch = "wasm32")))]
+pub use openmetrics::MetricsListener;
+pub use openmetrics::{OPENMETRICS_CONTENT_TYPE, render_openmetrics};
+pub use projection::{
+    DetailLevel, EventSink, ProjectedEvent, ProjectedField, ProjectedSpan, SpanLifecycle, Timestamp,
+};
+pub use pseudonym::Pseudonymization;
+pub use rate_limit::{RateLimit, RateLimitId, RateLimitedSite, SUPPRESSION_SUMMARY};
+pub use rolling_file::{FileFormat, ReopenHandle, RollingFileWriter, RotationPolicy};
+pub use sampling::Sampling;
 pub use sinks::{
     AsyncSink, ConsoleSink, FlushBarrier, FlushError, InMemorySink, OverflowPolicy,
     OwnedEventWriter, OwnedField, OwnedProjectedEvent, OwnedValue, SinkStats, StructuredWriter,
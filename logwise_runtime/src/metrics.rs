@@ -0,0 +1,399 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Aggregation for `counter!` and `measurement!` call sites.
+//!
+//! Once [`Runtime::enable_metrics`](crate::Runtime::enable_metrics) is called,
+//! metric-kind observations stop being fanned out one record at a time. A
+//! counter folds into a monotonic sum and a measurement into a [`Histogram`],
+//! and sinks receive the aggregate instead, as periodic metric records. That
+//! is what makes `counter!` usable on a hot path: an observation costs a lock
+//! and a hash, never a sink call.
+//!
+//! An observation's quantity is its numeric field named `value`. A counter
+//! without one counts one per call; a measurement without one, or any
+//! observation whose `value` is not a finite number, is rejected and counted.
+//! Every other core support-safe field is a label, and each distinct label
+//! set is its own series. Call sites that share an event name share a metric;
+//! one that disagrees with the first on counter versus measurement is
+//! rejected. Local-only and secret fields never become labels: an aggregate
+//! outlives the event, and is exactly what gets scraped and shipped off the
+//! machine.
+//!
+//! The number of series per metric is capped. An observation that would open
+//! a series past the cap is counted in [`MetricFamily::overflowed`] and
+//! dropped, because a label fed from an unbounded value would otherwise grow
+//! memory for as long as the process runs.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use logwise::{Detail, EventRef, FieldMetadata, Kind, Metadata, Privacy, ValueRef};
+
+use crate::spinlock::Spinlock;
+use crate::sys::{Duration, Instant};
+
+/// Linear subdivisions of each power of two in a [`Histogram`]. Bucket upper
+/// bounds are therefore at most 25% apart.
+pub const HISTOGRAM_SUB_BUCKETS: u32 = 4;
+
+/// The name of the field an observation's quantity is read from.
+pub const METRIC_VALUE_FIELD: &str = "value";
+
+/// One histogram bucket: observations greater than the previous bucket's
+/// bound and at most `upper_bound`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistogramBucket {
+    pub upper_bound: f64,
+    pub count: u64,
+}
+
+/// The distribution of a measurement.
+///
+/// Buckets are log-linear over positive values and only the occupied ones are
+/// kept, in ascending order. Zero and negative observations share a bucket
+/// whose upper bound is `0.0`; `min`, `max` and `sum` stay exact regardless.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// The aggregated state of one series.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricValue {
+    /// The running total of a counter since metrics were enabled.
+    Counter { total: f64 },
+    /// Every measurement since metrics were enabled.
+    Histogram(Histogram),
+}
+
+impl fmt::Display for MetricValue {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Counter { total } => write!(formatter, "total={total}"),
+            Self::Histogram(histogram) => write!(
+                formatter,
+                "count={} sum={} min={} max={} buckets={}",
+                histogram.count,
+                histogram.sum,
+                histogram.min,
+                histogram.max,
+                histogram.buckets.len()
+            ),
+        }
+    }
+}
+
+/// One label set of a metric and its aggregate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricSeries {
+    /// Support-safe field names and their rendered values, in declaration
+    /// order.
+    pub labels: Vec<(&'static str, String)>,
+    pub value: MetricValue,
+}
+
+/// Every series of one metric name.
+#[derive(Clone, Debug)]
+pub struct MetricFamily {
+    /// The first call site observed with this name.
+    pub metadata: &'static Metadata,
+    /// The declaration of every label seen for this name, across call sites.
+    pub label_fields: Vec<&'static FieldMetadata>,
+    /// Ordered by labels.
+    pub series: Vec<MetricSeries>,
+    /// Observations dropped because they would have opened a series past the
+    /// cardinality limit.
+    pub overflowed: u64,
+}
+
+/// A point-in-time copy of the aggregated metric state.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsSnapshot {
+    /// Ordered by event name.
+    pub families: Vec<MetricFamily>,
+    /// Observations that had no usable quantity.
+    pub rejected: u64,
+}
+
+struct Family {
+    metadata: &'static Metadata,
+    label_fields: Vec<&'static FieldMetadata>,
+    series: HashMap<Vec<(&'static str, String)>, MetricValue>,
+    overflowed: u64,
+}
+
+#[derive(Default)]
+struct State {
+    enabled: bool,
+    cardinality_limit: usize,
+    flush_interval: Option<Duration>,
+    next_flush: Option<Instant>,
+    families: HashMap<&'static str, Family>,
+    rejected: u64,
+}
+
+pub(crate) struct Aggregator {
+    state: Spinlock<State>,
+}
+
+impl Aggregator {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Spinlock::new(State::default()),
+        }
+    }
+
+    pub(crate) fn enable(&self, cardinality_limit: usize, flush_interval: Option<Duration>) {
+        self.state.with_mut(|state| {
+            state.enabled = true;
+            state.cardinality_limit = cardinality_limit;
+            state.flush_interval = flush_interval;
+            state.next_flush = flush_interval.map(|interval| Instant::now() + interval);
+        });
+    }
+
+    pub(crate) fn aggregates(&self, metadata: &Metadata) -> bool {
+        matches!(metadata.kind, Kind::Counter | Kind::Measurement)
+            && self.state.with(|state| state.enabled)
+    }
+
+    /// Folds one observation in. Returns whether a periodic flush is due.
+    pub(crate) fn record(&self, event: EventRef<'_>) -> bool {
+        let metadata = event.metadata;
+        let mut quantity = None;
+        let mut labels = Vec::new();
+        let mut label_fields = Vec::new();
+        for field in event.fields.iter().flatten() {
+            if field.metadata.name == METRIC_VALUE_FIELD {
+                quantity = number(field.value);
+            } else if field.metadata.privacy == Privacy::SupportSafe
+                && field.metadata.detail == Detail::Core
+            {
+                labels.push((field.metadata.name, label_value(field.value)));
+                label_fields.push(field.metadata);
+            }
+        }
+        let declares_value = metadata
+            .fields
+            .iter()
+            .any(|field| field.name == METRIC_VALUE_FIELD);
+        let quantity = match (metadata.kind, quantity) {
+            (Kind::Counter, None) if !declares_value => Some(1.0),
+            (Kind::Counter, Some(increment)) if increment >= 0.0 => Some(increment),
+            (Kind::Measurement, Some(value)) => Some(value),
+            _ => None,
+        };
+
+        let now = Instant::now();
+        self.state.with_mut(|state| {
+            let Some(quantity) = quantity else {
+                state.rejected += 1;
+                return false;
+            };
+            let limit = state.cardinality_limit;
+            let family = state
+                .families
+                .entry(metadata.event_name)
+                .or_insert_with(|| Family {
+                    metadata,
+                    label_fields: Vec::new(),
+                    series: HashMap::new(),
+                    overflowed: 0,
+                });
+            if family.metadata.kind != metadata.kind {
+                state.rejected += 1;
+                return false;
+            }
+            for field in label_fields {
+                if !family
+                    .label_fields
+                    .iter()
+                    .any(|known| known.name == field.name)
+                {
+                    family.label_fields.push(field);
+                }
+            }
+            if !family.series.contains_key(&labels) && family.series.len() >= limit {
+                family.overflowed += 1;
+            } else {
+                let value = family.series.entry(labels).or_insert_with(|| {
+                    if metadata.kind == Kind::Counter {
+                        MetricValue::Counter { total: 0.0 }
+                    } else {
+                        MetricValue::Histogram(Histogram {
+                            count: 0,
+                            sum: 0.0,
+                            min: f64::INFINITY,
+                            max: f64::NEG_INFINITY,
+                            buckets: Vec::new(),
+                        })
+                    }
+                });
+                match value {
+                    MetricValue::Counter { total } => *total += quantity,
+                    MetricValue::Histogram(histogram) => observe(histogram, quantity),
+                }
+            }
+            match (state.next_flush, state.flush_interval) {
+                (Some(due), Some(interval)) if now >= due => {
+                    state.next_flush = Some(now + interval);
+                    true
+                }
+                _ => false,
+            }
+        })
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let mut snapshot = self.state.with(|state| MetricsSnapshot {
+            families: state
+                .families
+                .values()
+                .map(|family| MetricFamily {
+                    metadata: family.metadata,
+                    label_fields: family.label_fields.clone(),
+                    series: family
+                        .series
+                        .iter()
+                        .map(|(labels, value)| MetricSeries {
+                            labels: labels.clone(),
+                            value: value.clone(),
+                        })
+                        .collect(),
+                    overflowed: family.overflowed,
+                })
+                .collect(),
+            rejected: state.rejected,
+        });
+        snapshot
+            .families
+            .sort_by_key(|family| family.metadata.event_name);
+        for family in &mut snapshot.families {
+            family
+                .series
+                .sort_by(|left, right| left.labels.cmp(&right.labels));
+        }
+        snapshot
+    }
+}
+
+fn number(value: ValueRef<'_>) -> Option<f64> {
+    let number = match value {
+        ValueRef::I64(value) => value as f64,
+        ValueRef::U64(value) => value as f64,
+        ValueRef::F64(value) => value,
+        _ => return None,
+    };
+    number.is_finite().then_some(number)
+}
+
+fn label_value(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Bool(value) => value.to_string(),
+        ValueRef::I64(value) => value.to_string(),
+        ValueRef::U64(value) => value.to_string(),
+        ValueRef::F64(value) => value.to_string(),
+        ValueRef::Str(value) => value.to_owned(),
+        ValueRef::Debug(value) => format!("{value:?}"),
+        ValueRef::Display(value) => format!("{value}"),
+    }
+}
+
+fn observe(histogram: &mut Histogram, value: f64) {
+    histogram.count += 1;
+    histogram.sum += value;
+    histogram.min = histogram.min.min(value);
+    histogram.max = histogram.max.max(value);
+    let upper_bound = bucket_upper_bound(value);
+    match histogram
+        .buckets
+        .binary_search_by(|bucket| bucket.upper_bound.total_cmp(&upper_bound))
+    {
+        Ok(index) => histogram.buckets[index].count += 1,
+        Err(index) => histogram.buckets.insert(
+            index,
+            HistogramBucket {
+                upper_bound,
+                count: 1,
+            },
+        ),
+    }
+}
+
+/// The smallest log-linear bound that is at least `value`: a power of two
+/// split into [`HISTOGRAM_SUB_BUCKETS`] equal steps.
+fn bucket_upper_bound(value: f64) -> f64 {
+    if value <= 0.0 {
+        return 0.0;
+    }
+    let mut exponent = value.log2().floor() as i32;
+    // `log2` can land one off either side of an exact power of two.
+    if 2_f64.powi(exponent) > value {
+        exponent -= 1;
+    } else if 2_f64.powi(exponent + 1) <= value {
+        exponent += 1;
+    }
+    let base = 2_f64.powi(exponent);
+    let steps = f64::from(HISTOGRAM_SUB_BUCKETS);
+    let step = ((value / base - 1.0) * steps).ceil();
+    base * (1.0 + step / steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn bucket_bounds_are_log_linear_and_inclusive() {
+        assert_eq!(bucket_upper_bound(-3.0), 0.0);
+        assert_eq!(bucket_upper_bound(0.0), 0.0);
+        assert_eq!(bucket_upper_bound(1.0), 1.0);
+        assert_eq!(bucket_upper_bound(1.1), 1.25);
+        assert_eq!(bucket_upper_bound(1.25), 1.25);
+        assert_eq!(bucket_upper_bound(1.9), 2.0);
+        assert_eq!(bucket_upper_bound(2.0), 2.0);
+        assert_eq!(bucket_upper_bound(100.0), 112.0);
+        assert_eq!(bucket_upper_bound(0.3), 0.3125);
+
+        let mut histogram = Histogram {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            buckets: Vec::new(),
+        };
+        for value in [3.0, 1.0, 3.5, -1.0] {
+            observe(&mut histogram, value);
+        }
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.sum, 6.5);
+        assert_eq!(histogram.min, -1.0);
+        assert_eq!(histogram.max, 3.5);
+        assert_eq!(
+            histogram.buckets,
+            vec![
+                HistogramBucket {
+                    upper_bound: 0.0,
+                    count: 1
+                },
+                HistogramBucket {
+                    upper_bound: 1.0,
+                    count: 1
+                },
+                HistogramBucket {
+                    upper_bound: 3.0,
+                    count: 1
+                },
+                HistogramBucket {
+                    upper_bound: 3.5,
+                    count: 1
+                },
+            ]
+        );
+    }
+}
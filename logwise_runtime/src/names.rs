@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The lowercase names of the facade's enums, shared by every format that
+//! writes or reads them: directives, JSON Lines, flight dumps and the
+//! control socket.
+
+use logwise::{Class, Detail, Kind, Privacy, Severity};
+
+pub(crate) const fn class_name(class: Class) -> &'static str {
+    match class {
+        Class::Operational => "operational",
+        Class::Diagnostic => "diagnostic",
+        Class::Forensic => "forensic",
+        Class::Performance => "performance",
+        Class::Metric => "metric",
+    }
+}
+
+pub(crate) const fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Trace => "trace",
+        Severity::Debug => "debug",
+        Severity::Info => "info",
+        Severity::Warn => "warn",
+        Severity::Error => "error",
+        Severity::Critical => "critical",
+    }
+}
+
+pub(crate) const fn kind_name(kind: Kind) -> &'static str {
+    match kind {
+        Kind::Event => "event",
+        Kind::AdHocText => "ad_hoc_text",
+        Kind::Span => "span",
+        Kind::Counter => "counter",
+        Kind::Measurement => "measurement",
+    }
+}
+
+pub(crate) const fn privacy_name(privacy: Privacy) -> &'static str {
+    match privacy {
+        Privacy::SupportSafe => "support",
+        Privacy::LocalOnly => "local",
+        Privacy::Secret => "secret",
+    }
+}
+
+pub(crate) const fn detail_name(detail: Detail) -> &'static str {
+    match detail {
+        Detail::Core => "core",
+        Detail::Detail => "detail",
+    }
+}
@@ -0,0 +1,408 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! OpenMetrics text exposition of the runtime's aggregated metrics.
+//!
+//! [`render_openmetrics`] turns a [`MetricsSnapshot`] into the text format
+//! Prometheus scrapes. Metric names are event names with every character
+//! outside `[a-zA-Z0-9_:]` replaced by `_`, `# HELP` and `# TYPE` come from the
+//! call site's [`Metadata`], and labels are the series' support-safe labels —
+//! the aggregator never admits any other kind, so nothing here has to filter.
+//! Cardinality overflow and rejected observations are exposed as two extra
+//! counters, so a scrape shows what aggregation lost. Their `metric` label
+//! is the event name as written at the call site.
+//!
+//! Sanitizing can give two event names one metric name, such as `app.hits`
+//! and `app_hits`, and a metric name also claims its sample suffixes, so
+//! `app.hits` as a histogram collides with `app.hits_count`. Only the first
+//! family to claim a name is exposed; the series of every later one are
+//! counted in `logwise_metric_collision_total` by event name. Labels are
+//! sanitized the same way, and a label that would repeat one already on the
+//! sample, including the histogram's `le`, gets a `_` appended until it is
+//! unique: a field named `le` is exposed as `le_`.
+//!
+//! With the `metrics-http` feature, `MetricsListener` serves that text over
+//! HTTP from a loopback address. It is deliberately minimal: one thread, one
+//! request per connection, `GET /metrics` only.
+
+use std::fmt::{self, Write};
+
+use logwise::{Kind, Metadata};
+
+use crate::metrics::{Histogram, MetricFamily, MetricValue, MetricsSnapshot};
+
+/// The `Content-Type` of [`render_openmetrics`] output.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Renders `snapshot` as OpenMetrics text, ending with `# EOF`.
+pub fn render_openmetrics(snapshot: &MetricsSnapshot) -> String {
+    let mut output = String::new();
+    write_openmetrics(&mut output, snapshot).expect("writing to a String cannot fail");
+    output
+}
+
+/// The families this module adds, with their samples, which no metric may
+/// claim.
+const RESERVED_NAMES: [&str; 9] = [
+    "logwise_metric_overflow",
+    "logwise_metric_overflow_total",
+    "logwise_metric_overflow_created",
+    "logwise_metric_rejected",
+    "logwise_metric_rejected_total",
+    "logwise_metric_rejected_created",
+    "logwise_metric_collision",
+    "logwise_metric_collision_total",
+    "logwise_metric_collision_created",
+];
+
+fn write_openmetrics(output: &mut String, snapshot: &MetricsSnapshot) -> fmt::Result {
+    let mut claimed: Vec<String> = RESERVED_NAMES.iter().map(|name| (*name).into()).collect();
+    let mut exposed = Vec::new();
+    let mut collided = Vec::new();
+    for family in &snapshot.families {
+        let name = family_name(family.metadata)?;
+        let samples = sample_names(&name, family.metadata.kind);
+        if samples.iter().any(|sample| claimed.contains(sample)) {
+            collided.push(family);
+            continue;
+        }
+        claimed.extend(samples);
+        write_family(output, family, &name)?;
+        exposed.push(family);
+    }
+    if exposed.iter().any(|family| family.overflowed != 0) {
+        writeln!(
+            output,
+            "# HELP logwise_metric_overflow Observations dropped by the per-metric cardinality cap."
+        )?;
+        writeln!(output, "# TYPE logwise_metric_overflow counter")?;
+        for family in &exposed {
+            write!(output, "logwise_metric_overflow_total{{metric=\"")?;
+            write_label_value(output, family.metadata.event_name)?;
+            writeln!(output, "\"}} {}", family.overflowed)?;
+        }
+    }
+    if !collided.is_empty() {
+        writeln!(
+            output,
+            "# HELP logwise_metric_collision Series not exposed because their metric name is already taken."
+        )?;
+        writeln!(output, "# TYPE logwise_metric_collision counter")?;
+        for family in &collided {
+            write!(output, "logwise_metric_collision_total{{metric=\"")?;
+            write_label_value(output, family.metadata.event_name)?;
+            writeln!(output, "\"}} {}", family.series.len())?;
+        }
+    }
+    writeln!(
+        output,
+        "# HELP logwise_metric_rejected Observations with no usable quantity."
+    )?;
+    writeln!(output, "# TYPE logwise_metric_rejected counter")?;
+    writeln!(
+        output,
+        "logwise_metric_rejected_total {}",
+        snapshot.rejected
+    )?;
+    writeln!(output, "# EOF")
+}
+
+/// The metric family name for a call site's event name.
+fn family_name(metadata: &Metadata) -> Result<String, fmt::Error> {
+    let mut name = String::new();
+    write_metric_name(&mut name, metadata.event_name)?;
+    if metadata.kind == Kind::Counter {
+        // The sample carries `_total`; the family name must not.
+        if let Some(stripped) = name.strip_suffix("_total") {
+            name.truncate(stripped.len());
+        }
+    }
+    Ok(name)
+}
+
+/// Every name a family claims: its own, and each sample name it may use.
+fn sample_names(name: &str, kind: Kind) -> Vec<String> {
+    let suffixes: &[&str] = match kind {
+        Kind::Counter => &["", "_total", "_created"],
+        _ => &["", "_bucket", "_count", "_sum", "_created"],
+    };
+    suffixes
+        .iter()
+        .map(|suffix| format!("{name}{suffix}"))
+        .collect()
+}
+
+fn write_family(output: &mut String, family: &MetricFamily, name: &str) -> fmt::Result {
+    let metadata = family.metadata;
+    let (kind, type_name) = match metadata.kind {
+        Kind::Counter => ("counter", "counter"),
+        _ => ("measurement", "histogram"),
+    };
+    write!(output, "# HELP {name} ")?;
+    write_help(output, metadata, kind)?;
+    writeln!(output)?;
+    writeln!(output, "# TYPE {name} {type_name}")?;
+    for series in &family.series {
+        match &series.value {
+            MetricValue::Counter { total } => {
+                write!(output, "{name}_total")?;
+                write_labels(output, &series.labels, None)?;
+                writeln!(output, " {}", Float(*total))?;
+            }
+            MetricValue::Histogram(histogram) => {
+                write_histogram(output, name, &series.labels, histogram)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_histogram(
+    output: &mut String,
+    name: &str,
+    labels: &[(&'static str, String)],
+    histogram: &Histogram,
+) -> fmt::Result {
+    let mut cumulative = 0;
+    for bucket in &histogram.buckets {
+        cumulative += bucket.count;
+        write!(output, "{name}_bucket")?;
+        write_labels(output, labels, Some(Float(bucket.upper_bound)))?;
+        writeln!(output, " {cumulative}")?;
+    }
+    write!(output, "{name}_bucket")?;
+    write_labels(output, labels, Some(Float(f64::INFINITY)))?;
+    writeln!(output, " {}", histogram.count)?;
+    write!(output, "{name}_count")?;
+    write_labels(output, labels, None)?;
+    writeln!(output, " {}", histogram.count)?;
+    // OpenMetrics requires `_sum` to behave like a counter, which it no
+    // longer does once a negative value has been observed.
+    if histogram.min >= 0.0 {
+        write!(output, "{name}_sum")?;
+        write_labels(output, labels, None)?;
+        writeln!(output, " {}", Float(histogram.sum))?;
+    }
+    Ok(())
+}
+
+fn write_help(output: &mut String, metadata: &Metadata, kind: &str) -> fmt::Result {
+    let help = format!(
+        "logwise {kind} {} from {}",
+        metadata.event_name, metadata.module
+    );
+    for character in help.chars() {
+        match character {
+            '\\' => output.write_str("\\\\")?,
+            '\n' => output.write_str("\\n")?,
+            other => output.write_char(other)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_labels(
+    output: &mut String,
+    labels: &[(&'static str, String)],
+    upper_bound: Option<Float>,
+) -> fmt::Result {
+    if labels.is_empty() && upper_bound.is_none() {
+        return Ok(());
+    }
+    output.write_char('{')?;
+    let mut separator = "";
+    // Every sample of a family reserves `le`, so a label keeps its name
+    // whether or not the sample is a bucket.
+    let mut written = vec![String::from("le")];
+    for (name, value) in labels {
+        output.write_str(separator)?;
+        let mut label = String::new();
+        write_label_name(&mut label, name)?;
+        while written.contains(&label) {
+            label.push('_');
+        }
+        output.write_str(&label)?;
+        written.push(label);
+        output.write_str("=\"")?;
+        write_label_value(output, value)?;
+        output.write_char('"')?;
+        separator = ",";
+    }
+    if let Some(upper_bound) = upper_bound {
+        write!(output, "{separator}le=\"{upper_bound}\"")?;
+    }
+    output.write_char('}')
+}
+
+fn write_label_value(output: &mut String, value: &str) -> fmt::Result {
+    for character in value.chars() {
+        match character {
+            '\\' => output.write_str("\\\\")?,
+            '"' => output.write_str("\\\"")?,
+            '\n' => output.write_str("\\n")?,
+            other => output.write_char(other)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_metric_name(output: &mut String, event_name: &str) -> fmt::Result {
+    for (index, character) in event_name.chars().enumerate() {
+        let valid = character.is_ascii_alphabetic()
+            || character == '_'
+            || character == ':'
+            || (index != 0 && character.is_ascii_digit());
+        output.write_char(if valid { character } else { '_' })?;
+    }
+    Ok(())
+}
+
+fn write_label_name(output: &mut String, field_name: &str) -> fmt::Result {
+    for (index, character) in field_name.chars().enumerate() {
+        let valid = character.is_ascii_alphabetic()
+            || character == '_'
+            || (index != 0 && character.is_ascii_digit());
+        output.write_char(if valid { character } else { '_' })?;
+    }
+    Ok(())
+}
+
+/// An OpenMetrics number: `+Inf`/`-Inf`/`NaN`, and otherwise a float that
+/// always shows its decimal point, which is the canonical `le` form.
+#[derive(Clone, Copy)]
+struct Float(f64);
+
+impl fmt::Display for Float {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            value if value.is_nan() => formatter.write_str("NaN"),
+            f64::INFINITY => formatter.write_str("+Inf"),
+            f64::NEG_INFINITY => formatter.write_str("-Inf"),
+            value => write!(formatter, "{value:?}"),
+        }
+    }
+}
+
+#[cfg(all(feature = "metrics-http", not(target_arch = "wasm32")))]
+pub use listener::MetricsListener;
+
+#[cfg(all(feature = "metrics-http", not(target_arch = "wasm32")))]
+mod listener {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+
+    use super::{OPENMETRICS_CONTENT_TYPE, render_openmetrics};
+    use crate::Runtime;
+
+    /// How long a connection may take to send its request before it is
+    /// dropped. A stalled client must not stop the next scrape.
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+    /// Request lines and headers beyond this are not read.
+    const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+
+    /// A minimal HTTP endpoint serving `GET /metrics` from a runtime.
+    ///
+    /// Only loopback addresses are accepted: this is for scraping and
+    /// `curl`ing from the same machine, not a hardened server. Dropping the
+    /// listener stops and joins its thread.
+    #[derive(Debug)]
+    pub struct MetricsListener {
+        local_addr: SocketAddr,
+        stopped: Arc<AtomicBool>,
+        worker: Option<JoinHandle<()>>,
+    }
+
+    impl MetricsListener {
+        /// Binds `address` and starts serving. Fails with
+        /// [`io::ErrorKind::InvalidInput`] for a non-loopback address.
+        pub fn bind(runtime: &'static Runtime, address: impl ToSocketAddrs) -> io::Result<Self> {
+            let mut addresses = address.to_socket_addrs()?;
+            let address = addresses
+                .find(|address| address.ip().is_loopback())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the metrics listener binds loopback addresses only",
+                    )
+                })?;
+            let listener = TcpListener::bind(address)?;
+            let local_addr = listener.local_addr()?;
+            let stopped = Arc::new(AtomicBool::new(false));
+            let worker_stopped = stopped.clone();
+            let worker = std::thread::Builder::new()
+                .name("logwise-metrics".into())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if worker_stopped.load(Ordering::Acquire) {
+                            break;
+                        }
+                        // A broken connection is the client's problem; keep
+                        // serving the next one.
+                        if let Ok(stream) = stream {
+                            let _ = serve(runtime, stream);
+                        }
+                    }
+                })?;
+            Ok(Self {
+                local_addr,
+                stopped,
+                worker: Some(worker),
+            })
+        }
+
+        pub fn local_addr(&self) -> SocketAddr {
+            self.local_addr
+        }
+    }
+
+    impl Drop for MetricsListener {
+        fn drop(&mut self) {
+            self.stopped.store(true, Ordering::Release);
+            // `accept` has no timeout; a connection of our own wakes it.
+            let _ = TcpStream::connect(self.local_addr);
+            if let Some(worker) = self.worker.take() {
+                let _ = worker.join();
+            }
+        }
+    }
+
+    fn serve(runtime: &Runtime, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(io::Read::take(&stream, MAX_REQUEST_BYTES));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+                break;
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, content_type, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => (
+                "200 OK",
+                OPENMETRICS_CONTENT_TYPE,
+                render_openmetrics(&runtime.metrics()),
+            ),
+            (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "not found\n".into()),
+            _ => (
+                "405 Method Not Allowed",
+                "text/plain",
+                "method not allowed\n".into(),
+            ),
+        };
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )?;
+        stream.flush()
+    }
+}
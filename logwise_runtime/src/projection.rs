@@ -11,10 +11,90 @@
 //!
 //! `omitted_fields` records how much a view did not get, so a sink can tell
 //! "nothing was logged" apart from "you were not allowed to see it".
+//!
+//! Every projected event carries the [`Timestamp`] the runtime stamped on its
+//! dispatch, taken once and shared by every view of that dispatch.
+//!
+//! Spans reach sinks through the same projection. A span-kind event carries a
+//! [`ProjectedSpan`] saying whether it opened or closed the span and, on
+//! close, how long it took and whether it crossed its warning threshold.
+//! Aggregated metrics do the same with a [`MetricValue`]: a flushed series is
+//! an event of its call site whose fields are the series' labels.
 
 use core::fmt;
+use core::time::Duration;
+use std::sync::OnceLock;
+
+use logwise::{ContextToken, Detail, Metadata, Privacy, SpanTiming, SpanToken, ValueRef};
+
+use crate::metrics::MetricValue;
+use crate::sys::{Instant, SystemTime, UNIX_EPOCH};
+
+/// When the runtime dispatched an event.
+///
+/// The two clocks answer different questions. `monotonic` never goes
+/// backwards, so it is what to subtract to learn how far apart two events
+/// were; it is measured from a process-local origin and means nothing outside
+/// this process. `wall` says when an event happened in terms the rest of the
+/// world understands, and can jump when the system clock is adjusted.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Timestamp {
+    /// Time since this process's clock origin.
+    pub monotonic: Duration,
+    /// Time since the Unix epoch, or zero if the system clock reads earlier.
+    pub wall: Duration,
+}
 
-use logwise::{ContextToken, Detail, Metadata, Privacy, ValueRef};
+impl Timestamp {
+    /// Reads both clocks.
+    pub fn now() -> Self {
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        let origin = *ORIGIN.get_or_init(Instant::now);
+        Self {
+            monotonic: Instant::now().saturating_duration_since(origin),
+            wall: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Renders the wall-clock time as RFC 3339 UTC with microsecond precision.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.wall.as_secs();
+        let (year, month, day) = civil_from_days(seconds / 86_400);
+        let second_of_day = seconds % 86_400;
+        write!(
+            formatter,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+            second_of_day / 3_600,
+            second_of_day / 60 % 60,
+            second_of_day % 60,
+            self.wall.subsec_micros(),
+        )
+    }
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date, after
+/// Howard Hinnant's `civil_from_days`, restricted to dates after the epoch.
+const fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
 
 /// A field already authorized for one sink view.
 #[derive(Clone, Copy, Debug)]
@@ -23,6 +103,9 @@ pub struct ProjectedField<'a> {
     pub privacy: Privacy,
     pub detail: Detail,
     pub value: ValueRef<'a>,
+    /// Set when `value` is a [`pseudonym`](crate::pseudonym) token standing
+    /// in for a local-only value this view may not see.
+    pub pseudonymized: bool,
 }
 
 /// The only event representation exposed to runtime sinks.
@@ -33,9 +116,67 @@ pub struct ProjectedField<'a> {
 pub struct ProjectedEvent<'a> {
     pub metadata: &'static Metadata,
     pub context: ContextToken,
+    pub timestamp: Timestamp,
     pub fields: Vec<ProjectedField<'a>>,
     pub message: Option<fmt::Arguments<'a>>,
     pub omitted_fields: usize,
+    /// Set when this event opens or closes a span, and `None` for an ordinary
+    /// event.
+    pub span: Option<ProjectedSpan>,
+    /// Set when this event is a flushed metric series.
+    pub metric: Option<&'a MetricValue>,
+}
+
+/// Which end of a span a projected event reports.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SpanLifecycle {
+    /// The span's guard was created.
+    Started,
+    /// The span's guard was dropped.
+    Completed {
+        /// Time measured for the span's [`SpanTiming`] question.
+        elapsed: Duration,
+        /// Whether `elapsed` reached the span's warning threshold.
+        threshold_exceeded: bool,
+    },
+}
+
+/// Span state attached to the start and completion events of a span.
+///
+/// A completion carries the fields the span was opened with, projected again
+/// for the receiving sink, so a sink does not need to pair the two events to
+/// know what a slow span was doing.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ProjectedSpan {
+    pub token: SpanToken,
+    pub timing: SpanTiming,
+    pub warning_threshold: Option<Duration>,
+    pub lifecycle: SpanLifecycle,
+}
+
+impl fmt::Display for ProjectedSpan {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lifecycle {
+            SpanLifecycle::Started => write!(formatter, "span=started timing={:?}", self.timing)?,
+            SpanLifecycle::Completed {
+                elapsed,
+                threshold_exceeded,
+            } => {
+                write!(
+                    formatter,
+                    "span=completed timing={:?} elapsed={elapsed:?}",
+                    self.timing
+                )?;
+                if let Some(threshold) = self.warning_threshold {
+                    write!(formatter, " threshold={threshold:?}")?;
+                }
+                if threshold_exceeded {
+                    formatter.write_str(" threshold_exceeded")?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// A synchronous destination for an already projected view.
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Correlating local-only values on remote sinks without revealing them.
+//!
+//! A remote sink sees support-safe fields and nothing else, so it can count
+//! errors but cannot tell that thirty of them came from the same user.
+//! [`Runtime::set_pseudonymization`](crate::Runtime::set_pseudonymization)
+//! attaches a [`Pseudonymization`] to a remote sink naming the fields it may
+//! see as tokens. Each such value reaches that sink as a `u64` keyed hash in
+//! place of the value, with
+//! [`ProjectedField::pseudonymized`](crate::ProjectedField::pseudonymized)
+//! set. Its `privacy` remains what the call site declared.
+//!
+//! # Which fields qualify
+//!
+//! A field is replaced by a token for a sink only when all of these hold:
+//!
+//! - the sink is remote; local sinks already see the value;
+//! - the call site declares the field [`Privacy::LocalOnly`];
+//! - the sink's policy names the field;
+//! - the sink's detail level admits the field, as for any other field.
+//!
+//! [`Privacy::Secret`] fields are never eligible, whatever the policy names,
+//! and support-safe fields are delivered as they are. Everything else stays
+//! omitted.
+//!
+//! # Tokens
+//!
+//! A token is SipHash over the field name and the value, keyed by a secret
+//! the runtime draws when it is created and never exposes. The same value in
+//! the same field gives the same token on every remote sink for the life of
+//! the runtime, and a different one after a restart, so tokens correlate
+//! events within one process and cannot be joined across processes or
+//! reversed by hashing guesses elsewhere. Integers hash by numeric value,
+//! and strings, `Debug` values and `Display` values by their text. On wasm32
+//! the key is seeded from the clocks rather than an OS random source.
+//!
+//! Call sites that a pseudonymizing sink selects evaluate their local-only
+//! fields, since the runtime needs the values to hash them.
+
+use core::fmt::{self, Write as _};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use logwise::{Privacy, ValueRef};
+
+use crate::projection::Timestamp;
+
+/// The local-only fields a remote sink receives as tokens.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Pseudonymization {
+    fields: Vec<String>,
+}
+
+impl Pseudonymization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names one more field to pseudonymize.
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        self.fields.push(name.into());
+        self
+    }
+
+    /// Whether a field declared with `privacy` is tokenized under this
+    /// policy.
+    pub(crate) fn covers(&self, name: &str, privacy: Privacy) -> bool {
+        privacy == Privacy::LocalOnly && self.fields.iter().any(|field| field == name)
+    }
+}
+
+/// The runtime's pseudonymization key.
+pub(crate) struct PseudonymKey {
+    keys: RandomState,
+    seed: Timestamp,
+}
+
+impl PseudonymKey {
+    pub(crate) fn new() -> Self {
+        Self {
+            keys: RandomState::new(),
+            seed: Timestamp::now(),
+        }
+    }
+
+    pub(crate) fn token(&self, name: &str, value: ValueRef<'_>) -> u64 {
+        let mut hasher = self.keys.build_hasher();
+        self.seed.hash(&mut hasher);
+        name.hash(&mut hasher);
+        match value {
+            ValueRef::Bool(value) => {
+                hasher.write_u8(0);
+                value.hash(&mut hasher);
+            }
+            ValueRef::I64(value) if value < 0 => {
+                hasher.write_u8(1);
+                value.hash(&mut hasher);
+            }
+            ValueRef::I64(value) => {
+                hasher.write_u8(2);
+                (value as u64).hash(&mut hasher);
+            }
+            ValueRef::U64(value) => {
+                hasher.write_u8(2);
+                value.hash(&mut hasher);
+            }
+            ValueRef::F64(value) => {
+                hasher.write_u8(3);
+                value.to_bits().hash(&mut hasher);
+            }
+            ValueRef::Str(value) => {
+                hasher.write_u8(4);
+                hasher.write(value.as_bytes());
+            }
+            ValueRef::Debug(value) => {
+                hasher.write_u8(4);
+                let _ = write!(Text(&mut hasher), "{value:?}");
+            }
+            ValueRef::Display(value) => {
+                hasher.write_u8(4);
+                let _ = write!(Text(&mut hasher), "{value}");
+            }
+        }
+        hasher.finish()
+    }
+}
+
+/// Streams formatted text into a hasher without allocating it.
+struct Text<'a, H>(&'a mut H);
+
+impl<H: Hasher> fmt::Write for Text<'_, H> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.0.write(text.as_bytes());
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Rate limits for noisy call sites, with a summary of what they held back.
+//!
+//! One `event!` in a retry loop can flood every sink and evict everything
+//! useful from a [`FlightRecorder`](crate::FlightRecorder).
+//! [`Runtime::add_rate_limit`](crate::Runtime::add_rate_limit) puts a token
+//! bucket in front of the call sites a [`Filter`](crate::Filter) selects: each
+//! call site gets its own bucket, or with [`RateLimit::per_context`] each
+//! call site in each context does. The first matching limit applies. An
+//! event that finds its bucket empty is suppressed before any sink sees it.
+//!
+//! When a bucket that suppressed events admits one again, the sinks that
+//! would have received that event first get a synthetic
+//! `logwise.rate_limit.suppressed` event:
+//!
+//! ```text
+//! suppressed 4812 occurrences of `net.retry` in 10s
+//! ```
+//!
+//! Its support-safe fields are `event`, `suppressed`, and the wall-clock
+//! `first` and `last` suppression times, so a remote sink, which never sees
+//! messages, gets the summary too. A bucket that is never used again keeps
+//! its count, which is still visible in
+//! [`RuntimeDeliveryStats::rate_limited`](crate::RuntimeDeliveryStats::rate_limited).
+//! Per-context buckets are bounded: past 65536, the least recently refilled
+//! are forgotten, and a summary they were holding is never sent.
+//!
+//! Limits apply to events and logs. Spans and aggregated metrics are not
+//! limited.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use logwise::{Class, Detail, FieldMetadata, Kind, Metadata, Privacy, Severity};
+
+use crate::projection::Timestamp;
+use crate::spinlock::Spinlock;
+use crate::sys::{Duration, Instant};
+
+/// Buckets kept before idle, full ones are first swept. Each sweep waits
+/// until the map has doubled from what the last one left.
+const BUCKET_LIMIT: usize = 4096;
+
+/// Buckets kept at most. Past this, the least recently refilled buckets are
+/// forgotten even if they are suppressing, down to three quarters of it.
+const BUCKET_CAP: usize = 16 * BUCKET_LIMIT;
+
+static SUMMARY_FIELDS: [FieldMetadata; 4] = [
+    FieldMetadata::new("event", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("suppressed", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("first", Privacy::SupportSafe, Detail::Core),
+    FieldMetadata::new("last", Privacy::SupportSafe, Detail::Core),
+];
+
+/// The call site of every suppression summary.
+pub static SUPPRESSION_SUMMARY: Metadata = Metadata {
+    event_name: "logwise.rate_limit.suppressed",
+    package: "logwise_runtime",
+    target: "logwise_runtime",
+    module: "logwise_runtime::rate_limit",
+    domain: None,
+    severity: Severity::Warn,
+    class: Class::Operational,
+    kind: Kind::Event,
+    location: None,
+    fields: &SUMMARY_FIELDS,
+};
+
+/// A token bucket: up to `events` at once, refilled at `events` per `per`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RateLimit {
+    events: u32,
+    per: Duration,
+    per_context: bool,
+}
+
+impl RateLimit {
+    pub const fn new(events: u32, per: Duration) -> Self {
+        Self {
+            events,
+            per,
+            per_context: false,
+        }
+    }
+
+    /// Gives each context its own bucket, so one busy task cannot use up
+    /// another's allowance.
+    pub const fn per_context(mut self) -> Self {
+        self.per_context = true;
+        self
+    }
+
+    pub(crate) const fn is_per_context(&self) -> bool {
+        self.per_context
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RateLimitId(pub(crate) u64);
+
+/// How many events one call site has had suppressed.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimitedSite {
+    pub metadata: &'static Metadata,
+    pub suppressed: u64,
+}
+
+impl PartialEq for RateLimitedSite {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self.metadata, other.metadata) && self.suppressed == other.suppressed
+    }
+}
+
+impl Eq for RateLimitedSite {}
+
+impl Hash for RateLimitedSite {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        core::ptr::hash(self.metadata, state);
+        self.suppressed.hash(state);
+    }
+}
+
+/// What a bucket held back since it last admitted an event.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Suppressed {
+    pub(crate) count: u64,
+    pub(crate) first: Timestamp,
+    pub(crate) last: Timestamp,
+}
+
+impl Suppressed {
+    pub(crate) fn window(&self) -> Duration {
+        let elapsed = self.last.monotonic.saturating_sub(self.first.monotonic);
+        Duration::from_millis(elapsed.as_millis() as u64)
+    }
+}
+
+/// Whether an event may pass its bucket.
+pub(crate) enum Admission {
+    /// Admitted, after whatever the bucket had suppressed.
+    Admit(Option<Suppressed>),
+    Suppress,
+}
+
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    rate: f64,
+    refilled: Instant,
+    suppressed: Option<Suppressed>,
+}
+
+impl Bucket {
+    fn is_idle(&self, now: Instant) -> bool {
+        self.suppressed.is_none()
+            && self.tokens + now.saturating_duration_since(self.refilled).as_secs_f64() * self.rate
+                >= self.capacity
+    }
+}
+
+type BucketKey = (u64, usize, u64);
+
+struct Buckets {
+    /// Keyed by limit, call site address, and context ID or zero.
+    buckets: HashMap<BucketKey, Bucket>,
+    /// The size at which the next new bucket sweeps idle ones first.
+    sweep_at: usize,
+    sites: HashMap<usize, RateLimitedSite>,
+}
+
+impl Default for Buckets {
+    fn default() -> Self {
+        Self {
+            buckets: HashMap::new(),
+            sweep_at: BUCKET_LIMIT,
+            sites: HashMap::new(),
+        }
+    }
+}
+
+impl Buckets {
+    /// Makes room for one more bucket. Sweeps are amortized: after one, the
+    /// map must double before the next. Suppressed counts stay in `sites`;
+    /// an evicted bucket only loses its pending summary.
+    fn make_room(&mut self, now: Instant) {
+        if self.buckets.len() < self.sweep_at {
+            return;
+        }
+        self.buckets.retain(|_, bucket| !bucket.is_idle(now));
+        if self.buckets.len() >= BUCKET_CAP {
+            let mut oldest: Vec<(Instant, BucketKey)> = self
+                .buckets
+                .iter()
+                .map(|(key, bucket)| (bucket.refilled, *key))
+                .collect();
+            let evict = oldest.len() - BUCKET_CAP / 4 * 3;
+            oldest.select_nth_unstable_by_key(evict - 1, |(refilled, _)| *refilled);
+            for (_, key) in &oldest[..evict] {
+                self.buckets.remove(key);
+            }
+        }
+        self.sweep_at = (self.buckets.len() * 2).clamp(BUCKET_LIMIT, BUCKET_CAP);
+    }
+}
+
+pub(crate) struct Limiter {
+    buckets: Spinlock<Buckets>,
+}
+
+impl Limiter {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: Spinlock::new(Buckets::default()),
+        }
+    }
+
+    pub(crate) fn admit(
+        &self,
+        id: RateLimitId,
+        limit: RateLimit,
+        metadata: &'static Metadata,
+        context: u64,
+    ) -> Admission {
+        let now = Instant::now();
+        let capacity = f64::from(limit.events);
+        let rate = capacity / limit.per.as_secs_f64().max(f64::MIN_POSITIVE);
+        let site = metadata as *const Metadata as usize;
+        let key = (id.0, site, context);
+        self.buckets.with_mut(|state| {
+            if !state.buckets.contains_key(&key) {
+                state.make_room(now);
+            }
+            let bucket = state.buckets.entry(key).or_insert(Bucket {
+                tokens: capacity,
+                capacity,
+                rate,
+                refilled: now,
+                suppressed: None,
+            });
+            let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+            bucket.refilled = now;
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return Admission::Admit(bucket.suppressed.take());
+            }
+
+            let stamp = Timestamp::now();
+            let suppressed = bucket.suppressed.get_or_insert(Suppressed {
+                count: 0,
+                first: stamp,
+                last: stamp,
+            });
+            suppressed.count += 1;
+            suppressed.last = stamp;
+            state
+                .sites
+                .entry(site)
+                .or_insert(RateLimitedSite {
+                    metadata,
+                    suppressed: 0,
+                })
+                .suppressed += 1;
+            Admission::Suppress
+        })
+    }
+
+    /// Forgets the buckets of a removed limit. What they suppressed stays
+    /// counted.
+    pub(crate) fn forget(&self, id: RateLimitId) {
+        self.buckets
+            .with_mut(|state| state.buckets.retain(|(limit, _, _), _| *limit != id.0));
+    }
+
+    /// Every call site that has had events suppressed, most suppressed first.
+    pub(crate) fn sites(&self) -> Vec<RateLimitedSite> {
+        let mut sites: Vec<_> = self
+            .buckets
+            .with(|state| state.sites.values().copied().collect());
+        sites.sort_by(|a, b| {
+            b.suppressed
+                .cmp(&a.suppressed)
+                .then_with(|| a.metadata.event_name.cmp(b.metadata.event_name))
+        });
+        sites
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(refilled: Instant, suppressing: bool) -> Bucket {
+        Bucket {
+            tokens: 0.0,
+            capacity: 1.0,
+            rate: 0.0,
+            refilled,
+            suppressed: suppressing.then(|| {
+                let stamp = Timestamp::now();
+                Suppressed {
+                    count: 1,
+                    first: stamp,
+                    last: stamp,
+                }
+            }),
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_lite::wasm_lite_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn sweeps_are_amortized_and_capped() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(1);
+        let mut state = Buckets::default();
+        for context in 0..BUCKET_LIMIT as u64 {
+            state.buckets.insert((0, 0, context), bucket(start, false));
+        }
+        // Idle, full buckets go; the next sweep waits for the floor again.
+        state.buckets.get_mut(&(0, 0, 0)).unwrap().tokens = 1.0;
+        state.make_room(later);
+        assert_eq!(state.buckets.len(), BUCKET_LIMIT - 1);
+        assert_eq!(state.sweep_at, 2 * (BUCKET_LIMIT - 1));
+        state.make_room(later);
+        assert_eq!(state.buckets.len(), BUCKET_LIMIT - 1);
+
+        // Past the cap, the least recently refilled go, suppressing or not.
+        for context in BUCKET_LIMIT as u64..=BUCKET_CAP as u64 {
+            state.buckets.insert((0, 0, context), bucket(later, true));
+        }
+        state.sweep_at = BUCKET_CAP;
+        state.make_room(later);
+        assert_eq!(state.buckets.len(), BUCKET_CAP / 4 * 3);
+        assert!(
+            state
+                .buckets
+                .values()
+                .all(|bucket| bucket.refilled == later)
+        );
+        assert_eq!(state.sweep_at, BUCKET_CAP);
+    }
+}
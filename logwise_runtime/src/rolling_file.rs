@@ -0,0 +1,303 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A file [`OwnedEventWriter`] that rotates by size and wall-clock interval.
+//!
+//! `AsyncSink` plus `StructuredWriter<File>` writes to one file forever.
+//! [`RollingFileWriter`] writes to `path` until the next record would take it
+//! past [`RotationPolicy::max_bytes`], or until a record's wall-clock
+//! timestamp falls in a later [`RotationPolicy::interval`] than the first
+//! record in the file. Then it rotates:
+//!
+//! ```text
+//! path.N      deleted
+//! path.N-1 -> path.N
+//! ...
+//! path.1   -> path.2
+//! path     -> path.1
+//! path        reopened empty
+//! ```
+//!
+//! `N` is [`RotationPolicy::retain`], so the names on disk are always `path`
+//! and `path.1` through `path.N`, newest first. Intervals are counted from
+//! the Unix epoch, so an hourly policy rotates on the hour, and a record is
+//! never split across files.
+//!
+//! # External rotation
+//!
+//! Before each record the writer checks that `path` still names the file it
+//! has open (by device and inode on Unix, by existence elsewhere) and
+//! reopens it if not, so `logrotate`'s rename-and-create mode works without
+//! `copytruncate`. A [`ReopenHandle`] forces the same reopen. Requesting it
+//! only stores an atomic flag, so a `SIGHUP` handler the application
+//! installs, with `signal-hook` for example, may call it directly.
+//!
+//! # Failures
+//!
+//! A failed rotation or reopen is returned from
+//! [`write_event`](OwnedEventWriter::write_event), which `AsyncSink` counts
+//! in [`SinkStats::write_errors`](crate::SinkStats::write_errors). The record
+//! itself is still appended to whichever file the writer can open at `path`,
+//! and the rotation is retried with the next record, so a full or read-only
+//! directory costs errors in the stats, not a panic or a silent stop.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::json_lines::encode_event;
+use crate::sinks::{OwnedEventWriter, OwnedProjectedEvent, write_structured};
+use crate::sys::{Duration, UNIX_EPOCH};
+
+/// How a [`RollingFileWriter`] lays out each record.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileFormat {
+    /// The [`StructuredWriter`](crate::StructuredWriter) text line.
+    Structured,
+    /// The [`JsonLinesWriter`](crate::JsonLinesWriter) schema.
+    JsonLines,
+}
+
+/// When a [`RollingFileWriter`] rotates, and how many old files it keeps.
+///
+/// The default never rotates and keeps nothing.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RotationPolicy {
+    max_bytes: Option<u64>,
+    interval: Option<Duration>,
+    retain: usize,
+}
+
+impl RotationPolicy {
+    pub const fn new() -> Self {
+        Self {
+            max_bytes: None,
+            interval: None,
+            retain: 0,
+        }
+    }
+
+    /// Rotates before a record would grow the file past `bytes`. A record
+    /// larger than `bytes` still gets a file to itself.
+    pub const fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Rotates when a record's wall-clock time is in a later multiple of
+    /// `interval` since the Unix epoch than the file's first record. A zero
+    /// interval never rotates.
+    pub const fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Keeps `files` rotated files, `path.1` through `path.{files}`. With
+    /// zero, rotation discards the current file.
+    pub const fn retain(mut self, files: usize) -> Self {
+        self.retain = files;
+        self
+    }
+
+    fn period(&self, wall: Duration) -> Option<u128> {
+        let interval = self.interval?.as_nanos();
+        (interval != 0).then(|| wall.as_nanos() / interval)
+    }
+}
+
+/// Asks a [`RollingFileWriter`] to reopen its path before the next record.
+///
+/// Call [`request`](Self::request) from whatever the application uses to
+/// learn that its logs were rotated out from under it. It is safe to call
+/// from a signal handler.
+#[derive(Clone, Debug)]
+pub struct ReopenHandle(Arc<AtomicBool>);
+
+impl ReopenHandle {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+#[derive(Debug)]
+pub struct RollingFileWriter {
+    path: PathBuf,
+    policy: RotationPolicy,
+    format: FileFormat,
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    size: u64,
+    /// The interval of the file's first record, once known.
+    period: Option<u128>,
+    reopen: Arc<AtomicBool>,
+    record: Vec<u8>,
+    line: String,
+}
+
+impl RollingFileWriter {
+    /// Opens `path` for appending, creating it if needed. An existing file
+    /// counts toward `max_bytes`, and its modification time places it in an
+    /// interval.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        policy: RotationPolicy,
+        format: FileFormat,
+    ) -> io::Result<Self> {
+        let mut writer = Self {
+            path: path.into(),
+            policy,
+            format,
+            file: None,
+            identity: None,
+            size: 0,
+            period: None,
+            reopen: Arc::new(AtomicBool::new(false)),
+            record: Vec::new(),
+            line: String::new(),
+        };
+        writer.open_path()?;
+        Ok(writer)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The name of the `index`th newest rotated file, starting at 1.
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        name.into()
+    }
+
+    pub fn reopen_handle(&self) -> ReopenHandle {
+        ReopenHandle(self.reopen.clone())
+    }
+
+    fn open_path(&mut self) -> io::Result<()> {
+        self.file = None;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        self.identity = identity(&metadata);
+        self.size = metadata.len();
+        self.period = if self.size == 0 {
+            None
+        } else {
+            metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .and_then(|wall| self.policy.period(wall))
+        };
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Reopens if asked to, or if `path` no longer names the open file.
+    fn reopen_if_moved(&mut self) -> io::Result<()> {
+        let requested = self.reopen.swap(false, Ordering::AcqRel);
+        let moved = self.file.is_some()
+            && match fs::metadata(&self.path) {
+                Ok(metadata) => identity(&metadata) != self.identity,
+                Err(_) => true,
+            };
+        if requested || moved || self.file.is_none() {
+            self.open_path()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn rotation_due(&self, period: Option<u128>, record_bytes: u64) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let oversized = self
+            .policy
+            .max_bytes
+            .is_some_and(|max_bytes| self.size.saturating_add(record_bytes) > max_bytes);
+        let elapsed =
+            matches!((self.period, period), (Some(current), Some(next)) if next > current);
+        oversized || elapsed
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.policy.retain == 0 {
+            ignore_missing(fs::remove_file(&self.path))?;
+        } else {
+            ignore_missing(fs::remove_file(self.rotated_path(self.policy.retain)))?;
+            for index in (1..self.policy.retain).rev() {
+                ignore_missing(fs::rename(
+                    self.rotated_path(index),
+                    self.rotated_path(index + 1),
+                ))?;
+            }
+            ignore_missing(fs::rename(&self.path, self.rotated_path(1)))?;
+        }
+        self.open_path()
+    }
+}
+
+impl OwnedEventWriter for RollingFileWriter {
+    fn write_event(&mut self, event: &OwnedProjectedEvent) -> io::Result<()> {
+        self.record.clear();
+        match self.format {
+            FileFormat::Structured => write_structured(&mut self.record, event)?,
+            FileFormat::JsonLines => {
+                self.line.clear();
+                encode_event(&mut self.line, event);
+                self.line.push('\n');
+                self.record.extend_from_slice(self.line.as_bytes());
+            }
+        }
+
+        let period = self.policy.period(event.timestamp.wall);
+        let mut result = self.reopen_if_moved();
+        if result.is_ok() && self.rotation_due(period, self.record.len() as u64) {
+            result = self.rotate();
+        }
+        if self.file.is_none() {
+            // Rotation failed partway; keep the record at `path` if possible.
+            let _ = self.open_path();
+        }
+        let Some(file) = &mut self.file else {
+            return result;
+        };
+        file.write_all(&self.record)?;
+        self.size += self.record.len() as u64;
+        if self.period.is_none() {
+            self.period = period;
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn ignore_missing(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+#[cfg(unix)]
+fn identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Per-sink sampling, for sinks that cannot take everything their filter
+//! selects.
+//!
+//! [`Runtime::set_sampling`](crate::Runtime::set_sampling) attaches a
+//! [`Sampling`] policy to a registered sink. It runs after the sink's
+//! [`Filter`](crate::Filter) and [`FieldFilter`](crate::FieldFilter), on the
+//! projected view, so it never changes what a call site evaluates; it only
+//! thins what reaches the sink. Everything it discards is counted in
+//! [`RuntimeDeliveryStats::sampled_out`](crate::RuntimeDeliveryStats::sampled_out).
+//!
+//! # Keeping task trees whole
+//!
+//! [`Sampling::PerContext`] decides once per root context. Every context the
+//! runtime creates carries its root's sampling key in the otherwise unused
+//! flags word of its [`ContextToken`](logwise::ContextToken): a root's key is
+//! a hash of its ID, and a child copies its parent's. A sink keeps a tree
+//! when the key falls below its rate, so the decision is the same for every
+//! event and every sink in the tree, a sink at 10% keeps a subset of what a
+//! sink at 50% keeps, and the decision survives a trip across the wire with
+//! the token. A token without a key, such as one from another runtime, is
+//! keyed by the ID of its oldest known ancestor.
+//!
+//! # Keeping trees that failed
+//!
+//! [`Sampling::KeepOnError`] holds a root context's events back, already
+//! projected for the sink, and forwards them in order the moment an event at
+//! [`Severity::Error`] or above occurs anywhere in the tree. From then on the
+//! tree's events go straight through. A tree that never fails is eventually
+//! evicted by newer ones, and its held events are counted as sampled out.
+//! Both the events held per tree and the trees held are bounded.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use logwise::Severity;
+
+use crate::projection::{EventSink, ProjectedEvent, ProjectedField, Timestamp};
+use crate::sinks::OwnedProjectedEvent;
+use crate::spinlock::Spinlock;
+
+/// How a sink thins the events its filters select.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    /// Keeps each event independently with probability `rate`, from 0.0 to
+    /// 1.0.
+    Rate(f64),
+    /// Keeps all of a root context's tree, or none of it, for a `rate`
+    /// fraction of trees. Events outside any context are kept per event at
+    /// the same rate.
+    PerContext(f64),
+    /// Holds each root context's events until one at error severity or above
+    /// occurs in it. Outside any context, only those events are kept.
+    KeepOnError {
+        /// Events held per tree; the oldest is discarded past this.
+        events_per_context: usize,
+        /// Trees held at once; the least recently active is discarded past
+        /// this.
+        contexts: usize,
+    },
+}
+
+/// One sink's policy and, for tail sampling, what it is holding.
+pub(crate) struct Sampler {
+    policy: Sampling,
+    random: AtomicU64,
+    tail: Spinlock<Tail>,
+}
+
+/// What a sampler decided about one event.
+pub(crate) enum Decision<'a> {
+    Deliver(ProjectedEvent<'a>),
+    /// Events a failed tree was holding, to deliver in order, then the event
+    /// itself if it was not among them.
+    Release(Vec<OwnedProjectedEvent>, Option<ProjectedEvent<'a>>),
+    /// Discarded, or held for later.
+    Withhold,
+}
+
+#[derive(Default)]
+struct Tail {
+    trees: HashMap<u64, Tree>,
+    /// Root IDs, least recently active first.
+    recency: VecDeque<u64>,
+}
+
+#[derive(Default)]
+struct Tree {
+    held: VecDeque<OwnedProjectedEvent>,
+    failed: bool,
+}
+
+impl Tail {
+    /// Marks `root` most recently active, evicting the least recently active
+    /// trees to make room for it if it is new.
+    fn touch(&mut self, root: u64, contexts: usize, sampled_out: &AtomicU64) -> &mut Tree {
+        if let Some(position) = self.recency.iter().position(|id| *id == root) {
+            self.recency.remove(position);
+        } else {
+            while self.recency.len() >= contexts.max(1)
+                && let Some(evicted) = self.recency.pop_front()
+            {
+                if let Some(tree) = self.trees.remove(&evicted) {
+                    sampled_out.fetch_add(tree.held.len() as u64, Ordering::Relaxed);
+                }
+            }
+        }
+        self.recency.push_back(root);
+        self.trees.entry(root).or_default()
+    }
+}
+
+impl Sampler {
+    pub(crate) fn new(policy: Sampling) -> Self {
+        let seed = Timestamp::now().wall.as_nanos() as u64;
+        Self {
+            policy,
+            random: AtomicU64::new(seed),
+            tail: Spinlock::new(Tail::default()),
+        }
+    }
+
+    /// Decides one projected event. `root` is the event's root context ID and
+    /// `key` its sampling key, both zero outside any context. Discards and
+    /// evictions are added to `sampled_out`.
+    pub(crate) fn decide<'a>(
+        &self,
+        event: ProjectedEvent<'a>,
+        root: u64,
+        key: u64,
+        sampled_out: &AtomicU64,
+    ) -> Decision<'a> {
+        let keep = match self.policy {
+            Sampling::Rate(rate) => self.next_random() < threshold(rate),
+            Sampling::PerContext(rate) if key != 0 => key < threshold(rate),
+            Sampling::PerContext(rate) => self.next_random() < threshold(rate),
+            Sampling::KeepOnError {
+                events_per_context,
+                contexts,
+            } => return self.tail(event, root, events_per_context, contexts, sampled_out),
+        };
+        if keep {
+            Decision::Deliver(event)
+        } else {
+            sampled_out.fetch_add(1, Ordering::Relaxed);
+            Decision::Withhold
+        }
+    }
+
+    fn tail<'a>(
+        &self,
+        event: ProjectedEvent<'a>,
+        root: u64,
+        events_per_context: usize,
+        contexts: usize,
+        sampled_out: &AtomicU64,
+    ) -> Decision<'a> {
+        let failing = event.metadata.severity as u8 >= Severity::Error as u8;
+        if root == 0 {
+            if failing {
+                return Decision::Deliver(event);
+            }
+            sampled_out.fetch_add(1, Ordering::Relaxed);
+            return Decision::Withhold;
+        }
+        let (failed, held) = self.tail.with_mut(|tail| {
+            let tree = tail.touch(root, contexts, sampled_out);
+            if failing {
+                tree.failed = true;
+                (true, tree.held.drain(..).collect())
+            } else {
+                (tree.failed, Vec::new())
+            }
+        });
+        if failing {
+            return Decision::Release(held, Some(event));
+        }
+        if failed {
+            return Decision::Deliver(event);
+        }
+        if events_per_context == 0 {
+            sampled_out.fetch_add(1, Ordering::Relaxed);
+            return Decision::Withhold;
+        }
+
+        // Copy outside the lock; formatting values is user code. The tree
+        // may have failed in the meantime, in which case the copy goes
+        // straight out.
+        let owned = OwnedProjectedEvent::copy_from(event, usize::MAX);
+        self.tail.with_mut(|tail| {
+            let tree = tail.touch(root, contexts, sampled_out);
+            if tree.failed {
+                return Decision::Release(vec![owned], None);
+            }
+            if tree.held.len() == events_per_context {
+                tree.held.pop_front();
+                sampled_out.fetch_add(1, Ordering::Relaxed);
+            }
+            tree.held.push_back(owned);
+            Decision::Withhold
+        })
+    }
+
+    /// Discards whatever is held, returning how many events that was.
+    pub(crate) fn discard_held(&self) -> usize {
+        self.tail.with_mut(|tail| {
+            tail.recency.clear();
+            tail.trees.drain().map(|(_, tree)| tree.held.len()).sum()
+        })
+    }
+
+    /// SplitMix64 over a shared counter: cheap, lock-free, and good enough to
+    /// thin a stream.
+    fn next_random(&self) -> u64 {
+        mix(self
+            .random
+            .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed))
+    }
+}
+
+/// Emits a held event again, borrowing its owned values.
+pub(crate) fn replay(sink: &dyn EventSink, event: &OwnedProjectedEvent) {
+    fn view<'a>(
+        event: &'a OwnedProjectedEvent,
+        message: Option<core::fmt::Arguments<'a>>,
+    ) -> ProjectedEvent<'a> {
+        ProjectedEvent {
+            metadata: event.metadata,
+            context: event.context,
+            timestamp: event.timestamp,
+            fields: event
+                .fields
+                .iter()
+                .map(|field| ProjectedField {
+                    name: field.name,
+                    privacy: field.privacy,
+                    detail: field.detail,
+                    value: field.value.as_value_ref(),
+                    pseudonymized: field.pseudonymized,
+                })
+                .collect(),
+            message,
+            omitted_fields: event.omitted_fields,
+            span: event.span,
+            metric: event.metric.as_ref(),
+        }
+    }
+    match &event.message {
+        Some(message) => sink.emit(view(event, Some(format_args!("{message}")))),
+        None => sink.emit(view(event, None)),
+    }
+}
+
+/// The sampling key of every context in the tree rooted at `root`.
+pub(crate) const fn root_key(root: u64) -> u64 {
+    mix(root) | 1
+}
+
+fn threshold(rate: f64) -> u64 {
+    if rate >= 1.0 {
+        u64::MAX
+    } else if rate > 0.0 {
+        (rate * u64::MAX as f64) as u64
+    } else {
+        0
+    }
+}
+
+const fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
@@ -28,7 +28,7 @@ use std::task::{Context, Poll, Waker};
 
 use logwise::{ContextToken, Detail, Metadata, Privacy, ValueRef};
 
-use crate::{EventSink, ProjectedEvent};
+use crate::{EventSink, MetricValue, ProjectedEvent, ProjectedSpan, Timestamp};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum OwnedValue {
@@ -39,22 +39,63 @@ pub enum OwnedValue {
     String(String),
 }
 
+impl OwnedValue {
+    /// Borrows this value back in the facade's shape, for code that feeds a
+    /// retained value through the same path as a live one.
+    pub fn as_value_ref(&self) -> ValueRef<'_> {
+        match self {
+            Self::Bool(value) => ValueRef::Bool(*value),
+            Self::I64(value) => ValueRef::I64(*value),
+            Self::U64(value) => ValueRef::U64(*value),
+            Self::F64(value) => ValueRef::F64(*value),
+            Self::String(value) => ValueRef::Str(value),
+        }
+    }
+}
+
+macro_rules! owned_value_from {
+    ($($source:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$source> for OwnedValue {
+                fn from(value: $source) -> Self {
+                    Self::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+owned_value_from! {
+    bool => Bool,
+    i32 => I64,
+    i64 => I64,
+    u32 => U64,
+    u64 => U64,
+    f64 => F64,
+    &str => String,
+    String => String,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct OwnedField {
     pub name: &'static str,
     pub privacy: Privacy,
     pub detail: Detail,
     pub value: OwnedValue,
+    pub pseudonymized: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct OwnedProjectedEvent {
     pub metadata: &'static Metadata,
     pub context: ContextToken,
+    pub timestamp: Timestamp,
     pub fields: Vec<OwnedField>,
     pub message: Option<String>,
     pub omitted_fields: usize,
     pub truncated_fields: usize,
+    pub span: Option<ProjectedSpan>,
+    pub metric: Option<MetricValue>,
 }
 
 impl OwnedProjectedEvent {
@@ -68,6 +109,7 @@ impl OwnedProjectedEvent {
                 privacy: field.privacy,
                 detail: field.detail,
                 value: own_value(field.value, max_string_bytes, &mut truncated_fields),
+                pseudonymized: field.pseudonymized,
             })
             .collect();
         let message = event
@@ -76,15 +118,18 @@ impl OwnedProjectedEvent {
         Self {
             metadata: event.metadata,
             context: event.context,
+            timestamp: event.timestamp,
             fields,
             message,
             omitted_fields: event.omitted_fields,
             truncated_fields,
+            span: event.span,
+            metric: event.metric.cloned(),
         }
     }
 }
 
-fn own_value(value: ValueRef<'_>, max: usize, truncated: &mut usize) -> OwnedValue {
+pub(crate) fn own_value(value: ValueRef<'_>, max: usize, truncated: &mut usize) -> OwnedValue {
     match value {
         ValueRef::Bool(value) => OwnedValue::Bool(value),
         ValueRef::I64(value) => OwnedValue::I64(value),
@@ -243,14 +288,26 @@ impl fmt::Display for RenderedEvent<'_> {
         let event = self.0;
         write!(
             formatter,
-            "{:?} {}",
-            event.metadata.severity, event.metadata.event_name
+            "{} {:?} {}",
+            event.timestamp, event.metadata.severity, event.metadata.event_name
         )?;
         if let Some(message) = event.message {
             write!(formatter, " {message}")?;
         }
         for field in &event.fields {
-            write!(formatter, " {}={:?}", field.name, field.value)?;
+            if field.pseudonymized
+                && let ValueRef::U64(token) = field.value
+            {
+                write!(formatter, " {}=#{token:016x}", field.name)?;
+            } else {
+                write!(formatter, " {}={:?}", field.name, field.value)?;
+            }
+        }
+        if let Some(span) = event.span {
+            write!(formatter, " {span}")?;
+        }
+        if let Some(metric) = event.metric {
+            write!(formatter, " {metric}")?;
         }
         if event.omitted_fields != 0 {
             write!(formatter, " omitted={}", event.omitted_fields)?;
@@ -289,18 +346,7 @@ impl<W> StructuredWriter<W> {
 
 impl<W: Write + Send + 'static> OwnedEventWriter for StructuredWriter<W> {
     fn write_event(&mut self, event: &OwnedProjectedEvent) -> io::Result<()> {
-        write!(
-            self.writer,
-            "{:?} {}",
-            event.metadata.severity, event.metadata.event_name
-        )?;
-        if let Some(message) = &event.message {
-            write!(self.writer, " {message}")?;
-        }
-        for field in &event.fields {
-            write!(self.writer, " {}={:?}", field.name, field.value)?;
-        }
-        writeln!(self.writer)
+        write_structured(&mut self.writer, event)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -308,6 +354,37 @@ impl<W: Write + Send + 'static> OwnedEventWriter for StructuredWriter<W> {
     }
 }
 
+/// Writes one event as a `StructuredWriter` line, newline included.
+pub(crate) fn write_structured(
+    writer: &mut impl Write,
+    event: &OwnedProjectedEvent,
+) -> io::Result<()> {
+    write!(
+        writer,
+        "{} {:?} {}",
+        event.timestamp, event.metadata.severity, event.metadata.event_name
+    )?;
+    if let Some(message) = &event.message {
+        write!(writer, " {message}")?;
+    }
+    for field in &event.fields {
+        if field.pseudonymized
+            && let OwnedValue::U64(token) = field.value
+        {
+            write!(writer, " {}=#{token:016x}", field.name)?;
+        } else {
+            write!(writer, " {}={:?}", field.name, field.value)?;
+        }
+    }
+    if let Some(span) = event.span {
+        write!(writer, " {span}")?;
+    }
+    if let Some(metric) = &event.metric {
+        write!(writer, " {metric}")?;
+    }
+    writeln!(writer)
+}
+
 struct QueuedEvent {
     sequence: u64,
     event: OwnedProjectedEvent,
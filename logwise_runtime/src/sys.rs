@@ -16,4 +16,4 @@
 //! the [`heartbeat`](crate::heartbeat) function and
 //! [`InMemoryLogger::periodic_drain_to_console`](crate::InMemoryLogger::periodic_drain_to_console).
 
-pub use wasm_lite_std::time::{Duration, Instant};
+pub use wasm_lite_std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
@@ -19,7 +19,7 @@ use std::sync::{Arc, Mutex, MutexGuard};
 use logwise::{Class, ContextToken, Kind, Metadata, Severity};
 use logwise_runtime::{
     ConsoleSink, EventSink, InMemoryLogger, Level, LogRecord, Logger, ProjectedEvent,
-    StdErrorLogger,
+    StdErrorLogger, Timestamp,
 };
 
 unsafe extern "C" {
@@ -156,9 +156,12 @@ fn console_event() {
     ConsoleSink.emit(ProjectedEvent {
         metadata: &METADATA,
         context: ContextToken::NONE,
+        timestamp: Timestamp::now(),
         fields: Vec::new(),
         message: None,
         omitted_fields: 0,
+        span: None,
+        metric: None,
     });
 }
 
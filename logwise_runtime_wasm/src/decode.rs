@@ -0,0 +1,990 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A borrowing, allocation-free reader for the `logwise_v1` wire format.
+//!
+//! [`decode_envelope`] validates one complete frame — magic, version,
+//! declared length, every string, tag and discriminant — and returns a
+//! [`DecodedEnvelope`] whose strings borrow from the input. Because the whole
+//! frame is checked up front, iterating its [`fields`](DecodedEnvelope::fields)
+//! and [`links`](DecodedEnvelope::links) cannot fail.
+//!
+//! [`Frames`] walks a byte stream of concatenated frames. A frame that does
+//! not decode is reported with its offset, and reading resumes at the next
+//! `LW1\0` after it, so one corrupt record costs that record and not the rest
+//! of the stream.
+//!
+//! Version 2 splits an event across two frames: a site record with the call
+//! site's schema, sent once, and event records that name it. [`decode_frame`]
+//! reads either version and returns a [`Frame`]; a [`SiteEvent`] becomes a
+//! [`DecodedEnvelope`] once [`resolve`](SiteEvent::resolve)d against the
+//! [`DecodedSite`] it names, which the caller keeps. [`decode_envelope`]
+//! reads only self-describing version 1 frames.
+//!
+//! A frame whose flags carry bits this reader does not know may end in a
+//! trailer it cannot read; those bytes are skipped by the declared length, as
+//! `LOGWISE_V1.md` promises. Without unknown flags, bytes left over before the
+//! declared length are an error.
+
+use core::fmt;
+use core::time::Duration;
+
+use logwise::{Class, ContextToken, Detail, Kind, Privacy, Severity, SpanTiming, SpanToken};
+
+use crate::wire::{
+    ABI_VERSION, DICTIONARY_ABI_VERSION, FLAG_TIMESTAMPED, FLAG_TRUNCATED, MAGIC, RECORD_EVENT,
+    RECORD_SITE, RECORD_SPAN_END, RECORD_SPAN_START, WireSpan, WireSpanCompletion, WireTimestamp,
+};
+
+/// Magic, version, flags and length.
+const HEADER_BYTES: usize = 12;
+const LINK_BYTES: usize = 16;
+
+const SEVERITIES: [Severity; 6] = [
+    Severity::Trace,
+    Severity::Debug,
+    Severity::Info,
+    Severity::Warn,
+    Severity::Error,
+    Severity::Critical,
+];
+const CLASSES: [Class; 5] = [
+    Class::Operational,
+    Class::Diagnostic,
+    Class::Forensic,
+    Class::Performance,
+    Class::Metric,
+];
+const KINDS: [Kind; 5] = [
+    Kind::Event,
+    Kind::AdHocText,
+    Kind::Span,
+    Kind::Counter,
+    Kind::Measurement,
+];
+const PRIVACIES: [Privacy; 3] = [Privacy::SupportSafe, Privacy::LocalOnly, Privacy::Secret];
+const DETAILS: [Detail; 2] = [Detail::Core, Detail::Detail];
+const TIMINGS: [SpanTiming; 3] = [
+    SpanTiming::WallTime,
+    SpanTiming::ActiveTime,
+    SpanTiming::WakeLatency,
+];
+
+/// Why bytes are not a `logwise_v1` envelope. Offsets count from the start of
+/// the frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The bytes end before the frame does: `needed` bytes are required to
+    /// read it, counting from its start.
+    Incomplete { needed: usize },
+    /// The frame does not start with `LW1\0`.
+    BadMagic,
+    /// The frame declares an ABI version this reader does not implement.
+    UnsupportedVersion(u16),
+    /// The declared length is shorter than the fixed header.
+    BadLength { declared: u32 },
+    /// A value at `offset` runs past the declared length.
+    Overrun { offset: usize },
+    /// The body ends at `offset`, before the declared length, and no unknown
+    /// flag announces a trailer.
+    TrailingBytes { offset: usize },
+    /// The string at `offset` is not UTF-8.
+    InvalidUtf8 { offset: usize },
+    /// A presence tag, value tag or boolean at `offset` has no meaning.
+    InvalidTag { offset: usize, tag: u8 },
+    /// A severity, class, kind, privacy or detail at `offset` is not one the
+    /// facade defines.
+    InvalidDiscriminant { offset: usize, value: u8 },
+    /// A version 2 record, which [`decode_frame`] reads, was given to
+    /// [`decode_envelope`].
+    DictionaryRecord { record: u8 },
+    /// An event record was resolved against a site it does not name.
+    SiteMismatch {
+        worker: u64,
+        site: u32,
+        given_worker: u64,
+        given_site: u32,
+    },
+    /// The field entry at `offset` refers to a position past the end of its
+    /// site's schema.
+    UnknownField { offset: usize, index: u16 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Incomplete { needed } => {
+                write!(formatter, "incomplete frame: need {needed} bytes")
+            }
+            Self::BadMagic => formatter.write_str("missing LW1 magic"),
+            Self::UnsupportedVersion(version) => {
+                write!(formatter, "unsupported logwise ABI version {version}")
+            }
+            Self::BadLength { declared } => {
+                write!(
+                    formatter,
+                    "declared length {declared} is shorter than the header"
+                )
+            }
+            Self::Overrun { offset } => {
+                write!(formatter, "value at byte {offset} runs past the frame")
+            }
+            Self::TrailingBytes { offset } => {
+                write!(
+                    formatter,
+                    "unexpected bytes after the body at byte {offset}"
+                )
+            }
+            Self::InvalidUtf8 { offset } => write!(formatter, "invalid UTF-8 at byte {offset}"),
+            Self::InvalidTag { offset, tag } => {
+                write!(formatter, "invalid tag {tag} at byte {offset}")
+            }
+            Self::InvalidDiscriminant { offset, value } => {
+                write!(formatter, "invalid discriminant {value} at byte {offset}")
+            }
+            Self::DictionaryRecord { record } => {
+                write!(
+                    formatter,
+                    "version 2 record of kind {record} needs its site"
+                )
+            }
+            Self::SiteMismatch {
+                worker,
+                site,
+                given_worker,
+                given_site,
+            } => write!(
+                formatter,
+                "event for site {site} of worker {worker} resolved against site {given_site} of worker {given_worker}"
+            ),
+            Self::UnknownField { offset, index } => {
+                write!(
+                    formatter,
+                    "field {index} at byte {offset} is not in the site schema"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Where a decoded event was logged.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WireLocation<'a> {
+    pub file: &'a str,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// One field value, as the guest encoded it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WireValue<'a> {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Str(&'a str),
+    /// The guest's `Debug` text.
+    Debug(&'a str),
+    /// The guest's `Display` text.
+    Display(&'a str),
+}
+
+impl<'a> WireValue<'a> {
+    pub const fn as_bool(self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value as an `i64`, from either integer tag if it fits.
+    pub const fn as_i64(self) -> Option<i64> {
+        match self {
+            Self::I64(value) => Some(value),
+            Self::U64(value) if value <= i64::MAX as u64 => Some(value as i64),
+            _ => None,
+        }
+    }
+
+    /// The value as a `u64`, from either integer tag if it fits.
+    pub const fn as_u64(self) -> Option<u64> {
+        match self {
+            Self::U64(value) => Some(value),
+            Self::I64(value) if value >= 0 => Some(value as u64),
+            _ => None,
+        }
+    }
+
+    pub const fn as_f64(self) -> Option<f64> {
+        match self {
+            Self::F64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The text of a string, `Debug` or `Display` value.
+    pub const fn as_str(self) -> Option<&'a str> {
+        match self {
+            Self::Str(value) | Self::Debug(value) | Self::Display(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// One encoded field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodedField<'a> {
+    pub name: &'a str,
+    pub privacy: Privacy,
+    pub detail: Detail,
+    pub value: WireValue<'a>,
+}
+
+/// One validated envelope, borrowing its strings from the input.
+#[derive(Clone, Copy, Debug)]
+pub struct DecodedEnvelope<'a> {
+    /// The frame's declared length, which is how many input bytes it used.
+    pub length: usize,
+    pub flags: u16,
+    pub sequence: u64,
+    pub dropped_before: u64,
+    pub truncated_before: u64,
+    pub worker: u64,
+    pub context: ContextToken,
+    pub severity: Severity,
+    pub class: Class,
+    pub kind: Kind,
+    pub event_name: &'a str,
+    pub package: &'a str,
+    pub target: &'a str,
+    pub module: &'a str,
+    pub domain: Option<&'a str>,
+    pub test: Option<&'a str>,
+    pub location: Option<WireLocation<'a>>,
+    pub omitted_fields: u32,
+    pub message: Option<&'a str>,
+    pub timestamp: Option<WireTimestamp>,
+    /// The span lifecycle of a version 2 span record.
+    pub span: Option<WireSpan>,
+    links: &'a [u8],
+    fields: &'a [u8],
+    field_count: u16,
+    /// For a version 2 event, the site's field schema its entries index.
+    schema: Option<&'a [u8]>,
+}
+
+impl<'a> DecodedEnvelope<'a> {
+    /// Whether the guest truncated a value in this envelope.
+    pub const fn truncated(&self) -> bool {
+        self.flags & FLAG_TRUNCATED != 0
+    }
+
+    pub fn links(&self) -> Links<'a> {
+        Links {
+            chunks: self.links.chunks_exact(LINK_BYTES),
+        }
+    }
+
+    pub fn fields(&self) -> Fields<'a> {
+        Fields {
+            reader: Reader::new(self.fields),
+            remaining: self.field_count,
+            schema: self.schema,
+            schema_cursor: Reader::new(self.schema.unwrap_or_default()),
+            schema_index: 0,
+        }
+    }
+
+    /// The first field called `name`.
+    pub fn field(&self, name: &str) -> Option<DecodedField<'a>> {
+        self.fields().find(|field| field.name == name)
+    }
+}
+
+/// The context links of a [`DecodedEnvelope`].
+#[derive(Clone, Debug)]
+pub struct Links<'a> {
+    chunks: core::slice::ChunksExact<'a, u8>,
+}
+
+impl Iterator for Links<'_> {
+    type Item = ContextToken;
+
+    fn next(&mut self) -> Option<ContextToken> {
+        let link = self.chunks.next()?;
+        let mut reader = Reader::new(link);
+        Some(ContextToken::from_parts(
+            reader.u64().ok()?,
+            reader.u64().ok()?,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Links<'_> {}
+
+/// The fields of a [`DecodedEnvelope`], in encoded order.
+#[derive(Clone, Debug)]
+pub struct Fields<'a> {
+    reader: Reader<'a>,
+    remaining: u16,
+    schema: Option<&'a [u8]>,
+    /// Where in `schema` the entry numbered `schema_index` starts.
+    schema_cursor: Reader<'a>,
+    schema_index: u16,
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = DecodedField<'a>;
+
+    fn next(&mut self) -> Option<DecodedField<'a>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // Validated by `decode_frame` and `resolve`, so this cannot fail.
+        let Some(schema) = self.schema else {
+            return self.reader.field().ok();
+        };
+        let index = self.reader.u16().ok()?;
+        // Encoders write fields in schema order, so the cursor normally only
+        // moves forward; an index behind it starts the schema over.
+        if index < self.schema_index {
+            self.schema_cursor = Reader::new(schema);
+            self.schema_index = 0;
+        }
+        while self.schema_index < index {
+            self.schema_cursor.schema_field().ok()?;
+            self.schema_index += 1;
+        }
+        let (name, privacy, detail) = self.schema_cursor.schema_field().ok()?;
+        self.schema_index += 1;
+        Some(DecodedField {
+            name,
+            privacy,
+            detail,
+            value: self.reader.value().ok()?,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl ExactSizeIterator for Fields<'_> {}
+
+/// Decodes the version 1 frame at the start of `bytes`. Bytes after its
+/// declared length are not examined.
+pub fn decode_envelope(bytes: &[u8]) -> Result<DecodedEnvelope<'_>, DecodeError> {
+    match decode_frame(bytes)? {
+        Frame::Envelope(envelope) => Ok(envelope),
+        Frame::Site(_) => Err(DecodeError::DictionaryRecord {
+            record: RECORD_SITE,
+        }),
+        Frame::Event(event) => Err(DecodeError::DictionaryRecord {
+            record: event.record,
+        }),
+    }
+}
+
+/// One decoded frame of either version.
+#[derive(Clone, Copy, Debug)]
+pub enum Frame<'a> {
+    /// A self-describing version 1 envelope.
+    Envelope(DecodedEnvelope<'a>),
+    /// A version 2 call-site definition.
+    Site(DecodedSite<'a>),
+    /// A version 2 event or span record, which names its site.
+    Event(SiteEvent<'a>),
+}
+
+impl Frame<'_> {
+    /// The frame's declared length, which is how many input bytes it used.
+    pub const fn length(&self) -> usize {
+        match self {
+            Self::Envelope(envelope) => envelope.length,
+            Self::Site(site) => site.length,
+            Self::Event(event) => event.length,
+        }
+    }
+}
+
+/// A validated version 2 site record: the schema that event records naming
+/// `site` for `worker` leave out.
+#[derive(Clone, Copy, Debug)]
+pub struct DecodedSite<'a> {
+    pub length: usize,
+    pub worker: u64,
+    /// The stream-local site ID. 0 is redefined before each use.
+    pub site: u32,
+    pub severity: Severity,
+    pub class: Class,
+    pub kind: Kind,
+    pub event_name: &'a str,
+    pub package: &'a str,
+    pub target: &'a str,
+    pub module: &'a str,
+    pub domain: Option<&'a str>,
+    pub location: Option<WireLocation<'a>>,
+    frame: &'a [u8],
+    schema: &'a [u8],
+    field_count: u16,
+}
+
+impl<'a> DecodedSite<'a> {
+    /// The whole frame, for a reader that keeps sites past the input buffer.
+    pub const fn bytes(&self) -> &'a [u8] {
+        self.frame
+    }
+
+    /// How many fields the call site declares.
+    pub const fn field_count(&self) -> usize {
+        self.field_count as usize
+    }
+}
+
+/// A validated version 2 event or span record, not yet joined to its site.
+#[derive(Clone, Copy, Debug)]
+pub struct SiteEvent<'a> {
+    pub length: usize,
+    pub flags: u16,
+    pub sequence: u64,
+    pub dropped_before: u64,
+    pub truncated_before: u64,
+    pub worker: u64,
+    pub site: u32,
+    record: u8,
+    context: ContextToken,
+    links: &'a [u8],
+    test: Option<&'a str>,
+    omitted_fields: u32,
+    frame: &'a [u8],
+    fields_at: usize,
+    fields_end: usize,
+    field_count: u16,
+    message: Option<&'a str>,
+    span: Option<WireSpan>,
+    timestamp: Option<WireTimestamp>,
+}
+
+impl<'a> SiteEvent<'a> {
+    /// Joins this record to the site it names, checking that each field
+    /// entry is in that site's schema.
+    pub fn resolve(&self, site: &DecodedSite<'a>) -> Result<DecodedEnvelope<'a>, DecodeError> {
+        if (site.worker, site.site) != (self.worker, self.site) {
+            return Err(DecodeError::SiteMismatch {
+                worker: self.worker,
+                site: self.site,
+                given_worker: site.worker,
+                given_site: site.site,
+            });
+        }
+        let mut reader = Reader {
+            bytes: &self.frame[..self.fields_end],
+            position: self.fields_at,
+        };
+        for _ in 0..self.field_count {
+            let offset = reader.position;
+            let index = reader.u16()?;
+            if index >= site.field_count {
+                return Err(DecodeError::UnknownField { offset, index });
+            }
+            reader.value()?;
+        }
+        Ok(DecodedEnvelope {
+            length: self.length,
+            flags: self.flags,
+            sequence: self.sequence,
+            dropped_before: self.dropped_before,
+            truncated_before: self.truncated_before,
+            worker: self.worker,
+            context: self.context,
+            severity: site.severity,
+            class: site.class,
+            kind: site.kind,
+            event_name: site.event_name,
+            package: site.package,
+            target: site.target,
+            module: site.module,
+            domain: site.domain,
+            test: self.test,
+            location: site.location,
+            omitted_fields: self.omitted_fields,
+            message: self.message,
+            timestamp: self.timestamp,
+            span: self.span,
+            links: self.links,
+            fields: &self.frame[self.fields_at..self.fields_end],
+            field_count: self.field_count,
+            schema: Some(site.schema),
+        })
+    }
+}
+
+/// Decodes the frame at the start of `bytes`, of either version. Bytes after
+/// its declared length are not examined.
+pub fn decode_frame(bytes: &[u8]) -> Result<Frame<'_>, DecodeError> {
+    let magic_bytes = bytes.len().min(MAGIC.len());
+    if bytes[..magic_bytes] != MAGIC[..magic_bytes] {
+        return Err(DecodeError::BadMagic);
+    }
+    if bytes.len() < HEADER_BYTES {
+        return Err(DecodeError::Incomplete {
+            needed: HEADER_BYTES,
+        });
+    }
+    let mut header = Reader::new(&bytes[..HEADER_BYTES]);
+    header.take(MAGIC.len())?;
+    let version = header.u16()?;
+    if version != ABI_VERSION && version != DICTIONARY_ABI_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let flags = header.u16()?;
+    let declared = header.u32()?;
+    let length = declared as usize;
+    if length < HEADER_BYTES {
+        return Err(DecodeError::BadLength { declared });
+    }
+    if bytes.len() < length {
+        return Err(DecodeError::Incomplete { needed: length });
+    }
+
+    let mut reader = Reader {
+        bytes: &bytes[..length],
+        position: HEADER_BYTES,
+    };
+    if version == ABI_VERSION {
+        return envelope(reader, flags).map(Frame::Envelope);
+    }
+    let offset = reader.position;
+    match reader.u8()? {
+        RECORD_SITE => site(reader, flags).map(Frame::Site),
+        record @ (RECORD_EVENT | RECORD_SPAN_START | RECORD_SPAN_END) => {
+            site_event(reader, flags, record).map(Frame::Event)
+        }
+        tag => Err(DecodeError::InvalidTag { offset, tag }),
+    }
+}
+
+fn envelope(mut reader: Reader<'_>, flags: u16) -> Result<DecodedEnvelope<'_>, DecodeError> {
+    let sequence = reader.u64()?;
+    let dropped_before = reader.u64()?;
+    let truncated_before = reader.u64()?;
+    let worker = reader.u64()?;
+    let context = reader.context()?;
+    let link_count = reader.u16()? as usize;
+    let links = reader.take(link_count * LINK_BYTES)?;
+    let severity = reader.discriminant(&SEVERITIES, |severity| severity as u8)?;
+    let class = reader.discriminant(&CLASSES, |class| class as u8)?;
+    let kind = reader.discriminant(&KINDS, |kind| kind as u8)?;
+    let event_name = reader.text()?;
+    let package = reader.text()?;
+    let target = reader.text()?;
+    let module = reader.text()?;
+    let domain = reader.optional(Reader::text)?;
+    let test = reader.optional(Reader::text)?;
+    let location = reader.location()?;
+    let omitted_fields = reader.u32()?;
+    let field_count = reader.u16()?;
+    let fields_start = reader.position;
+    for _ in 0..field_count {
+        reader.field()?;
+    }
+    let fields = &reader.bytes[fields_start..reader.position];
+    let message = reader.optional(Reader::text)?;
+    let timestamp = reader.trailer(flags)?;
+
+    Ok(DecodedEnvelope {
+        length: reader.bytes.len(),
+        flags,
+        sequence,
+        dropped_before,
+        truncated_before,
+        worker,
+        context,
+        severity,
+        class,
+        kind,
+        event_name,
+        package,
+        target,
+        module,
+        domain,
+        test,
+        location,
+        omitted_fields,
+        message,
+        timestamp,
+        span: None,
+        links,
+        fields,
+        field_count,
+        schema: None,
+    })
+}
+
+fn site(mut reader: Reader<'_>, flags: u16) -> Result<DecodedSite<'_>, DecodeError> {
+    let worker = reader.u64()?;
+    let site = reader.u32()?;
+    let severity = reader.discriminant(&SEVERITIES, |severity| severity as u8)?;
+    let class = reader.discriminant(&CLASSES, |class| class as u8)?;
+    let kind = reader.discriminant(&KINDS, |kind| kind as u8)?;
+    let event_name = reader.text()?;
+    let package = reader.text()?;
+    let target = reader.text()?;
+    let module = reader.text()?;
+    let domain = reader.optional(Reader::text)?;
+    let location = reader.location()?;
+    let field_count = reader.u16()?;
+    let schema_start = reader.position;
+    for _ in 0..field_count {
+        reader.schema_field()?;
+    }
+    let schema = &reader.bytes[schema_start..reader.position];
+    reader.trailer(flags)?;
+
+    Ok(DecodedSite {
+        length: reader.bytes.len(),
+        worker,
+        site,
+        severity,
+        class,
+        kind,
+        event_name,
+        package,
+        target,
+        module,
+        domain,
+        location,
+        frame: reader.bytes,
+        schema,
+        field_count,
+    })
+}
+
+fn site_event(
+    mut reader: Reader<'_>,
+    flags: u16,
+    record: u8,
+) -> Result<SiteEvent<'_>, DecodeError> {
+    let sequence = reader.u64()?;
+    let dropped_before = reader.u64()?;
+    let truncated_before = reader.u64()?;
+    let worker = reader.u64()?;
+    let site = reader.u32()?;
+    let context = reader.context()?;
+    let link_count = reader.u16()? as usize;
+    let links = reader.take(link_count * LINK_BYTES)?;
+    let test = reader.optional(Reader::text)?;
+    let omitted_fields = reader.u32()?;
+    let field_count = reader.u16()?;
+    let fields_at = reader.position;
+    for _ in 0..field_count {
+        reader.u16()?;
+        reader.value()?;
+    }
+    let fields_end = reader.position;
+    let message = reader.optional(Reader::text)?;
+    let span = if record == RECORD_EVENT {
+        None
+    } else {
+        let token = SpanToken::from_parts(reader.u64()?, reader.u64()?);
+        let timing = reader.discriminant(&TIMINGS, |timing| timing as u8)?;
+        let warning_threshold = reader.optional(Reader::duration)?;
+        let completion = if record == RECORD_SPAN_END {
+            Some(WireSpanCompletion {
+                elapsed: reader.duration()?,
+                threshold_exceeded: reader.tag(1)? == 1,
+            })
+        } else {
+            None
+        };
+        Some(WireSpan {
+            token,
+            timing,
+            warning_threshold,
+            completion,
+        })
+    };
+    let timestamp = reader.trailer(flags)?;
+
+    Ok(SiteEvent {
+        length: reader.bytes.len(),
+        flags,
+        sequence,
+        dropped_before,
+        truncated_before,
+        worker,
+        site,
+        record,
+        context,
+        links,
+        test,
+        omitted_fields,
+        frame: reader.bytes,
+        fields_at,
+        fields_end,
+        field_count,
+        message,
+        span,
+        timestamp,
+    })
+}
+
+/// A frame in a [`Frames`] stream that could not be decoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FrameError {
+    /// Where in the stream the frame started.
+    pub offset: usize,
+    pub error: DecodeError,
+    /// Bytes discarded to reach the next frame.
+    pub skipped: usize,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "frame at byte {}: {}; skipped {} bytes",
+            self.offset, self.error, self.skipped
+        )
+    }
+}
+
+impl core::error::Error for FrameError {}
+
+/// Decodes concatenated frames, resynchronizing on the next `LW1\0` after
+/// one that does not decode.
+///
+/// An incomplete frame at the end of the input ends iteration with
+/// [`DecodeError::Incomplete`] and leaves [`position`](Self::position) at its
+/// start, so a streaming host can keep those bytes and retry once more
+/// arrive. An incomplete frame with another frame after it is corrupt, and is
+/// skipped like any other.
+#[derive(Clone, Debug)]
+pub struct Frames<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    stalled: bool,
+}
+
+impl<'a> Frames<'a> {
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            stalled: false,
+        }
+    }
+
+    /// The offset of the first byte not yet consumed.
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Result<Frame<'a>, FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stalled || self.position == self.bytes.len() {
+            return None;
+        }
+        let offset = self.position;
+        let remaining = &self.bytes[offset..];
+        let error = match decode_frame(remaining) {
+            Ok(frame) => {
+                self.position += frame.length();
+                return Some(Ok(frame));
+            }
+            Err(error) => error,
+        };
+        let resume = find_magic(&remaining[1..]).map(|found| found + 1);
+        let skipped = match (error, resume) {
+            (DecodeError::Incomplete { .. }, None) => {
+                self.stalled = true;
+                0
+            }
+            (_, Some(resume)) => resume,
+            // Keep a partial magic at the very end: it may start a frame.
+            (_, None) => remaining.len() - partial_magic_suffix(remaining),
+        };
+        self.position += skipped;
+        Some(Err(FrameError {
+            offset,
+            error,
+            skipped,
+        }))
+    }
+}
+
+pub(crate) fn find_magic(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .windows(MAGIC.len())
+        .position(|window| window == MAGIC.as_slice())
+}
+
+/// How many trailing bytes of `bytes` are a proper prefix of the magic.
+pub(crate) fn partial_magic_suffix(bytes: &[u8]) -> usize {
+    (1..MAGIC.len())
+        .rev()
+        .find(|length| bytes.len() >= *length && bytes.ends_with(&MAGIC[..*length]))
+        .unwrap_or(0)
+}
+
+#[derive(Clone, Debug)]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+        let start = self.position;
+        let end = start
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(DecodeError::Overrun { offset: start })?;
+        self.position = end;
+        Ok(&self.bytes[start..end])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn text(&mut self) -> Result<&'a str, DecodeError> {
+        let length = self.u16()? as usize;
+        let offset = self.position;
+        core::str::from_utf8(self.take(length)?).map_err(|_| DecodeError::InvalidUtf8 { offset })
+    }
+
+    fn tag(&mut self, limit: u8) -> Result<u8, DecodeError> {
+        let offset = self.position;
+        match self.u8()? {
+            tag if tag <= limit => Ok(tag),
+            tag => Err(DecodeError::InvalidTag { offset, tag }),
+        }
+    }
+
+    fn optional<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, DecodeError> {
+        match self.tag(1)? {
+            0 => Ok(None),
+            _ => read(self).map(Some),
+        }
+    }
+
+    fn discriminant<T: Copy>(
+        &mut self,
+        values: &[T],
+        discriminant: impl Fn(T) -> u8,
+    ) -> Result<T, DecodeError> {
+        let offset = self.position;
+        let value = self.u8()?;
+        values
+            .iter()
+            .copied()
+            .find(|candidate| discriminant(*candidate) == value)
+            .ok_or(DecodeError::InvalidDiscriminant { offset, value })
+    }
+
+    fn field(&mut self) -> Result<DecodedField<'a>, DecodeError> {
+        let (name, privacy, detail) = self.schema_field()?;
+        Ok(DecodedField {
+            name,
+            privacy,
+            detail,
+            value: self.value()?,
+        })
+    }
+
+    fn schema_field(&mut self) -> Result<(&'a str, Privacy, Detail), DecodeError> {
+        Ok((
+            self.text()?,
+            self.discriminant(&PRIVACIES, |privacy| privacy as u8)?,
+            self.discriminant(&DETAILS, |detail| detail as u8)?,
+        ))
+    }
+
+    fn value(&mut self) -> Result<WireValue<'a>, DecodeError> {
+        let offset = self.position;
+        Ok(match self.u8()? {
+            1 => WireValue::Bool(self.tag(1)? == 1),
+            2 => WireValue::I64(self.u64()? as i64),
+            3 => WireValue::U64(self.u64()?),
+            4 => WireValue::F64(f64::from_bits(self.u64()?)),
+            5 => WireValue::Str(self.text()?),
+            6 => WireValue::Debug(self.text()?),
+            7 => WireValue::Display(self.text()?),
+            tag => return Err(DecodeError::InvalidTag { offset, tag }),
+        })
+    }
+
+    fn duration(&mut self) -> Result<Duration, DecodeError> {
+        self.u64().map(Duration::from_nanos)
+    }
+
+    fn context(&mut self) -> Result<ContextToken, DecodeError> {
+        Ok(ContextToken::from_parts(self.u64()?, self.u64()?))
+    }
+
+    fn location(&mut self) -> Result<Option<WireLocation<'a>>, DecodeError> {
+        self.optional(|reader| {
+            Ok(WireLocation {
+                file: reader.text()?,
+                line: reader.u32()?,
+                column: reader.u32()?,
+            })
+        })
+    }
+
+    /// The timestamp trailer, if `flags` announce one, then the check that
+    /// nothing unannounced follows.
+    fn trailer(&mut self, flags: u16) -> Result<Option<WireTimestamp>, DecodeError> {
+        let timestamp = if flags & FLAG_TIMESTAMPED != 0 {
+            Some(WireTimestamp {
+                monotonic: self.duration()?,
+                wall: self.duration()?,
+            })
+        } else {
+            None
+        };
+        let known_flags = FLAG_TRUNCATED | FLAG_TIMESTAMPED;
+        if self.position != self.bytes.len() && flags & !known_flags == 0 {
+            return Err(DecodeError::TrailingBytes {
+                offset: self.position,
+            });
+        }
+        Ok(timestamp)
+    }
+}
@@ -8,12 +8,34 @@
 //! The reserved `logwise_v1` host ABI is implemented in the dedicated wasm
 //! transport issue. This package intentionally has no dependency on an
 //! executor, `wasm_lite`, `wasm-bindgen`, or `web-sys`.
+//!
+//! The `runtime-sink` feature adds [`TransportSink`], which puts the wire
+//! behind the standard runtime's privacy projection and sink registration.
+//! The `log-file` feature adds [`LogFileWriter`] and [`LogFileReader`], which
+//! keep the same frames in a file for native services.
+
+#[cfg(feature = "runtime-sink")]
+extern crate std;
 
+mod decode;
+#[cfg(feature = "log-file")]
+mod log_file;
+#[cfg(feature = "runtime-sink")]
+mod sink;
 mod wire;
 
+pub use decode::{
+    DecodeError, DecodedEnvelope, DecodedField, DecodedSite, Fields, Frame, FrameError, Frames,
+    Links, SiteEvent, WireLocation, WireValue, decode_envelope, decode_frame,
+};
+#[cfg(feature = "log-file")]
+pub use log_file::{LOG_FILE_VERSION, LogFileHeader, LogFileReader, LogFileWriter};
+#[cfg(feature = "runtime-sink")]
+pub use sink::{MAX_ENVELOPE_BYTES, TransportSink, set_current_test, set_current_worker};
 pub use wire::{
-    ABI_VERSION, EncodeError, EncodedEnvelope, Envelope, HostStatus, Identity, Transport,
-    encode_envelope,
+    ABI_VERSION, DICTIONARY_ABI_VERSION, EncodeError, EncodedEnvelope, Envelope, HostStatus,
+    Identity, Transport, WireSpan, WireSpanCompletion, WireTimestamp, encode_envelope,
+    encode_event, encode_site, encode_timestamped_envelope,
 };
 
 /// Origin of text intercepted by a JavaScript console monkeypatch.
@@ -0,0 +1,526 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! `logwise_v1` frames in a file, for native services.
+//!
+//! A log file is a [`LogFileHeader`] followed by version 1 envelopes, exactly
+//! as a wasm host would receive them, so the decoder, `Frames` and the
+//! `logwise` binary read native and wasm logs alike. Version 2 is not used:
+//! its event records depend on a site record written earlier, and a file
+//! should stay readable from any frame.
+//!
+//! ```text
+//! LWFILE\0\0   magic
+//! u16          format version, 1
+//! u32          header length in bytes, counting the magic
+//! u32          process ID
+//! u64          start time, nanoseconds since the Unix epoch
+//! u16, bytes   process name, UTF-8
+//! ...          envelopes
+//! ```
+//!
+//! A reader skips to the declared header length, so a later format version
+//! may add header fields without breaking it. Every envelope's `worker` is the
+//! process ID and its `test` is absent, and every envelope carries the
+//! timestamp trailer: the file header is the agreement a version 1 stream to a
+//! host lacks.
+//!
+//! [`LogFileWriter`] is an [`OwnedEventWriter`], normally behind an
+//! `AsyncSink`. It sees the runtime's projection, so it only ever encodes the
+//! fields its registration allows. The wire has no links, span lifecycles or
+//! metric series for it to carry: links are left empty, span starts and
+//! completions are envelopes of the span's call site, metric flushes are not
+//! written, and fields are encoded as a
+//! [`TransportSink`](crate::TransportSink) encodes them. A record that could
+//! not be written is counted in the next one's `dropped_before`.
+//!
+//! # Reading
+//!
+//! [`LogFileReader`] validates the header and then yields envelopes in file
+//! order. A damaged frame is skipped to the next `LW1\0` and counted in
+//! [`damaged`](LogFileReader::damaged). A frame cut short by the end of the
+//! file, as a crash leaves behind, ends iteration and is reported by
+//! [`torn_tail`](LogFileReader::torn_tail) rather than as damage.
+//! [`seek_sequence`](LogFileReader::seek_sequence) binary-searches the file
+//! for a sequence number, which one writer's envelopes carry in increasing
+//! order.
+
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use core::fmt;
+use core::time::Duration;
+
+use logwise::EventRef;
+use logwise_runtime::{OwnedEventWriter, OwnedProjectedEvent, Timestamp};
+
+use crate::decode::{
+    DecodeError, DecodedEnvelope, Frame, decode_envelope, decode_frame, find_magic,
+    partial_magic_suffix,
+};
+use crate::sink::{MAX_ENVELOPE_BYTES, wire_fields};
+use crate::wire::{Identity, Record, Transport, WireTimestamp};
+
+/// The first bytes of every log file.
+const FILE_MAGIC: &[u8; 8] = b"LWFILE\0\0";
+/// The header layout [`LogFileHeader::encode`] writes.
+pub const LOG_FILE_VERSION: u16 = 1;
+/// Magic, version and header length.
+const PREFIX_BYTES: usize = 14;
+/// Prefix, process ID, start time and name length.
+const FIXED_HEADER_BYTES: usize = PREFIX_BYTES + 4 + 8 + 2;
+/// How much [`LogFileReader`] reads at a time.
+const READ_BYTES: usize = 64 * 1024;
+
+/// Which process wrote a log file, and when it started.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogFileHeader {
+    pub process_id: u32,
+    pub process_name: String,
+    /// Wall-clock time since the Unix epoch.
+    pub started: Duration,
+}
+
+impl LogFileHeader {
+    /// This process, starting now. The name is the executable's file name.
+    pub fn current() -> Self {
+        let process_name = std::env::current_exe()
+            .ok()
+            .and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+        Self {
+            process_id: std::process::id(),
+            process_name,
+            started: Timestamp::now().wall,
+        }
+    }
+
+    /// The header bytes a log file starts with. A name longer than the format
+    /// allows is cut at a UTF-8 boundary.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut name = self.process_name.as_str();
+        if name.len() > u16::MAX as usize {
+            let mut end = u16::MAX as usize;
+            while !name.is_char_boundary(end) {
+                end -= 1;
+            }
+            name = &name[..end];
+        }
+        let length = FIXED_HEADER_BYTES + name.len();
+        let mut bytes = Vec::with_capacity(length);
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.extend_from_slice(&LOG_FILE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(length as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.process_id.to_le_bytes());
+        let started = u64::try_from(self.started.as_nanos()).unwrap_or(u64::MAX);
+        bytes.extend_from_slice(&started.to_le_bytes());
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes
+    }
+
+    /// Reads the header at the start of `bytes`, and how many bytes it
+    /// declares. [`DecodeError::BadMagic`] means `bytes` is not a log file,
+    /// and [`DecodeError::Incomplete`] that it needs more of one.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let prefix = bytes.len().min(FILE_MAGIC.len());
+        if bytes[..prefix] != FILE_MAGIC[..prefix] {
+            return Err(DecodeError::BadMagic);
+        }
+        let Some(prefix) = bytes.get(..PREFIX_BYTES) else {
+            return Err(DecodeError::Incomplete {
+                needed: PREFIX_BYTES,
+            });
+        };
+        let version = u16::from_le_bytes([prefix[8], prefix[9]]);
+        if version != LOG_FILE_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let declared = u32::from_le_bytes([prefix[10], prefix[11], prefix[12], prefix[13]]);
+        let length = declared as usize;
+        if length < FIXED_HEADER_BYTES {
+            return Err(DecodeError::BadLength { declared });
+        }
+        let Some(header) = bytes.get(..length) else {
+            return Err(DecodeError::Incomplete { needed: length });
+        };
+        let process_id = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        let mut started = [0; 8];
+        started.copy_from_slice(&header[18..26]);
+        let name_bytes = u16::from_le_bytes([header[26], header[27]]) as usize;
+        let Some(name) = header.get(FIXED_HEADER_BYTES..FIXED_HEADER_BYTES + name_bytes) else {
+            return Err(DecodeError::Overrun {
+                offset: FIXED_HEADER_BYTES - 2,
+            });
+        };
+        let process_name = core::str::from_utf8(name).map_err(|_| DecodeError::InvalidUtf8 {
+            offset: FIXED_HEADER_BYTES,
+        })?;
+        Ok((
+            Self {
+                process_id,
+                process_name: process_name.to_string(),
+                started: Duration::from_nanos(u64::from_le_bytes(started)),
+            },
+            length,
+        ))
+    }
+}
+
+/// Displays as `name (pid N) started <RFC 3339 time>`.
+impl fmt::Display for LogFileHeader {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let started = Timestamp {
+            monotonic: Duration::ZERO,
+            wall: self.started,
+        };
+        write!(
+            formatter,
+            "{} (pid {}) started {started}",
+            self.process_name, self.process_id
+        )
+    }
+}
+
+/// An [`OwnedEventWriter`] that appends `logwise_v1` envelopes after a
+/// [`LogFileHeader`].
+pub struct LogFileWriter<W> {
+    writer: W,
+    transport: Transport,
+    worker: u64,
+    output: Vec<u8>,
+    scratch: Vec<u8>,
+}
+
+impl<W> fmt::Debug for LogFileWriter<W> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("LogFileWriter")
+            .field("worker", &self.worker)
+            .field("scratch_bytes", &self.scratch.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl LogFileWriter<BufWriter<File>> {
+    /// Creates or truncates `path` and writes a header for this process.
+    pub fn create(path: impl AsRef<Path>, max_value_bytes: usize) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Self::new(file, &LogFileHeader::current(), max_value_bytes)
+    }
+}
+
+impl<W: Write> LogFileWriter<W> {
+    /// Writes `header` to `writer`; envelopes follow it. Values longer than
+    /// `max_value_bytes` are truncated, as on the wire.
+    pub fn new(mut writer: W, header: &LogFileHeader, max_value_bytes: usize) -> io::Result<Self> {
+        writer.write_all(&header.encode())?;
+        Ok(Self {
+            writer,
+            transport: Transport::new(max_value_bytes).with_timestamps(),
+            worker: u64::from(header.process_id),
+            output: std::vec![0; MAX_ENVELOPE_BYTES],
+            scratch: std::vec![0; max_value_bytes.min(u16::MAX as usize)],
+        })
+    }
+
+    /// The sequence and loss counters behind this file's envelopes.
+    pub const fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub const fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub const fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write(
+        &mut self,
+        event: &OwnedProjectedEvent,
+        message: Option<fmt::Arguments<'_>>,
+    ) -> io::Result<()> {
+        let metadata = event.metadata;
+        let (fields, omitted) = wire_fields(
+            metadata,
+            event
+                .fields
+                .iter()
+                .map(|field| (field.name, field.pseudonymized, field.value.as_value_ref())),
+            event.omitted_fields,
+        );
+        let record = Record {
+            event: EventRef {
+                metadata,
+                context: event.context,
+                fields: &fields,
+                message,
+            },
+            timestamp: Some(WireTimestamp {
+                monotonic: event.timestamp.monotonic,
+                wall: event.timestamp.wall,
+            }),
+            omitted_fields: omitted,
+            span: None,
+            links: &[],
+            identity: Identity {
+                worker: self.worker,
+                test: None,
+            },
+        };
+        let encoded = self
+            .transport
+            .encode_record(record, &mut self.output, &mut self.scratch)
+            .map_err(|_| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    "event does not fit a logwise_v1 envelope",
+                )
+            })?;
+        let written = self.writer.write_all(encoded.bytes);
+        if written.is_err() {
+            self.transport.record_drop();
+        }
+        written
+    }
+}
+
+impl<W: Write + Send + 'static> OwnedEventWriter for LogFileWriter<W> {
+    fn write_event(&mut self, event: &OwnedProjectedEvent) -> io::Result<()> {
+        if event.metric.is_some() {
+            return Ok(());
+        }
+        match &event.message {
+            Some(message) => self.write(event, Some(format_args!("{message}"))),
+            None => self.write(event, None),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads the envelopes of a log file written by [`LogFileWriter`].
+#[derive(Debug)]
+pub struct LogFileReader<R> {
+    reader: R,
+    header: LogFileHeader,
+    /// Where the first envelope starts.
+    data_start: u64,
+    /// Read but not yet consumed bytes, from file offset `base`.
+    buffer: Vec<u8>,
+    base: u64,
+    start: usize,
+    ended: bool,
+    damaged: u64,
+    /// Whether the bytes at `start` follow damage already counted.
+    resyncing: bool,
+    torn_tail: Option<u64>,
+}
+
+/// What [`LogFileReader::next_record`] does with the bytes at `start`.
+enum Step {
+    Record(usize),
+    /// Read until this many bytes are buffered.
+    Fill(usize),
+    /// Pass a damaged frame.
+    Skip(usize),
+    /// Pass damage that may continue past what is buffered.
+    Resync(usize),
+    Torn,
+}
+
+impl<R: Read + Seek> LogFileReader<R> {
+    /// Reads and validates the header. A file that is not a log file, or is
+    /// of a later format version, is [`ErrorKind::InvalidData`].
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let data_start = reader.stream_position()?;
+        let mut bytes = Vec::new();
+        let mut needed = PREFIX_BYTES;
+        let (header, length) = loop {
+            if bytes.len() < needed {
+                let filled = bytes.len();
+                bytes.resize(needed, 0);
+                reader.read_exact(&mut bytes[filled..])?;
+            }
+            match LogFileHeader::decode(&bytes) {
+                Ok(decoded) => break decoded,
+                Err(DecodeError::Incomplete { needed: more }) => needed = more,
+                Err(error) => return Err(io::Error::new(ErrorKind::InvalidData, error)),
+            }
+        };
+        let data_start = data_start + length as u64;
+        reader.seek(SeekFrom::Start(data_start))?;
+        Ok(Self {
+            reader,
+            header,
+            data_start,
+            buffer: Vec::new(),
+            base: data_start,
+            start: 0,
+            ended: false,
+            damaged: 0,
+            resyncing: false,
+            torn_tail: None,
+        })
+    }
+
+    pub const fn header(&self) -> &LogFileHeader {
+        &self.header
+    }
+
+    /// Frames skipped because they did not decode as version 1 envelopes.
+    pub const fn damaged(&self) -> u64 {
+        self.damaged
+    }
+
+    /// The file offset of a frame the file ends inside, once reading has
+    /// reached it.
+    pub const fn torn_tail(&self) -> Option<u64> {
+        self.torn_tail
+    }
+
+    /// The next envelope, or `None` at the end of the file.
+    pub fn next_record(&mut self) -> io::Result<Option<DecodedEnvelope<'_>>> {
+        loop {
+            let remaining = &self.buffer[self.start..];
+            if remaining.is_empty() {
+                if self.ended {
+                    return Ok(None);
+                }
+                self.fill(1)?;
+                continue;
+            }
+            let step = match decode_frame(remaining) {
+                Ok(Frame::Envelope(envelope)) => Step::Record(envelope.length),
+                // A version 2 frame, which a log file never holds.
+                Ok(frame) => Step::Skip(frame.length()),
+                Err(DecodeError::Incomplete { needed })
+                    if !self.ended && needed <= MAX_ENVELOPE_BYTES =>
+                {
+                    Step::Fill(needed)
+                }
+                Err(error) => match find_magic(&remaining[1..]) {
+                    Some(found) => Step::Skip(found + 1),
+                    // Keep a partial magic at the end of what is buffered: it
+                    // may start the next frame.
+                    None if !self.ended => {
+                        Step::Resync(remaining.len() - partial_magic_suffix(&remaining[1..]))
+                    }
+                    None if matches!(error, DecodeError::Incomplete { .. }) => Step::Torn,
+                    None => Step::Skip(remaining.len()),
+                },
+            };
+            match step {
+                Step::Record(length) => {
+                    self.resyncing = false;
+                    let at = self.start;
+                    self.start += length;
+                    return decode_envelope(&self.buffer[at..self.start])
+                        .map(Some)
+                        .map_err(|error| io::Error::new(ErrorKind::InvalidData, error));
+                }
+                Step::Fill(needed) => self.fill(needed)?,
+                Step::Skip(count) => {
+                    if !self.resyncing {
+                        self.damaged += 1;
+                    }
+                    self.resyncing = false;
+                    self.start += count;
+                }
+                Step::Resync(count) => {
+                    if !self.resyncing {
+                        self.damaged += 1;
+                    }
+                    self.resyncing = true;
+                    self.start += count;
+                }
+                Step::Torn => {
+                    self.torn_tail = Some(self.base + self.start as u64);
+                    self.start = self.buffer.len();
+                }
+            }
+        }
+    }
+
+    /// Positions the reader at the first envelope whose sequence number is
+    /// at least `sequence`, returning whether there is one.
+    ///
+    /// The search probes the file at offsets between the header and the end,
+    /// so it reads a few frames per halving rather than the whole file. A
+    /// damaged stretch longer than an envelope can hide the frames behind it
+    /// from a probe.
+    pub fn seek_sequence(&mut self, sequence: u64) -> io::Result<bool> {
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        let (mut low, mut high) = (self.data_start, end);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.probe(middle)? {
+                Some((at, found)) if found < sequence => low = at + 1,
+                _ => high = middle,
+            }
+        }
+        let found = self.probe(low)?;
+        let position = found.map_or(end, |(at, _)| at);
+        self.reader.seek(SeekFrom::Start(position))?;
+        self.buffer.clear();
+        self.base = position;
+        self.start = 0;
+        self.ended = false;
+        self.resyncing = false;
+        self.torn_tail = None;
+        Ok(found.is_some())
+    }
+
+    /// The offset and sequence number of the first envelope at or after
+    /// `offset`, looking no further than two envelopes ahead.
+    fn probe(&mut self, offset: u64) -> io::Result<Option<(u64, u64)>> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut window = Vec::new();
+        (&mut self.reader)
+            .take(2 * MAX_ENVELOPE_BYTES as u64)
+            .read_to_end(&mut window)?;
+        let mut at = 0;
+        while let Some(found) = find_magic(&window[at..]) {
+            at += found;
+            if let Ok(envelope) = decode_envelope(&window[at..]) {
+                return Ok(Some((offset + at as u64, envelope.sequence)));
+            }
+            at += 1;
+        }
+        Ok(None)
+    }
+
+    /// Reads until `needed` bytes from `start` are buffered or the file ends.
+    fn fill(&mut self, needed: usize) -> io::Result<()> {
+        self.buffer.drain(..self.start);
+        self.base += self.start as u64;
+        self.start = 0;
+        while !self.ended && self.buffer.len() < needed {
+            let filled = self.buffer.len();
+            self.buffer
+                .resize(filled + READ_BYTES.max(needed - filled), 0);
+            let count = loop {
+                match self.reader.read(&mut self.buffer[filled..]) {
+                    Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                    result => break result?,
+                }
+            };
+            self.buffer.truncate(filled + count);
+            self.ended = count == 0;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The wire as a runtime sink.
+//!
+//! [`Transport::encode`] takes a raw facade event, so whatever calls it has
+//! to decide on its own which fields may leave the guest. [`TransportSink`]
+//! instead receives the runtime's [`ProjectedEvent`] like every other sink:
+//! registered with [`Runtime::add_remote_sink`] it only ever encodes
+//! support-safe fields, and with [`Runtime::add_local_sink`] it may carry
+//! local-only ones, under the same filters, detail levels and delivery
+//! accounting.
+//!
+//! Each envelope carries the number of declared fields the view did not get
+//! and the non-parent links the runtime recorded for the event's context, and
+//! at version 2 the runtime's dispatch timestamp. The worker and test
+//! identity are whatever the platform integration last set on the emitting
+//! thread with [`set_current_worker`] and [`set_current_test`].
+//!
+//! Version 1 of the wire has no record for a span's lifecycle, so span starts
+//! and completions are sent as envelopes of the span's call site; after
+//! [`prefer_dictionary`](TransportSink::prefer_dictionary) they are version 2
+//! span records until the host asks for version 1. No version has a metric
+//! series, so metric flushes are not sent. A pseudonymized field is omitted
+//! and counted, since the wire cannot tell a token from a value.
+
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use logwise::{ContextToken, EventRef, FieldRef, Metadata, ValueRef};
+use logwise_runtime::{EventSink, ProjectedEvent, Runtime, SpanLifecycle};
+
+use crate::wire::{
+    HostStatus, Identity, Record, Transport, WireSpan, WireSpanCompletion, WireTimestamp, host_emit,
+};
+
+/// The largest envelope a [`TransportSink`] encodes. A larger one is dropped
+/// and counted in [`Transport::dropped`].
+pub const MAX_ENVELOPE_BYTES: usize = 64 * 1024;
+
+struct CurrentIdentity {
+    worker: u64,
+    test: Option<String>,
+}
+
+struct Buffers {
+    output: Vec<u8>,
+    scratch: Vec<u8>,
+}
+
+std::thread_local! {
+    static IDENTITY: RefCell<CurrentIdentity> = const {
+        RefCell::new(CurrentIdentity {
+            worker: 0,
+            test: None,
+        })
+    };
+    static BUFFERS: RefCell<Buffers> = const {
+        RefCell::new(Buffers {
+            output: Vec::new(),
+            scratch: Vec::new(),
+        })
+    };
+}
+
+/// The projected fields the wire carries, each with its declaration, and the
+/// `omitted_fields` to encode alongside them.
+///
+/// Pseudonymized fields are left out. The encoder counts every declared field
+/// it was not given, so only what the projection omitted beyond that is
+/// passed on.
+pub(crate) fn wire_fields<'a>(
+    metadata: &'static Metadata,
+    projected: impl IntoIterator<Item = (&'a str, bool, ValueRef<'a>)>,
+    omitted_fields: usize,
+) -> (Vec<Option<FieldRef<'a>>>, u32) {
+    let fields: Vec<_> = projected
+        .into_iter()
+        .filter(|(_, pseudonymized, _)| !pseudonymized)
+        .filter_map(|(name, _, value)| {
+            let declared = metadata
+                .fields
+                .iter()
+                .find(|declared| declared.name == name)?;
+            Some(Some(FieldRef::new(declared, value)))
+        })
+        .collect();
+    let implicit = metadata.fields.len().saturating_sub(fields.len());
+    let omitted = omitted_fields.saturating_sub(implicit);
+    (fields, u32::try_from(omitted).unwrap_or(u32::MAX))
+}
+
+/// Names the worker events from this thread come from. Defaults to 0.
+pub fn set_current_worker(worker: u64) {
+    IDENTITY.with_borrow_mut(|identity| identity.worker = worker);
+}
+
+/// Names the test running on this thread, or clears it.
+pub fn set_current_test(test: Option<&str>) {
+    IDENTITY.with_borrow_mut(|identity| identity.test = test.map(ToString::to_string));
+}
+
+type Deliver = Box<dyn Fn(&[u8]) -> HostStatus + Send + Sync>;
+
+/// An [`EventSink`] that encodes each projected event as a `logwise_v1`
+/// envelope.
+pub struct TransportSink {
+    runtime: &'static Runtime,
+    transport: Transport,
+    max_value_bytes: usize,
+    deliver: Deliver,
+}
+
+impl core::fmt::Debug for TransportSink {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        formatter
+            .debug_struct("TransportSink")
+            .field("max_value_bytes", &self.max_value_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TransportSink {
+    /// Sends envelopes through the `logwise_v1.emit` host import, which
+    /// reports [`HostStatus::Unavailable`] without the `host-abi` feature.
+    /// `runtime` is where context links are looked up.
+    pub fn new(runtime: &'static Runtime, max_value_bytes: usize) -> Self {
+        Self::with_delivery(runtime, max_value_bytes, host_emit)
+    }
+
+    /// Hands envelopes to `deliver` instead of the host import, as an
+    /// in-process host or a test would. Anything but
+    /// [`HostStatus::Accepted`] counts as dropped.
+    pub fn with_delivery(
+        runtime: &'static Runtime,
+        max_value_bytes: usize,
+        deliver: impl Fn(&[u8]) -> HostStatus + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            runtime,
+            transport: Transport::new(max_value_bytes),
+            max_value_bytes,
+            deliver: Box::new(deliver),
+        }
+    }
+
+    /// Speaks the version 2 wire, with its call-site dictionary and span
+    /// records, until the host answers [`HostStatus::VersionMismatch`].
+    #[must_use]
+    pub const fn prefer_dictionary(mut self) -> Self {
+        self.transport = Transport::with_dictionary(self.max_value_bytes);
+        self
+    }
+
+    /// The sequence and loss counters behind this sink's envelopes.
+    pub const fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn encode(&self, event: &ProjectedEvent<'_>, output: &mut [u8], scratch: &mut [u8]) {
+        let metadata = event.metadata;
+        let (fields, omitted) = wire_fields(
+            metadata,
+            event
+                .fields
+                .iter()
+                .map(|field| (field.name, field.pseudonymized, field.value)),
+            event.omitted_fields,
+        );
+        let links = self
+            .runtime
+            .context(event.context)
+            .map(|context| context.links)
+            .unwrap_or_default();
+        let links: &[ContextToken] = &links;
+        let timestamp = WireTimestamp {
+            monotonic: event.timestamp.monotonic,
+            wall: event.timestamp.wall,
+        };
+        let span = event.span.map(|span| WireSpan {
+            token: span.token,
+            timing: span.timing,
+            warning_threshold: span.warning_threshold,
+            completion: match span.lifecycle {
+                SpanLifecycle::Started => None,
+                SpanLifecycle::Completed {
+                    elapsed,
+                    threshold_exceeded,
+                } => Some(WireSpanCompletion {
+                    elapsed,
+                    threshold_exceeded,
+                }),
+            },
+        });
+        let event = EventRef {
+            metadata,
+            context: event.context,
+            fields: &fields,
+            message: event.message,
+        };
+        IDENTITY.with_borrow(|identity| {
+            let record = Record {
+                event,
+                timestamp: Some(timestamp),
+                omitted_fields: omitted,
+                span,
+                links,
+                identity: Identity {
+                    worker: identity.worker,
+                    test: identity.test.as_deref(),
+                },
+            };
+            // Encoding failures are already counted as drops.
+            let _ = self
+                .transport
+                .deliver(record, output, scratch, &self.deliver);
+        });
+    }
+}
+
+impl EventSink for TransportSink {
+    fn emit(&self, event: ProjectedEvent<'_>) {
+        if event.metric.is_some() {
+            return;
+        }
+        let scratch_bytes = self.max_value_bytes.min(u16::MAX as usize);
+        let buffered = BUFFERS.with(|buffers| {
+            // A sink that logs from inside `deliver` reaches here again with
+            // the buffers taken, and falls back to fresh ones.
+            let Ok(mut buffers) = buffers.try_borrow_mut() else {
+                return false;
+            };
+            let Buffers { output, scratch } = &mut *buffers;
+            output.resize(MAX_ENVELOPE_BYTES, 0);
+            if scratch.len() < scratch_bytes {
+                scratch.resize(scratch_bytes, 0);
+            }
+            self.encode(&event, output, &mut scratch[..scratch_bytes]);
+            true
+        });
+        if !buffered {
+            let mut output = std::vec![0; MAX_ENVELOPE_BYTES];
+            let mut scratch = std::vec![0; scratch_bytes];
+            self.encode(&event, &mut output, &mut scratch);
+        }
+    }
+}
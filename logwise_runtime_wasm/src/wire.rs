@@ -13,15 +13,47 @@
 //! written against this format without reading the encoder. `sequence`,
 //! `dropped_before` and `truncated_before` let a reader reconstruct exactly
 //! what it did not receive.
+//!
+//! Version 2 records carry the dispatch [`WireTimestamp`] as a trailer after
+//! the message, announced by a flag bit. Version 1 is frozen, so
+//! [`encode_envelope`] never writes one: a strict version 1 host checks that
+//! it consumed every byte. A reader that has agreed to the trailer, such as a
+//! log file's, gets it from [`encode_timestamped_envelope`] or a
+//! [`Transport::with_timestamps`].
+//!
+//! Version 1 repeats every schema string in every envelope.
+//! [`DICTIONARY_ABI_VERSION`] instead sends each call site's [`Metadata`] once,
+//! as a site record keyed by a stream-local ID, and then refers to it; it also
+//! has records for a span's start and end. A [`Transport`] built with
+//! [`with_dictionary`](Transport::with_dictionary) speaks version 2 until the
+//! host answers [`HostStatus::VersionMismatch`], then re-sends that event as
+//! version 1 and stays there.
 
 use core::fmt::{self, Write};
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicU16, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
 
-use logwise::{ContextToken, EventRef, Privacy, ValueRef};
+use logwise::{
+    ContextToken, EventRef, FieldMetadata, FieldRef, Metadata, Privacy, SpanTiming, SpanToken,
+    ValueRef,
+};
 
+/// The baseline version every host reads: self-describing envelopes.
 pub const ABI_VERSION: u16 = 1;
-const MAGIC: &[u8; 4] = b"LW1\0";
-const FLAG_TRUNCATED: u16 = 1;
+/// The version with a call-site dictionary and span records.
+pub const DICTIONARY_ABI_VERSION: u16 = 2;
+pub(crate) const MAGIC: &[u8; 4] = b"LW1\0";
+pub(crate) const FLAG_TRUNCATED: u16 = 1;
+pub(crate) const FLAG_TIMESTAMPED: u16 = 1 << 1;
+
+pub(crate) const RECORD_SITE: u8 = 1;
+pub(crate) const RECORD_EVENT: u8 = 2;
+pub(crate) const RECORD_SPAN_START: u8 = 3;
+pub(crate) const RECORD_SPAN_END: u8 = 4;
+
+/// How many call sites a [`Transport`] remembers having sent. Past this, a
+/// site is defined again before each event that uses it, under site ID 0.
+const SITE_SLOTS: usize = 512;
 
 /// Host/test identity attached by the platform integration above the facade.
 #[derive(Clone, Copy, Debug, Default)]
@@ -30,10 +62,40 @@ pub struct Identity<'a> {
     pub test: Option<&'a str>,
 }
 
+/// When the guest runtime dispatched an event. `monotonic` is measured from a
+/// guest-local origin and only orders events from the same guest; `wall` is
+/// time since the Unix epoch. Both are encoded as `u64` nanoseconds,
+/// saturating.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WireTimestamp {
+    pub monotonic: Duration,
+    pub wall: Duration,
+}
+
+/// The span lifecycle a version 2 span record carries.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WireSpan {
+    pub token: SpanToken,
+    pub timing: SpanTiming,
+    pub warning_threshold: Option<Duration>,
+    /// `None` for a span start.
+    pub completion: Option<WireSpanCompletion>,
+}
+
+/// How a completed span went.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WireSpanCompletion {
+    pub elapsed: Duration,
+    pub threshold_exceeded: bool,
+}
+
 /// A projected event plus transport-owned ordering and loss metadata.
 #[derive(Clone, Copy)]
 pub struct Envelope<'a> {
     pub event: EventRef<'a>,
+    /// Encoded at version 2, and at version 1 only where the reader has
+    /// agreed to the trailer.
+    pub timestamp: Option<WireTimestamp>,
     pub sequence: u64,
     pub dropped_before: u64,
     pub truncated_before: u64,
@@ -73,65 +135,172 @@ pub struct Transport {
     dropped: AtomicU64,
     truncated: AtomicU64,
     max_value_bytes: usize,
+    version: AtomicU16,
+    /// Whether version 1 envelopes carry the timestamp trailer.
+    timestamps: bool,
+    /// The address of the [`Metadata`] behind each site ID, or 0 if unused.
+    sites: [AtomicUsize; SITE_SLOTS],
+    /// One more than the worker the host last accepted each site for, or 0.
+    announced: [AtomicU64; SITE_SLOTS],
+}
+
+/// One event on its way to the encoder, before the transport numbers it.
+#[derive(Clone, Copy)]
+pub(crate) struct Record<'a> {
+    pub(crate) event: EventRef<'a>,
+    pub(crate) timestamp: Option<WireTimestamp>,
+    pub(crate) omitted_fields: u32,
+    pub(crate) span: Option<WireSpan>,
+    pub(crate) links: &'a [ContextToken],
+    pub(crate) identity: Identity<'a>,
+}
+
+/// A site record written ahead of an event, to remember once the host has it.
+#[derive(Clone, Copy)]
+struct Announcement {
+    slot: usize,
+    worker: u64,
 }
 
 impl Transport {
     pub const fn new(max_value_bytes: usize) -> Self {
+        Self::with_version(max_value_bytes, ABI_VERSION)
+    }
+
+    /// A transport that speaks [`DICTIONARY_ABI_VERSION`] until the host
+    /// reports [`HostStatus::VersionMismatch`].
+    pub const fn with_dictionary(max_value_bytes: usize) -> Self {
+        Self::with_version(max_value_bytes, DICTIONARY_ABI_VERSION)
+    }
+
+    const fn with_version(max_value_bytes: usize, version: u16) -> Self {
         Self {
             next_sequence: AtomicU64::new(0),
             dropped: AtomicU64::new(0),
             truncated: AtomicU64::new(0),
             max_value_bytes,
+            version: AtomicU16::new(version),
+            timestamps: false,
+            sites: [const { AtomicUsize::new(0) }; SITE_SLOTS],
+            announced: [const { AtomicU64::new(0) }; SITE_SLOTS],
         }
     }
 
+    /// Writes the timestamp trailer at version 1 as well, for a host that
+    /// has said it reads one. Version 2 always carries it.
+    #[must_use]
+    pub const fn with_timestamps(mut self) -> Self {
+        self.timestamps = true;
+        self
+    }
+
+    /// The version the next envelope is encoded in.
+    pub fn version(&self) -> u16 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Encodes one event in the current [`version`](Self::version).
+    ///
+    /// At version 2 the bytes start with a site record when this call site
+    /// has not been sent for `identity.worker` before, and the site then
+    /// counts as sent. A caller that does not deliver the bytes should call
+    /// [`resend_sites`](Self::resend_sites).
     pub fn encode<'buffer>(
         &self,
         event: EventRef<'_>,
+        timestamp: Option<WireTimestamp>,
         links: &[ContextToken],
         identity: Identity<'_>,
         output: &'buffer mut [u8],
         scratch: &mut [u8],
     ) -> Result<EncodedEnvelope<'buffer>, EncodeError> {
-        let sequence = self.next_sequence.fetch_add(1, Ordering::AcqRel) + 1;
-        let envelope = Envelope {
+        let record = Record {
             event,
-            sequence,
-            dropped_before: self.dropped.load(Ordering::Acquire),
-            truncated_before: self.truncated.load(Ordering::Acquire),
+            timestamp,
             omitted_fields: 0,
+            span: None,
             links,
             identity,
         };
-        match encode_envelope(envelope, self.max_value_bytes, output, scratch) {
-            Ok(encoded) => {
-                self.truncated
-                    .fetch_add(encoded.truncated_values as u64, Ordering::Relaxed);
-                Ok(encoded)
-            }
-            Err(error) => {
-                self.dropped.fetch_add(1, Ordering::Relaxed);
-                Err(error)
-            }
-        }
+        self.encode_record(record, output, scratch)
+    }
+
+    /// [`encode`](Self::encode) for a [`Record`], counting it as delivered.
+    pub(crate) fn encode_record<'buffer>(
+        &self,
+        record: Record<'_>,
+        output: &'buffer mut [u8],
+        scratch: &mut [u8],
+    ) -> Result<EncodedEnvelope<'buffer>, EncodeError> {
+        let sequence = self.next_sequence();
+        let (encoded, announcement) =
+            self.encode_at(sequence, self.version(), record, output, scratch)?;
+        self.settle(HostStatus::Accepted, encoded.truncated_values, announcement);
+        Ok(encoded)
+    }
+
+    /// Counts an encoded envelope that never reached its reader.
+    #[cfg(feature = "log-file")]
+    pub(crate) fn record_drop(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
     }
 
     pub fn emit(
         &self,
         event: EventRef<'_>,
+        timestamp: Option<WireTimestamp>,
         links: &[ContextToken],
         identity: Identity<'_>,
         output: &mut [u8],
         scratch: &mut [u8],
     ) -> Result<HostStatus, EncodeError> {
-        let encoded = self.encode(event, links, identity, output, scratch)?;
-        let status = host_emit(encoded.bytes);
-        if status != HostStatus::Accepted {
-            self.dropped.fetch_add(1, Ordering::Relaxed);
+        let record = Record {
+            event,
+            timestamp,
+            omitted_fields: 0,
+            span: None,
+            links,
+            identity,
+        };
+        self.deliver(record, output, scratch, host_emit)
+    }
+
+    /// Encodes `record` and hands it to `deliver`.
+    ///
+    /// A version 2 envelope refused with [`HostStatus::VersionMismatch`] is
+    /// encoded again as version 1 under the same sequence number and
+    /// delivered once more, and the transport stays at version 1. Anything
+    /// but [`HostStatus::Accepted`] from the final delivery counts as dropped.
+    pub(crate) fn deliver(
+        &self,
+        record: Record<'_>,
+        output: &mut [u8],
+        scratch: &mut [u8],
+        deliver: impl Fn(&[u8]) -> HostStatus,
+    ) -> Result<HostStatus, EncodeError> {
+        let sequence = self.next_sequence();
+        let version = self.version();
+        let (encoded, announcement) = self.encode_at(sequence, version, record, output, scratch)?;
+        let status = deliver(encoded.bytes);
+        if status != HostStatus::VersionMismatch || version == ABI_VERSION {
+            self.settle(status, encoded.truncated_values, announcement);
+            return Ok(status);
         }
+        self.version.store(ABI_VERSION, Ordering::Release);
+        let (encoded, _) = self.encode_at(sequence, ABI_VERSION, record, output, scratch)?;
+        let status = deliver(encoded.bytes);
+        self.settle(status, encoded.truncated_values, None);
         Ok(status)
     }
 
+    /// Forgets which call sites have been sent, so each is defined again
+    /// before its next use, as a host that restarted its reader needs.
+    pub fn resend_sites(&self) {
+        for announced in &self.announced {
+            announced.store(0, Ordering::Release);
+        }
+    }
+
     pub fn dropped(&self) -> u64 {
         self.dropped.load(Ordering::Relaxed)
     }
@@ -139,116 +308,422 @@ impl Transport {
     pub fn truncated(&self) -> u64 {
         self.truncated.load(Ordering::Relaxed)
     }
+
+    fn next_sequence(&self) -> u64 {
+        self.next_sequence.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    /// Counts what one delivered envelope truncated, and either the site it
+    /// defined or, if the host did not accept it, one drop.
+    fn settle(&self, status: HostStatus, truncated_values: u32, announced: Option<Announcement>) {
+        self.truncated
+            .fetch_add(truncated_values as u64, Ordering::Relaxed);
+        if status != HostStatus::Accepted {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        } else if let Some(Announcement { slot, worker }) = announced {
+            self.announced[slot].store(worker.wrapping_add(1), Ordering::Release);
+        }
+    }
+
+    fn encode_at<'buffer>(
+        &self,
+        sequence: u64,
+        version: u16,
+        record: Record<'_>,
+        output: &'buffer mut [u8],
+        scratch: &mut [u8],
+    ) -> Result<(EncodedEnvelope<'buffer>, Option<Announcement>), EncodeError> {
+        let envelope = Envelope {
+            event: record.event,
+            timestamp: record.timestamp,
+            sequence,
+            dropped_before: self.dropped.load(Ordering::Acquire),
+            truncated_before: self.truncated.load(Ordering::Acquire),
+            omitted_fields: record.omitted_fields,
+            links: record.links,
+            identity: record.identity,
+        };
+        let encoded = if version == ABI_VERSION {
+            let timestamp = envelope.timestamp.filter(|_| self.timestamps);
+            encode_version_1(envelope, timestamp, self.max_value_bytes, output, scratch)
+                .map(|encoded| (encoded, None))
+        } else {
+            self.encode_with_site(envelope, record.span, output, scratch)
+        };
+        if encoded.is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        encoded
+    }
+
+    /// A version 2 event record, preceded by its site record unless the host
+    /// already has that for this worker.
+    fn encode_with_site<'buffer>(
+        &self,
+        envelope: Envelope<'_>,
+        span: Option<WireSpan>,
+        output: &'buffer mut [u8],
+        scratch: &mut [u8],
+    ) -> Result<(EncodedEnvelope<'buffer>, Option<Announcement>), EncodeError> {
+        let worker = envelope.identity.worker;
+        let marker = worker.wrapping_add(1);
+        let slot = self.slot(envelope.event.metadata);
+        let site = slot.map_or(0, |slot| slot as u32 + 1);
+        let (defined, announcement) = match slot {
+            Some(slot) if marker != 0 && self.announced[slot].load(Ordering::Acquire) == marker => {
+                (0, None)
+            }
+            slot => {
+                let defined = encode_site(worker, site, envelope.event.metadata, output)?.len();
+                (defined, slot.map(|slot| Announcement { slot, worker }))
+            }
+        };
+        let event = encode_event(
+            envelope,
+            site,
+            span,
+            self.max_value_bytes,
+            &mut output[defined..],
+            scratch,
+        )?;
+        let length = defined + event.bytes.len();
+        let truncated_values = event.truncated_values;
+        Ok((
+            EncodedEnvelope {
+                bytes: &output[..length],
+                truncated_values,
+            },
+            announcement,
+        ))
+    }
+
+    /// The table slot for `metadata`, claiming a free one on first use.
+    fn slot(&self, metadata: &'static Metadata) -> Option<usize> {
+        let address = core::ptr::from_ref(metadata) as usize;
+        let hash = (address as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32;
+        let start = hash as usize % SITE_SLOTS;
+        (0..SITE_SLOTS)
+            .map(|probe| (start + probe) % SITE_SLOTS)
+            .find(|slot| {
+                match self.sites[*slot].compare_exchange(
+                    0,
+                    address,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => true,
+                    Err(current) => current == address,
+                }
+            })
+    }
 }
 
 /// Encodes the stable `logwise_v1` golden wire format without allocation.
 /// Secret fields are omitted defensively even if a caller bypasses runtime
 /// projection. Dynamic strings are truncated at UTF-8 boundaries.
+///
+/// Version 1 has no timestamp, so `envelope.timestamp` is not encoded.
 pub fn encode_envelope<'buffer>(
     envelope: Envelope<'_>,
     max_value_bytes: usize,
     output: &'buffer mut [u8],
     scratch: &mut [u8],
 ) -> Result<EncodedEnvelope<'buffer>, EncodeError> {
-    let mut encoder = Encoder {
+    encode_version_1(envelope, None, max_value_bytes, output, scratch)
+}
+
+/// [`encode_envelope`] followed by the timestamp trailer, if `envelope` has
+/// a timestamp. Only for a reader that has agreed to the trailer: a strict
+/// version 1 host rejects the extra bytes.
+pub fn encode_timestamped_envelope<'buffer>(
+    envelope: Envelope<'_>,
+    max_value_bytes: usize,
+    output: &'buffer mut [u8],
+    scratch: &mut [u8],
+) -> Result<EncodedEnvelope<'buffer>, EncodeError> {
+    encode_version_1(
+        envelope,
+        envelope.timestamp,
+        max_value_bytes,
         output,
-        position: 0,
-        max_value_bytes: max_value_bytes.min(u16::MAX as usize),
         scratch,
-        truncated_values: 0,
-    };
-    encoder.bytes(MAGIC)?;
-    encoder.u16(ABI_VERSION)?;
-    let flags_at = encoder.reserve_u16()?;
-    let length_at = encoder.reserve_u32()?;
+    )
+}
+
+fn encode_version_1<'buffer>(
+    envelope: Envelope<'_>,
+    timestamp: Option<WireTimestamp>,
+    max_value_bytes: usize,
+    output: &'buffer mut [u8],
+    scratch: &mut [u8],
+) -> Result<EncodedEnvelope<'buffer>, EncodeError> {
+    let mut encoder = Encoder::new(output, max_value_bytes, scratch);
+    let header = encoder.header(ABI_VERSION)?;
     encoder.u64(envelope.sequence)?;
     encoder.u64(envelope.dropped_before)?;
     encoder.u64(envelope.truncated_before)?;
     encoder.u64(envelope.identity.worker)?;
-    let (context, context_flags) = envelope.event.context.into_parts();
-    encoder.u64(context)?;
-    encoder.u64(context_flags)?;
-    let link_count = u16::try_from(envelope.links.len()).map_err(|_| EncodeError::TooManyLinks)?;
-    encoder.u16(link_count)?;
-    for link in envelope.links {
-        let (id, flags) = link.into_parts();
-        encoder.u64(id)?;
-        encoder.u64(flags)?;
-    }
+    encoder.context(envelope.event.context)?;
+    encoder.links(envelope.links)?;
 
     let metadata = envelope.event.metadata;
-    encoder.u8(metadata.severity as u8)?;
-    encoder.u8(metadata.class as u8)?;
-    encoder.u8(metadata.kind as u8)?;
-    encoder.schema(metadata.event_name)?;
-    encoder.schema(metadata.package)?;
-    encoder.schema(metadata.target)?;
-    encoder.schema(metadata.module)?;
-    encoder.optional_schema(metadata.domain.map(|domain| domain.name))?;
+    encoder.schema_head(metadata)?;
     encoder.optional_dynamic(envelope.identity.test)?;
-    match metadata.location {
-        Some(location) => {
-            encoder.u8(1)?;
-            encoder.schema(location.file)?;
-            encoder.u32(location.line)?;
-            encoder.u32(location.column)?;
-        }
-        None => encoder.u8(0)?,
-    }
-
-    let retained_fields = envelope
-        .event
-        .fields
-        .iter()
-        .flatten()
-        .filter(|field| field.metadata.privacy != Privacy::Secret)
-        .count();
-    let field_count = u16::try_from(retained_fields).map_err(|_| EncodeError::TooManyFields)?;
-    let implicit_omissions = metadata.fields.len().saturating_sub(retained_fields);
-    let implicit_omissions = u32::try_from(implicit_omissions).unwrap_or(u32::MAX);
-    encoder.u32(envelope.omitted_fields.saturating_add(implicit_omissions))?;
+    encoder.location(metadata)?;
+
+    let retained_fields = || {
+        envelope
+            .event
+            .fields
+            .iter()
+            .flatten()
+            .filter(|field| field.metadata.privacy != Privacy::Secret)
+    };
+    encoder.omitted(envelope, retained_fields().count())?;
+    let field_count =
+        u16::try_from(retained_fields().count()).map_err(|_| EncodeError::TooManyFields)?;
     encoder.u16(field_count)?;
-    for field in envelope
-        .event
-        .fields
-        .iter()
-        .flatten()
-        .filter(|field| field.metadata.privacy != Privacy::Secret)
-    {
+    for field in retained_fields() {
         encoder.schema(field.metadata.name)?;
         encoder.u8(field.metadata.privacy as u8)?;
         encoder.u8(field.metadata.detail as u8)?;
         encoder.value(field.value)?;
     }
-    match envelope.event.message {
-        Some(message) => {
-            encoder.u8(1)?;
-            encoder.formatted(message)?;
+    encoder.message(envelope.event.message)?;
+    encoder.finish(header, timestamp)
+}
+
+/// Encodes the version 2 site record that defines call site `site` for
+/// `worker`: the schema an event record leaves out, including the name,
+/// privacy and detail of every declared field in declaration order. Event
+/// records refer to fields by that position.
+pub fn encode_site<'buffer>(
+    worker: u64,
+    site: u32,
+    metadata: &Metadata,
+    output: &'buffer mut [u8],
+) -> Result<&'buffer [u8], EncodeError> {
+    let mut encoder = Encoder::new(output, 0, &mut []);
+    let header = encoder.header(DICTIONARY_ABI_VERSION)?;
+    encoder.u8(RECORD_SITE)?;
+    encoder.u64(worker)?;
+    encoder.u32(site)?;
+    encoder.schema_head(metadata)?;
+    encoder.location(metadata)?;
+    let field_count =
+        u16::try_from(metadata.fields.len()).map_err(|_| EncodeError::TooManyFields)?;
+    encoder.u16(field_count)?;
+    for field in metadata.fields {
+        encoder.schema(field.name)?;
+        encoder.u8(field.privacy as u8)?;
+        encoder.u8(field.detail as u8)?;
+    }
+    encoder.finish(header, None).map(|encoded| encoded.bytes)
+}
+
+/// Encodes a version 2 event record that refers to call site `site` instead
+/// of spelling out its schema, or a span record when `span` is given. The
+/// reader must already have, or be sent first, the [`encode_site`] record
+/// for the same worker. Secret fields are omitted as in [`encode_envelope`].
+pub fn encode_event<'buffer>(
+    envelope: Envelope<'_>,
+    site: u32,
+    span: Option<WireSpan>,
+    max_value_bytes: usize,
+    output: &'buffer mut [u8],
+    scratch: &mut [u8],
+) -> Result<EncodedEnvelope<'buffer>, EncodeError> {
+    let mut encoder = Encoder::new(output, max_value_bytes, scratch);
+    let header = encoder.header(DICTIONARY_ABI_VERSION)?;
+    encoder.u8(match span {
+        None => RECORD_EVENT,
+        Some(WireSpan {
+            completion: None, ..
+        }) => RECORD_SPAN_START,
+        Some(_) => RECORD_SPAN_END,
+    })?;
+    encoder.u64(envelope.sequence)?;
+    encoder.u64(envelope.dropped_before)?;
+    encoder.u64(envelope.truncated_before)?;
+    encoder.u64(envelope.identity.worker)?;
+    encoder.u32(site)?;
+    encoder.context(envelope.event.context)?;
+    encoder.links(envelope.links)?;
+    encoder.optional_dynamic(envelope.identity.test)?;
+
+    let declared = envelope.event.metadata.fields;
+    let retained_fields = || {
+        envelope
+            .event
+            .fields
+            .iter()
+            .flatten()
+            .filter(|field| field.metadata.privacy != Privacy::Secret)
+            .filter_map(|field| Some((field_index(declared, field)?, field.value)))
+    };
+    encoder.omitted(envelope, retained_fields().count())?;
+    let field_count =
+        u16::try_from(retained_fields().count()).map_err(|_| EncodeError::TooManyFields)?;
+    encoder.u16(field_count)?;
+    for (index, value) in retained_fields() {
+        encoder.u16(u16::try_from(index).map_err(|_| EncodeError::TooManyFields)?)?;
+        encoder.value(value)?;
+    }
+    encoder.message(envelope.event.message)?;
+    if let Some(span) = span {
+        let (token, flags) = span.token.into_parts();
+        encoder.u64(token)?;
+        encoder.u64(flags)?;
+        encoder.u8(span.timing as u8)?;
+        match span.warning_threshold {
+            Some(threshold) => {
+                encoder.u8(1)?;
+                encoder.duration(threshold)?;
+            }
+            None => encoder.u8(0)?,
+        }
+        if let Some(completion) = span.completion {
+            encoder.duration(completion.elapsed)?;
+            encoder.u8(u8::from(completion.threshold_exceeded))?;
         }
-        None => encoder.u8(0)?,
-    }
-
-    let flags = u16::from(encoder.truncated_values != 0) * FLAG_TRUNCATED;
-    encoder.patch_u16(flags_at, flags);
-    let length = u32::try_from(encoder.position).map_err(|_| EncodeError::BufferTooSmall)?;
-    encoder.patch_u32(length_at, length);
-    let position = encoder.position;
-    let truncated_values = encoder.truncated_values;
-    Ok(EncodedEnvelope {
-        bytes: &output[..position],
-        truncated_values,
-    })
+    }
+    encoder.finish(header, envelope.timestamp)
 }
 
-struct Encoder<'a> {
+/// Where `field` was declared, by identity and failing that by name.
+fn field_index(declared: &[FieldMetadata], field: &FieldRef<'_>) -> Option<usize> {
+    declared
+        .iter()
+        .position(|candidate| core::ptr::eq(candidate, field.metadata))
+        .or_else(|| {
+            declared
+                .iter()
+                .position(|candidate| candidate.name == field.metadata.name)
+        })
+}
+
+/// Where the flags and length of a frame under construction go.
+struct Header {
+    flags_at: usize,
+    length_at: usize,
+}
+
+struct Encoder<'a, 's> {
     output: &'a mut [u8],
     position: usize,
     max_value_bytes: usize,
-    scratch: &'a mut [u8],
+    scratch: &'s mut [u8],
     truncated_values: u32,
 }
 
-impl Encoder<'_> {
+impl<'a, 's> Encoder<'a, 's> {
+    fn new(output: &'a mut [u8], max_value_bytes: usize, scratch: &'s mut [u8]) -> Self {
+        Self {
+            output,
+            position: 0,
+            max_value_bytes: max_value_bytes.min(u16::MAX as usize),
+            scratch,
+            truncated_values: 0,
+        }
+    }
+
+    fn header(&mut self, version: u16) -> Result<Header, EncodeError> {
+        self.bytes(MAGIC)?;
+        self.u16(version)?;
+        Ok(Header {
+            flags_at: self.reserve_u16()?,
+            length_at: self.reserve_u32()?,
+        })
+    }
+
+    /// Appends the timestamp trailer, patches the header and returns the
+    /// frame.
+    fn finish(
+        mut self,
+        header: Header,
+        timestamp: Option<WireTimestamp>,
+    ) -> Result<EncodedEnvelope<'a>, EncodeError> {
+        if let Some(timestamp) = timestamp {
+            self.duration(timestamp.monotonic)?;
+            self.duration(timestamp.wall)?;
+        }
+        let mut flags = 0;
+        if self.truncated_values != 0 {
+            flags |= FLAG_TRUNCATED;
+        }
+        if timestamp.is_some() {
+            flags |= FLAG_TIMESTAMPED;
+        }
+        self.patch_u16(header.flags_at, flags);
+        let length = u32::try_from(self.position).map_err(|_| EncodeError::BufferTooSmall)?;
+        self.patch_u32(header.length_at, length);
+        Ok(EncodedEnvelope {
+            bytes: &self.output[..self.position],
+            truncated_values: self.truncated_values,
+        })
+    }
+
+    fn context(&mut self, context: ContextToken) -> Result<(), EncodeError> {
+        let (id, flags) = context.into_parts();
+        self.u64(id)?;
+        self.u64(flags)
+    }
+
+    fn links(&mut self, links: &[ContextToken]) -> Result<(), EncodeError> {
+        let count = u16::try_from(links.len()).map_err(|_| EncodeError::TooManyLinks)?;
+        self.u16(count)?;
+        for link in links {
+            self.context(*link)?;
+        }
+        Ok(())
+    }
+
+    /// Severity, class, kind, the four schema names and the domain.
+    fn schema_head(&mut self, metadata: &Metadata) -> Result<(), EncodeError> {
+        self.u8(metadata.severity as u8)?;
+        self.u8(metadata.class as u8)?;
+        self.u8(metadata.kind as u8)?;
+        self.schema(metadata.event_name)?;
+        self.schema(metadata.package)?;
+        self.schema(metadata.target)?;
+        self.schema(metadata.module)?;
+        self.optional_schema(metadata.domain.map(|domain| domain.name))
+    }
+
+    fn location(&mut self, metadata: &Metadata) -> Result<(), EncodeError> {
+        match metadata.location {
+            Some(location) => {
+                self.u8(1)?;
+                self.schema(location.file)?;
+                self.u32(location.line)?;
+                self.u32(location.column)
+            }
+            None => self.u8(0),
+        }
+    }
+
+    /// The caller's omissions plus every declared field not retained.
+    fn omitted(&mut self, envelope: Envelope<'_>, retained: usize) -> Result<(), EncodeError> {
+        let implicit = envelope
+            .event
+            .metadata
+            .fields
+            .len()
+            .saturating_sub(retained);
+        let implicit = u32::try_from(implicit).unwrap_or(u32::MAX);
+        self.u32(envelope.omitted_fields.saturating_add(implicit))
+    }
+
+    fn message(&mut self, message: Option<fmt::Arguments<'_>>) -> Result<(), EncodeError> {
+        match message {
+            Some(message) => {
+                self.u8(1)?;
+                self.formatted(message)
+            }
+            None => self.u8(0),
+        }
+    }
+
     fn reserve(&mut self, count: usize) -> Result<usize, EncodeError> {
         let start = self.position;
         self.position = self
@@ -281,6 +756,10 @@ impl Encoder<'_> {
         self.bytes(&value.to_le_bytes())
     }
 
+    fn duration(&mut self, value: Duration) -> Result<(), EncodeError> {
+        self.u64(u64::try_from(value.as_nanos()).unwrap_or(u64::MAX))
+    }
+
     fn reserve_u16(&mut self) -> Result<usize, EncodeError> {
         let position = self.reserve(2)?;
         self.patch_u16(position, 0);
@@ -426,7 +905,7 @@ impl Write for ScratchWriter<'_> {
 
 #[cfg(all(target_arch = "wasm32", feature = "host-abi"))]
 #[allow(unsafe_code)]
-fn host_emit(bytes: &[u8]) -> HostStatus {
+pub(crate) fn host_emit(bytes: &[u8]) -> HostStatus {
     #[link(wasm_import_module = "logwise_v1")]
     unsafe extern "C" {
         #[link_name = "emit"]
@@ -440,7 +919,7 @@ fn host_emit(bytes: &[u8]) -> HostStatus {
 }
 
 #[cfg(not(all(target_arch = "wasm32", feature = "host-abi")))]
-fn host_emit(_bytes: &[u8]) -> HostStatus {
+pub(crate) fn host_emit(_bytes: &[u8]) -> HostStatus {
     HostStatus::Unavailable
 }
 
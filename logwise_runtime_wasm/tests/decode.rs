@@ -0,0 +1,798 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+// The decoder is exercised natively; the guest only ever encodes.
+#![cfg(not(target_arch = "wasm32"))]
+
+use core::fmt;
+use core::time::Duration;
+use logwise::{
+    Class, ContextToken, Detail, Domain, EventRef, FieldMetadata, FieldRef, Kind, Location,
+    Metadata, Privacy, Severity, SpanTiming, SpanToken, ValueRef,
+};
+
+use logwise_runtime_wasm::{
+    DecodeError, DecodedEnvelope, Envelope, Frame, FrameError, Frames, Identity, WireLocation,
+    WireSpan, WireSpanCompletion, WireTimestamp, WireValue, decode_envelope, decode_frame,
+    encode_event, encode_site, encode_timestamped_envelope,
+};
+
+/// The golden vector's frame, decoded rather than walked by hand.
+#[test]
+fn decodes_the_golden_vector() {
+    let hex = include_str!("../golden/logwise_v1_event.hex").trim();
+    let bytes = unhex(hex);
+    let envelope = decode_envelope(&bytes).unwrap();
+    assert_eq!(envelope.length, bytes.len());
+    assert!(envelope.truncated());
+    assert_eq!(
+        (
+            envelope.sequence,
+            envelope.dropped_before,
+            envelope.truncated_before,
+            envelope.worker
+        ),
+        (42, 3, 9, 7)
+    );
+    assert_eq!(envelope.context.into_parts(), (11, 12));
+    let links = envelope
+        .links()
+        .map(ContextToken::into_parts)
+        .collect::<Vec<_>>();
+    assert_eq!(links, [(21, 22)]);
+    assert_eq!(
+        (envelope.severity, envelope.class, envelope.kind),
+        (Severity::Warn, Class::Forensic, Kind::Event)
+    );
+    assert_eq!(
+        [
+            envelope.event_name,
+            envelope.package,
+            envelope.target,
+            envelope.module
+        ],
+        ["golden.event", "fixture", "guest", "golden"]
+    );
+    assert_eq!((envelope.domain, envelope.test), (None, Some("case")));
+    assert_eq!(
+        envelope.location,
+        Some(WireLocation {
+            file: "golden.rs",
+            line: 17,
+            column: 4
+        })
+    );
+    assert_eq!(envelope.omitted_fields, 3);
+    assert_eq!(envelope.fields().len(), 3);
+    assert_eq!(
+        envelope.field("active").unwrap().value,
+        WireValue::Bool(true)
+    );
+    let count = envelope.field("count").unwrap();
+    assert_eq!(
+        (count.privacy, count.detail, count.value.as_u64()),
+        (Privacy::LocalOnly, Detail::Detail, Some(99))
+    );
+    assert_eq!(envelope.field("label").unwrap().value.as_str(), Some("hél"));
+    assert!(envelope.field("secret").is_none());
+    assert_eq!(envelope.message, None);
+    assert_eq!(envelope.timestamp, None);
+
+    // Each kind of damage is reported where it happened.
+    let mut damaged = bytes.clone();
+    damaged[0] = b'X';
+    assert_eq!(
+        decode_envelope(&damaged).unwrap_err(),
+        DecodeError::BadMagic
+    );
+    let mut damaged = bytes.clone();
+    damaged[4] = 3;
+    assert_eq!(
+        decode_envelope(&damaged).unwrap_err(),
+        DecodeError::UnsupportedVersion(3)
+    );
+    assert_eq!(
+        decode_envelope(&bytes[..40]).unwrap_err(),
+        DecodeError::Incomplete {
+            needed: bytes.len()
+        }
+    );
+    assert_eq!(
+        decode_envelope(&bytes[..7]).unwrap_err(),
+        DecodeError::Incomplete { needed: 12 }
+    );
+    let severity_at = 12 + 6 * 8 + 2 + 16;
+    let mut damaged = bytes.clone();
+    damaged[severity_at] = 9;
+    assert_eq!(
+        decode_envelope(&damaged).unwrap_err(),
+        DecodeError::InvalidDiscriminant {
+            offset: severity_at,
+            value: 9
+        }
+    );
+    let mut damaged = bytes.clone();
+    damaged[8..12].copy_from_slice(&4_u32.to_le_bytes());
+    assert_eq!(
+        decode_envelope(&damaged).unwrap_err(),
+        DecodeError::BadLength { declared: 4 }
+    );
+    let mut damaged = bytes.clone();
+    damaged.push(0);
+    let declared = u32::try_from(damaged.len()).unwrap();
+    damaged[8..12].copy_from_slice(&declared.to_le_bytes());
+    assert_eq!(
+        decode_envelope(&damaged).unwrap_err(),
+        DecodeError::TrailingBytes {
+            offset: bytes.len()
+        }
+    );
+    // An unknown flag announces a trailer this reader may skip.
+    damaged[6] |= 0x80;
+    let envelope = decode_envelope(&damaged).unwrap();
+    assert_eq!(envelope.length, damaged.len());
+    assert_eq!(envelope.flags, 0x81);
+}
+
+#[test]
+fn random_envelopes_round_trip() {
+    let mut random = SplitMix(0x5eed_1095_0000_0001);
+    let mut output = vec![0_u8; 1 << 16];
+    for _ in 0..2_000 {
+        let case = Case::random(&mut random);
+        let encoded = match &case.message {
+            Some(text) => case.encode(Some(format_args!("{text}")), &mut output),
+            None => case.encode(None, &mut output),
+        };
+        let decoded = decode_envelope(&encoded).unwrap();
+        assert_eq!(decoded.length, encoded.len());
+        assert_eq!(decoded.span, None);
+        case.check(&decoded);
+
+        let (site, event) = match &case.message {
+            Some(text) => case.encode_v2(Some(format_args!("{text}")), &mut output),
+            None => case.encode_v2(None, &mut output),
+        };
+        let Ok(Frame::Site(site)) = decode_frame(&site) else {
+            panic!("a site record");
+        };
+        assert_eq!(site.field_count(), case.metadata.fields.len());
+        let Ok(Frame::Event(record)) = decode_frame(&event) else {
+            panic!("an event record");
+        };
+        let decoded = record.resolve(&site).unwrap();
+        assert_eq!(decoded.length, event.len());
+        assert_eq!(decoded.span, case.span);
+        case.check(&decoded);
+    }
+}
+
+#[test]
+fn dictionary_records_resolve_only_against_their_site() {
+    let mut random = SplitMix(0xd1c7);
+    let mut output = vec![0_u8; 1 << 16];
+    let case = std::iter::repeat_with(|| Case::random(&mut random))
+        .find(|case| {
+            case.metadata
+                .fields
+                .iter()
+                .zip(&case.values)
+                .any(|(field, value)| value.is_some() && field.privacy != Privacy::Secret)
+        })
+        .unwrap();
+    let (site_bytes, event_bytes) = case.encode_v2(None, &mut output);
+    let Ok(Frame::Site(site)) = decode_frame(&site_bytes) else {
+        panic!("a site record");
+    };
+    let Ok(Frame::Event(event)) = decode_frame(&event_bytes) else {
+        panic!("an event record");
+    };
+    assert!(event.resolve(&site).is_ok());
+    assert_eq!(
+        decode_envelope(&site_bytes).unwrap_err(),
+        DecodeError::DictionaryRecord { record: 1 }
+    );
+    assert_eq!(
+        decode_envelope(&event_bytes).unwrap_err(),
+        DecodeError::DictionaryRecord {
+            record: [2, 3, 4][case
+                .span
+                .map_or(0, |span| 1 + usize::from(span.completion.is_some()))]
+        }
+    );
+
+    let mut other = site_bytes.clone();
+    // The site ID follows the record kind and the worker.
+    other[12 + 1 + 8] ^= 1;
+    let Ok(Frame::Site(other)) = decode_frame(&other) else {
+        panic!("a site record");
+    };
+    assert_eq!(
+        event.resolve(&other).unwrap_err(),
+        DecodeError::SiteMismatch {
+            worker: case.worker,
+            site: event.site,
+            given_worker: case.worker,
+            given_site: event.site ^ 1,
+        }
+    );
+
+    // A schema with fewer fields than the event refers to.
+    let narrower = Metadata {
+        fields: &case.metadata.fields[..0],
+        ..*case.metadata
+    };
+    let mut narrow = vec![0_u8; 1 << 12];
+    let narrow = encode_site(case.worker, event.site, &narrower, &mut narrow).unwrap();
+    let Ok(Frame::Site(narrow)) = decode_frame(narrow) else {
+        panic!("a site record");
+    };
+    assert!(matches!(
+        event.resolve(&narrow),
+        Err(DecodeError::UnknownField { .. })
+    ));
+
+    // Entries out of schema order still name the right fields.
+    static ORDERED: [FieldMetadata; 3] = [
+        FieldMetadata::new("a", Privacy::SupportSafe, Detail::Core),
+        FieldMetadata::new("b", Privacy::SupportSafe, Detail::Core),
+        FieldMetadata::new("c", Privacy::SupportSafe, Detail::Core),
+    ];
+    let ordered = Metadata {
+        fields: &ORDERED,
+        ..*case.metadata
+    };
+    let ordered: &'static Metadata = Box::leak(Box::new(ordered));
+    let fields =
+        [2, 0, 1, 2].map(|index| Some(FieldRef::new(&ORDERED[index], ValueRef::U64(index as u64))));
+    let envelope = Envelope {
+        event: EventRef::structured(ordered, ContextToken::NONE, &fields),
+        timestamp: None,
+        sequence: 0,
+        dropped_before: 0,
+        truncated_before: 0,
+        omitted_fields: 0,
+        links: &[],
+        identity: Identity {
+            worker: case.worker,
+            test: None,
+        },
+    };
+    let site = encode_site(case.worker, 7, ordered, &mut output)
+        .unwrap()
+        .to_vec();
+    let mut scratch = [0_u8; SCRATCH_BYTES];
+    let event = encode_event(envelope, 7, None, 64, &mut output, &mut scratch)
+        .unwrap()
+        .bytes
+        .to_vec();
+    let (Ok(Frame::Site(site)), Ok(Frame::Event(event))) =
+        (decode_frame(&site), decode_frame(&event))
+    else {
+        panic!("a site record and an event record");
+    };
+    let decoded: Vec<_> = event
+        .resolve(&site)
+        .unwrap()
+        .fields()
+        .map(|field| (field.name, field.value))
+        .collect();
+    assert_eq!(
+        decoded,
+        [("c", 2), ("a", 0), ("b", 1), ("c", 2)].map(|(name, value)| (name, WireValue::U64(value)))
+    );
+}
+
+#[test]
+fn damaged_frames_never_panic() {
+    let mut random = SplitMix(0xbad_f00d);
+    let mut output = vec![0_u8; 1 << 16];
+    for _ in 0..500 {
+        let case = Case::random(&mut random);
+        let mut stream = Vec::new();
+        for _ in 0..3 {
+            stream.extend(case.encode(None, &mut output));
+        }
+        let (site, event) = case.encode_v2(None, &mut output);
+        stream.extend(site);
+        stream.extend(event);
+        for _ in 0..20 {
+            let mut damaged = stream.clone();
+            match random.below(4) {
+                0 => {
+                    for _ in 0..=random.below(4) {
+                        let at = random.below(damaged.len() as u64) as usize;
+                        damaged[at] ^= 1 << random.below(8);
+                    }
+                }
+                1 => damaged.truncate(random.below(damaged.len() as u64) as usize),
+                2 => {
+                    let at = random.below(damaged.len() as u64) as usize;
+                    damaged.insert(at, random.next() as u8);
+                }
+                _ => {
+                    let at = random.below(damaged.len() as u64) as usize;
+                    damaged.splice(at..at, *b"LW1\0");
+                }
+            }
+            if let Ok(envelope) = decode_envelope(&damaged) {
+                assert!(envelope.length <= damaged.len());
+                let _ = envelope.links().count();
+                let _ = envelope
+                    .fields()
+                    .map(|field| field.name.len())
+                    .sum::<usize>();
+            }
+            let mut frames = Frames::new(&damaged);
+            let mut position = 0;
+            let mut site = None;
+            while let Some(frame) = frames.next() {
+                assert!(frames.position() >= position);
+                assert!(frames.position() <= damaged.len());
+                match frame {
+                    Err(error) => assert_eq!(error.offset, position),
+                    Ok(Frame::Site(decoded)) => site = Some(decoded),
+                    Ok(Frame::Event(event)) => {
+                        if let Some(Ok(envelope)) = site.map(|site| event.resolve(&site)) {
+                            let _ = envelope
+                                .fields()
+                                .map(|field| field.name.len())
+                                .sum::<usize>();
+                        }
+                    }
+                    Ok(Frame::Envelope(_)) => {}
+                }
+                position = frames.position();
+            }
+        }
+    }
+}
+
+#[test]
+fn frames_resynchronize_after_a_corrupt_frame() {
+    let mut random = SplitMix(7);
+    let mut output = vec![0_u8; 1 << 16];
+    // Random text may carry the magic, which would resynchronize early.
+    let frames = std::iter::repeat_with(|| Case::random(&mut random).encode(None, &mut output))
+        .filter(|frame| !frame[1..].windows(4).any(|window| window == b"LW1\0"))
+        .take(3)
+        .collect::<Vec<_>>();
+    let starts = [0, frames[0].len(), frames[0].len() + frames[1].len()];
+    let mut stream = frames.concat();
+    stream[starts[1] + 4] = 3;
+
+    let mut decoded = Frames::new(&stream);
+    assert_eq!(decoded.next().unwrap().unwrap().length(), frames[0].len());
+    assert_eq!(
+        decoded.next().unwrap().unwrap_err(),
+        FrameError {
+            offset: starts[1],
+            error: DecodeError::UnsupportedVersion(3),
+            skipped: frames[1].len(),
+        }
+    );
+    assert_eq!(decoded.next().unwrap().unwrap().length(), frames[2].len());
+    assert!(decoded.next().is_none());
+    assert_eq!(decoded.position(), stream.len());
+
+    // A frame cut short at the end stalls, so a streaming host can keep the
+    // tail and retry with more bytes.
+    let cut = starts[2] + 20;
+    let mut decoded = Frames::new(&stream[..cut]);
+    assert!(decoded.next().unwrap().is_ok());
+    assert!(decoded.next().unwrap().is_err());
+    let error = decoded.next().unwrap().unwrap_err();
+    assert_eq!(
+        error,
+        FrameError {
+            offset: starts[2],
+            error: DecodeError::Incomplete {
+                needed: frames[2].len()
+            },
+            skipped: 0,
+        }
+    );
+    assert!(decoded.next().is_none());
+    assert_eq!(decoded.position(), starts[2]);
+
+    // Garbage ending in part of a magic keeps that part.
+    let mut garbage = b"noise LW".to_vec();
+    let mut decoded = Frames::new(&garbage);
+    assert_eq!(
+        decoded.next().unwrap().unwrap_err(),
+        FrameError {
+            offset: 0,
+            error: DecodeError::BadMagic,
+            skipped: 6,
+        }
+    );
+    assert!(matches!(
+        decoded.next(),
+        Some(Err(FrameError {
+            error: DecodeError::Incomplete { .. },
+            ..
+        }))
+    ));
+    assert_eq!(decoded.position(), 6);
+    garbage.truncate(6);
+    garbage.extend(&frames[0]);
+    let results = Frames::new(&garbage).collect::<Vec<_>>();
+    assert_eq!(results.len(), 2);
+    assert!(results[1].is_ok());
+}
+
+/// Declared fields and the values a call site materialized for them.
+struct Case {
+    metadata: &'static Metadata,
+    values: Vec<Option<Value>>,
+    context: (u64, u64),
+    links: Vec<(u64, u64)>,
+    message: Option<String>,
+    timestamp: Option<WireTimestamp>,
+    span: Option<WireSpan>,
+    sequence: u64,
+    dropped_before: u64,
+    truncated_before: u64,
+    omitted_fields: u32,
+    worker: u64,
+    test: Option<String>,
+    max_value_bytes: usize,
+}
+
+#[derive(Clone)]
+enum Value {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Str(String),
+    Debug(String),
+    Display(String),
+}
+
+const SCRATCH_BYTES: usize = 48;
+
+impl Case {
+    fn random(random: &mut SplitMix) -> Self {
+        let fields = (0..random.below(6))
+            .map(|_| FieldMetadata {
+                name: leak(random.text(12)),
+                privacy: [Privacy::SupportSafe, Privacy::LocalOnly, Privacy::Secret]
+                    [random.below(3) as usize],
+                detail: [Detail::Core, Detail::Detail][random.below(2) as usize],
+            })
+            .collect::<Vec<_>>();
+        let kind = [
+            Kind::Event,
+            Kind::AdHocText,
+            Kind::Span,
+            Kind::Counter,
+            Kind::Measurement,
+        ][random.below(5) as usize];
+        let metadata = Box::leak(Box::new(Metadata {
+            event_name: leak(random.text(24)),
+            package: leak(random.text(8)),
+            target: leak(random.text(8)),
+            module: leak(random.text(8)),
+            domain: random.chance().then(|| Domain::new(leak(random.text(16)))),
+            severity: [
+                Severity::Trace,
+                Severity::Debug,
+                Severity::Info,
+                Severity::Warn,
+                Severity::Error,
+                Severity::Critical,
+            ][random.below(6) as usize],
+            class: [
+                Class::Operational,
+                Class::Diagnostic,
+                Class::Forensic,
+                Class::Performance,
+                Class::Metric,
+            ][random.below(5) as usize],
+            kind,
+            location: random.chance().then(|| {
+                Location::new(
+                    leak(random.text(16)),
+                    random.next() as u32,
+                    random.next() as u32,
+                )
+            }),
+            fields: Box::leak(fields.into_boxed_slice()),
+        }));
+        let values = metadata
+            .fields
+            .iter()
+            .map(|_| random.chance().then(|| random.value()))
+            .collect();
+        Self {
+            metadata,
+            values,
+            context: (random.next(), random.next()),
+            links: (0..random.below(4))
+                .map(|_| (random.next(), random.next()))
+                .collect(),
+            message: random.chance().then(|| random.text(64)),
+            timestamp: random.chance().then(|| WireTimestamp {
+                monotonic: Duration::from_nanos(random.next()),
+                wall: Duration::from_nanos(random.next()),
+            }),
+            span: random.chance().then(|| WireSpan {
+                token: SpanToken::from_parts(random.next(), random.next()),
+                timing: [
+                    SpanTiming::WallTime,
+                    SpanTiming::ActiveTime,
+                    SpanTiming::WakeLatency,
+                ][random.below(3) as usize],
+                warning_threshold: random.chance().then(|| Duration::from_nanos(random.next())),
+                completion: random.chance().then(|| WireSpanCompletion {
+                    elapsed: Duration::from_nanos(random.next()),
+                    threshold_exceeded: random.chance(),
+                }),
+            }),
+            sequence: random.next(),
+            dropped_before: random.next(),
+            truncated_before: random.next(),
+            omitted_fields: random.next() as u32,
+            worker: random.next(),
+            test: random.chance().then(|| random.text(16)),
+            max_value_bytes: [0, 3, 16, 1_024][random.below(4) as usize],
+        }
+    }
+
+    fn encode(&self, message: Option<fmt::Arguments<'_>>, output: &mut [u8]) -> Vec<u8> {
+        let mut scratch = [0_u8; SCRATCH_BYTES];
+        self.with_envelope(message, |envelope| {
+            encode_timestamped_envelope(envelope, self.max_value_bytes, output, &mut scratch)
+                .unwrap()
+                .bytes
+                .to_vec()
+        })
+    }
+
+    /// The version 2 site record, then the event or span record.
+    fn encode_v2(
+        &self,
+        message: Option<fmt::Arguments<'_>>,
+        output: &mut [u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let site_id = self.sequence as u32;
+        let site = encode_site(self.worker, site_id, self.metadata, output)
+            .unwrap()
+            .to_vec();
+        let mut scratch = [0_u8; SCRATCH_BYTES];
+        let event = self.with_envelope(message, |envelope| {
+            encode_event(
+                envelope,
+                site_id,
+                self.span,
+                self.max_value_bytes,
+                output,
+                &mut scratch,
+            )
+            .unwrap()
+            .bytes
+            .to_vec()
+        });
+        (site, event)
+    }
+
+    fn with_envelope<T>(
+        &self,
+        message: Option<fmt::Arguments<'_>>,
+        encode: impl FnOnce(Envelope<'_>) -> T,
+    ) -> T {
+        let fields = self
+            .metadata
+            .fields
+            .iter()
+            .zip(&self.values)
+            .map(|(metadata, value)| {
+                value.as_ref().map(|value| {
+                    let value = match value {
+                        Value::Bool(value) => ValueRef::Bool(*value),
+                        Value::I64(value) => ValueRef::I64(*value),
+                        Value::U64(value) => ValueRef::U64(*value),
+                        Value::F64(value) => ValueRef::F64(*value),
+                        Value::Str(value) => ValueRef::Str(value),
+                        Value::Debug(value) => ValueRef::Debug(value),
+                        Value::Display(value) => ValueRef::Display(value),
+                    };
+                    FieldRef::new(metadata, value)
+                })
+            })
+            .collect::<Vec<_>>();
+        let links = self
+            .links
+            .iter()
+            .map(|(id, flags)| ContextToken::from_parts(*id, *flags))
+            .collect::<Vec<_>>();
+        let mut event = EventRef::structured(
+            self.metadata,
+            ContextToken::from_parts(self.context.0, self.context.1),
+            &fields,
+        );
+        event.message = message;
+        let envelope = Envelope {
+            event,
+            timestamp: self.timestamp,
+            sequence: self.sequence,
+            dropped_before: self.dropped_before,
+            truncated_before: self.truncated_before,
+            omitted_fields: self.omitted_fields,
+            links: &links,
+            identity: Identity {
+                worker: self.worker,
+                test: self.test.as_deref(),
+            },
+        };
+        encode(envelope)
+    }
+
+    fn check(&self, decoded: &DecodedEnvelope<'_>) {
+        let metadata = self.metadata;
+        assert_eq!(decoded.sequence, self.sequence);
+        assert_eq!(decoded.dropped_before, self.dropped_before);
+        assert_eq!(decoded.truncated_before, self.truncated_before);
+        assert_eq!(decoded.worker, self.worker);
+        assert_eq!(decoded.context.into_parts(), self.context);
+        assert_eq!(
+            decoded
+                .links()
+                .map(ContextToken::into_parts)
+                .collect::<Vec<_>>(),
+            self.links
+        );
+        assert_eq!(decoded.severity, metadata.severity);
+        assert_eq!(decoded.class, metadata.class);
+        assert_eq!(decoded.kind, metadata.kind);
+        assert_eq!(decoded.event_name, metadata.event_name);
+        assert_eq!(decoded.package, metadata.package);
+        assert_eq!(decoded.target, metadata.target);
+        assert_eq!(decoded.module, metadata.module);
+        assert_eq!(decoded.domain, metadata.domain.map(|domain| domain.name));
+        assert_eq!(
+            decoded.location,
+            metadata.location.map(|location| WireLocation {
+                file: location.file,
+                line: location.line,
+                column: location.column,
+            })
+        );
+        assert_eq!(decoded.timestamp, self.timestamp);
+
+        let mut truncated = false;
+        let mut dynamic = |text: &str, limit: usize| {
+            let kept = prefix(text, limit);
+            truncated |= kept.len() != text.len();
+            kept.to_owned()
+        };
+        let test = self
+            .test
+            .as_deref()
+            .map(|test| dynamic(test, self.max_value_bytes));
+        assert_eq!(decoded.test, test.as_deref());
+        let formatted = self.max_value_bytes.min(SCRATCH_BYTES);
+        let expected = metadata
+            .fields
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(field, value)| Some((field, value.as_ref()?)))
+            .filter(|(field, _)| field.privacy != Privacy::Secret)
+            .map(|(field, value)| {
+                let value = match value {
+                    Value::Bool(value) => Expected::Bool(*value),
+                    Value::I64(value) => Expected::I64(*value),
+                    Value::U64(value) => Expected::U64(*value),
+                    Value::F64(value) => Expected::F64(value.to_bits()),
+                    Value::Str(value) => Expected::Str(dynamic(value, self.max_value_bytes)),
+                    Value::Debug(value) => {
+                        Expected::Debug(dynamic(&format!("{value:?}"), formatted))
+                    }
+                    Value::Display(value) => Expected::Display(dynamic(value, formatted)),
+                };
+                (field.name, field.privacy, field.detail, value)
+            })
+            .collect::<Vec<_>>();
+        let message = self
+            .message
+            .as_deref()
+            .map(|message| dynamic(message, formatted));
+        assert_eq!(decoded.message, message.as_deref());
+        let fields = decoded
+            .fields()
+            .map(|field| {
+                let value = match field.value {
+                    WireValue::Bool(value) => Expected::Bool(value),
+                    WireValue::I64(value) => Expected::I64(value),
+                    WireValue::U64(value) => Expected::U64(value),
+                    WireValue::F64(value) => Expected::F64(value.to_bits()),
+                    WireValue::Str(value) => Expected::Str(value.to_owned()),
+                    WireValue::Debug(value) => Expected::Debug(value.to_owned()),
+                    WireValue::Display(value) => Expected::Display(value.to_owned()),
+                };
+                (field.name, field.privacy, field.detail, value)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fields, expected);
+        assert_eq!(decoded.fields().len(), expected.len());
+        let omitted = u32::try_from(metadata.fields.len() - expected.len()).unwrap();
+        assert_eq!(
+            decoded.omitted_fields,
+            self.omitted_fields.saturating_add(omitted)
+        );
+        assert_eq!(decoded.truncated(), truncated);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Expected {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(u64),
+    Str(String),
+    Debug(String),
+    Display(String),
+}
+
+/// The longest prefix of `text` within `limit` bytes that ends on a char
+/// boundary, as the encoder truncates.
+fn prefix(text: &str, limit: usize) -> &str {
+    let mut length = text.len().min(limit);
+    while !text.is_char_boundary(length) {
+        length -= 1;
+    }
+    &text[..length]
+}
+
+fn leak(text: String) -> &'static str {
+    Box::leak(text.into_boxed_str())
+}
+
+fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).unwrap())
+        .collect()
+}
+
+/// A deterministic generator, so a failing case reproduces.
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound.max(1)
+    }
+
+    fn chance(&mut self) -> bool {
+        self.next() & 1 == 1
+    }
+
+    /// Text that mixes multi-byte characters, quotes, NULs and the magic.
+    fn text(&mut self, max: u64) -> String {
+        const PIECES: [&str; 8] = ["a", "Z", "é", "日本", "🦀", "\"", "\0", "LW1\0"];
+        (0..self.below(max + 1))
+            .map(|_| PIECES[self.below(PIECES.len() as u64) as usize])
+            .collect()
+    }
+
+    fn value(&mut self) -> Value {
+        match self.below(7) {
+            0 => Value::Bool(self.chance()),
+            1 => Value::I64(self.next() as i64),
+            2 => Value::U64(self.next()),
+            3 => Value::F64(f64::from_bits(self.next())),
+            4 => Value::Str(self.text(40)),
+            5 => Value::Debug(self.text(40)),
+            _ => Value::Display(self.text(40)),
+        }
+    }
+}
@@ -4,11 +4,16 @@
 // encoder is exercised in-browser by logwise_integration_tests/wasm_wire.rs.
 #![cfg(not(target_arch = "wasm32"))]
 
+use core::time::Duration;
 use logwise::{
     Class, ContextToken, Detail, EventRef, FieldMetadata, FieldRef, Kind, Location, Metadata,
-    Privacy, Severity, ValueRef,
+    Privacy, Severity, SpanTiming, SpanToken, ValueRef,
+};
+
+use logwise_runtime_wasm::{
+    ABI_VERSION, DICTIONARY_ABI_VERSION, Envelope, Identity, WireSpan, WireSpanCompletion,
+    WireTimestamp, encode_envelope, encode_event, encode_site, encode_timestamped_envelope,
 };
-use logwise_runtime_wasm::{ABI_VERSION, Envelope, Identity, encode_envelope};
 
 static FIELDS: &[FieldMetadata] = &[
     FieldMetadata::new("active", Privacy::SupportSafe, Detail::Core),
@@ -41,6 +46,7 @@ fn golden_vector_matches_minimal_embedder() {
     let links = [ContextToken::from_parts(21, 22)];
     let envelope = Envelope {
         event,
+        timestamp: None,
         sequence: 42,
         dropped_before: 3,
         truncated_before: 9,
@@ -113,6 +119,147 @@ fn golden_vector_matches_minimal_embedder() {
     assert_eq!(host.text(), "hél");
     assert_eq!(host.u8(), 0, "no message");
     assert!(host.remaining().is_empty());
+
+    let untimed = encoded.bytes.to_vec();
+    let timed = Envelope {
+        timestamp: Some(WireTimestamp {
+            monotonic: Duration::from_millis(1_500),
+            wall: Duration::from_secs(1_700_000_000),
+        }),
+        ..envelope
+    };
+    let mut timed_output = [0_u8; 512];
+    let encoded = encode_envelope(timed, 4, &mut timed_output, &mut scratch).unwrap();
+    assert_eq!(
+        encoded.bytes, untimed,
+        "version 1 has no timestamp for a strict host to trip over"
+    );
+
+    // A reader that agreed to the trailer, such as a log file's, gets it.
+    let encoded = encode_timestamped_envelope(timed, 4, &mut timed_output, &mut scratch).unwrap();
+    let mut host = MinimalHost::new(encoded.bytes);
+    host.take(6);
+    assert_eq!(host.u16(), 0b11, "truncation and timestamp flags");
+    assert_eq!(host.u32() as usize, encoded.bytes.len());
+    assert_eq!(
+        &encoded.bytes[12..untimed.len()],
+        &untimed[12..],
+        "the trailer leaves the version-1 body untouched"
+    );
+    host.take(untimed.len() - 12);
+    assert_eq!(host.u64(), 1_500_000_000);
+    assert_eq!(host.u64(), 1_700_000_000_000_000_000);
+    assert!(host.remaining().is_empty());
+}
+
+#[test]
+fn version_2_golden_vector_matches_minimal_embedder() {
+    let fields = [
+        Some(FieldRef::new(&FIELDS[0], ValueRef::Bool(true))),
+        None,
+        Some(FieldRef::new(&FIELDS[2], ValueRef::Str("héllo"))),
+        Some(FieldRef::new(&FIELDS[3], ValueRef::Str("never encode"))),
+    ];
+    let event = EventRef::structured(&METADATA, ContextToken::from_parts(11, 12), &fields);
+    let envelope = Envelope {
+        event,
+        timestamp: Some(WireTimestamp {
+            monotonic: Duration::from_millis(1_500),
+            wall: Duration::from_secs(1_700_000_000),
+        }),
+        sequence: 42,
+        dropped_before: 3,
+        truncated_before: 9,
+        omitted_fields: 0,
+        links: &[],
+        identity: Identity {
+            worker: 7,
+            test: None,
+        },
+    };
+    let span = WireSpan {
+        token: SpanToken::from_parts(5, 0),
+        timing: SpanTiming::ActiveTime,
+        warning_threshold: Some(Duration::from_millis(2)),
+        completion: Some(WireSpanCompletion {
+            elapsed: Duration::from_millis(3),
+            threshold_exceeded: true,
+        }),
+    };
+    let mut output = [0_u8; 512];
+    let site = encode_site(7, 1, &METADATA, &mut output).unwrap().to_vec();
+    let mut scratch = [0_u8; 32];
+    let encoded = encode_event(envelope, 1, Some(span), 4, &mut output, &mut scratch).unwrap();
+    assert_eq!(encoded.truncated_values, 1);
+    let stream = [site.as_slice(), encoded.bytes].concat();
+
+    let hex = stream
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    assert_eq!(hex, include_str!("../golden/logwise_v2_span.hex").trim());
+
+    let mut host = MinimalHost::new(&stream);
+    assert_eq!(host.take(4), b"LW1\0");
+    assert_eq!(host.u16(), DICTIONARY_ABI_VERSION);
+    assert_eq!(host.u16(), 0, "no flags");
+    assert_eq!(host.u32() as usize, site.len());
+    assert_eq!(host.u8(), 1, "site record");
+    assert_eq!(host.u64(), 7, "worker");
+    assert_eq!(host.u32(), 1, "site ID");
+    assert_eq!(host.u8(), Severity::Warn as u8);
+    assert_eq!(host.u8(), Class::Forensic as u8);
+    assert_eq!(host.u8(), Kind::Event as u8);
+    assert_eq!(host.text(), "golden.event");
+    assert_eq!(host.text(), "fixture");
+    assert_eq!(host.text(), "guest");
+    assert_eq!(host.text(), "golden");
+    assert_eq!(host.u8(), 0, "no domain");
+    assert_eq!(host.u8(), 1, "location present");
+    assert_eq!(host.text(), "golden.rs");
+    assert_eq!(host.u32(), 17);
+    assert_eq!(host.u32(), 4);
+    assert_eq!(host.u16(), 4, "every declared field, secrets included");
+    for field in FIELDS {
+        assert_eq!(host.text(), field.name);
+        assert_eq!(host.u8(), field.privacy as u8);
+        assert_eq!(host.u8(), field.detail as u8);
+    }
+
+    let event_start = site.len();
+    assert_eq!(host.take(4), b"LW1\0");
+    assert_eq!(host.u16(), DICTIONARY_ABI_VERSION);
+    assert_eq!(host.u16(), 0b11, "truncation and timestamp flags");
+    assert_eq!(host.u32() as usize, stream.len() - event_start);
+    assert_eq!(host.u8(), 4, "span end record");
+    assert_eq!(host.u64(), 42);
+    assert_eq!(host.u64(), 3);
+    assert_eq!(host.u64(), 9);
+    assert_eq!(host.u64(), 7);
+    assert_eq!(host.u32(), 1, "site ID");
+    assert_eq!(host.u64(), 11);
+    assert_eq!(host.u64(), 12);
+    assert_eq!(host.u16(), 0, "no links");
+    assert_eq!(host.u8(), 0, "no test identity");
+    assert_eq!(host.u32(), 2, "one unset and one secret field omitted");
+    assert_eq!(host.u16(), 2);
+    assert_eq!(host.u16(), 0, "field 0: active");
+    assert_eq!(host.u8(), 1, "bool tag");
+    assert_eq!(host.u8(), 1);
+    assert_eq!(host.u16(), 2, "field 2: label");
+    assert_eq!(host.u8(), 5, "string tag");
+    assert_eq!(host.text(), "hél");
+    assert_eq!(host.u8(), 0, "no message");
+    assert_eq!(host.u64(), 5, "span token");
+    assert_eq!(host.u64(), 0);
+    assert_eq!(host.u8(), SpanTiming::ActiveTime as u8);
+    assert_eq!(host.u8(), 1, "threshold present");
+    assert_eq!(host.u64(), 2_000_000);
+    assert_eq!(host.u64(), 3_000_000, "elapsed");
+    assert_eq!(host.u8(), 1, "threshold exceeded");
+    assert_eq!(host.u64(), 1_500_000_000);
+    assert_eq!(host.u64(), 1_700_000_000_000_000_000);
+    assert!(host.remaining().is_empty());
 }
 
 struct MinimalHost<'a> {